@@ -16,7 +16,7 @@ pub enum GlyphFlow {
     Vertical,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TextDirection {
     LeftToRight,
     RightToLeft,
@@ -88,6 +88,7 @@ pub enum GlyphPaint {
     CurrentColor,
     LinearGradient(GlyphLinearGradient),
     RadialGradient(GlyphRadialGradient),
+    SweepGradient(GlyphSweepGradient),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -128,12 +129,51 @@ pub struct GlyphRadialGradient {
     pub r: f32,
     pub fx: f32,
     pub fy: f32,
+    /// Radius of the focal circle; `0.0` for a focal point.
+    pub fr: f32,
     pub units: GlyphGradientUnits,
     pub transform: [f32; 6],
     pub spread: GlyphGradientSpread,
     pub stops: Vec<GlyphGradientStop>,
 }
 
+/// Conic gradient around (`cx`, `cy`), as used by COLRv1 sweep paints.
+///
+/// Angles are in degrees, measured from the +x axis towards +y in gradient
+/// space; `transform` maps gradient space to glyph space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphSweepGradient {
+    pub cx: f32,
+    pub cy: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub transform: [f32; 6],
+    pub spread: GlyphGradientSpread,
+    pub stops: Vec<GlyphGradientStop>,
+}
+
+/// How a layer is blended with the layers below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlyphBlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FillRule {
     NonZero,
@@ -159,6 +199,46 @@ pub struct PathGlyphLayer {
     pub stroke_width: f32,
     pub offset_x: f32,
     pub offset_y: f32,
+    pub blend_mode: GlyphBlendMode,
+    /// Multiplies the alpha of the whole layer, `1.0` is opaque.
+    pub opacity: f32,
+    /// Soft mask resolved from SVG `mask`; `clip_commands` already holds its outline.
+    pub mask: Option<GlyphMask>,
+    /// Filter primitives applied in order to the rendered layer.
+    pub filters: Vec<GlyphFilter>,
+}
+
+/// How the layers of a [`GlyphMask`] become coverage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphMaskMode {
+    /// Luminance times alpha, as SVG `mask-type: luminance`.
+    Luminance,
+    /// Alpha only, as SVG `mask-type: alpha`.
+    Alpha,
+}
+
+/// Soft mask drawn in the same glyph space as the masked layer.
+#[derive(Debug, Clone)]
+pub struct GlyphMask {
+    pub mode: GlyphMaskMode,
+    pub layers: Vec<PathGlyphLayer>,
+}
+
+/// Filter primitive of a path layer; lengths are in glyph space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlyphFilter {
+    GaussianBlur {
+        std_deviation_x: f32,
+        std_deviation_y: f32,
+    },
+    Offset {
+        dx: f32,
+        dy: f32,
+    },
+    /// Replaces the color of the result with `color` (`0xAARRGGBB`), keeping its coverage.
+    Flood {
+        color: u32,
+    },
 }
 
 impl PathGlyphLayer {
@@ -172,6 +252,10 @@ impl PathGlyphLayer {
             stroke_width: 1.0,
             offset_x: 0.0,
             offset_y: 0.0,
+            blend_mode: GlyphBlendMode::Normal,
+            opacity: 1.0,
+            mask: None,
+            filters: Vec::new(),
         }
     }
 
@@ -185,6 +269,10 @@ impl PathGlyphLayer {
             stroke_width,
             offset_x: 0.0,
             offset_y: 0.0,
+            blend_mode: GlyphBlendMode::Normal,
+            opacity: 1.0,
+            mask: None,
+            filters: Vec::new(),
         }
     }
 }
@@ -233,6 +321,19 @@ impl RasterGlyphLayer {
             height: None,
         }
     }
+
+    /// Source pixels as `(width, height, RGBA8)` with straight alpha, decoding
+    /// PNG payloads. The result is not scaled to `width` / `height`.
+    pub fn to_rgba(&self) -> Result<(u32, u32, Vec<u8>), std::io::Error> {
+        match &self.source {
+            RasterGlyphSource::Rgba {
+                width,
+                height,
+                data,
+            } => Ok((*width, *height, data.clone())),
+            RasterGlyphSource::Encoded(data) => crate::png::decode(data),
+        }
+    }
 }
 
 /// Embedded SVG glyph payload extracted from an OpenType `SVG ` table.
@@ -305,6 +406,156 @@ impl GlyphRun {
     }
 }
 
+/// How ruby annotations are distributed over their base text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RubyKind {
+    /// One annotation per base character.
+    Mono,
+    /// One annotation spread over the whole base text.
+    Group,
+    /// Per-character annotations that fall back to group ruby when one does not fit.
+    Jukugo,
+}
+
+/// One base text segment with optional ruby (furigana) annotations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RubyText {
+    pub base: String,
+    pub annotations: Vec<String>,
+    pub kind: RubyKind,
+}
+
+impl RubyText {
+    /// Base text without any annotation.
+    pub fn plain(base: impl Into<String>) -> Self {
+        Self {
+            base: base.into(),
+            annotations: Vec::new(),
+            kind: RubyKind::Group,
+        }
+    }
+
+    /// Group ruby such as `紫陽花` / `あじさい`.
+    pub fn group(base: impl Into<String>, ruby: impl Into<String>) -> Self {
+        Self {
+            base: base.into(),
+            annotations: vec![ruby.into()],
+            kind: RubyKind::Group,
+        }
+    }
+
+    /// Mono ruby with one annotation per base character.
+    pub fn mono(base: impl Into<String>, annotations: &[&str]) -> Self {
+        Self {
+            base: base.into(),
+            annotations: annotations.iter().map(|ruby| ruby.to_string()).collect(),
+            kind: RubyKind::Mono,
+        }
+    }
+
+    /// Jukugo ruby such as `東京` / `とう`, `きょう`.
+    pub fn jukugo(base: impl Into<String>, annotations: &[&str]) -> Self {
+        Self {
+            base: base.into(),
+            annotations: annotations.iter().map(|ruby| ruby.to_string()).collect(),
+            kind: RubyKind::Jukugo,
+        }
+    }
+}
+
+/// Which line a [`TextDecoration`] draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextDecorationLine {
+    Underline,
+    Overline,
+    LineThrough,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextDecorationStyle {
+    #[default]
+    Solid,
+    Double,
+    Dotted,
+    Wavy,
+}
+
+/// Text decoration drawn from `post` / `OS/2` metrics.
+///
+/// In vertical flow the underline runs along the left side of the column and the overline
+/// along the right side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextDecoration {
+    pub line: TextDecorationLine,
+    pub style: TextDecorationStyle,
+    /// Packed `0xAARRGGBB`; `None` draws with `GlyphPaint::CurrentColor`.
+    pub color: Option<u32>,
+    /// Leaves gaps where glyph outlines cross an underline or overline.
+    pub skip_ink: bool,
+}
+
+impl TextDecoration {
+    pub fn new(line: TextDecorationLine) -> Self {
+        Self {
+            line,
+            style: TextDecorationStyle::Solid,
+            color: None,
+            skip_ink: true,
+        }
+    }
+
+    pub fn underline() -> Self {
+        Self::new(TextDecorationLine::Underline)
+    }
+
+    pub fn overline() -> Self {
+        Self::new(TextDecorationLine::Overline)
+    }
+
+    pub fn line_through() -> Self {
+        Self::new(TextDecorationLine::LineThrough)
+    }
+
+    pub fn with_style(mut self, style: TextDecorationStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_skip_ink(mut self, skip_ink: bool) -> Self {
+        self.skip_ink = skip_ink;
+        self
+    }
+}
+
+/// One run of styled text for [`spans2commands`].
+#[derive(Clone)]
+pub struct RichTextSpan<'a> {
+    pub text: String,
+    pub options: FontOptions<'a>,
+    /// Packed `0xAARRGGBB` fill that replaces `GlyphPaint::CurrentColor`.
+    pub color: Option<u32>,
+}
+
+impl<'a> RichTextSpan<'a> {
+    pub fn new(text: impl Into<String>, options: FontOptions<'a>) -> Self {
+        Self {
+            text: text.into(),
+            options,
+            color: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FontStretch(pub f32);
 
@@ -331,6 +582,22 @@ impl Default for FontStyle {
     }
 }
 
+/// CSS `font-optical-sizing`: whether the font size drives the `opsz` axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontOpticalSizing {
+    #[default]
+    Auto,
+    None,
+}
+
+/// CSS `font-palette: light | dark`: picks the first CPAL palette marked
+/// as usable on that background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontPaletteType {
+    Light,
+    Dark,
+}
+
 /// GSUB variant selection exposed through the public API.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FontVariant {
@@ -367,6 +634,290 @@ impl FontVariant {
     }
 }
 
+/// CSS `font-variant-caps`. Uses `smcp`/`c2sc` when the font has them and
+/// synthesizes scaled capitals otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontVariantCaps {
+    #[default]
+    Normal,
+    /// Lowercase letters become small capitals.
+    SmallCaps,
+    /// Both lowercase and uppercase letters become small capitals.
+    AllSmallCaps,
+}
+
+impl FontVariantCaps {
+    #[cfg_attr(not(feature = "layout"), allow(dead_code))]
+    pub(crate) fn gsub_feature_tags(self) -> &'static [[u8; 4]] {
+        const SMCP: &[[u8; 4]] = &[*b"smcp"];
+        const C2SC: &[[u8; 4]] = &[*b"smcp", *b"c2sc"];
+
+        match self {
+            Self::Normal => &[],
+            Self::SmallCaps => SMCP,
+            Self::AllSmallCaps => C2SC,
+        }
+    }
+}
+
+/// CSS `font-variant-position`. Uses `sups`/`subs` when the font has them and
+/// synthesizes from the OS/2 superscript and subscript metrics otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontVariantPosition {
+    #[default]
+    Normal,
+    Super,
+    Sub,
+}
+
+impl FontVariantPosition {
+    #[cfg_attr(not(feature = "layout"), allow(dead_code))]
+    pub(crate) fn gsub_feature_tag(self) -> Option<[u8; 4]> {
+        match self {
+            Self::Normal => None,
+            Self::Super => Some(*b"sups"),
+            Self::Sub => Some(*b"subs"),
+        }
+    }
+}
+
+/// East Asian glyph width selected through the `fwid` family of GSUB features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphWidth {
+    /// `fwid`, full-width forms.
+    Full,
+    /// `hwid`, half-width forms.
+    Half,
+    /// `pwid`, proportional forms.
+    Proportional,
+    /// `twid`, third-width forms.
+    Third,
+    /// `qwid`, quarter-width forms.
+    Quarter,
+}
+
+impl GlyphWidth {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Full,
+        Self::Half,
+        Self::Proportional,
+        Self::Third,
+        Self::Quarter,
+    ];
+
+    pub fn tag(self) -> [u8; 4] {
+        match self {
+            Self::Full => *b"fwid",
+            Self::Half => *b"hwid",
+            Self::Proportional => *b"pwid",
+            Self::Third => *b"twid",
+            Self::Quarter => *b"qwid",
+        }
+    }
+}
+
+/// Digit shapes selected through `lnum` / `onum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericFigure {
+    Lining,
+    OldStyle,
+}
+
+impl NumericFigure {
+    pub fn tag(self) -> [u8; 4] {
+        match self {
+            Self::Lining => *b"lnum",
+            Self::OldStyle => *b"onum",
+        }
+    }
+}
+
+/// Digit advances selected through `pnum` / `tnum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericSpacing {
+    Proportional,
+    Tabular,
+}
+
+impl NumericSpacing {
+    pub fn tag(self) -> [u8; 4] {
+        match self {
+            Self::Proportional => *b"pnum",
+            Self::Tabular => *b"tnum",
+        }
+    }
+}
+
+/// One OpenType feature request such as `ruby=1` or `liga=0`.
+///
+/// A value of `0` turns the feature off; any other value requests it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontFeatureSetting {
+    pub tag: [u8; 4],
+    pub value: u32,
+}
+
+impl FontFeatureSetting {
+    /// Creates one feature setting from a four-character OpenType tag.
+    pub fn new(tag: &str, value: u32) -> Result<Self, Error> {
+        Ok(Self {
+            tag: parse_variation_tag(tag)?,
+            value,
+        })
+    }
+
+    /// Returns the OpenType tag as a string such as `"ruby"`.
+    pub fn tag_string(&self) -> String {
+        String::from_utf8_lossy(&self.tag).into_owned()
+    }
+
+    /// Returns `true` when the feature is requested.
+    pub fn is_enabled(&self) -> bool {
+        self.value != 0
+    }
+}
+
+/// Baseline used to align glyphs from different faces or scripts.
+///
+/// Values come from the BASE table when present; otherwise they are estimated
+/// from `hhea` metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Baseline {
+    /// `romn`, the Latin baseline.
+    Alphabetic,
+    /// `hang`, used by Devanagari, Tibetan and similar scripts.
+    Hanging,
+    /// `ideo`, the bottom of the ideographic em box.
+    Ideographic,
+    /// `idtp`, the top of the ideographic em box.
+    IdeographicEmTop,
+    /// `icfb`, the bottom of the ideographic character face.
+    IdeographicFaceBottom,
+    /// `icft`, the top of the ideographic character face.
+    IdeographicFaceTop,
+    /// `math`, the center line used for math operators.
+    Math,
+}
+
+impl Baseline {
+    /// Returns the BASE table tag such as `*b"romn"`.
+    pub fn tag(self) -> [u8; 4] {
+        match self {
+            Self::Alphabetic => *b"romn",
+            Self::Hanging => *b"hang",
+            Self::Ideographic => *b"ideo",
+            Self::IdeographicEmTop => *b"idtp",
+            Self::IdeographicFaceBottom => *b"icfb",
+            Self::IdeographicFaceTop => *b"icft",
+            Self::Math => *b"math",
+        }
+    }
+
+    /// Parses a BASE table tag.
+    pub fn from_tag(tag: [u8; 4]) -> Option<Self> {
+        match &tag {
+            b"romn" => Some(Self::Alphabetic),
+            b"hang" => Some(Self::Hanging),
+            b"ideo" => Some(Self::Ideographic),
+            b"idtp" => Some(Self::IdeographicEmTop),
+            b"icfb" => Some(Self::IdeographicFaceBottom),
+            b"icft" => Some(Self::IdeographicFaceTop),
+            b"math" => Some(Self::Math),
+            _ => None,
+        }
+    }
+}
+
+/// Min/max extent of a script from the BASE table, in pixels.
+///
+/// Positive values are above the baseline, as in the font's design space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScriptExtent {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+/// MATH table constants scaled to px for the requested font size.
+///
+/// Percent values are passed through unchanged; horizontal kerns use the
+/// stretched x scale, everything else the y scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MathConstants {
+    pub script_percent_scale_down: i16,
+    pub script_script_percent_scale_down: i16,
+    pub delimited_sub_formula_min_height: f32,
+    pub display_operator_min_height: f32,
+    pub math_leading: f32,
+    pub axis_height: f32,
+    pub accent_base_height: f32,
+    pub flattened_accent_base_height: f32,
+    pub subscript_shift_down: f32,
+    pub subscript_top_max: f32,
+    pub subscript_baseline_drop_min: f32,
+    pub superscript_shift_up: f32,
+    pub superscript_shift_up_cramped: f32,
+    pub superscript_bottom_min: f32,
+    pub superscript_baseline_drop_max: f32,
+    pub sub_superscript_gap_min: f32,
+    pub superscript_bottom_max_with_subscript: f32,
+    pub space_after_script: f32,
+    pub upper_limit_gap_min: f32,
+    pub upper_limit_baseline_rise_min: f32,
+    pub lower_limit_gap_min: f32,
+    pub lower_limit_baseline_drop_min: f32,
+    pub stack_top_shift_up: f32,
+    pub stack_top_display_style_shift_up: f32,
+    pub stack_bottom_shift_down: f32,
+    pub stack_bottom_display_style_shift_down: f32,
+    pub stack_gap_min: f32,
+    pub stack_display_style_gap_min: f32,
+    pub stretch_stack_top_shift_up: f32,
+    pub stretch_stack_bottom_shift_down: f32,
+    pub stretch_stack_gap_above_min: f32,
+    pub stretch_stack_gap_below_min: f32,
+    pub fraction_numerator_shift_up: f32,
+    pub fraction_numerator_display_style_shift_up: f32,
+    pub fraction_denominator_shift_down: f32,
+    pub fraction_denominator_display_style_shift_down: f32,
+    pub fraction_numerator_gap_min: f32,
+    pub fraction_num_display_style_gap_min: f32,
+    pub fraction_rule_thickness: f32,
+    pub fraction_denominator_gap_min: f32,
+    pub fraction_denom_display_style_gap_min: f32,
+    pub skewed_fraction_horizontal_gap: f32,
+    pub skewed_fraction_vertical_gap: f32,
+    pub overbar_vertical_gap: f32,
+    pub overbar_rule_thickness: f32,
+    pub overbar_extra_ascender: f32,
+    pub underbar_vertical_gap: f32,
+    pub underbar_rule_thickness: f32,
+    pub underbar_extra_descender: f32,
+    pub radical_vertical_gap: f32,
+    pub radical_display_style_vertical_gap: f32,
+    pub radical_rule_thickness: f32,
+    pub radical_extra_ascender: f32,
+    pub radical_kern_before_degree: f32,
+    pub radical_kern_after_degree: f32,
+    pub radical_degree_bottom_raise_percent: i16,
+}
+
+/// Corner of a glyph used for MATH cut-in kerning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathKernCorner {
+    TopRight,
+    TopLeft,
+    BottomRight,
+    BottomLeft,
+}
+
+/// One pre-built size variant of a stretchy MATH glyph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MathGlyphVariant {
+    pub glyph_id: u16,
+    /// Length along the stretch axis in px.
+    pub advance: f32,
+}
+
 /// One variable-font axis value such as `wght=700` or `wdth=75`.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FontVariationSetting {
@@ -430,14 +981,31 @@ pub struct FontOptions<'a> {
     pub font_stretch: FontStretch,
     pub font_style: FontStyle,
     pub font_variant: FontVariant,
+    pub font_variant_caps: FontVariantCaps,
+    pub font_variant_position: FontVariantPosition,
     pub font_weight: FontWeight,
+    pub font_optical_sizing: FontOpticalSizing,
+    /// CPAL palette index for color glyphs; takes precedence over `palette_type`.
+    pub palette: Option<usize>,
+    pub palette_type: Option<FontPaletteType>,
+    /// `(entry, 0xAARRGGBB)` pairs replacing palette entries, like CSS `override-colors`.
+    pub palette_overrides: Vec<(u16, u32)>,
+    /// Packed `0xAARRGGBB` for the CPAL foreground entry (`0xFFFF`); `None`
+    /// draws it with `GlyphPaint::CurrentColor`.
+    pub foreground_color: Option<u32>,
+    /// Draws `EBDT` bitmaps instead of outlines when a strike matches `font_size`.
+    pub embedded_bitmaps: bool,
     pub line_height: Option<f32>,
     pub variations: Vec<FontVariationSetting>,
+    pub features: Vec<FontFeatureSetting>,
+    pub decorations: Vec<TextDecoration>,
+    pub dominant_baseline: Option<Baseline>,
 }
 
 impl<'a> FontOptions<'a> {
     pub fn new(font: &'a crate::FontFace) -> Self {
         Self::from_font_ref(FontRef::Loaded(font))
+            .with_default_variations(font.default_variations())
     }
 
     pub fn from_family(font_family: &'a crate::FontFamily) -> Self {
@@ -467,9 +1035,20 @@ impl<'a> FontOptions<'a> {
                 font_stretch: FontStretch::default(),
                 font_style: FontStyle::default(),
                 font_variant: FontVariant::default(),
+                font_variant_caps: FontVariantCaps::default(),
+                font_variant_position: FontVariantPosition::default(),
                 font_weight: FontWeight::default(),
+                font_optical_sizing: FontOpticalSizing::default(),
+                palette: None,
+                palette_type: None,
+                palette_overrides: Vec::new(),
+                foreground_color: None,
+                embedded_bitmaps: false,
                 line_height: None,
                 variations: Vec::new(),
+                features: Vec::new(),
+                decorations: Vec::new(),
+                dominant_baseline: None,
             }
         }
     }
@@ -485,9 +1064,20 @@ impl<'a> FontOptions<'a> {
             font_stretch: FontStretch::default(),
             font_style: FontStyle::default(),
             font_variant: FontVariant::default(),
+            font_variant_caps: FontVariantCaps::default(),
+            font_variant_position: FontVariantPosition::default(),
             font_weight: FontWeight::default(),
+            font_optical_sizing: FontOpticalSizing::default(),
+            palette: None,
+            palette_type: None,
+            palette_overrides: Vec::new(),
+            foreground_color: None,
+            embedded_bitmaps: false,
             line_height: None,
             variations: Vec::new(),
+            features: Vec::new(),
+            decorations: Vec::new(),
+            dominant_baseline: None,
         }
     }
 
@@ -536,11 +1126,54 @@ impl<'a> FontOptions<'a> {
         self
     }
 
+    pub fn with_font_variant_caps(mut self, caps: FontVariantCaps) -> Self {
+        self.font_variant_caps = caps;
+        self
+    }
+
+    pub fn with_font_variant_position(mut self, position: FontVariantPosition) -> Self {
+        self.font_variant_position = position;
+        self
+    }
+
     pub fn with_font_weight(mut self, font_weight: FontWeight) -> Self {
         self.font_weight = font_weight;
         self
     }
 
+    pub fn with_font_optical_sizing(mut self, font_optical_sizing: FontOpticalSizing) -> Self {
+        self.font_optical_sizing = font_optical_sizing;
+        self
+    }
+
+    pub fn with_palette(mut self, palette: usize) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    pub fn with_palette_type(mut self, palette_type: FontPaletteType) -> Self {
+        self.palette_type = Some(palette_type);
+        self
+    }
+
+    /// Replaces palette entry `entry` with `color` (`0xAARRGGBB`).
+    pub fn with_palette_override(mut self, entry: u16, color: u32) -> Self {
+        self.palette_overrides
+            .retain(|(existing, _)| *existing != entry);
+        self.palette_overrides.push((entry, color));
+        self
+    }
+
+    pub fn with_foreground_color(mut self, color: u32) -> Self {
+        self.foreground_color = Some(color);
+        self
+    }
+
+    pub fn with_embedded_bitmaps(mut self, embedded_bitmaps: bool) -> Self {
+        self.embedded_bitmaps = embedded_bitmaps;
+        self
+    }
+
     pub fn with_font_name(mut self, font_name: &'a str) -> Self {
         self.font_name = Some(font_name);
         self
@@ -586,6 +1219,177 @@ impl<'a> FontOptions<'a> {
         self
     }
 
+    /// Axis value implied by the CSS properties, as browsers apply them to
+    /// variable fonts: `wght` from the weight, `wdth` from the stretch,
+    /// `ital`/`slnt` from the style and `opsz` from the font size.
+    /// Italic falls back to `slnt` when the font has no `ital` axis.
+    pub(crate) fn css_variation(&self, tag: [u8; 4], has_italic_axis: bool) -> Option<f32> {
+        // CSS oblique without an angle is 14deg; `slnt` counts clockwise as negative.
+        const OBLIQUE_SLANT: f32 = -14.0;
+        match &tag {
+            b"wght" => Some(self.font_weight.0 as f32),
+            b"wdth" => Some(self.font_stretch.0 * 100.0),
+            b"ital" => Some(if self.font_style == FontStyle::Italic {
+                1.0
+            } else {
+                0.0
+            }),
+            b"slnt" => Some(match self.font_style {
+                FontStyle::Oblique => OBLIQUE_SLANT,
+                FontStyle::Italic if !has_italic_axis => OBLIQUE_SLANT,
+                _ => 0.0,
+            }),
+            b"opsz" if self.font_optical_sizing == FontOpticalSizing::Auto => Some(self.font_size),
+            _ => None,
+        }
+    }
+
+    /// Adds `defaults` for axes that are not set yet.
+    pub(crate) fn with_default_variations(mut self, defaults: &[FontVariationSetting]) -> Self {
+        for setting in defaults {
+            if !self
+                .variations
+                .iter()
+                .any(|existing| existing.tag == setting.tag)
+            {
+                self.variations.push(*setting);
+            }
+        }
+        self
+    }
+
+    pub fn with_feature(mut self, tag: &str, value: u32) -> Self {
+        if let Ok(setting) = FontFeatureSetting::new(tag, value) {
+            if let Some(existing) = self
+                .features
+                .iter_mut()
+                .find(|existing| existing.tag == setting.tag)
+            {
+                existing.value = value;
+            } else {
+                self.features.push(setting);
+            }
+        }
+        self
+    }
+
+    pub fn with_features(mut self, features: &[FontFeatureSetting]) -> Self {
+        self.features = features.to_vec();
+        self
+    }
+
+    /// Enables one of `fwid` / `hwid` / `pwid` / `twid` / `qwid` and drops the others.
+    pub fn with_glyph_width(self, width: GlyphWidth) -> Self {
+        let others = GlyphWidth::ALL
+            .iter()
+            .filter(|other| **other != width)
+            .map(|other| other.tag())
+            .collect::<Vec<_>>();
+        self.with_exclusive_feature(width.tag(), &others)
+    }
+
+    /// Enables `lnum` or `onum` and drops the other.
+    pub fn with_numeric_figure(self, figure: NumericFigure) -> Self {
+        let other = match figure {
+            NumericFigure::Lining => NumericFigure::OldStyle,
+            NumericFigure::OldStyle => NumericFigure::Lining,
+        };
+        self.with_exclusive_feature(figure.tag(), &[other.tag()])
+    }
+
+    /// Enables `pnum` or `tnum` and drops the other.
+    pub fn with_numeric_spacing(self, spacing: NumericSpacing) -> Self {
+        let other = match spacing {
+            NumericSpacing::Proportional => NumericSpacing::Tabular,
+            NumericSpacing::Tabular => NumericSpacing::Proportional,
+        };
+        self.with_exclusive_feature(spacing.tag(), &[other.tag()])
+    }
+
+    /// Enables `frac`, which turns `1/2` or `1⁄2` into a diagonal fraction.
+    pub fn with_diagonal_fractions(self) -> Self {
+        self.with_feature("frac", 1)
+    }
+
+    fn with_exclusive_feature(mut self, tag: [u8; 4], others: &[[u8; 4]]) -> Self {
+        self.features
+            .retain(|existing| existing.tag != tag && !others.contains(&existing.tag));
+        self.features.push(FontFeatureSetting { tag, value: 1 });
+        self
+    }
+
+    pub fn clear_features(mut self) -> Self {
+        self.features.clear();
+        self
+    }
+
+    pub fn with_decoration(mut self, decoration: TextDecoration) -> Self {
+        self.decorations
+            .retain(|existing| existing.line != decoration.line);
+        self.decorations.push(decoration);
+        self
+    }
+
+    pub fn with_underline(self) -> Self {
+        self.with_decoration(TextDecoration::underline())
+    }
+
+    pub fn with_overline(self) -> Self {
+        self.with_decoration(TextDecoration::overline())
+    }
+
+    pub fn with_line_through(self) -> Self {
+        self.with_decoration(TextDecoration::line_through())
+    }
+
+    pub fn clear_decorations(mut self) -> Self {
+        self.decorations.clear();
+        self
+    }
+
+    /// Aligns fallback faces in a `FontFamily` to this baseline of the primary face.
+    pub fn with_dominant_baseline(mut self, baseline: Baseline) -> Self {
+        self.dominant_baseline = Some(baseline);
+        self
+    }
+
+    /// `font_variant_caps`, treating `FontVariant::SmallCaps` as `SmallCaps`.
+    pub(crate) fn effective_caps(&self) -> FontVariantCaps {
+        if self.font_variant_caps == FontVariantCaps::Normal
+            && self.font_variant == FontVariant::SmallCaps
+        {
+            FontVariantCaps::SmallCaps
+        } else {
+            self.font_variant_caps
+        }
+    }
+
+    /// Returns the GSUB feature tags requested by the variant options and `features`.
+    #[cfg_attr(not(feature = "layout"), allow(dead_code))]
+    pub(crate) fn gsub_feature_tags(&self) -> Vec<[u8; 4]> {
+        let mut tags = self.font_variant.gsub_feature_tags().to_vec();
+        tags.extend_from_slice(self.effective_caps().gsub_feature_tags());
+        tags.extend(self.font_variant_position.gsub_feature_tag());
+        for feature in &self.features {
+            if feature.is_enabled() && !tags.contains(&feature.tag) {
+                tags.push(feature.tag);
+            } else if !feature.is_enabled() {
+                tags.retain(|tag| *tag != feature.tag);
+            }
+        }
+        tags
+    }
+
+    /// Tags switched off with a zero value, such as `liga=0` or `kern=0`.
+    /// These also suppress the features the shaper applies by default.
+    pub(crate) fn disabled_feature_tags(&self) -> Vec<[u8; 4]> {
+        self.features
+            .iter()
+            .filter(|feature| !feature.is_enabled())
+            .map(|feature| feature.tag)
+            .collect()
+    }
+
     pub fn with_vertical_flow(self) -> Self {
         self.with_text_direction(TextDirection::TopToBottom)
     }
@@ -622,16 +1426,85 @@ pub fn text2commands(text: &str, options: FontOptions<'_>) -> Result<GlyphRun, E
     if let Some(FontRef::Family(font_family)) = options.font {
         return font_family.text2glyph_run(text, options);
     }
+    if let Some(FontRef::Loaded(face)) = options.font {
+        return face.font().text2glyph_run_with_plan(
+            text,
+            &options,
+            &face.shape_plan(&options),
+            Some(face.outline_cache()),
+        );
+    }
     let font = options.resolve_font()?;
     font.text2glyph_run(text, &options)
 }
 
+/// Like [`text2commands`], also returning the cluster of every glyph and
+/// shaping the chars in each of `sizes` at that size.
+pub(crate) fn text2clustered_run(
+    text: &str,
+    options: FontOptions<'_>,
+    sizes: &[crate::fontreader::ClusterSize],
+) -> Result<(GlyphRun, Vec<Option<usize>>), Error> {
+    if let Some(FontRef::Family(font_family)) = options.font {
+        return font_family.text2clustered_run(text, options, sizes);
+    }
+    if let Some(FontRef::Loaded(face)) = options.font {
+        return face.font().text2clustered_run(
+            text,
+            &options,
+            &face.shape_plan(&options),
+            Some(face.outline_cache()),
+            sizes,
+        );
+    }
+    let font = options.resolve_font()?;
+    font.text2clustered_run(
+        text,
+        &options,
+        &crate::shapeplan::ShapePlan::new(font, &options),
+        None,
+        sizes,
+    )
+}
+
+/// Shapes independent strings or paragraphs on scoped threads, returning one
+/// result per input in the same order.
+///
+/// Every thread shapes with the same `options`; a [`crate::FontFace`] shares
+/// its shape plans and outline cache between them.
+pub fn shape_parallel<T: AsRef<str> + Sync>(
+    texts: &[T],
+    options: &FontOptions<'_>,
+) -> Vec<Result<GlyphRun, Error>> {
+    crate::util::parallel_map(texts, |text| text2commands(text.as_ref(), options.clone()))
+}
+
+/// Shapes styled spans into one [`GlyphRun`], continuing the pen across span boundaries.
+pub fn spans2commands(spans: &[RichTextSpan<'_>]) -> Result<GlyphRun, Error> {
+    crate::richtext::layout_rich_text(spans, None)
+}
+
+/// Splits Unity-style rich text markup such as `<b>`, `<i>`, `<color>`, and `<size>` into spans.
+///
+/// Unknown or malformed tags are kept as literal text.
+pub fn parse_rich_text<'a>(markup: &'a str, options: &FontOptions<'a>) -> Vec<RichTextSpan<'a>> {
+    crate::richtext::parse_markup(markup, options)
+}
+
+/// Shapes Unity-style rich text markup into one [`GlyphRun`].
+pub fn rich_text2commands<'a>(
+    markup: &'a str,
+    options: FontOptions<'a>,
+) -> Result<GlyphRun, Error> {
+    spans2commands(&parse_rich_text(markup, &options))
+}
+
 pub(crate) fn parse_variation_tag(tag: &str) -> Result<[u8; 4], Error> {
     let bytes = tag.as_bytes();
     if bytes.len() != 4 {
         return Err(Error::new(
             ErrorKind::InvalidInput,
-            format!("variation tag must be 4 ASCII bytes, got {tag:?}"),
+            format!("OpenType tag must be 4 ASCII bytes, got {tag:?}"),
         ));
     }
     let mut result = [0u8; 4];
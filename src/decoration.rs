@@ -0,0 +1,559 @@
+//! Underline, overline, and line-through geometry for shaped runs.
+
+use crate::commands::{
+    Command, Glyph, GlyphLayer, GlyphPaint, PathGlyphLayer, PositionedGlyph, TextDecoration,
+    TextDecorationLine, TextDecorationStyle,
+};
+
+/// Font-level decoration metrics in pixels. Offsets are measured from the baseline, y up.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DecorationMetrics {
+    /// Top of the underline stroke (`post.underlinePosition`).
+    pub(crate) underline_position: f32,
+    pub(crate) underline_thickness: f32,
+    /// Top of the strikeout stroke (`OS/2.yStrikeoutPosition`).
+    pub(crate) strikeout_position: f32,
+    pub(crate) strikeout_thickness: f32,
+    pub(crate) ascent: f32,
+    pub(crate) descent: f32,
+    /// Em-box width used as the column width in vertical flow.
+    pub(crate) em_size: f32,
+}
+
+/// One laid-out line: the glyphs on it and its inline span.
+#[derive(Debug, Clone)]
+pub(crate) struct DecoratedLine {
+    pub(crate) glyphs: std::ops::Range<usize>,
+    /// Baseline y in horizontal flow, column left edge x in vertical flow.
+    pub(crate) cross_origin: f32,
+    pub(crate) inline_start: f32,
+    pub(crate) inline_end: f32,
+}
+
+/// Cross-axis band of one decoration stroke, in run coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Band {
+    start: f32,
+    end: f32,
+}
+
+impl Band {
+    fn thickness(self) -> f32 {
+        self.end - self.start
+    }
+
+    fn center(self) -> f32 {
+        (self.start + self.end) * 0.5
+    }
+
+    fn offset(self, delta: f32) -> Self {
+        Self {
+            start: self.start + delta,
+            end: self.end + delta,
+        }
+    }
+}
+
+/// Builds one zero-advance glyph per decoration and line.
+pub(crate) fn build_decoration_glyphs(
+    glyphs: &[PositionedGlyph],
+    lines: &[DecoratedLine],
+    decorations: &[TextDecoration],
+    metrics: &DecorationMetrics,
+    vertical: bool,
+) -> Vec<PositionedGlyph> {
+    let mut output = Vec::new();
+    for line in lines {
+        if line.inline_end - line.inline_start <= 0.0 {
+            continue;
+        }
+        for decoration in decorations {
+            let (band, outward) = decoration_band(decoration.line, metrics, line, vertical);
+            let skip_ink =
+                decoration.skip_ink && decoration.line != TextDecorationLine::LineThrough;
+            let ink = if skip_ink {
+                let window = match decoration.style {
+                    TextDecorationStyle::Wavy => Band {
+                        start: band.start - band.thickness(),
+                        end: band.end + band.thickness(),
+                    },
+                    TextDecorationStyle::Double => {
+                        let second = band.offset(outward * band.thickness() * 2.0);
+                        Band {
+                            start: band.start.min(second.start),
+                            end: band.end.max(second.end),
+                        }
+                    }
+                    _ => band,
+                };
+                ink_intervals(&glyphs[line.glyphs.clone()], window, vertical)
+            } else {
+                Vec::new()
+            };
+            let segments =
+                subtract_intervals(line.inline_start, line.inline_end, &ink, band.thickness());
+            let commands =
+                decoration_commands(decoration.style, band, outward, &segments, vertical);
+            if commands.is_empty() {
+                continue;
+            }
+            let paint = decoration
+                .color
+                .map(GlyphPaint::Solid)
+                .unwrap_or(GlyphPaint::CurrentColor);
+            let layer = if decoration.style == TextDecorationStyle::Wavy {
+                PathGlyphLayer::stroke(commands, paint, band.thickness())
+            } else {
+                PathGlyphLayer::new(commands, paint)
+            };
+            output.push(PositionedGlyph::new(
+                Glyph::new(vec![GlyphLayer::Path(layer)]),
+                0.0,
+                0.0,
+            ));
+        }
+    }
+    output
+}
+
+/// Returns the first stroke band and the direction that points away from the text.
+fn decoration_band(
+    line: TextDecorationLine,
+    metrics: &DecorationMetrics,
+    decorated: &DecoratedLine,
+    vertical: bool,
+) -> (Band, f32) {
+    let underline = metrics.underline_thickness;
+    let strikeout = metrics.strikeout_thickness;
+    let origin = decorated.cross_origin;
+    if !vertical {
+        return match line {
+            TextDecorationLine::Underline => {
+                let start = origin - metrics.underline_position;
+                (
+                    Band {
+                        start,
+                        end: start + underline,
+                    },
+                    1.0,
+                )
+            }
+            TextDecorationLine::Overline => {
+                let start = origin - metrics.ascent;
+                (
+                    Band {
+                        start,
+                        end: start + underline,
+                    },
+                    -1.0,
+                )
+            }
+            TextDecorationLine::LineThrough => {
+                let start = origin - metrics.strikeout_position;
+                (
+                    Band {
+                        start,
+                        end: start + strikeout,
+                    },
+                    0.0,
+                )
+            }
+        };
+    }
+
+    match line {
+        TextDecorationLine::Underline => {
+            let gap = (-metrics.underline_position - metrics.descent).max(0.0);
+            let end = origin - gap;
+            (
+                Band {
+                    start: end - underline,
+                    end,
+                },
+                -1.0,
+            )
+        }
+        TextDecorationLine::Overline => {
+            let end = origin + metrics.em_size;
+            (
+                Band {
+                    start: end - underline,
+                    end,
+                },
+                1.0,
+            )
+        }
+        TextDecorationLine::LineThrough => {
+            let center = origin + metrics.em_size * 0.5;
+            (
+                Band {
+                    start: center - strikeout * 0.5,
+                    end: center + strikeout * 0.5,
+                },
+                0.0,
+            )
+        }
+    }
+}
+
+fn decoration_commands(
+    style: TextDecorationStyle,
+    band: Band,
+    outward: f32,
+    segments: &[(f32, f32)],
+    vertical: bool,
+) -> Vec<Command> {
+    let thickness = band.thickness();
+    let mut commands = Vec::new();
+    for &(start, end) in segments {
+        match style {
+            TextDecorationStyle::Solid => push_rect(&mut commands, start, end, band, vertical),
+            TextDecorationStyle::Double => {
+                let (first, second) = if outward == 0.0 {
+                    (band.offset(-thickness), band.offset(thickness))
+                } else {
+                    (band, band.offset(outward * thickness * 2.0))
+                };
+                push_rect(&mut commands, start, end, first, vertical);
+                push_rect(&mut commands, start, end, second, vertical);
+            }
+            TextDecorationStyle::Dotted => {
+                let radius = thickness * 0.5;
+                let mut center = start + radius;
+                while center + radius <= end + f32::EPSILON {
+                    push_dot(&mut commands, center, band.center(), radius, vertical);
+                    center += thickness * 2.0;
+                }
+            }
+            TextDecorationStyle::Wavy => {
+                let wavelength = thickness * 4.0;
+                let amplitude = thickness;
+                let cross = band.center();
+                commands.push(move_to(start, cross, vertical));
+                let mut position = start;
+                let mut sign = -1.0;
+                while position < end {
+                    let next = (position + wavelength * 0.5).min(end);
+                    let control = (position + next) * 0.5;
+                    commands.push(bezier(
+                        (control, cross + sign * amplitude * 2.0),
+                        (next, cross),
+                        vertical,
+                    ));
+                    position = next;
+                    sign = -sign;
+                }
+            }
+        }
+    }
+    commands
+}
+
+fn point(inline: f32, cross: f32, vertical: bool) -> (f32, f32) {
+    if vertical {
+        (cross, inline)
+    } else {
+        (inline, cross)
+    }
+}
+
+fn move_to(inline: f32, cross: f32, vertical: bool) -> Command {
+    let (x, y) = point(inline, cross, vertical);
+    Command::MoveTo(x, y)
+}
+
+fn line_to(inline: f32, cross: f32, vertical: bool) -> Command {
+    let (x, y) = point(inline, cross, vertical);
+    Command::Line(x, y)
+}
+
+fn bezier(control: (f32, f32), end: (f32, f32), vertical: bool) -> Command {
+    Command::Bezier(
+        point(control.0, control.1, vertical),
+        point(end.0, end.1, vertical),
+    )
+}
+
+fn push_rect(commands: &mut Vec<Command>, start: f32, end: f32, band: Band, vertical: bool) {
+    commands.push(move_to(start, band.start, vertical));
+    commands.push(line_to(end, band.start, vertical));
+    commands.push(line_to(end, band.end, vertical));
+    commands.push(line_to(start, band.end, vertical));
+    commands.push(Command::Close);
+}
+
+fn push_dot(commands: &mut Vec<Command>, inline: f32, cross: f32, radius: f32, vertical: bool) {
+    // Four cubic arcs; 0.5523 is the usual circle approximation constant.
+    let k = radius * 0.552_284_8;
+    let arcs = [
+        ((radius, k), (k, radius), (0.0, radius)),
+        ((-k, radius), (-radius, k), (-radius, 0.0)),
+        ((-radius, -k), (-k, -radius), (0.0, -radius)),
+        ((k, -radius), (radius, -k), (radius, 0.0)),
+    ];
+    commands.push(move_to(inline + radius, cross, vertical));
+    for (c1, c2, end) in arcs {
+        commands.push(Command::CubicBezier(
+            point(inline + c1.0, cross + c1.1, vertical),
+            point(inline + c2.0, cross + c2.1, vertical),
+            point(inline + end.0, cross + end.1, vertical),
+        ));
+    }
+    commands.push(Command::Close);
+}
+
+/// Inline ranges where glyph outlines enter the cross-axis `window`.
+fn ink_intervals(glyphs: &[PositionedGlyph], window: Band, vertical: bool) -> Vec<(f32, f32)> {
+    let mut intervals = Vec::new();
+    for glyph in glyphs {
+        for layer in &glyph.glyph.layers {
+            let GlyphLayer::Path(path) = layer else {
+                continue;
+            };
+            let origin_x = glyph.x + path.offset_x;
+            let origin_y = glyph.y + path.offset_y;
+            let mut range: Option<(f32, f32)> = None;
+            for_each_segment(&path.commands, |from, to| {
+                let from = (from.0 + origin_x, from.1 + origin_y);
+                let to = (to.0 + origin_x, to.1 + origin_y);
+                let (from, to) = if vertical {
+                    ((from.1, from.0), (to.1, to.0))
+                } else {
+                    (from, to)
+                };
+                if let Some((lo, hi)) = clip_segment_to_band(from, to, window) {
+                    range = Some(match range {
+                        Some((start, end)) => (start.min(lo), end.max(hi)),
+                        None => (lo, hi),
+                    });
+                }
+            });
+            if let Some(range) = range {
+                intervals.push(range);
+            }
+        }
+    }
+    intervals
+}
+
+/// Inline extent of the part of segment `from -> to` (inline, cross) inside `band`.
+fn clip_segment_to_band(from: (f32, f32), to: (f32, f32), band: Band) -> Option<(f32, f32)> {
+    let delta = to.1 - from.1;
+    let (t0, t1) = if delta.abs() <= f32::EPSILON {
+        if from.1 < band.start || from.1 > band.end {
+            return None;
+        }
+        (0.0, 1.0)
+    } else {
+        let a = (band.start - from.1) / delta;
+        let b = (band.end - from.1) / delta;
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        let (t0, t1) = (a.max(0.0), b.min(1.0));
+        if t0 > t1 {
+            return None;
+        }
+        (t0, t1)
+    };
+    let p0 = from.0 + (to.0 - from.0) * t0;
+    let p1 = from.0 + (to.0 - from.0) * t1;
+    Some((p0.min(p1), p0.max(p1)))
+}
+
+/// Flattens path commands into line segments.
+fn for_each_segment(commands: &[Command], mut visit: impl FnMut((f32, f32), (f32, f32))) {
+    const STEPS: usize = 8;
+    let mut current = (0.0, 0.0);
+    let mut start = (0.0, 0.0);
+    for command in commands {
+        match *command {
+            Command::MoveTo(x, y) => {
+                current = (x, y);
+                start = current;
+            }
+            Command::Line(x, y) => {
+                visit(current, (x, y));
+                current = (x, y);
+            }
+            Command::Bezier(control, end) => {
+                let mut previous = current;
+                for step in 1..=STEPS {
+                    let t = step as f32 / STEPS as f32;
+                    let u = 1.0 - t;
+                    let next = (
+                        u * u * current.0 + 2.0 * u * t * control.0 + t * t * end.0,
+                        u * u * current.1 + 2.0 * u * t * control.1 + t * t * end.1,
+                    );
+                    visit(previous, next);
+                    previous = next;
+                }
+                current = end;
+            }
+            Command::CubicBezier(c1, c2, end) => {
+                let mut previous = current;
+                for step in 1..=STEPS {
+                    let t = step as f32 / STEPS as f32;
+                    let u = 1.0 - t;
+                    let next = (
+                        u * u * u * current.0
+                            + 3.0 * u * u * t * c1.0
+                            + 3.0 * u * t * t * c2.0
+                            + t * t * t * end.0,
+                        u * u * u * current.1
+                            + 3.0 * u * u * t * c1.1
+                            + 3.0 * u * t * t * c2.1
+                            + t * t * t * end.1,
+                    );
+                    visit(previous, next);
+                    previous = next;
+                }
+                current = end;
+            }
+            Command::Close => {
+                visit(current, start);
+                current = start;
+            }
+        }
+    }
+}
+
+/// Removes padded `ink` ranges from `[start, end]`, dropping pieces shorter than `gap`.
+fn subtract_intervals(start: f32, end: f32, ink: &[(f32, f32)], gap: f32) -> Vec<(f32, f32)> {
+    let mut ink: Vec<(f32, f32)> = ink.iter().map(|&(lo, hi)| (lo - gap, hi + gap)).collect();
+    ink.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut segments = Vec::new();
+    let mut cursor = start;
+    for (lo, hi) in ink {
+        if lo > cursor {
+            segments.push((cursor, lo.min(end)));
+        }
+        cursor = cursor.max(hi);
+        if cursor >= end {
+            break;
+        }
+    }
+    if cursor < end {
+        segments.push((cursor, end));
+    }
+    segments.retain(|&(lo, hi)| hi - lo >= gap);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> DecorationMetrics {
+        DecorationMetrics {
+            underline_position: -2.0,
+            underline_thickness: 1.0,
+            strikeout_position: 5.0,
+            strikeout_thickness: 1.0,
+            ascent: 16.0,
+            descent: 4.0,
+            em_size: 20.0,
+        }
+    }
+
+    fn descender_glyph(x: f32) -> PositionedGlyph {
+        let commands = vec![
+            Command::MoveTo(4.0, -10.0),
+            Command::Line(6.0, -10.0),
+            Command::Line(6.0, 4.0),
+            Command::Line(4.0, 4.0),
+            Command::Close,
+        ];
+        PositionedGlyph::new(
+            Glyph::new(vec![GlyphLayer::Path(PathGlyphLayer::new(
+                commands,
+                GlyphPaint::CurrentColor,
+            ))]),
+            x,
+            0.0,
+        )
+    }
+
+    #[test]
+    fn underline_skips_ink_around_descenders() {
+        let glyphs = vec![descender_glyph(0.0)];
+        let lines = [DecoratedLine {
+            glyphs: 0..1,
+            cross_origin: 0.0,
+            inline_start: 0.0,
+            inline_end: 20.0,
+        }];
+        let decorations = build_decoration_glyphs(
+            &glyphs,
+            &lines,
+            &[TextDecoration::underline()],
+            &metrics(),
+            false,
+        );
+
+        assert_eq!(decorations.len(), 1);
+        let GlyphLayer::Path(layer) = &decorations[0].glyph.layers[0] else {
+            panic!("expected path layer");
+        };
+        let rects = layer
+            .commands
+            .iter()
+            .filter(|command| matches!(command, Command::Close))
+            .count();
+        assert_eq!(rects, 2);
+        assert!(matches!(layer.commands[0], Command::MoveTo(x, y) if x == 0.0 && y == 2.0));
+    }
+
+    #[test]
+    fn line_through_ignores_skip_ink() {
+        let glyphs = vec![descender_glyph(0.0)];
+        let lines = [DecoratedLine {
+            glyphs: 0..1,
+            cross_origin: 0.0,
+            inline_start: 0.0,
+            inline_end: 20.0,
+        }];
+        let decorations = build_decoration_glyphs(
+            &glyphs,
+            &lines,
+            &[TextDecoration::line_through().with_style(TextDecorationStyle::Double)],
+            &metrics(),
+            false,
+        );
+
+        let GlyphLayer::Path(layer) = &decorations[0].glyph.layers[0] else {
+            panic!("expected path layer");
+        };
+        assert_eq!(layer.commands.len(), 10);
+    }
+
+    #[test]
+    fn vertical_underline_runs_along_left_edge() {
+        let lines = [DecoratedLine {
+            glyphs: 0..0,
+            cross_origin: 0.0,
+            inline_start: 0.0,
+            inline_end: 40.0,
+        }];
+        let decorations = build_decoration_glyphs(
+            &[],
+            &lines,
+            &[TextDecoration::underline().with_color(0xff00_00ff)],
+            &metrics(),
+            true,
+        );
+
+        let GlyphLayer::Path(layer) = &decorations[0].glyph.layers[0] else {
+            panic!("expected path layer");
+        };
+        assert_eq!(layer.paint, GlyphPaint::Solid(0xff00_00ff));
+        assert!(matches!(layer.commands[0], Command::MoveTo(x, y) if x == -1.0 && y == 0.0));
+        assert!(matches!(layer.commands[1], Command::Line(x, y) if x == -1.0 && y == 40.0));
+    }
+
+    #[test]
+    fn subtract_intervals_drops_short_pieces() {
+        let segments = subtract_intervals(0.0, 10.0, &[(2.0, 3.0), (3.5, 8.5)], 1.0);
+
+        assert_eq!(segments, vec![(0.0, 1.0)]);
+    }
+}
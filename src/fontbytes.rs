@@ -0,0 +1,247 @@
+//! Shared font bytes and tables parsed on first access.
+
+use bin_rs::reader::{BinaryReader, BytesReader};
+use bin_rs::Endian;
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, OnceLock};
+
+/// Immutable font bytes shared by a [`crate::FontFile`], its faces and their clones.
+///
+/// Accepts anything that can lend a byte slice for as long as the font lives:
+/// `Vec<u8>`, `Arc<[u8]>`, a `&'static [u8]` from `include_bytes!`, or a
+/// memory map.
+#[derive(Clone)]
+pub struct FontBytes {
+    bytes: Arc<dyn AsRef<[u8]> + Send + Sync>,
+}
+
+impl FontBytes {
+    /// Wraps owned or memory-mapped bytes without copying them.
+    pub fn new(bytes: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        Self {
+            bytes: Arc::new(bytes),
+        }
+    }
+
+    /// Borrows bytes that live for the whole program.
+    pub fn from_static(bytes: &'static [u8]) -> Self {
+        Self::new(bytes)
+    }
+
+    /// Returns the font bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        (*self.bytes).as_ref()
+    }
+
+    /// Returns the byte length.
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    /// Returns `true` when there are no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a big-endian reader over one table record. A length running past
+    /// the end of the data is clamped; an offset past it is an error.
+    pub(crate) fn table_reader(&self, offset: u32, length: u32) -> Result<BytesReader, Error> {
+        let bytes = self.as_bytes();
+        let start = offset as usize;
+        if start > bytes.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "table offset {offset:#x} is past the end of {} bytes of font data",
+                    bytes.len()
+                ),
+            ));
+        }
+        let end = start.saturating_add(length as usize).min(bytes.len());
+        let mut reader = BytesReader::new(&bytes[start..end]);
+        reader.set_endian(Endian::BigEndian);
+        Ok(reader)
+    }
+}
+
+impl From<Vec<u8>> for FontBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<Arc<[u8]>> for FontBytes {
+    fn from(bytes: Arc<[u8]>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<&'static [u8]> for FontBytes {
+    fn from(bytes: &'static [u8]) -> Self {
+        Self::from_static(bytes)
+    }
+}
+
+impl std::fmt::Debug for FontBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FontBytes")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// The unparsed bytes of one table record.
+#[derive(Debug, Clone)]
+pub(crate) struct RawTable {
+    pub(crate) tag: u32,
+    data: FontBytes,
+    offset: u32,
+    length: u32,
+}
+
+impl RawTable {
+    pub(crate) fn new(tag: u32, data: FontBytes, offset: u32, length: u32) -> Self {
+        Self {
+            tag,
+            data,
+            offset,
+            length,
+        }
+    }
+
+    /// Returns the table bytes, clamped to the available data.
+    pub(crate) fn bytes(&self) -> &[u8] {
+        let bytes = self.data.as_bytes();
+        let start = (self.offset as usize).min(bytes.len());
+        let end = start.saturating_add(self.length as usize).min(bytes.len());
+        &bytes[start..end]
+    }
+}
+
+/// One table record whose parsed form is built on first access and then shared
+/// by every clone.
+pub(crate) struct LazyTable<T> {
+    inner: Arc<LazyTableInner<T>>,
+}
+
+struct LazyTableInner<T> {
+    data: FontBytes,
+    offset: u32,
+    length: u32,
+    parsed: OnceLock<Result<T, ParseError>>,
+}
+
+/// A parse failure kept so every later access can report it again.
+#[derive(Debug)]
+struct ParseError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl<T> LazyTable<T> {
+    pub(crate) fn new(data: FontBytes, offset: u32, length: u32) -> Self {
+        Self {
+            inner: Arc::new(LazyTableInner {
+                data,
+                offset,
+                length,
+                parsed: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Returns the table, running `parse` over a reader positioned on the
+    /// table's own bytes the first time. A table that fails to parse keeps
+    /// returning that error.
+    pub(crate) fn get<F>(&self, parse: F) -> Result<&T, Error>
+    where
+        F: FnOnce(&mut BytesReader, u32) -> Result<T, Error>,
+    {
+        self.inner
+            .parsed
+            .get_or_init(|| {
+                self.inner
+                    .data
+                    .table_reader(self.inner.offset, self.inner.length)
+                    .and_then(|mut reader| parse(&mut reader, self.inner.length))
+                    .map_err(|error| ParseError {
+                        kind: error.kind(),
+                        message: error.to_string(),
+                    })
+            })
+            .as_ref()
+            .map_err(|error| Error::new(error.kind, error.message.clone()))
+    }
+
+    /// Returns the error of a parse that already ran and failed.
+    pub(crate) fn error(&self) -> Option<Error> {
+        match self.inner.parsed.get()? {
+            Ok(_) => None,
+            Err(error) => Some(Error::new(error.kind, error.message.clone())),
+        }
+    }
+
+    pub(crate) fn is_parsed(&self) -> bool {
+        self.inner.parsed.get().is_some()
+    }
+}
+
+impl<T> Clone for LazyTable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for LazyTable<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyTable")
+            .field("offset", &self.inner.offset)
+            .field("length", &self.inner.length)
+            .field("parsed", &self.is_parsed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bin_rs::reader::BinaryReader;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn lazy_table_parses_its_own_range_once_and_shares_it_with_clones() {
+        let data = FontBytes::from(vec![0xAA, 0xBB, 0x12, 0x34, 0xCC]);
+        let table = LazyTable::<u16>::new(data, 2, 2);
+        let clone = table.clone();
+        assert!(!table.is_parsed());
+
+        let calls = AtomicUsize::new(0);
+        let parse = |reader: &mut BytesReader, length: u32| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(length, 2);
+            reader.read_u16_be()
+        };
+        assert_eq!(table.get(parse).ok(), Some(&0x1234));
+        assert_eq!(clone.get(parse).ok(), Some(&0x1234));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(clone.is_parsed());
+    }
+
+    #[test]
+    fn lazy_table_keeps_its_parse_error() {
+        let table = LazyTable::<u16>::new(FontBytes::from_static(&[0, 1, 2]), 2, 4);
+        assert!(table.error().is_none(), "nothing has been parsed yet");
+        let error = table.get(|reader, _| reader.read_u16_be()).unwrap_err();
+        assert!(table.is_parsed(), "a failed parse is not retried");
+        let again = table.get(|_, _| Ok(0)).unwrap_err();
+        assert_eq!(again.kind(), error.kind());
+        assert_eq!(again.to_string(), error.to_string());
+        assert_eq!(table.error().map(|error| error.kind()), Some(error.kind()));
+
+        let past_end = LazyTable::<u8>::new(FontBytes::from_static(&[0, 1, 2]), 4, 1);
+        let error = past_end.get(|reader, _| reader.read_u8()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+}
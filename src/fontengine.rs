@@ -3,10 +3,12 @@
 #[cfg(feature = "svg-fonts")]
 use crate::commands::SvgGlyphLayer;
 use crate::commands::{
-    Command, FillRule, FontOptions, FontVariant, FontVariationSetting, GlyphBounds,
-    GlyphGradientSpread, GlyphGradientUnits, GlyphLayer, GlyphLinearGradient, GlyphPaint,
-    GlyphRadialGradient, GlyphRun, PathPaintMode, PositionedGlyph, RasterGlyphLayer,
-    RasterGlyphSource, TextDirection,
+    Baseline, Command, FillRule, FontFeatureSetting, FontOptions, FontPaletteType, FontVariant,
+    FontVariantCaps, FontVariantPosition, FontVariationSetting, GlyphBlendMode, GlyphBounds,
+    GlyphFilter, GlyphGradientSpread, GlyphGradientUnits, GlyphLayer, GlyphLinearGradient,
+    GlyphMask, GlyphMaskMode, GlyphPaint, GlyphRadialGradient, GlyphRun, GlyphSweepGradient,
+    GlyphWidth, NumericFigure, NumericSpacing, PathPaintMode, PositionedGlyph, RasterGlyphLayer,
+    RasterGlyphSource, RichTextSpan, RubyText, ScriptExtent, TextDecoration, TextDirection,
 };
 use crate::fontface::FontFace;
 use crate::util;
@@ -54,6 +56,7 @@ pub struct FontEngine<'a> {
     options: FontOptions<'a>,
     shaping_policy: ShapingPolicy,
     svg_unit: String,
+    ruby_scale: f32,
 }
 
 impl<'a> FontEngine<'a> {
@@ -64,6 +67,7 @@ impl<'a> FontEngine<'a> {
             options: FontOptions::new(face),
             shaping_policy: ShapingPolicy::default(),
             svg_unit: "px".to_string(),
+            ruby_scale: crate::ruby::DEFAULT_RUBY_SCALE,
         }
     }
 
@@ -98,6 +102,49 @@ impl<'a> FontEngine<'a> {
         self
     }
 
+    /// Selects small capitals through `smcp`/`c2sc`, synthesized when the font lacks them.
+    pub fn with_font_variant_caps(mut self, caps: FontVariantCaps) -> Self {
+        self.options = self.options.with_font_variant_caps(caps);
+        self
+    }
+
+    /// Selects superscript or subscript forms through `sups`/`subs`, synthesized
+    /// from the OS/2 metrics when the font lacks them.
+    pub fn with_font_variant_position(mut self, position: FontVariantPosition) -> Self {
+        self.options = self.options.with_font_variant_position(position);
+        self
+    }
+
+    /// Selects the CPAL palette used by COLR glyphs.
+    pub fn with_palette(mut self, palette: usize) -> Self {
+        self.options = self.options.with_palette(palette);
+        self
+    }
+
+    /// Selects the first palette meant for a light or dark background.
+    pub fn with_palette_type(mut self, palette_type: FontPaletteType) -> Self {
+        self.options = self.options.with_palette_type(palette_type);
+        self
+    }
+
+    /// Replaces one palette entry with `color` (`0xAARRGGBB`).
+    pub fn with_palette_override(mut self, entry: u16, color: u32) -> Self {
+        self.options = self.options.with_palette_override(entry, color);
+        self
+    }
+
+    /// Sets the text color used for the CPAL foreground entry.
+    pub fn with_foreground_color(mut self, color: u32) -> Self {
+        self.options = self.options.with_foreground_color(color);
+        self
+    }
+
+    /// Prefers hand-tuned `EBDT` bitmaps over outlines at their exact ppem.
+    pub fn with_embedded_bitmaps(mut self, embedded_bitmaps: bool) -> Self {
+        self.options = self.options.with_embedded_bitmaps(embedded_bitmaps);
+        self
+    }
+
     /// Sets one variable-font axis value such as `wght=700`.
     pub fn with_variation(mut self, tag: &str, value: f32) -> Self {
         self.options = self.options.with_variation(tag, value);
@@ -116,6 +163,101 @@ impl<'a> FontEngine<'a> {
         self
     }
 
+    /// Selects an `fvar` named instance such as `"Bold Condensed"` by subfamily
+    /// or PostScript name. Unknown names leave the variations unchanged.
+    pub fn with_named_instance(mut self, name: &str) -> Self {
+        if let Some(instance) = self.face.find_named_instance(name) {
+            self.options = self.options.with_variations(&instance.coordinates);
+        }
+        self
+    }
+
+    /// Sets one OpenType feature such as `ruby=1` or `liga=0`.
+    pub fn with_feature(mut self, tag: &str, value: u32) -> Self {
+        self.options = self.options.with_feature(tag, value);
+        self
+    }
+
+    /// Replaces the current OpenType feature settings.
+    pub fn with_features(mut self, features: &[FontFeatureSetting]) -> Self {
+        self.options = self.options.with_features(features);
+        self
+    }
+
+    /// Clears all OpenType feature settings.
+    pub fn clear_features(mut self) -> Self {
+        self.options = self.options.clear_features();
+        self
+    }
+
+    /// Selects full-, half-, proportional-, third- or quarter-width glyphs.
+    pub fn with_glyph_width(mut self, width: GlyphWidth) -> Self {
+        self.options = self.options.with_glyph_width(width);
+        self
+    }
+
+    /// Selects lining or old-style figures.
+    pub fn with_numeric_figure(mut self, figure: NumericFigure) -> Self {
+        self.options = self.options.with_numeric_figure(figure);
+        self
+    }
+
+    /// Selects proportional or tabular figures.
+    pub fn with_numeric_spacing(mut self, spacing: NumericSpacing) -> Self {
+        self.options = self.options.with_numeric_spacing(spacing);
+        self
+    }
+
+    /// Shorthand for `tnum`, which gives every digit the same advance.
+    pub fn with_tabular_numbers(self) -> Self {
+        self.with_numeric_spacing(NumericSpacing::Tabular)
+    }
+
+    /// Renders `1/2` style sequences as diagonal fractions through `frac`.
+    pub fn with_diagonal_fractions(mut self) -> Self {
+        self.options = self.options.with_diagonal_fractions();
+        self
+    }
+
+    /// Adds a text decoration, replacing any existing one on the same line.
+    pub fn with_decoration(mut self, decoration: TextDecoration) -> Self {
+        self.options = self.options.with_decoration(decoration);
+        self
+    }
+
+    /// Shorthand for a solid underline.
+    pub fn with_underline(self) -> Self {
+        self.with_decoration(TextDecoration::underline())
+    }
+
+    /// Shorthand for a solid overline.
+    pub fn with_overline(self) -> Self {
+        self.with_decoration(TextDecoration::overline())
+    }
+
+    /// Shorthand for a solid line-through.
+    pub fn with_line_through(self) -> Self {
+        self.with_decoration(TextDecoration::line_through())
+    }
+
+    /// Clears all text decorations.
+    pub fn clear_decorations(mut self) -> Self {
+        self.options = self.options.clear_decorations();
+        self
+    }
+
+    /// Aligns `FontFamily` fallback faces to this baseline of the primary face.
+    pub fn with_dominant_baseline(mut self, baseline: Baseline) -> Self {
+        self.options = self.options.with_dominant_baseline(baseline);
+        self
+    }
+
+    /// Sets the ruby font size relative to the base font size. The default is `0.5`.
+    pub fn with_ruby_scale(mut self, ruby_scale: f32) -> Self {
+        self.ruby_scale = ruby_scale;
+        self
+    }
+
     /// Convenience shorthand for `jp78`.
     pub fn with_jis78(self) -> Self {
         self.with_font_variant(FontVariant::Jis78)
@@ -181,6 +323,16 @@ impl<'a> FontEngine<'a> {
         &self.options.variations
     }
 
+    /// Returns the currently selected OpenType feature settings.
+    pub fn feature_settings(&self) -> &[FontFeatureSetting] {
+        &self.options.features
+    }
+
+    /// Returns the ruby font size relative to the base font size.
+    pub fn ruby_scale(&self) -> f32 {
+        self.ruby_scale
+    }
+
     /// Returns the effective options used by this engine.
     pub fn options(&self) -> FontOptions<'a> {
         self.options
@@ -188,6 +340,38 @@ impl<'a> FontEngine<'a> {
             .with_text_direction(self.shaping_policy.text_direction())
     }
 
+    /// Returns the position of `baseline` for an OpenType script tag in px.
+    pub fn baseline_offset(&self, baseline: Baseline, script: &str) -> Result<f32, Error> {
+        self.face.baseline_offset(baseline, script, self.options())
+    }
+
+    /// Returns the BASE min/max extent for a script and optional language tag.
+    pub fn script_extent(
+        &self,
+        script: &str,
+        language: Option<&str>,
+    ) -> Result<Option<ScriptExtent>, Error> {
+        self.face.script_extent(script, language, self.options())
+    }
+
+    /// Returns the MATH table constants scaled to the engine's font size.
+    #[cfg(feature = "layout")]
+    pub fn math_constants(&self) -> Result<Option<crate::MathConstants>, Error> {
+        self.face.math_constants(self.options())
+    }
+
+    /// Builds a stretched MATH glyph at least `target_size` px long.
+    #[cfg(feature = "layout")]
+    pub fn math_stretch_glyph(
+        &self,
+        glyph_id: u16,
+        target_size: f32,
+        vertical: bool,
+    ) -> Result<GlyphRun, Error> {
+        self.face
+            .math_stretch_glyph(glyph_id, target_size, vertical, self.options())
+    }
+
     /// Shapes text into a [`GlyphRun`].
     pub fn shape(&self, text: &str) -> Result<GlyphRun, Error> {
         self.text2glyph_run(text)
@@ -200,6 +384,26 @@ impl<'a> FontEngine<'a> {
         crate::commands::text2commands(text, options)
     }
 
+    /// Shapes independent strings on scoped threads; see [`crate::shape_parallel`].
+    pub fn shape_parallel<T: AsRef<str> + Sync>(
+        &self,
+        texts: &[T],
+    ) -> Vec<Result<GlyphRun, Error>> {
+        let mut options = self.options();
+        options.font = Some(crate::FontRef::Loaded(self.face));
+        crate::commands::shape_parallel(texts, &options)
+    }
+
+    /// Shapes base text with ruby annotations into one [`GlyphRun`].
+    pub fn text2glyph_run_with_ruby(&self, spans: &[RubyText]) -> Result<GlyphRun, Error> {
+        let options = self.options();
+        crate::ruby::layout_ruby(spans, &options, self.ruby_scale, |text, options| {
+            let mut options = options.clone();
+            options.font = Some(crate::FontRef::Loaded(self.face));
+            crate::commands::text2commands(text, options)
+        })
+    }
+
     /// Alias for [`FontEngine::text2glyph_run`].
     pub fn text2commands(&self, text: &str) -> Result<GlyphRun, Error> {
         self.text2glyph_run(text)
@@ -209,7 +413,12 @@ impl<'a> FontEngine<'a> {
     pub fn measure(&self, text: &str) -> Result<f64, Error> {
         let mut options = self.options();
         options.font = Some(crate::FontRef::Loaded(self.face));
-        self.face.font().measure_with_options(text, &options)
+        self.face.font().measure_with_plan(
+            text,
+            &options,
+            &self.face.shape_plan(&options),
+            Some(self.face.outline_cache()),
+        )
     }
 
     /// Renders shaped text to SVG.
@@ -227,6 +436,42 @@ impl<'a> FontEngine<'a> {
         let run = self.text2glyph_run(text)?;
         glyph_run_to_svg(&run, &self.svg_unit)
     }
+
+    /// Shapes styled spans into one [`GlyphRun`]. Spans without a font use this engine's face.
+    pub fn spans2glyph_run(&self, spans: &[RichTextSpan<'_>]) -> Result<GlyphRun, Error> {
+        crate::richtext::layout_rich_text(spans, Some(crate::FontRef::Loaded(self.face)))
+    }
+
+    /// Renders styled spans to SVG.
+    pub fn spans2svg(&self, spans: &[RichTextSpan<'_>]) -> Result<String, Error> {
+        let run = self.spans2glyph_run(spans)?;
+        glyph_run_to_svg(&run, &self.svg_unit)
+    }
+
+    /// Shapes Unity-style rich text markup such as `<b>`, `<color=red>`, or `<size=24>`.
+    pub fn rich_text2glyph_run(&self, markup: &str) -> Result<GlyphRun, Error> {
+        let mut options = self.options();
+        options.font = Some(crate::FontRef::Loaded(self.face));
+        let spans = crate::commands::parse_rich_text(markup, &options);
+        self.spans2glyph_run(&spans)
+    }
+
+    /// Alias for [`FontEngine::rich_text2glyph_run`].
+    pub fn rich_text2commands(&self, markup: &str) -> Result<GlyphRun, Error> {
+        self.rich_text2glyph_run(markup)
+    }
+
+    /// Renders Unity-style rich text markup to SVG.
+    pub fn rich_text2svg(&self, markup: &str) -> Result<String, Error> {
+        let run = self.rich_text2glyph_run(markup)?;
+        glyph_run_to_svg(&run, &self.svg_unit)
+    }
+
+    /// Renders base text with ruby annotations to SVG.
+    pub fn render_svg_with_ruby(&self, spans: &[RubyText]) -> Result<String, Error> {
+        let run = self.text2glyph_run_with_ruby(spans)?;
+        glyph_run_to_svg(&run, &self.svg_unit)
+    }
 }
 
 #[cfg(test)]
@@ -351,6 +596,149 @@ mod tests {
         assert!(svg.contains("<clipPath id=\"glyph-clip-"));
         assert!(svg.contains("clip-path=\"url(#glyph-clip-"));
     }
+
+    #[test]
+    fn glyph_run_to_svg_moves_user_space_paint_with_glyph_origin() {
+        let mut layer = crate::PathGlyphLayer::new(
+            vec![Command::MoveTo(0.0, 0.0), Command::Line(10.0, 0.0), Command::Close],
+            GlyphPaint::LinearGradient(crate::GlyphLinearGradient {
+                x1: 0.0,
+                y1: 0.0,
+                x2: 10.0,
+                y2: 0.0,
+                units: crate::GlyphGradientUnits::UserSpaceOnUse,
+                transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+                spread: crate::GlyphGradientSpread::Pad,
+                stops: Vec::new(),
+            }),
+        );
+        layer.clip_commands = vec![
+            Command::MoveTo(1.0, 2.0),
+            Command::Line(3.0, 2.0),
+            Command::Close,
+        ];
+        let run = GlyphRun::new(vec![PositionedGlyph::new(
+            crate::Glyph::new(vec![GlyphLayer::Path(layer)]),
+            20.0,
+            5.0,
+        )]);
+
+        let svg = glyph_run_to_svg(&run, "px").expect("svg export");
+
+        assert!(svg.contains("gradientTransform=\"matrix(1 0 0 1 20 5)\""));
+        assert!(svg.contains("<clipPath id=\"glyph-clip-0\"><path d=\"M21 7 "));
+    }
+
+    #[test]
+    fn glyph_run_to_svg_writes_mask_and_filter_defs() {
+        let square = vec![
+            Command::MoveTo(0.0, 0.0),
+            Command::Line(10.0, 0.0),
+            Command::Line(10.0, 10.0),
+            Command::Close,
+        ];
+        let mut layer = crate::PathGlyphLayer::new(square.clone(), GlyphPaint::CurrentColor);
+        layer.opacity = 0.5;
+        layer.mask = Some(crate::GlyphMask {
+            mode: crate::GlyphMaskMode::Alpha,
+            layers: vec![crate::PathGlyphLayer::new(
+                square,
+                GlyphPaint::Solid(0xff00_0000),
+            )],
+        });
+        layer.filters = vec![
+            GlyphFilter::GaussianBlur {
+                std_deviation_x: 2.0,
+                std_deviation_y: 2.0,
+            },
+            GlyphFilter::Flood { color: 0x80ff_0000 },
+        ];
+        let run = GlyphRun::new(vec![PositionedGlyph::new(
+            crate::Glyph::new(vec![GlyphLayer::Path(layer)]),
+            5.0,
+            0.0,
+        )]);
+
+        let svg = glyph_run_to_svg(&run, "px").expect("svg export");
+
+        assert!(svg.contains("<g filter=\"url(#glyph-filter-0)\" mask=\"url(#glyph-mask-1)\" opacity=\"0.5\"><path d=\"M5 0 "));
+        assert!(svg.contains("<mask id=\"glyph-mask-1\" mask-type=\"alpha\""));
+        assert!(svg
+            .contains("<feGaussianBlur in=\"SourceGraphic\" stdDeviation=\"2 2\" result=\"f0\"/>"));
+        assert!(svg.contains("<feFlood flood-color=\"#ff0000\" flood-opacity=\"0.5019608\"/><feComposite in2=\"f0\" operator=\"in\" result=\"f1\"/>"));
+    }
+
+    #[test]
+    fn glyph_run_to_svg_draws_sweep_gradient_wedges() {
+        let mut layer = crate::PathGlyphLayer::new(
+            vec![
+                Command::MoveTo(0.0, 0.0),
+                Command::Line(10.0, 0.0),
+                Command::Line(10.0, 10.0),
+                Command::Close,
+            ],
+            GlyphPaint::SweepGradient(crate::GlyphSweepGradient {
+                cx: 5.0,
+                cy: 5.0,
+                start_angle: 0.0,
+                end_angle: 360.0,
+                transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+                spread: GlyphGradientSpread::Pad,
+                stops: vec![
+                    crate::GlyphGradientStop {
+                        offset: 0.0,
+                        color: 0xffff_0000,
+                    },
+                    crate::GlyphGradientStop {
+                        offset: 1.0,
+                        color: 0xff00_00ff,
+                    },
+                ],
+            }),
+        );
+        layer.blend_mode = GlyphBlendMode::Screen;
+        let run = GlyphRun::new(vec![PositionedGlyph::new(
+            crate::Glyph::new(vec![GlyphLayer::Path(layer)]),
+            20.0,
+            0.0,
+        )]);
+
+        let svg = glyph_run_to_svg(&run, "px").expect("svg export");
+
+        assert!(svg.contains("<clipPath id=\"glyph-clip-0\"><path d=\"M20 0 "));
+        assert!(
+            svg.contains("<g clip-path=\"url(#glyph-clip-0)\" style=\"mix-blend-mode:screen\">")
+        );
+        assert!(svg.contains("transform=\"matrix(1 0 0 1 20 0)\""));
+        assert!(svg.contains("fill=\"#fb0004\""));
+        assert!(svg.contains("fill=\"#0400fb\""));
+    }
+
+    #[test]
+    fn glyph_run_to_svg_embeds_rgba_raster_as_png() {
+        let mut raster =
+            crate::RasterGlyphLayer::from_rgba(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+        raster.offset_y = -8.0;
+        raster.width = Some(8);
+        raster.height = Some(4);
+        let run = GlyphRun::new(vec![PositionedGlyph::new(
+            crate::Glyph::new(vec![GlyphLayer::Raster(raster.clone())]),
+            10.0,
+            0.0,
+        )]);
+
+        let svg = glyph_run_to_svg(&run, "px").expect("svg export");
+        let start = svg.find("data:image/png;base64,").expect("png data") + 22;
+        let end = start + svg[start..].find('"').unwrap();
+        let png = general_purpose::STANDARD.decode(&svg[start..end]).unwrap();
+        assert!(svg.contains("<image x=\"10\" y=\"-8\" width=\"8\" height=\"4\""));
+        assert_eq!(
+            crate::RasterGlyphLayer::from_encoded(png)
+                .to_rgba()
+                .unwrap(),
+            raster.to_rgba().unwrap()
+        );
+    }
 }
 
 pub(crate) fn glyph_run_to_svg(run: &GlyphRun, fontunit: &str) -> Result<String, Error> {
@@ -380,19 +768,7 @@ pub(crate) fn glyph_run_to_svg(run: &GlyphRun, fontunit: &str) -> Result<String,
         for layer in &glyph.glyph.layers {
             match layer {
                 GlyphLayer::Path(path) => {
-                    let d = draw_commands_to_svg_path(
-                        &path.commands,
-                        glyph.x + path.offset_x,
-                        glyph.y + path.offset_y,
-                    );
-                    if d.is_empty() {
-                        continue;
-                    }
-                    body += &format!(
-                        "<path d=\"{}\" {} />",
-                        d,
-                        path_to_svg_attributes(path, &mut registry)
-                    );
+                    body += &path_layer_to_svg(path, (glyph.x, glyph.y), &mut registry);
                 }
                 GlyphLayer::Raster(raster) => {
                     body += &raster_layer_to_svg_image(raster, glyph.x, glyph.y)?;
@@ -606,7 +982,14 @@ fn normalize_svg_color(color: u32) -> u32 {
     }
 }
 
-fn paint_to_svg_attributes(paint: &GlyphPaint, registry: &mut SvgDefRegistry, attribute: &str) -> String {
+/// `origin` is where the layer's glyph space sits in the SVG; user-space
+/// gradients are moved along with the path.
+fn paint_to_svg_attributes(
+    paint: &GlyphPaint,
+    registry: &mut SvgDefRegistry,
+    attribute: &str,
+    origin: (f32, f32),
+) -> String {
     match paint {
         GlyphPaint::CurrentColor => format!("{attribute}=\"currentColor\""),
         GlyphPaint::Solid(color) => {
@@ -628,42 +1011,280 @@ fn paint_to_svg_attributes(paint: &GlyphPaint, registry: &mut SvgDefRegistry, at
             }
         }
         GlyphPaint::LinearGradient(gradient) => {
-            let id = registry.register_linear_gradient(gradient);
+            let mut gradient = gradient.clone();
+            if gradient.units == GlyphGradientUnits::UserSpaceOnUse {
+                gradient.transform = translate_transform(gradient.transform, origin);
+            }
+            let id = registry.register_linear_gradient(&gradient);
             format!("{attribute}=\"url(#{id})\"")
         }
         GlyphPaint::RadialGradient(gradient) => {
-            let id = registry.register_radial_gradient(gradient);
+            let mut gradient = gradient.clone();
+            if gradient.units == GlyphGradientUnits::UserSpaceOnUse {
+                gradient.transform = translate_transform(gradient.transform, origin);
+            }
+            let id = registry.register_radial_gradient(&gradient);
             format!("{attribute}=\"url(#{id})\"")
         }
+        // SVG has no conic gradient; fills are drawn as wedges by
+        // `sweep_layer_to_svg`, strokes fall back to the mid color.
+        GlyphPaint::SweepGradient(gradient) => paint_to_svg_attributes(
+            &GlyphPaint::Solid(gradient_color_at(&gradient.stops, 0.5)),
+            registry,
+            attribute,
+            origin,
+        ),
+    }
+}
+
+/// One path layer placed at `position`; effects wrap it in a group.
+fn path_layer_to_svg(
+    path: &crate::commands::PathGlyphLayer,
+    position: (f32, f32),
+    registry: &mut SvgDefRegistry,
+) -> String {
+    let origin = (position.0 + path.offset_x, position.1 + path.offset_y);
+    let effects = path_effects_to_svg_attributes(path, origin, registry);
+    let fragment = if let (GlyphPaint::SweepGradient(gradient), PathPaintMode::Fill) =
+        (&path.paint, path.paint_mode)
+    {
+        sweep_layer_to_svg(path, gradient, origin, registry)
+    } else {
+        let d = draw_commands_to_svg_path(&path.commands, origin.0, origin.1);
+        if d.is_empty() {
+            return String::new();
+        }
+        format!(
+            "<path d=\"{}\" {} />",
+            d,
+            path_to_svg_attributes(path, registry, origin)
+        )
+    };
+    if effects.is_empty() {
+        fragment
+    } else {
+        format!("<g{effects}>{fragment}</g>")
+    }
+}
+
+/// SVG applies `filter` before `mask` and `opacity`, matching the layer contract.
+fn path_effects_to_svg_attributes(
+    path: &crate::commands::PathGlyphLayer,
+    origin: (f32, f32),
+    registry: &mut SvgDefRegistry,
+) -> String {
+    let mut attributes = String::new();
+    if !path.filters.is_empty() {
+        let id = registry.register_filter(&path.filters);
+        let _ = write!(attributes, " filter=\"url(#{id})\"");
+    }
+    if let Some(mask) = &path.mask {
+        let id = registry.register_mask(mask, origin);
+        let _ = write!(attributes, " mask=\"url(#{id})\"");
     }
+    if path.opacity < 1.0 {
+        let _ = write!(attributes, " opacity=\"{}\"", path.opacity.max(0.0));
+    }
+    attributes
 }
 
 fn path_to_svg_attributes(
     path: &crate::commands::PathGlyphLayer,
     registry: &mut SvgDefRegistry,
+    origin: (f32, f32),
 ) -> String {
     let clip = if path.clip_commands.is_empty() {
         String::new()
     } else {
-        let id = registry.register_clip_path(&path.clip_commands);
+        let id = registry.register_clip_path(&path.clip_commands, origin, FillRule::NonZero);
         format!(" clip-path=\"url(#{id})\"")
     };
+    let blend = blend_mode_to_svg_attribute(path.blend_mode);
     match path.paint_mode {
         PathPaintMode::Fill => format!(
-            "{}{}{}",
-            paint_to_svg_attributes(&path.paint, registry, "fill"),
+            "{}{}{}{}",
+            paint_to_svg_attributes(&path.paint, registry, "fill", origin),
             fill_rule_to_svg_attribute(path.fill_rule),
-            clip
+            clip,
+            blend
         ),
         PathPaintMode::Stroke => format!(
-            "fill=\"none\" {} stroke-width=\"{}\"{}",
-            paint_to_svg_attributes(&path.paint, registry, "stroke"),
+            "fill=\"none\" {} stroke-width=\"{}\"{}{}",
+            paint_to_svg_attributes(&path.paint, registry, "stroke", origin),
             path.stroke_width,
-            clip
+            clip,
+            blend
         ),
     }
 }
 
+/// Angle step of the wedges that approximate a sweep gradient.
+const SWEEP_WEDGE_DEGREES: f32 = 4.0;
+
+/// Draws a sweep-filled layer as solid wedges clipped to the layer outline.
+fn sweep_layer_to_svg(
+    path: &crate::commands::PathGlyphLayer,
+    gradient: &GlyphSweepGradient,
+    origin: (f32, f32),
+    registry: &mut SvgDefRegistry,
+) -> String {
+    let Some(radius) = sweep_gradient_radius(&path.commands, gradient) else {
+        return String::new();
+    };
+    let shape = registry.register_clip_path(&path.commands, origin, path.fill_rule);
+    let mut fragment = format!(
+        "<g clip-path=\"url(#{shape})\"{}>",
+        blend_mode_to_svg_attribute(path.blend_mode)
+    );
+    let mut groups = 2;
+    if !path.clip_commands.is_empty() {
+        let id = registry.register_clip_path(&path.clip_commands, origin, FillRule::NonZero);
+        let _ = write!(fragment, "<g clip-path=\"url(#{id})\">");
+        groups += 1;
+    }
+    let _ = write!(
+        fragment,
+        "<g transform=\"{}\">",
+        gradient_transform_to_svg(&translate_transform(gradient.transform, origin))
+    );
+
+    let wedges = (360.0 / SWEEP_WEDGE_DEGREES) as usize;
+    for index in 0..wedges {
+        let start = index as f32 * SWEEP_WEDGE_DEGREES;
+        // A small overlap hides anti-aliasing seams between wedges.
+        let end = start + SWEEP_WEDGE_DEGREES + 0.5;
+        let offset = sweep_gradient_offset(gradient, start + SWEEP_WEDGE_DEGREES / 2.0);
+        let color = gradient_color_at(&gradient.stops, offset);
+        let point = |angle: f32| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            (gradient.cx + radius * cos, gradient.cy + radius * sin)
+        };
+        let (x0, y0) = point(start);
+        let (x1, y1) = point(end);
+        let _ = write!(
+            fragment,
+            "<path d=\"M{} {} L{} {} L{} {} Z\" {} />",
+            gradient.cx,
+            gradient.cy,
+            x0,
+            y0,
+            x1,
+            y1,
+            paint_to_svg_attributes(&GlyphPaint::Solid(color), registry, "fill", origin)
+        );
+    }
+    fragment += &"</g>".repeat(groups);
+    fragment
+}
+
+/// Radius in gradient space that covers the whole outline.
+fn sweep_gradient_radius(commands: &[Command], gradient: &GlyphSweepGradient) -> Option<f32> {
+    let [a, b, c, d, e, f] = gradient.transform;
+    let determinant = a * d - b * c;
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let mut radius = 0.0f32;
+    for command in commands {
+        let points: &[(f32, f32)] = match command {
+            Command::MoveTo(x, y) | Command::Line(x, y) => &[(*x, *y)],
+            Command::Bezier(control, (x, y)) => &[*control, (*x, *y)],
+            Command::CubicBezier(first, second, end) => &[*first, *second, *end],
+            Command::Close => &[],
+        };
+        for (x, y) in points {
+            let (x, y) = (x - e, y - f);
+            let gradient_x = (d * x - c * y) / determinant;
+            let gradient_y = (a * y - b * x) / determinant;
+            radius = radius.max((gradient_x - gradient.cx).hypot(gradient_y - gradient.cy));
+        }
+    }
+    (radius > 0.0).then_some(radius * 1.01 + 1.0)
+}
+
+/// Color-line offset for `angle` (degrees) after applying the spread method.
+fn sweep_gradient_offset(gradient: &GlyphSweepGradient, angle: f32) -> f32 {
+    let span = gradient.end_angle - gradient.start_angle;
+    if span.abs() < f32::EPSILON {
+        return if angle < gradient.start_angle {
+            0.0
+        } else {
+            1.0
+        };
+    }
+    let offset = (angle - gradient.start_angle) / span;
+    match gradient.spread {
+        GlyphGradientSpread::Pad => offset.clamp(0.0, 1.0),
+        GlyphGradientSpread::Repeat => offset - offset.floor(),
+        GlyphGradientSpread::Reflect => {
+            let offset = offset.rem_euclid(2.0);
+            if offset > 1.0 {
+                2.0 - offset
+            } else {
+                offset
+            }
+        }
+    }
+}
+
+/// Interpolated `0xAARRGGBB` color of sorted `stops` at `offset`.
+fn gradient_color_at(stops: &[crate::GlyphGradientStop], offset: f32) -> u32 {
+    let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+        return 0;
+    };
+    if offset <= first.offset {
+        return first.color;
+    }
+    if offset >= last.offset {
+        return last.color;
+    }
+    for pair in stops.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        if offset <= to.offset {
+            let span = to.offset - from.offset;
+            let t = if span > 0.0 {
+                (offset - from.offset) / span
+            } else {
+                1.0
+            };
+            return (0..4).fold(0u32, |color, channel| {
+                let shift = channel * 8;
+                let from = ((from.color >> shift) & 0xff) as f32;
+                let to = ((to.color >> shift) & 0xff) as f32;
+                color | ((from + (to - from) * t).round() as u32) << shift
+            });
+        }
+    }
+    last.color
+}
+
+/// `transform` followed by a translation to `origin`.
+fn translate_transform(transform: [f32; 6], origin: (f32, f32)) -> [f32; 6] {
+    let [a, b, c, d, e, f] = transform;
+    [a, b, c, d, e + origin.0, f + origin.1]
+}
+
+fn blend_mode_to_svg_attribute(blend_mode: GlyphBlendMode) -> &'static str {
+    match blend_mode {
+        GlyphBlendMode::Normal => "",
+        GlyphBlendMode::Multiply => " style=\"mix-blend-mode:multiply\"",
+        GlyphBlendMode::Screen => " style=\"mix-blend-mode:screen\"",
+        GlyphBlendMode::Overlay => " style=\"mix-blend-mode:overlay\"",
+        GlyphBlendMode::Darken => " style=\"mix-blend-mode:darken\"",
+        GlyphBlendMode::Lighten => " style=\"mix-blend-mode:lighten\"",
+        GlyphBlendMode::ColorDodge => " style=\"mix-blend-mode:color-dodge\"",
+        GlyphBlendMode::ColorBurn => " style=\"mix-blend-mode:color-burn\"",
+        GlyphBlendMode::HardLight => " style=\"mix-blend-mode:hard-light\"",
+        GlyphBlendMode::SoftLight => " style=\"mix-blend-mode:soft-light\"",
+        GlyphBlendMode::Difference => " style=\"mix-blend-mode:difference\"",
+        GlyphBlendMode::Exclusion => " style=\"mix-blend-mode:exclusion\"",
+        GlyphBlendMode::Hue => " style=\"mix-blend-mode:hue\"",
+        GlyphBlendMode::Saturation => " style=\"mix-blend-mode:saturation\"",
+        GlyphBlendMode::Color => " style=\"mix-blend-mode:color\"",
+        GlyphBlendMode::Luminosity => " style=\"mix-blend-mode:luminosity\"",
+    }
+}
+
 #[derive(Default)]
 struct SvgDefRegistry {
     defs: Vec<String>,
@@ -685,14 +1306,76 @@ impl SvgDefRegistry {
         id
     }
 
-    fn register_clip_path(&mut self, commands: &[Command]) -> String {
+    fn register_clip_path(
+        &mut self,
+        commands: &[Command],
+        origin: (f32, f32),
+        fill_rule: FillRule,
+    ) -> String {
         let id = format!("glyph-clip-{}", self.next_id);
         self.next_id += 1;
-        self.defs.push(clip_path_to_svg_def(&id, commands));
+        self.defs
+            .push(clip_path_to_svg_def(&id, commands, origin, fill_rule));
+        id
+    }
+
+    fn register_mask(&mut self, mask: &GlyphMask, origin: (f32, f32)) -> String {
+        let id = format!("glyph-mask-{}", self.next_id);
+        self.next_id += 1;
+        let mut content = String::new();
+        for layer in &mask.layers {
+            content += &path_layer_to_svg(layer, origin, self);
+        }
+        let mask_type = match mask.mode {
+            GlyphMaskMode::Luminance => "luminance",
+            GlyphMaskMode::Alpha => "alpha",
+        };
+        self.defs.push(format!(
+            "<mask id=\"{id}\" mask-type=\"{mask_type}\" maskUnits=\"userSpaceOnUse\" x=\"-100000\" y=\"-100000\" width=\"200000\" height=\"200000\">{content}</mask>"
+        ));
+        id
+    }
+
+    fn register_filter(&mut self, filters: &[GlyphFilter]) -> String {
+        let id = format!("glyph-filter-{}", self.next_id);
+        self.next_id += 1;
+        self.defs.push(filter_to_svg_def(&id, filters));
         id
     }
 }
 
+fn filter_to_svg_def(id: &str, filters: &[GlyphFilter]) -> String {
+    let mut definition = format!(
+        "<filter id=\"{id}\" x=\"-50%\" y=\"-50%\" width=\"200%\" height=\"200%\" color-interpolation-filters=\"sRGB\">"
+    );
+    let mut previous = "SourceGraphic".to_string();
+    for (index, filter) in filters.iter().enumerate() {
+        let result = format!("f{index}");
+        let _ = match *filter {
+            GlyphFilter::GaussianBlur {
+                std_deviation_x,
+                std_deviation_y,
+            } => write!(
+                definition,
+                "<feGaussianBlur in=\"{previous}\" stdDeviation=\"{std_deviation_x} {std_deviation_y}\" result=\"{result}\"/>"
+            ),
+            GlyphFilter::Offset { dx, dy } => write!(
+                definition,
+                "<feOffset in=\"{previous}\" dx=\"{dx}\" dy=\"{dy}\" result=\"{result}\"/>"
+            ),
+            GlyphFilter::Flood { color } => write!(
+                    definition,
+                    "<feFlood flood-color=\"#{:06x}\" flood-opacity=\"{}\"/><feComposite in2=\"{previous}\" operator=\"in\" result=\"{result}\"/>",
+                    color & 0x00ff_ffff,
+                ((color >> 24) & 0xff) as f32 / 255.0
+            ),
+        };
+        previous = result;
+    }
+    definition += "</filter>";
+    definition
+}
+
 fn linear_gradient_to_svg_def(id: &str, gradient: &GlyphLinearGradient) -> String {
     let mut definition = format!(
         "<linearGradient id=\"{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" gradientUnits=\"{}\" gradientTransform=\"{}\" spreadMethod=\"{}\">",
@@ -711,14 +1394,20 @@ fn linear_gradient_to_svg_def(id: &str, gradient: &GlyphLinearGradient) -> Strin
 }
 
 fn radial_gradient_to_svg_def(id: &str, gradient: &GlyphRadialGradient) -> String {
+    let focal_radius = if gradient.fr > 0.0 {
+        format!(" fr=\"{}\"", gradient.fr)
+    } else {
+        String::new()
+    };
     let mut definition = format!(
-        "<radialGradient id=\"{}\" cx=\"{}\" cy=\"{}\" r=\"{}\" fx=\"{}\" fy=\"{}\" gradientUnits=\"{}\" gradientTransform=\"{}\" spreadMethod=\"{}\">",
+        "<radialGradient id=\"{}\" cx=\"{}\" cy=\"{}\" r=\"{}\" fx=\"{}\" fy=\"{}\"{} gradientUnits=\"{}\" gradientTransform=\"{}\" spreadMethod=\"{}\">",
         id,
         gradient.cx,
         gradient.cy,
         gradient.r,
         gradient.fx,
         gradient.fy,
+        focal_radius,
         gradient_units_to_svg(gradient.units),
         gradient_transform_to_svg(&gradient.transform),
         gradient_spread_to_svg(gradient.spread)
@@ -728,11 +1417,21 @@ fn radial_gradient_to_svg_def(id: &str, gradient: &GlyphRadialGradient) -> Strin
     definition
 }
 
-fn clip_path_to_svg_def(id: &str, commands: &[Command]) -> String {
+fn clip_path_to_svg_def(
+    id: &str,
+    commands: &[Command],
+    origin: (f32, f32),
+    fill_rule: FillRule,
+) -> String {
+    let clip_rule = match fill_rule {
+        FillRule::NonZero => "",
+        FillRule::EvenOdd => " clip-rule=\"evenodd\"",
+    };
     format!(
-        "<clipPath id=\"{}\"><path d=\"{}\"/></clipPath>",
+        "<clipPath id=\"{}\"><path d=\"{}\"{}/></clipPath>",
         id,
-        draw_commands_to_svg_path(commands, 0.0, 0.0)
+        draw_commands_to_svg_path(commands, origin.0, origin.1),
+        clip_rule
     )
 }
 
@@ -823,10 +1522,21 @@ fn raster_layer_to_svg_image(
                 encoded
             ))
         }
-        RasterGlyphSource::Rgba { .. } => Err(Error::new(
-            ErrorKind::Unsupported,
-            "RGBA raster glyph layers are not supported for SVG export yet",
-        )),
+        RasterGlyphSource::Rgba {
+            width: source_width,
+            height: source_height,
+            data,
+        } => {
+            let encoded = crate::png::encode_rgba(*source_width, *source_height, data)?;
+            Ok(format!(
+                "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"data:image/png;base64,{}\"/>",
+                glyph_x + raster.offset_x,
+                glyph_y + raster.offset_y,
+                width,
+                height,
+                general_purpose::STANDARD.encode(encoded)
+            ))
+        }
     }
 }
 
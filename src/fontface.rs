@@ -1,13 +1,20 @@
 //! Face-level metadata access and family fallback helpers.
 
 use crate::commands::{
-    FontOptions, FontRef, FontStretch, FontStyle, FontWeight, GlyphRun, PositionedGlyph,
-    TextDirection,
+    Baseline, FontOptions, FontRef, FontStretch, FontStyle, FontVariationSetting, FontWeight,
+    GlyphRun, ScriptExtent, TextDirection,
 };
+#[cfg(feature = "layout")]
+use crate::commands::{MathConstants, MathGlyphVariant, MathKernCorner};
 use crate::fontengine::{glyph_run_to_svg, FontEngine};
+use crate::opentype::color::cpal;
+use crate::opentype::requires::stat;
+use crate::outlinecache::OutlineCache;
+use crate::shapeplan::{ShapePlan, ShapePlanCache};
 use crate::{fontreader, ChunkedFontBuffer};
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
+use std::sync::Arc;
 
 /// Describes one face inside a [`FontFamily`].
 #[derive(Debug, Clone)]
@@ -22,6 +29,15 @@ pub struct FontFaceDescriptor {
     pub font_style: FontStyle,
     /// Requested stretch used for selection.
     pub font_stretch: FontStretch,
+    /// `STAT` style name such as `"Condensed SemiBold Italic"`, also accepted
+    /// after the family name when matching by face name.
+    pub style_name: Option<String>,
+    /// Weights a variable face reaches through its `wght` axis.
+    pub font_weight_range: Option<(FontWeight, FontWeight)>,
+    /// Stretches a variable face reaches through its `wdth` axis.
+    pub font_stretch_range: Option<(FontStretch, FontStretch)>,
+    /// Styles a variable face reaches through `ital` or `slnt`, besides `font_style`.
+    pub variable_styles: Vec<FontStyle>,
 }
 
 impl FontFaceDescriptor {
@@ -33,6 +49,10 @@ impl FontFaceDescriptor {
             font_weight: FontWeight::default(),
             font_style: FontStyle::default(),
             font_stretch: FontStretch::default(),
+            style_name: None,
+            font_weight_range: None,
+            font_stretch_range: None,
+            variable_styles: Vec::new(),
         }
     }
 
@@ -60,9 +80,61 @@ impl FontFaceDescriptor {
         self
     }
 
+    /// Sets the weight range a variable face covers.
+    pub fn with_font_weight_range(mut self, min: FontWeight, max: FontWeight) -> Self {
+        self.font_weight_range = Some((min, max));
+        self
+    }
+
+    /// Sets the stretch range a variable face covers.
+    pub fn with_font_stretch_range(mut self, min: FontStretch, max: FontStretch) -> Self {
+        self.font_stretch_range = Some((min, max));
+        self
+    }
+
     /// Builds a descriptor from an existing [`FontFace`].
+    ///
+    /// A variable face that is not a named instance also records the weight,
+    /// stretch and styles its axes cover.
     pub fn from_face(face: &FontFace) -> Self {
-        Self::from_font(face.font())
+        let mut descriptor = Self::from_font(face.font());
+        if face.named_instance.is_some() {
+            descriptor.font_name = Some(face.full_name());
+        }
+        descriptor.font_weight = face.weight();
+        descriptor.font_stretch = face.stretch();
+        descriptor.font_style = if face.is_italic() {
+            FontStyle::Italic
+        } else {
+            FontStyle::Normal
+        };
+        descriptor.style_name = face.style_name();
+        if face.named_instance.is_none() {
+            for axis in face.variation_axes() {
+                match axis.tag.as_str() {
+                    "wght" => {
+                        descriptor.font_weight_range = Some((
+                            FontWeight(axis.min_value.round().clamp(1.0, 1000.0) as u16),
+                            FontWeight(axis.max_value.round().clamp(1.0, 1000.0) as u16),
+                        ))
+                    }
+                    "wdth" => {
+                        descriptor.font_stretch_range = Some((
+                            FontStretch(axis.min_value / 100.0),
+                            FontStretch(axis.max_value / 100.0),
+                        ))
+                    }
+                    "ital" if axis.max_value >= 1.0 => {
+                        descriptor.variable_styles.push(FontStyle::Italic)
+                    }
+                    "slnt" if axis.min_value < 0.0 => {
+                        descriptor.variable_styles.push(FontStyle::Oblique)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        descriptor
     }
 
     #[cfg(feature = "raw")]
@@ -87,8 +159,24 @@ impl FontFaceDescriptor {
             font_weight,
             font_style,
             font_stretch,
+            style_name: None,
+            font_weight_range: None,
+            font_stretch_range: None,
+            variable_styles: Vec::new(),
         }
     }
+
+    fn matches_name(&self, normalized_name: &str) -> bool {
+        let font_name_matches = self
+            .font_name
+            .as_deref()
+            .is_some_and(|name| normalize_font_name(name) == normalized_name);
+        font_name_matches
+            || self.style_name.as_ref().is_some_and(|style_name| {
+                normalize_font_name(&format!("{} {}", self.family_name, style_name))
+                    == normalized_name
+            })
+    }
 }
 
 /// One variable-font axis exposed by a face.
@@ -104,15 +192,90 @@ pub struct FontVariationAxis {
     pub hidden: bool,
 }
 
+/// One `fvar` named instance such as "Bold Condensed".
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontNamedInstance {
+    /// Subfamily name in the requested locale, or English when unavailable.
+    pub name: Option<String>,
+    /// PostScript name when the font defines one.
+    pub postscript_name: Option<String>,
+    /// Axis values in user space, in `fvar` axis order.
+    pub coordinates: Vec<FontVariationSetting>,
+}
+
+impl FontNamedInstance {
+    fn coordinate(&self, tag: &[u8; 4]) -> Option<f32> {
+        self.coordinates
+            .iter()
+            .find(|setting| &setting.tag == tag)
+            .map(|setting| setting.value)
+    }
+}
+
+/// One `STAT` design axis, including axes the font does not vary such as `ital`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontStyleAxis {
+    /// OpenType tag such as `"wght"` or `"ital"`.
+    pub tag: String,
+    /// Human-readable axis name when available.
+    pub name: Option<String>,
+    /// Position of this axis' value names in composed style names.
+    pub ordering: u16,
+}
+
+/// One `CPAL` color palette.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontPalette {
+    /// Index to pass to [`crate::FontEngine::with_palette`].
+    pub index: usize,
+    /// Palette label in the requested locale, or English when unavailable.
+    pub name: Option<String>,
+    pub usable_with_light_background: bool,
+    pub usable_with_dark_background: bool,
+    /// Packed `0xAARRGGBB` entries.
+    pub colors: Vec<u32>,
+    /// Entry labels, shared by every palette of the font.
+    pub entry_names: Vec<Option<String>>,
+}
+
+/// One `STAT` axis value such as "SemiBold", "Condensed" or "Italic".
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontAxisValue {
+    /// Value name in the requested locale, or English when unavailable.
+    pub name: Option<String>,
+    /// Axis values this name stands for; ranges report their nominal value.
+    pub coordinates: Vec<FontVariationSetting>,
+    /// Inclusive range covered by the name, for `STAT` format 2 values.
+    pub range: Option<(f32, f32)>,
+    /// Style-linked value, e.g. Bold (700) for Regular (400).
+    pub linked_value: Option<f32>,
+    /// Whether the name is left out of composed style names, like "Regular".
+    pub elidable: bool,
+    /// Whether the value describes an older sibling font rather than this one.
+    pub older_sibling: bool,
+}
+
 /// Public wrapper around one parsed font face.
 #[derive(Debug, Clone)]
 pub struct FontFace {
-    pub(crate) font: fontreader::Font,
+    pub(crate) font: Arc<fontreader::Font>,
+    named_instance: Option<FontNamedInstance>,
+    shape_plans: ShapePlanCache,
+    outlines: OutlineCache,
 }
 
 impl FontFace {
     pub(crate) fn from_font(font: fontreader::Font) -> Self {
-        Self { font }
+        Self::from_shared_font(Arc::new(font))
+    }
+
+    pub(crate) fn from_shared_font(font: Arc<fontreader::Font>) -> Self {
+        Self {
+            font,
+            named_instance: None,
+            shape_plans: ShapePlanCache::default(),
+            outlines: OutlineCache::default(),
+        }
     }
 
     /// Returns the family name.
@@ -121,23 +284,40 @@ impl FontFace {
     }
 
     /// Returns the full face name, or the family name if unavailable.
+    ///
+    /// A named-instance face is called "family instance", e.g. "Roboto Flex Bold".
     pub fn full_name(&self) -> String {
+        if let Some(name) = self.named_instance.as_ref().and_then(|i| i.name.as_ref()) {
+            return format!("{} {}", self.family(), name);
+        }
         self.font.face_full_name().unwrap_or_else(|| self.family())
     }
 
-    /// Returns the OS/2 weight class as a [`FontWeight`].
+    /// Returns the OS/2 weight class, or the `wght` value of a named instance
+    /// or of a static face's `STAT` table.
     pub fn weight(&self) -> FontWeight {
-        FontWeight(self.font.face_weight_class())
+        self.style_coordinate(b"wght")
+            .map(|weight| FontWeight(weight.round().clamp(1.0, 1000.0) as u16))
+            .unwrap_or_else(|| FontWeight(self.font.face_weight_class()))
     }
 
-    /// Returns the width class mapped to a [`FontStretch`].
+    /// Returns the width class mapped to a [`FontStretch`], or the `wdth`
+    /// value of a named instance or `STAT`.
     pub fn stretch(&self) -> FontStretch {
-        FontStretch(width_class_to_stretch(self.font.face_width_class()))
+        self.style_coordinate(b"wdth")
+            .map(|width| FontStretch(width / 100.0))
+            .unwrap_or_else(|| FontStretch(width_class_to_stretch(self.font.face_width_class())))
     }
 
-    /// Returns whether the face is italic.
+    /// Returns whether the face is italic. A named instance or `STAT` value
+    /// with `ital` on or a non-zero `slnt` counts as italic.
     pub fn is_italic(&self) -> bool {
-        self.font.face_is_italic()
+        let italic = self.style_coordinate(b"ital").map(|ital| ital >= 0.5);
+        let slanted = self.style_coordinate(b"slnt").map(|slnt| slnt != 0.0);
+        match (italic, slanted) {
+            (None, None) => self.font.face_is_italic(),
+            (italic, slanted) => italic.unwrap_or(false) || slanted.unwrap_or(false),
+        }
     }
 
     /// Returns `true` when this face exposes one or more variable-font axes.
@@ -161,6 +341,335 @@ impl FontFace {
             .collect()
     }
 
+    /// Returns the `fvar` named instances with English names.
+    pub fn named_instances(&self) -> Vec<FontNamedInstance> {
+        self.named_instances_for_locale("en-US")
+    }
+
+    /// Returns the `fvar` named instances with names in `locale` such as
+    /// `"ja-JP"`, falling back to English.
+    pub fn named_instances_for_locale(&self, locale: &str) -> Vec<FontNamedInstance> {
+        let axes = self.font.face_variation_axes();
+        self.font
+            .face_named_instances()
+            .into_iter()
+            .map(|instance| FontNamedInstance {
+                name: self
+                    .font
+                    .face_name_by_id_for_locale(instance.subfamily_name_id, locale),
+                postscript_name: instance
+                    .postscript_name_id
+                    .and_then(|name_id| self.font.face_name_by_id_for_locale(name_id, locale)),
+                coordinates: axes
+                    .iter()
+                    .zip(instance.coordinates)
+                    .map(|(axis, value)| FontVariationSetting {
+                        tag: axis.tag.to_be_bytes(),
+                        value,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Finds a named instance by subfamily or PostScript name, ignoring case.
+    pub fn find_named_instance(&self, name: &str) -> Option<FontNamedInstance> {
+        let name = normalize_font_name(name);
+        self.named_instances().into_iter().find(|instance| {
+            [&instance.name, &instance.postscript_name]
+                .into_iter()
+                .flatten()
+                .any(|candidate| normalize_font_name(candidate) == name)
+        })
+    }
+
+    /// The named instance this face stands for when it was listed by
+    /// [`crate::FontFile::with_named_instance_faces`].
+    pub fn named_instance(&self) -> Option<&FontNamedInstance> {
+        self.named_instance.as_ref()
+    }
+
+    pub(crate) fn with_named_instance(mut self, instance: FontNamedInstance) -> Self {
+        self.named_instance = Some(instance);
+        self
+    }
+
+    /// Axis values the face applies when options do not set them.
+    pub(crate) fn default_variations(&self) -> &[FontVariationSetting] {
+        self.named_instance
+            .as_ref()
+            .map_or(&[], |instance| instance.coordinates.as_slice())
+    }
+
+    /// Returns the `STAT` design axes in table order.
+    pub fn style_axes(&self) -> Vec<FontStyleAxis> {
+        let Some(stat) = self.font.current_stat() else {
+            return Vec::new();
+        };
+        stat.axes
+            .iter()
+            .map(|axis| FontStyleAxis {
+                tag: String::from_utf8_lossy(&axis.tag.to_be_bytes()).into_owned(),
+                name: self.font.face_name_by_id(axis.name_id),
+                ordering: axis.ordering,
+            })
+            .collect()
+    }
+
+    /// Returns the `STAT` axis values with English names.
+    pub fn axis_values(&self) -> Vec<FontAxisValue> {
+        self.axis_values_for_locale("en-US")
+    }
+
+    /// Returns the `STAT` axis values with names in `locale`, falling back to English.
+    pub fn axis_values_for_locale(&self, locale: &str) -> Vec<FontAxisValue> {
+        let Some(stat) = self.font.current_stat() else {
+            return Vec::new();
+        };
+        stat.values
+            .iter()
+            .map(|value| {
+                let (range, linked_value) = match value.kind {
+                    stat::AxisValueKind::Range { min, max, .. } => (Some((min, max)), None),
+                    stat::AxisValueKind::Linked { linked_value, .. } => (None, Some(linked_value)),
+                    _ => (None, None),
+                };
+                FontAxisValue {
+                    name: self.font.face_name_by_id_for_locale(value.name_id, locale),
+                    coordinates: value
+                        .coordinates()
+                        .into_iter()
+                        .filter_map(|(axis_index, value)| {
+                            let axis = stat.axes.get(axis_index as usize)?;
+                            Some(FontVariationSetting {
+                                tag: axis.tag.to_be_bytes(),
+                                value,
+                            })
+                        })
+                        .collect(),
+                    range,
+                    linked_value,
+                    elidable: value.is_elidable(),
+                    older_sibling: value.is_older_sibling(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the `CPAL` palettes with English labels.
+    pub fn palettes(&self) -> Vec<FontPalette> {
+        self.palettes_for_locale("en-US")
+    }
+
+    /// Returns the `CPAL` palettes with labels in `locale`, falling back to English.
+    pub fn palettes_for_locale(&self, locale: &str) -> Vec<FontPalette> {
+        let Some(cpal) = self.font.current_cpal() else {
+            return Vec::new();
+        };
+        let entry_names = (0..cpal.num_palette_entries())
+            .map(|entry| {
+                cpal.entry_label(entry)
+                    .and_then(|name_id| self.font.face_name_by_id_for_locale(name_id, locale))
+            })
+            .collect::<Vec<_>>();
+        (0..cpal.num_palettes())
+            .map(|index| {
+                let palette_type = cpal.palette_type(index);
+                FontPalette {
+                    index,
+                    name: cpal
+                        .palette_label(index)
+                        .and_then(|name_id| self.font.face_name_by_id_for_locale(name_id, locale)),
+                    usable_with_light_background: palette_type & cpal::USABLE_WITH_LIGHT_BACKGROUND
+                        != 0,
+                    usable_with_dark_background: palette_type & cpal::USABLE_WITH_DARK_BACKGROUND
+                        != 0,
+                    colors: (0..cpal.num_palette_entries())
+                        .map(|entry| cpal.color(index, entry).map_or(0, |color| color.argb()))
+                        .collect(),
+                    entry_names: entry_names.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the name used when every axis value name is elided, usually "Regular".
+    pub fn elided_fallback_name(&self) -> Option<String> {
+        let stat = self.font.current_stat()?;
+        self.font
+            .face_name_by_id(stat.elided_fallback_name_id.unwrap_or(2))
+    }
+
+    /// Composes the `STAT` style name of this face or named instance, such as
+    /// "Condensed SemiBold Italic". Returns `None` without a `STAT` table.
+    pub fn style_name(&self) -> Option<String> {
+        self.style_name_for_locale("en-US")
+    }
+
+    /// Like [`Self::style_name`], with value names in `locale`.
+    pub fn style_name_for_locale(&self, locale: &str) -> Option<String> {
+        let stat = self.font.current_stat()?;
+        let coordinates = stat
+            .axes
+            .iter()
+            .enumerate()
+            .map(|(axis_index, axis)| self.stat_axis_coordinate(stat, axis_index, axis.tag))
+            .collect::<Vec<_>>();
+        self.stat_style_name(stat, &coordinates, locale)
+    }
+
+    /// Joins the `STAT` axis value names that match `coordinates`, or the
+    /// elided fallback name when none do.
+    fn stat_style_name(
+        &self,
+        stat: &stat::STAT,
+        coordinates: &[Option<f32>],
+        locale: &str,
+    ) -> Option<String> {
+        let names = stat
+            .style_values(coordinates)
+            .into_iter()
+            .filter_map(|value| self.font.face_name_by_id_for_locale(value.name_id, locale))
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            let name_id = stat.elided_fallback_name_id.unwrap_or(2);
+            return self.font.face_name_by_id_for_locale(name_id, locale);
+        }
+        Some(names.join(" "))
+    }
+
+    /// Writes a static font with `variations` baked into the outlines and
+    /// metrics. Axes left out keep this face's named instance or default.
+    ///
+    /// Composite glyphs are flattened and hinting is dropped. GPOS and GDEF
+    /// device deltas are baked in, and the `name` table is renamed after the
+    /// matching named instance, the `STAT` style name or the axis values.
+    pub fn instantiate(&self, variations: &[FontVariationSetting]) -> Result<Vec<u8>, Error> {
+        if !self.is_variable() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "static instances need a variable font",
+            ));
+        }
+        let options = FontOptions::new(self).with_variations(variations);
+        let options = options.with_default_variations(self.default_variations());
+        let user = self
+            .font
+            .face_variation_axes()
+            .into_iter()
+            .map(|axis| {
+                let value = options
+                    .variations
+                    .iter()
+                    .find(|setting| u32::from_be_bytes(setting.tag) == axis.tag)
+                    .map_or(axis.default_value, |setting| setting.value);
+                FontVariationSetting {
+                    tag: axis.tag.to_be_bytes(),
+                    value: value.clamp(axis.min_value, axis.max_value),
+                }
+            })
+            .collect::<Vec<_>>();
+        // Every axis is pinned, so the CSS weight/stretch/style/size defaults stay out.
+        let coordinates = self
+            .font
+            .normalized_variation_coords(&options.with_variations(&user));
+        let names = self.instance_names(&user);
+        crate::instancer::instantiate(&self.font, &user, &coordinates, &names)
+    }
+
+    /// Family and style names for a static instance at `user`.
+    fn instance_names(&self, user: &[FontVariationSetting]) -> crate::instancer::InstanceNames {
+        let named = self.named_instances().into_iter().find(|instance| {
+            instance.coordinates.len() == user.len()
+                && instance
+                    .coordinates
+                    .iter()
+                    .zip(user)
+                    .all(|(a, b)| (a.value - b.value).abs() < 0.001)
+        });
+        let stat_name = || {
+            let stat = self.font.current_stat()?;
+            let coordinates = stat
+                .axes
+                .iter()
+                .enumerate()
+                .map(|(axis_index, axis)| {
+                    user.iter()
+                        .find(|setting| u32::from_be_bytes(setting.tag) == axis.tag)
+                        .map(|setting| setting.value)
+                        .or_else(|| self.stat_axis_coordinate(stat, axis_index, axis.tag))
+                })
+                .collect::<Vec<_>>();
+            self.stat_style_name(stat, &coordinates, "en-US")
+        };
+        let style = named
+            .as_ref()
+            .and_then(|instance| instance.name.clone())
+            .or_else(stat_name)
+            .unwrap_or_else(|| {
+                user.iter()
+                    .map(|setting| {
+                        format!(
+                            "{}{}",
+                            String::from_utf8_lossy(&setting.tag).trim(),
+                            setting.value
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+        crate::instancer::InstanceNames {
+            family: self.family(),
+            style,
+            postscript_name: named.and_then(|instance| instance.postscript_name),
+        }
+    }
+
+    fn instance_coordinate(&self, tag: &[u8; 4]) -> Option<f32> {
+        self.named_instance.as_ref()?.coordinate(tag)
+    }
+
+    /// Named instance value, or for static faces the single value `STAT` gives.
+    fn style_coordinate(&self, tag: &[u8; 4]) -> Option<f32> {
+        self.instance_coordinate(tag).or_else(|| {
+            if self.is_variable() {
+                return None;
+            }
+            let stat = self.font.current_stat()?;
+            stat.own_value(stat.axis_index(u32::from_be_bytes(*tag))?)
+        })
+    }
+
+    /// Where this face sits on one `STAT` axis: the named instance, the `fvar`
+    /// default, the single value `STAT` gives, then OS/2.
+    fn stat_axis_coordinate(&self, stat: &stat::STAT, axis_index: usize, tag: u32) -> Option<f32> {
+        let tag_bytes = tag.to_be_bytes();
+        self.instance_coordinate(&tag_bytes)
+            .or_else(|| {
+                self.font
+                    .face_variation_axes()
+                    .into_iter()
+                    .find(|axis| axis.tag == tag)
+                    .map(|axis| axis.default_value)
+            })
+            .or_else(|| stat.own_value(axis_index))
+            .or_else(|| match &tag_bytes {
+                b"wght" => Some(self.font.face_weight_class() as f32),
+                b"wdth" => Some(width_class_to_stretch(self.font.face_width_class()) * 100.0),
+                b"ital" => Some(if self.font.face_is_italic() { 1.0 } else { 0.0 }),
+                _ => None,
+            })
+    }
+
+    /// Parses the outline, color and layout tables that are otherwise read on
+    /// first use, and returns an error naming the first malformed one.
+    ///
+    /// Loading only reads the table directory, so a broken `CFF `, `GSUB` or
+    /// `GPOS` otherwise shows up as blank glyphs or unshaped text.
+    pub fn check_tables(&self) -> Result<(), Error> {
+        self.font.check_tables()
+    }
+
     /// Dumps a small human-readable summary of this face.
     pub fn dump(&self) -> String {
         format!(
@@ -238,7 +747,180 @@ impl FontFace {
         mut options: FontOptions<'a>,
     ) -> Result<f64, Error> {
         options.font = Some(FontRef::Loaded(self));
-        self.font.measure_with_options(text, &options)
+        self.font.measure_with_plan(
+            text,
+            &options,
+            &self.shape_plan(&options),
+            Some(&self.outlines),
+        )
+    }
+
+    /// Returns the shape plan for the locale, direction, features and variation
+    /// settings in `options`, resolving it on first use.
+    pub fn shape_plan(&self, options: &FontOptions<'_>) -> Arc<ShapePlan> {
+        self.shape_plans.get_or_build(&self.font, options)
+    }
+
+    /// Number of shape plans currently cached by this face.
+    pub fn shape_plan_count(&self) -> usize {
+        self.shape_plans.len()
+    }
+
+    /// Drops all cached shape plans.
+    pub fn clear_shape_plans(&self) {
+        self.shape_plans.clear();
+    }
+
+    /// Sets how many glyph outlines this face keeps per variation instance
+    /// and orientation; `0` turns the outline cache off. Defaults to 512.
+    pub fn with_outline_cache_capacity(mut self, capacity: usize) -> Self {
+        self.outlines = OutlineCache::with_capacity(capacity);
+        self
+    }
+
+    /// Maximum number of outlines the cache holds before it is emptied.
+    pub fn outline_cache_capacity(&self) -> usize {
+        self.outlines.capacity()
+    }
+
+    /// Number of glyph outlines currently cached by this face.
+    pub fn cached_outline_count(&self) -> usize {
+        self.outlines.len()
+    }
+
+    /// Drops all cached glyph outlines.
+    pub fn clear_outline_cache(&self) {
+        self.outlines.clear();
+    }
+
+    pub(crate) fn outline_cache(&self) -> &OutlineCache {
+        &self.outlines
+    }
+
+    pub(crate) fn supports_text_unit(
+        &self,
+        unit: &fontreader::ParsedTextUnit,
+        options: &FontOptions<'_>,
+    ) -> bool {
+        self.font
+            .supports_text_unit(unit, &self.shape_plan(options), options.font_size)
+    }
+
+    pub(crate) fn text_unit_support(
+        &self,
+        unit: &fontreader::ParsedTextUnit,
+        options: &FontOptions<'_>,
+    ) -> fontreader::TextUnitSupport {
+        self.font
+            .text_unit_support(unit, &self.shape_plan(options), options.font_size)
+    }
+
+    /// Returns the position of `baseline` for a script in px.
+    ///
+    /// `script` is an OpenType script tag such as `"latn"` or `"hani"`. Horizontal
+    /// values are measured upward from the alphabetic baseline; vertical values are
+    /// measured rightward from the vertical origin.
+    pub fn baseline_offset<'a>(
+        &'a self,
+        baseline: Baseline,
+        script: &str,
+        options: FontOptions<'a>,
+    ) -> Result<f32, Error> {
+        let script_tag = u32::from_be_bytes(crate::commands::parse_variation_tag(script)?);
+        self.font.baseline_offset(&options, baseline, script_tag)
+    }
+
+    /// Returns the BASE min/max extent for a script and optional language tag.
+    pub fn script_extent<'a>(
+        &'a self,
+        script: &str,
+        language: Option<&str>,
+        options: FontOptions<'a>,
+    ) -> Result<Option<ScriptExtent>, Error> {
+        let script_tag = u32::from_be_bytes(crate::commands::parse_variation_tag(script)?);
+        let language_tag = language
+            .map(crate::commands::parse_variation_tag)
+            .transpose()?
+            .map(u32::from_be_bytes);
+        self.font.script_extent(&options, script_tag, language_tag)
+    }
+
+    /// Returns the nominal glyph id for `ch`, or `None` when the face has no glyph for it.
+    pub fn glyph_id(&self, ch: char) -> Option<u16> {
+        self.font.glyph_id_for_char(ch).ok().flatten()
+    }
+
+    /// Returns the MATH table constants in px, or `None` when the face has no MATH table.
+    #[cfg(feature = "layout")]
+    pub fn math_constants<'a>(
+        &'a self,
+        options: FontOptions<'a>,
+    ) -> Result<Option<MathConstants>, Error> {
+        self.font.math_constants(&options)
+    }
+
+    /// Returns the MATH italic correction for a glyph in px.
+    #[cfg(feature = "layout")]
+    pub fn math_italic_correction<'a>(
+        &'a self,
+        glyph_id: u16,
+        options: FontOptions<'a>,
+    ) -> Result<Option<f32>, Error> {
+        self.font.math_italic_correction(glyph_id, &options)
+    }
+
+    /// Returns the horizontal position where accents attach above a glyph, in px.
+    #[cfg(feature = "layout")]
+    pub fn math_top_accent_attachment<'a>(
+        &'a self,
+        glyph_id: u16,
+        options: FontOptions<'a>,
+    ) -> Result<Option<f32>, Error> {
+        self.font.math_top_accent_attachment(glyph_id, &options)
+    }
+
+    /// Returns the cut-in kern at `corner` for a script placed at `height` px.
+    #[cfg(feature = "layout")]
+    pub fn math_kern<'a>(
+        &'a self,
+        glyph_id: u16,
+        corner: MathKernCorner,
+        height: f32,
+        options: FontOptions<'a>,
+    ) -> Result<Option<f32>, Error> {
+        self.font.math_kern(glyph_id, corner, height, &options)
+    }
+
+    /// Returns `true` when the MATH table marks the glyph as an extended shape.
+    #[cfg(feature = "layout")]
+    pub fn is_math_extended_shape(&self, glyph_id: u16) -> bool {
+        self.font.is_math_extended_shape(glyph_id)
+    }
+
+    /// Lists the pre-built size variants of a stretchy glyph, smallest first.
+    #[cfg(feature = "layout")]
+    pub fn math_variants<'a>(
+        &'a self,
+        glyph_id: u16,
+        vertical: bool,
+        options: FontOptions<'a>,
+    ) -> Result<Vec<MathGlyphVariant>, Error> {
+        self.font.math_variants(glyph_id, vertical, &options)
+    }
+
+    /// Builds a glyph at least `target_size` px long from MATH variants or its glyph assembly.
+    ///
+    /// `vertical` selects delimiter-style growth along y; otherwise the glyph grows along x.
+    #[cfg(feature = "layout")]
+    pub fn math_stretch_glyph<'a>(
+        &'a self,
+        glyph_id: u16,
+        target_size: f32,
+        vertical: bool,
+        options: FontOptions<'a>,
+    ) -> Result<GlyphRun, Error> {
+        self.font
+            .math_stretch_glyph(glyph_id, target_size, vertical, &options)
     }
 
     pub(crate) fn font(&self) -> &fontreader::Font {
@@ -263,9 +945,21 @@ struct PendingFontFace {
 
 struct FamilyLayoutResult {
     run: GlyphRun,
+    /// Char index in the laid out text of each glyph, as from
+    /// `Font::text2clustered_run`.
+    clusters: Vec<Option<usize>>,
     max_line_width: f32,
 }
 
+/// Glyph units waiting to be shaped with one fallback face.
+#[derive(Default)]
+struct PendingFamilySegment {
+    text: String,
+    /// Char index in the laid out text of every char in `text`.
+    chars: Vec<usize>,
+    face: Option<usize>,
+}
+
 /// Cache and fallback layer for multiple faces.
 pub struct FontFamily {
     name: String,
@@ -317,10 +1011,11 @@ impl FontFamily {
         let start_index = self.faces.len();
 
         for face_index in 0..face_count {
-            let mut face_font = font.clone();
-            if face_font.font.set_font(face_index).is_err() {
+            let mut face_font = fontreader::Font::clone(&font.font);
+            if face_font.set_font(face_index).is_err() {
                 continue;
             }
+            let face_font = FontFace::from_font(face_font);
             let descriptor = FontFaceDescriptor::from_face(&face_font);
             self.faces.push(CachedFontFace {
                 descriptor,
@@ -487,7 +1182,7 @@ impl FontFamily {
         if options.font_family.is_none() {
             options.font_family = Some(self.name());
         }
-        let layout = self.layout_text_with_fallback(text, options)?;
+        let layout = self.layout_text_with_fallback(text, options, &[])?;
         glyph_run_to_svg(&layout.run, fontunit)
     }
 
@@ -500,7 +1195,7 @@ impl FontFamily {
             options.font_family = Some(self.name());
         }
         options.font = Some(FontRef::Family(self));
-        Ok(self.layout_text_with_fallback(text, options)?.run)
+        Ok(self.layout_text_with_fallback(text, options, &[])?.run)
     }
 
     /// Alias for [`FontFamily::text2glyph_run`].
@@ -527,7 +1222,7 @@ impl FontFamily {
             options.font_family = Some(self.name());
         }
         Ok(self
-            .layout_text_with_fallback(text, options)?
+            .layout_text_with_fallback(text, options, &[])?
             .max_line_width as f64)
     }
 
@@ -564,12 +1259,9 @@ impl FontFamily {
                 fontreader::ParsedTextUnit::Glyph { .. } => {
                     let face_index = if let Some(current_face) = pending_face {
                         if unit_prefers_face_continuity(&unit, &options)
-                            && self.faces[current_face].font.font().supports_text_unit(
-                                &unit,
-                                options.text_direction,
-                                options.locale,
-                                options.font_variant,
-                            )
+                            && self.faces[current_face]
+                                .font
+                                .supports_text_unit(&unit, &options)
                         {
                             current_face
                         } else {
@@ -587,10 +1279,23 @@ impl FontFamily {
         Ok(face_indices)
     }
 
+    /// Family counterpart of `Font::text2clustered_run`; each fallback
+    /// segment is shaped once with the sizes that fall inside it.
+    pub(crate) fn text2clustered_run<'a>(
+        &'a self,
+        text: &str,
+        options: FontOptions<'a>,
+        sizes: &[fontreader::ClusterSize],
+    ) -> Result<(GlyphRun, Vec<Option<usize>>), Error> {
+        let layout = self.layout_text_with_fallback(text, options, sizes)?;
+        Ok((layout.run, layout.clusters))
+    }
+
     fn layout_text_with_fallback<'a>(
         &'a self,
         text: &str,
         mut options: FontOptions<'a>,
+        sizes: &[fontreader::ClusterSize],
     ) -> Result<FamilyLayoutResult, Error> {
         if self.faces.is_empty() {
             return Err(Error::new(
@@ -636,29 +1341,47 @@ impl FontFamily {
             ));
         }
 
-        let mut glyphs = Vec::new();
+        // Decorations follow the primary face across the whole run rather
+        // than stepping with each fallback segment.
+        let decorations = std::mem::take(&mut options.decorations);
+        let is_vertical = options.text_direction.is_vertical();
+        let is_right_to_left = options.text_direction.is_right_to_left();
+        let mut decorated_lines = Vec::new();
+        let mut line_glyph_start = 0usize;
+
+        let mut layout = FamilyLayoutResult {
+            run: GlyphRun::default(),
+            clusters: Vec::new(),
+            max_line_width: 0.0,
+        };
         let mut cursor_x = 0.0f32;
         let mut cursor_y = 0.0f32;
-        let mut max_line_width = 0.0f32;
-        let mut pending_segment = String::new();
-        let mut pending_face: Option<usize> = None;
+        let mut pending = PendingFamilySegment::default();
 
-        for unit in fontreader::Font::parse_text_units_for_fallback(text) {
+        for (offset, unit) in fontreader::Font::parse_text_units_with_offsets(text) {
             match unit {
                 fontreader::ParsedTextUnit::Newline => {
                     self.flush_family_segment(
-                        &mut glyphs,
-                        &mut pending_segment,
-                        &mut pending_face,
+                        &mut layout,
+                        &mut pending,
                         &mut cursor_x,
                         &mut cursor_y,
                         &options,
+                        sizes,
                     )?;
-                    max_line_width = max_line_width.max(cursor_inline_extent(
+                    layout.max_line_width = layout.max_line_width.max(cursor_inline_extent(
                         cursor_x,
                         cursor_y,
                         options.text_direction,
                     ));
+                    decorated_lines.push(fontreader::decorated_line(
+                        line_glyph_start..layout.run.glyphs.len(),
+                        cursor_x,
+                        cursor_y,
+                        is_vertical,
+                        is_right_to_left,
+                    ));
+                    line_glyph_start = layout.run.glyphs.len();
                     match options.text_direction {
                         TextDirection::LeftToRight | TextDirection::RightToLeft => {
                             cursor_x = 0.0;
@@ -672,12 +1395,12 @@ impl FontFamily {
                 }
                 fontreader::ParsedTextUnit::Tab => {
                     self.flush_family_segment(
-                        &mut glyphs,
-                        &mut pending_segment,
-                        &mut pending_face,
+                        &mut layout,
+                        &mut pending,
                         &mut cursor_x,
                         &mut cursor_y,
                         &options,
+                        sizes,
                     )?;
                     match options.text_direction {
                         TextDirection::LeftToRight => cursor_x += line_height * 4.0,
@@ -686,14 +1409,11 @@ impl FontFamily {
                     }
                 }
                 fontreader::ParsedTextUnit::Glyph { .. } => {
-                    let face_index = if let Some(current_face) = pending_face {
+                    let face_index = if let Some(current_face) = pending.face {
                         if unit_prefers_face_continuity(&unit, &options)
-                            && self.faces[current_face].font.font().supports_text_unit(
-                                &unit,
-                                options.text_direction,
-                                options.locale,
-                                options.font_variant,
-                            )
+                            && self.faces[current_face]
+                                .font
+                                .supports_text_unit(&unit, &options)
                         {
                             current_face
                         } else {
@@ -702,80 +1422,174 @@ impl FontFamily {
                     } else {
                         self.select_face_for_unit(&unit, &candidate_indices, &options)
                     };
-                    if pending_face != Some(face_index) {
+                    if pending.face != Some(face_index) {
                         self.flush_family_segment(
-                            &mut glyphs,
-                            &mut pending_segment,
-                            &mut pending_face,
+                            &mut layout,
+                            &mut pending,
                             &mut cursor_x,
                             &mut cursor_y,
                             &options,
+                            sizes,
                         )?;
-                        pending_face = Some(face_index);
+                        pending.face = Some(face_index);
                     }
-                    push_text_unit(&mut pending_segment, &unit);
+                    let start = pending.text.chars().count();
+                    push_text_unit(&mut pending.text, &unit);
+                    let end = pending.text.chars().count();
+                    pending.chars.extend(offset..offset + (end - start));
                 }
             }
         }
 
         self.flush_family_segment(
-            &mut glyphs,
-            &mut pending_segment,
-            &mut pending_face,
+            &mut layout,
+            &mut pending,
             &mut cursor_x,
             &mut cursor_y,
             &options,
+            sizes,
         )?;
-        max_line_width = max_line_width.max(cursor_inline_extent(
+        layout.max_line_width = layout.max_line_width.max(cursor_inline_extent(
             cursor_x,
             cursor_y,
             options.text_direction,
         ));
 
-        Ok(FamilyLayoutResult {
-            run: GlyphRun::new(glyphs),
-            max_line_width,
-        })
+        if !decorations.is_empty() {
+            decorated_lines.push(fontreader::decorated_line(
+                line_glyph_start..layout.run.glyphs.len(),
+                cursor_x,
+                cursor_y,
+                is_vertical,
+                is_right_to_left,
+            ));
+            let primary = &self.faces[candidate_indices[0]].font;
+            let primary_options = options
+                .clone()
+                .with_default_variations(primary.default_variations());
+            let metrics = primary.font().scaled_decoration_metrics(&primary_options)?;
+            let decoration_glyphs = crate::decoration::build_decoration_glyphs(
+                &layout.run.glyphs,
+                &decorated_lines,
+                &decorations,
+                &metrics,
+                is_vertical,
+            );
+            layout
+                .clusters
+                .extend(decoration_glyphs.iter().map(|_| None));
+            layout.run.glyphs.extend(decoration_glyphs);
+        }
+
+        Ok(layout)
     }
 
     fn flush_family_segment<'a>(
         &'a self,
-        glyphs: &mut Vec<PositionedGlyph>,
-        pending_segment: &mut String,
-        pending_face: &mut Option<usize>,
+        layout: &mut FamilyLayoutResult,
+        pending: &mut PendingFamilySegment,
         cursor_x: &mut f32,
         cursor_y: &mut f32,
         options: &FontOptions<'a>,
+        sizes: &[fontreader::ClusterSize],
     ) -> Result<(), Error> {
-        let Some(face_index) = *pending_face else {
-            pending_segment.clear();
+        let pending = std::mem::take(pending);
+        let Some(face_index) = pending.face else {
             return Ok(());
         };
-        if pending_segment.is_empty() {
-            *pending_face = None;
+        if pending.text.is_empty() {
             return Ok(());
         }
 
         let face = &self.faces[face_index].font;
-        let mut segment_options = options.clone();
+        let mut segment_options = options
+            .clone()
+            .with_default_variations(face.default_variations());
         segment_options.font = Some(FontRef::Loaded(face));
 
-        let mut segment_run = face.text2glyph_run(pending_segment, segment_options)?;
+        // `sizes` is indexed by chars of the whole text; `pending.chars` is
+        // ascending, so each range maps to one contiguous range of the segment.
+        let segment_sizes = sizes
+            .iter()
+            .map(|size| fontreader::ClusterSize {
+                chars: pending.chars.partition_point(|&ch| ch < size.chars.start)
+                    ..pending.chars.partition_point(|&ch| ch < size.chars.end),
+                font_size: size.font_size,
+            })
+            .collect::<Vec<_>>();
+
+        let (baseline_shift_x, baseline_shift_y) =
+            self.baseline_shift(face_index, &pending.text, &segment_options)?;
+        let (mut segment_run, segment_clusters) = face.font().text2clustered_run(
+            &pending.text,
+            &segment_options,
+            &face.shape_plan(&segment_options),
+            Some(face.outline_cache()),
+            &segment_sizes,
+        )?;
         let (segment_advance_x, segment_advance_y) =
             glyph_run_cursor_delta(&segment_run, options.text_direction);
         for glyph in segment_run.glyphs.iter_mut() {
-            glyph.x += *cursor_x;
-            glyph.y += *cursor_y;
+            glyph.x += *cursor_x + baseline_shift_x;
+            glyph.y += *cursor_y + baseline_shift_y;
         }
 
-        glyphs.extend(segment_run.glyphs);
+        layout.run.glyphs.extend(segment_run.glyphs);
+        layout.clusters.extend(
+            segment_clusters
+                .into_iter()
+                .map(|cluster| cluster.map(|cluster| pending.chars[cluster])),
+        );
         *cursor_x += segment_advance_x;
         *cursor_y += segment_advance_y;
-        pending_segment.clear();
-        *pending_face = None;
         Ok(())
     }
 
+    /// Offsets a fallback segment so its dominant baseline lines up with the primary face.
+    fn baseline_shift(
+        &self,
+        face_index: usize,
+        segment: &str,
+        options: &FontOptions<'_>,
+    ) -> Result<(f32, f32), Error> {
+        let Some(baseline) = options.dominant_baseline else {
+            return Ok((0.0, 0.0));
+        };
+        let Some(&primary_face) = self
+            .face_candidate_indices(
+                options.font_family,
+                options.font_name,
+                options.font_weight,
+                options.font_style,
+                options.font_stretch,
+            )
+            .first()
+        else {
+            return Ok((0.0, 0.0));
+        };
+        if face_index == primary_face {
+            return Ok((0.0, 0.0));
+        }
+        let script_tag = segment
+            .chars()
+            .next()
+            .map(fontreader::baseline_script_tag)
+            .unwrap_or(u32::from_be_bytes(*b"DFLT"));
+        let primary = self.faces[primary_face]
+            .font
+            .font()
+            .baseline_offset(options, baseline, script_tag)?;
+        let fallback = self.faces[face_index]
+            .font
+            .font()
+            .baseline_offset(options, baseline, script_tag)?;
+        if options.text_direction.is_vertical() {
+            Ok((primary - fallback, 0.0))
+        } else {
+            Ok((0.0, fallback - primary))
+        }
+    }
+
     fn find_best_face(
         &self,
         family_name: Option<&str>,
@@ -802,10 +1616,7 @@ impl FontFamily {
                 };
 
                 if let Some(requested_name) = requested_name.as_deref() {
-                    let Some(face_name) = descriptor.font_name.as_deref() else {
-                        return None;
-                    };
-                    if normalize_font_name(face_name) != requested_name {
+                    if !descriptor.matches_name(requested_name) {
                         return None;
                     }
                 } else if !family_matches {
@@ -840,9 +1651,8 @@ impl FontFamily {
             .map(|(index, face)| {
                 let descriptor = &face.descriptor;
                 let descriptor_family = normalize_font_name(&descriptor.family_name);
-                let descriptor_name = descriptor.font_name.as_deref().map(normalize_font_name);
                 let group = if let Some(requested_name) = requested_name.as_deref() {
-                    if descriptor_name.as_deref() == Some(requested_name) {
+                    if descriptor.matches_name(requested_name) {
                         0
                     } else if let Some(requested_family) = requested_family.as_deref() {
                         if descriptor_family == requested_family {
@@ -894,12 +1704,7 @@ impl FontFamily {
         let mut best_rank = 0u8;
 
         for &index in candidates {
-            let support = self.faces[index].font.font().text_unit_support(
-                unit,
-                options.text_direction,
-                options.locale,
-                options.font_variant,
-            );
+            let support = self.faces[index].font.text_unit_support(unit, options);
             if !support.is_supported() {
                 continue;
             }
@@ -960,6 +1765,8 @@ fn face_support_rank(support: fontreader::TextUnitSupport, prefer_color: bool) -
         (false, true, false) => 4,
         (false, true, true) => 3,
         (false, false, true) => 2,
+        // Bitmap strikes only cover some sizes, so any outline wins.
+        _ if support.has_bitmap => 1,
         _ => 0,
     }
 }
@@ -1105,13 +1912,24 @@ fn face_match_score(
     font_style: FontStyle,
     font_stretch: FontStretch,
 ) -> u32 {
-    let weight_delta = descriptor.font_weight.0.abs_diff(font_weight.0) as u32;
-    let style_penalty = if descriptor.font_style == font_style {
+    // A variable face matches anything inside its axis ranges exactly.
+    let weight_delta = match descriptor.font_weight_range {
+        Some((min, max)) => font_weight.0.clamp(min.0, max.0.max(min.0)),
+        None => descriptor.font_weight.0,
+    }
+    .abs_diff(font_weight.0) as u32;
+    let style_penalty = if descriptor.font_style == font_style
+        || descriptor.variable_styles.contains(&font_style)
+    {
         0
     } else {
         10_000
     };
-    let stretch_delta = ((descriptor.font_stretch.0 - font_stretch.0).abs() * 1000.0) as u32;
+    let stretch = match descriptor.font_stretch_range {
+        Some((min, max)) => font_stretch.0.clamp(min.0, max.0.max(min.0)),
+        None => descriptor.font_stretch.0,
+    };
+    let stretch_delta = ((stretch - font_stretch.0).abs() * 1000.0) as u32;
     style_penalty + weight_delta + stretch_delta
 }
 
@@ -1119,6 +1937,59 @@ fn face_match_score(
 mod tests {
     use super::*;
 
+    #[test]
+    fn face_match_score_prefers_covering_variable_face() {
+        let static_bold = FontFaceDescriptor::new("Test").with_font_weight(FontWeight::BOLD);
+        let variable = FontFaceDescriptor::new("Test")
+            .with_font_weight_range(FontWeight::THIN, FontWeight::BLACK)
+            .with_font_stretch_range(FontStretch(0.75), FontStretch(1.0));
+        let score = |descriptor| {
+            face_match_score(
+                descriptor,
+                FontWeight(650),
+                FontStyle::Normal,
+                FontStretch(0.875),
+            )
+        };
+
+        assert_eq!(score(&variable), 0);
+        assert!(score(&static_bold) > score(&variable));
+        assert_eq!(
+            face_match_score(
+                &variable,
+                FontWeight::BOLD,
+                FontStyle::Normal,
+                FontStretch(1.25)
+            ),
+            250
+        );
+    }
+
+    #[test]
+    fn bitmap_only_faces_are_supported_but_rank_below_outlines() {
+        let bitmap = fontreader::TextUnitSupport {
+            has_glyph: true,
+            has_bitmap: true,
+            ..Default::default()
+        };
+        let outline = fontreader::TextUnitSupport {
+            has_glyph: true,
+            has_outline: true,
+            ..Default::default()
+        };
+
+        assert!(bitmap.is_supported());
+        assert!(!fontreader::TextUnitSupport {
+            has_bitmap: true,
+            ..Default::default()
+        }
+        .is_supported());
+        for prefer_color in [false, true] {
+            assert_eq!(face_support_rank(bitmap, prefer_color), 1);
+            assert!(face_support_rank(outline, prefer_color) > 1);
+        }
+    }
+
     #[test]
     fn unit_prefers_face_continuity_for_combining_marks() {
         let unit = crate::fontreader::ParsedTextUnit::Glyph {
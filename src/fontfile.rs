@@ -1,5 +1,6 @@
 //! File and collection entry points for the public API.
 
+use crate::fontbytes::FontBytes;
 use crate::fontface::FontFace;
 use crate::fontreader;
 #[cfg(not(target_arch = "wasm32"))]
@@ -10,6 +11,7 @@ use std::io::{Error, ErrorKind};
 #[cfg(not(target_arch = "wasm32"))]
 use std::net::TcpStream;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Source used by [`open_font`] and [`load_font`].
 pub enum FontSource<'a> {
@@ -22,20 +24,70 @@ pub enum FontSource<'a> {
 /// Owns a font file or collection and lets callers choose faces from it.
 #[derive(Debug, Clone)]
 pub struct FontFile {
-    font: fontreader::Font,
+    pub(crate) font: Arc<fontreader::Font>,
+    named_instance_faces: bool,
 }
 
 impl FontFile {
     /// Opens a font file from disk.
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
         let font = fontreader::Font::get_font_from_file(&path.as_ref().to_path_buf())?;
-        Ok(Self { font })
+        Ok(Self::from_font(font))
     }
 
     /// Opens a font from bytes already loaded in memory.
+    ///
+    /// The bytes are copied once; use [`FontFile::from_bytes`] to share them instead.
     pub fn from_buffer(buffer: &[u8]) -> Result<Self, Error> {
         let font = fontreader::Font::get_font_from_buffer(buffer)?;
-        Ok(Self { font })
+        Ok(Self::from_font(font))
+    }
+
+    /// Opens a font over shared bytes without copying them.
+    ///
+    /// Accepts a `Vec<u8>`, an `Arc<[u8]>`, a `&'static [u8]` or any
+    /// [`FontBytes`], such as a memory map wrapped with [`FontBytes::new`].
+    /// Only the table directory and small tables are read up front; outlines
+    /// and layout tables are parsed on first use.
+    pub fn from_bytes(bytes: impl Into<FontBytes>) -> Result<Self, Error> {
+        let font = fontreader::Font::get_font_from_bytes(bytes.into())?;
+        Ok(Self::from_font(font))
+    }
+
+    /// Opens a font embedded in the binary, e.g. with `include_bytes!`.
+    pub fn from_static(bytes: &'static [u8]) -> Result<Self, Error> {
+        Self::from_bytes(FontBytes::from_static(bytes))
+    }
+
+    fn from_font(font: fontreader::Font) -> Self {
+        Self {
+            font: Arc::new(font),
+            named_instance_faces: false,
+        }
+    }
+
+    /// Lists every `fvar` named instance as its own face in [`Self::face_count`],
+    /// [`Self::face`] and [`Self::faces`]. Faces without named instances still
+    /// count once.
+    pub fn with_named_instance_faces(mut self) -> Self {
+        self.named_instance_faces = true;
+        self
+    }
+
+    /// Maps a listed face index to the physical face and named instance.
+    fn locate_face(&self, index: usize) -> Option<(usize, Option<usize>)> {
+        if !self.named_instance_faces {
+            return Some((index, None));
+        }
+        let mut remaining = index;
+        for physical in 0..self.font.get_font_count() {
+            let count = self.font.named_instance_count_at(physical);
+            if remaining < count.max(1) {
+                return Some((physical, (count > 0).then_some(remaining)));
+            }
+            remaining -= count.max(1);
+        }
+        None
     }
 
     /// Opens a font from a generic [`FontSource`].
@@ -62,18 +114,45 @@ impl FontFile {
         #[cfg(not(target_arch = "wasm32"))]
         {
             let bytes = fetch_http_font(url)?;
-            Self::from_buffer(&bytes)
+            Self::from_bytes(bytes)
         }
     }
 
     /// Returns the number of faces in the file or collection.
     pub fn face_count(&self) -> usize {
-        self.font.get_font_count()
+        if !self.named_instance_faces {
+            return self.font.get_font_count();
+        }
+        (0..self.font.get_font_count())
+            .map(|physical| self.font.named_instance_count_at(physical).max(1))
+            .sum()
     }
 
     /// Returns one face by index.
     pub fn face(&self, index: usize) -> Result<FontFace, Error> {
-        let mut font = self.font.clone();
+        let Some((physical, instance)) = self.locate_face(index) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "font number is out of range",
+            ));
+        };
+        let face = self.physical_face(physical)?;
+        match instance {
+            Some(instance) => face
+                .named_instances()
+                .into_iter()
+                .nth(instance)
+                .map(|instance| face.with_named_instance(instance))
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "named instance is missing")),
+            None => Ok(face),
+        }
+    }
+
+    fn physical_face(&self, index: usize) -> Result<FontFace, Error> {
+        if index == self.font.get_font_number() {
+            return Ok(FontFace::from_shared_font(Arc::clone(&self.font)));
+        }
+        let mut font = fontreader::Font::clone(&self.font);
         font.set_font(index)
             .map_err(|message| Error::new(ErrorKind::InvalidInput, message))?;
         Ok(FontFace::from_font(font))
@@ -81,7 +160,7 @@ impl FontFile {
 
     /// Returns the currently selected face.
     pub fn current_face(&self) -> Result<FontFace, Error> {
-        self.face(self.font.get_font_number())
+        self.physical_face(self.font.get_font_number())
     }
 
     /// Returns all faces in the file or collection.
@@ -289,7 +368,7 @@ impl ChunkedFontBuffer {
     /// Decodes the collected bytes as a [`FontFile`].
     pub fn load_font_file(&self) -> Result<FontFile, Error> {
         let bytes = self.to_vec()?;
-        open_font_from_buffer(&bytes)
+        FontFile::from_bytes(bytes)
     }
 
     /// Decodes the collected bytes and returns the current face.
@@ -329,6 +329,38 @@ pub fn get_font_type<B: BinaryReader>(file: &mut B) -> Result<FontHeaders, std::
     }
 }
 
+/// Number of leading bytes holding the sfnt or TTC table directories, so the
+/// header can be read without copying the table data behind it. `None` for
+/// other formats.
+pub(crate) fn sfnt_directory_len(fontdata: &[u8]) -> Option<usize> {
+    let u16_at = |at: usize| {
+        fontdata
+            .get(at..at + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    };
+    let u32_at = |at: usize| {
+        fontdata
+            .get(at..at + 4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+    let directory_end = |at: usize| u16_at(at + 4).map(|num_tables| at + 12 + num_tables * 16);
+
+    let len = match fontdata.get(..4)? {
+        b"\x00\x01\x00\x00" | b"OTTO" => directory_end(0)?,
+        b"ttcf" => {
+            let num_fonts = u32_at(8)?;
+            // Version 2 headers carry three more u32 DSIG fields.
+            let mut len = 12 + num_fonts.checked_mul(4)? + 12;
+            for index in 0..num_fonts {
+                len = len.max(directory_end(u32_at(12 + index * 4)?)?);
+            }
+            len
+        }
+        _ => return None,
+    };
+    Some(len.min(fontdata.len()))
+}
+
 pub fn get_font_type_from_buffer(fontdata: &[u8]) -> Result<FontHeaders, std::io::Error> {
     let file = &mut BytesReader::new(fontdata);
     get_font_type(file)
@@ -339,6 +371,21 @@ mod tests {
     use super::*;
     use bin_rs::reader::BytesReader;
 
+    #[test]
+    fn sfnt_directory_len_covers_every_collection_directory() {
+        let mut otf = vec![0, 1, 0, 0, 0, 2];
+        otf.resize(64, 0);
+        assert_eq!(sfnt_directory_len(&otf), Some(12 + 2 * 16));
+
+        let mut ttc = b"ttcf\x00\x01\x00\x00\x00\x00\x00\x02".to_vec();
+        ttc.extend_from_slice(&20u32.to_be_bytes());
+        ttc.extend_from_slice(&40u32.to_be_bytes());
+        ttc.resize(200, 0);
+        ttc[44..46].copy_from_slice(&3u16.to_be_bytes());
+        assert_eq!(sfnt_directory_len(&ttc), Some(40 + 12 + 3 * 16));
+        assert_eq!(sfnt_directory_len(b"wOFF\x00\x01"), None);
+    }
+
     #[test]
     fn get_font_type_returns_error_on_short_buffer() {
         let mut reader = BytesReader::new(b"OT");
@@ -1,5 +1,6 @@
 use base64::{engine::general_purpose, Engine as _};
 use bin_rs::reader::{BinaryReader, BytesReader};
+use bin_rs::Endian;
 use std::collections::HashMap;
 #[cfg(debug_assertions)]
 use std::fs::File;
@@ -11,18 +12,25 @@ use crate::commands::SvgGlyphLayer;
 use crate::commands::{
     Command as DrawCommand, FontMetrics as DrawFontMetrics, Glyph, GlyphBounds, GlyphFlow,
     GlyphLayer, GlyphMetrics as DrawGlyphMetrics, GlyphPaint, GlyphRun, PathGlyphLayer,
-    PositionedGlyph, RasterGlyphLayer,
+    PathPaintMode, PositionedGlyph, RasterGlyphLayer,
 };
+use crate::fontbytes::{FontBytes, LazyTable, RawTable};
 use crate::fontheader;
 use crate::opentype::color::sbix;
 use crate::opentype::color::svg;
-use crate::opentype::color::{colr, cpal};
+use crate::opentype::color::{cbdt, cblc, colr, cpal, ebdt};
+#[cfg(feature = "layout")]
+use crate::opentype::extentions::base;
 #[cfg(feature = "layout")]
 use crate::opentype::extentions::gdef;
 #[cfg(feature = "layout")]
 use crate::opentype::extentions::gpos;
 #[cfg(feature = "layout")]
 use crate::opentype::extentions::gsub;
+#[cfg(feature = "layout")]
+use crate::opentype::extentions::math;
+#[cfg(feature = "layout")]
+use crate::opentype::layouts::DeviceContext;
 use crate::opentype::outline::glyf::ParsedGlyph;
 use crate::opentype::platforms::PlatformID;
 use crate::opentype::requires::cmap::CmapEncodings;
@@ -33,7 +41,12 @@ use crate::opentype::requires::vhea::VHEA;
 use crate::opentype::requires::vmtx::VerticalMetric;
 use crate::opentype::requires::*;
 use crate::opentype::{outline::*, OTFHeader};
+use crate::outlinecache::{CachedOutline, OutlineCache, OutlineKey};
+#[cfg(feature = "layout")]
+use crate::shapeplan::GposStage;
+use crate::shapeplan::{GsubStage, ShapePlan};
 use crate::util::sniff_encoded_image_dimensions;
+use std::sync::{Arc, OnceLock};
 
 #[cfg(debug_assertions)]
 use std::io::{BufWriter, Write};
@@ -89,10 +102,13 @@ pub enum PathCommand {
 pub enum BitmapGlyphFormat {
     Png,
     Jpeg,
+    Tiff,
 }
 
 #[derive(Debug, Clone)]
 pub struct BitmapGlyphCommands {
+    /// Top-left corner of the image in font units, in the y-down frame of the
+    /// glyph's `commands`: `origin_x + offset_x`, `offset_y - origin_y`.
     pub offset_x: f64,
     pub offset_y: f64,
     pub width: f64,
@@ -170,33 +186,105 @@ pub struct Font {
     pub(crate) avar: Option<avar::AVAR>,
     pub(crate) gvar: Option<gvar::GVAR>,
     pub(crate) loca: Option<loca::LOCA>, // openType font, CFF/CFF2 none
-    pub(crate) glyf: Option<glyf::GLYF>, // openType font, CFF/CFF2 none
+    // Outline, color and layout tables are parsed on first access.
+    pub(crate) glyf: Option<LazyTable<glyf::GLYF>>, // openType font, CFF/CFF2 none
     #[cfg(feature = "cff")]
-    pub(crate) cff: Option<cff::CFF>, // CFF font, openType none
+    pub(crate) cff: Option<LazyTable<cff::CFF>>, // CFF font, openType none
     pub(crate) hvar: Option<hvar::HVAR>,
     pub(crate) mvar: Option<mvar::MVAR>,
-    pub(crate) colr: Option<colr::COLR>,
+    pub(crate) stat: Option<stat::STAT>,
+    pub(crate) colr: Option<LazyTable<colr::COLR>>,
     pub(crate) cpal: Option<cpal::CPAL>,
     #[cfg(feature = "layout")]
-    pub(crate) gdef: Option<gdef::GDEF>,
+    pub(crate) base: Option<LazyTable<base::BASE>>,
     #[cfg(feature = "layout")]
-    pub(crate) gpos: Option<gpos::GPOS>,
+    pub(crate) gdef: Option<LazyTable<gdef::GDEF>>,
     #[cfg(feature = "layout")]
-    pub(crate) gsub: Option<gsub::GSUB>,
-    pub(crate) svg: Option<svg::SVG>,
-    pub(crate) sbix: Option<sbix::SBIX>,
+    pub(crate) gpos: Option<LazyTable<gpos::GPOS>>,
+    #[cfg(feature = "layout")]
+    pub(crate) gsub: Option<LazyTable<gsub::GSUB>>,
+    #[cfg(feature = "layout")]
+    pub(crate) math: Option<LazyTable<math::MATH>>,
+    pub(crate) svg: Option<LazyTable<svg::SVG>>,
+    pub(crate) sbix: Option<LazyTable<sbix::SBIX>>,
+    pub(crate) cblc: Option<LazyTable<cblc::CBLC>>,
+    pub(crate) cbdt: Option<LazyTable<cbdt::CBDT>>,
+    /// `EBLC`, or Apple `bloc` when there is no `EBLC`.
+    pub(crate) eblc: Option<LazyTable<cblc::CBLC>>,
+    /// `EBDT`, or Apple `bdat` when there is no `EBDT`.
+    pub(crate) ebdt: Option<LazyTable<ebdt::EBDT>>,
     pub(crate) vhea: Option<vhea::VHEA>,
     pub(crate) vvar: Option<vvar::VVAR>,
     pub(crate) vmtx: Option<vmtx::VMTX>,
     hmtx_pos: Option<Pointer>,
     vmtx_pos: Option<Pointer>,
     loca_pos: Option<Pointer>, // OpenType font, CFF/CFF2 none
-    glyf_pos: Option<Pointer>, // OpenType font, CFF/CFF2 none
-    sbix_pos: Option<Pointer>,
-    pub(crate) more_fonts: Box<Vec<Font>>,
+    /// Every table record of this face, for writing derived fonts.
+    pub(crate) raw_tables: Vec<RawTable>,
+    pub(crate) more_fonts: CollectionFaces,
     current_font: usize,
 }
 
+/// Faces after the first one in a collection, parsed when first selected.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CollectionFaces {
+    faces: Arc<Vec<CollectionFace>>,
+}
+
+#[derive(Debug)]
+struct CollectionFace {
+    data: FontBytes,
+    header: OTFHeader,
+    font: OnceLock<Option<Font>>,
+}
+
+impl CollectionFaces {
+    fn new(data: &FontBytes, headers: &[OTFHeader]) -> Self {
+        let faces = headers
+            .iter()
+            .map(|header| CollectionFace {
+                data: data.clone(),
+                header: header.clone(),
+                font: OnceLock::new(),
+            })
+            .collect();
+        Self {
+            faces: Arc::new(faces),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&Font> {
+        let face = self.faces.get(index)?;
+        face.font
+            .get_or_init(|| from_opentype(&face.data, &face.header).ok())
+            .as_ref()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Font> {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_parsed(&self, index: usize) -> bool {
+        self.faces
+            .get(index)
+            .is_some_and(|face| face.font.get().is_some())
+    }
+}
+
+impl std::ops::Index<usize> for CollectionFaces {
+    type Output = Font;
+
+    /// Only used with indices `Font::set_font` has already parsed.
+    fn index(&self, index: usize) -> &Font {
+        self.get(index).expect("collection face is parsed")
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum ResolvedTextUnit {
     Glyph(ResolvedGlyph),
@@ -218,9 +306,57 @@ pub(crate) enum ParsedTextUnit {
 #[derive(Debug, Clone, Copy)]
 struct ResolvedGlyph {
     ch: char,
+    /// Char index in the shaped text of the unit this glyph came from; a
+    /// ligature keeps the cluster of its first component.
+    cluster: usize,
     glyph_id: usize,
     prefer_color: bool,
     ligature_components: u16,
+    synthesis: Option<GlyphSynthesis>,
+}
+
+/// Font size for a char range of a run shaped in one pass, so rich text
+/// spans of different sizes still ligate and kern across their boundary.
+#[derive(Debug, Clone)]
+pub(crate) struct ClusterSize {
+    pub(crate) chars: std::ops::Range<usize>,
+    pub(crate) font_size: f32,
+}
+
+/// Transform for a caps or position form the font cannot substitute itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphSynthesis {
+    scale_x: f32,
+    scale_y: f32,
+    /// Shift in font units, y up.
+    shift_x: f32,
+    shift_y: f32,
+    /// Outline stroke in font units that restores the stem weight lost by scaling.
+    embolden: f32,
+}
+
+impl GlyphSynthesis {
+    fn then(self, next: Self) -> Self {
+        Self {
+            scale_x: self.scale_x * next.scale_x,
+            scale_y: self.scale_y * next.scale_y,
+            shift_x: self.shift_x * next.scale_x + next.shift_x,
+            shift_y: self.shift_y * next.scale_y + next.shift_y,
+            embolden: self.embolden * next.scale_y + next.embolden,
+        }
+    }
+
+    /// Advance in font units of a glyph drawn with this synthesis; the
+    /// embolden stroke widens it along the line.
+    fn advance(self, (advance_x, advance_y): (f32, f32), is_vertical: bool) -> (f32, f32) {
+        let advance_x = advance_x * self.scale_x;
+        let advance_y = advance_y * self.scale_y;
+        if is_vertical {
+            (advance_x, advance_y + self.embolden)
+        } else {
+            (advance_x + self.embolden, advance_y)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -242,11 +378,13 @@ pub(crate) struct TextUnitSupport {
     pub(crate) has_glyph: bool,
     pub(crate) has_outline: bool,
     pub(crate) has_color: bool,
+    /// An `EBLC` strike has the glyph at the requested size.
+    pub(crate) has_bitmap: bool,
 }
 
 impl TextUnitSupport {
     pub(crate) fn is_supported(self) -> bool {
-        self.has_glyph && (self.has_outline || self.has_color)
+        self.has_glyph && (self.has_outline || self.has_color || self.has_bitmap)
     }
 }
 
@@ -273,15 +411,24 @@ impl Font {
             cff: None,
             hvar: None,
             mvar: None,
+            stat: None,
             colr: None,
             cpal: None,
             #[cfg(feature = "layout")]
+            base: None,
+            #[cfg(feature = "layout")]
             gdef: None,
             #[cfg(feature = "layout")]
             gpos: None,
             #[cfg(feature = "layout")]
             gsub: None,
+            #[cfg(feature = "layout")]
+            math: None,
             sbix: None,
+            cblc: None,
+            cbdt: None,
+            eblc: None,
+            ebdt: None,
             svg: None,
             vhea: None,
             vvar: None,
@@ -289,9 +436,8 @@ impl Font {
             hmtx_pos: None,
             vmtx_pos: None,
             loca_pos: None,
-            glyf_pos: None,
-            sbix_pos: None,
-            more_fonts: Box::<Vec<Font>>::default(),
+            raw_tables: Vec::new(),
+            more_fonts: CollectionFaces::default(),
             current_font: 0,
         }
     }
@@ -315,7 +461,15 @@ impl Font {
     }
 
     pub fn get_font_from_buffer(fontdata: &[u8]) -> Result<Self, Error> {
-        let mut reader = BytesReader::new(fontdata);
+        Self::get_font_from_bytes(FontBytes::from(fontdata.to_vec()))
+    }
+
+    /// Loads a font over shared bytes. Only the table directory and the small
+    /// required tables are read here; everything else is parsed on first use.
+    pub(crate) fn get_font_from_bytes(data: FontBytes) -> Result<Self, Error> {
+        let fontdata = data.as_bytes();
+        let directory_len = fontheader::sfnt_directory_len(fontdata).unwrap_or(fontdata.len());
+        let mut reader = BytesReader::new(&fontdata[..directory_len]);
         let font_type = fontheader::get_font_type(&mut reader)?;
         if let fontheader::FontHeaders::WOFF2(header) = font_type {
             let declared_length = header.length as usize;
@@ -336,11 +490,10 @@ impl Font {
                     format!("Failed to decode WOFF2 font: {err}"),
                 )
             })?;
-            return Self::get_font_from_buffer(&ttf);
+            return Self::get_font_from_bytes(FontBytes::from(ttf));
         }
 
-        reader.seek(SeekFrom::Start(0))?;
-        font_load(&mut reader)
+        font_load(&data, font_type)
     }
 
     pub(crate) fn get_h_metrix_with_coords(&self, id: usize, coordinates: &[f32]) -> LongHorMetric {
@@ -575,7 +728,7 @@ impl Font {
     ) -> Result<String, Error> {
         let layout = self.get_layout(glyph_id, false);
         #[cfg(feature = "cff")]
-        if let Some(cff) = self.cff.as_ref() {
+        if let Some(cff) = self.current_cff() {
             let string = cff.to_svg(glyph_id, fontsize, fontunit, &layout, 0.0, 0.0)?;
             return Ok(string);
         }
@@ -697,7 +850,7 @@ impl Font {
         // cff ?
 
         #[cfg(feature = "cff")]
-        if let Some(cff) = self.cff.as_ref() {
+        if let Some(cff) = self.current_cff() {
             let glyf_data = self.get_glyph_with_uvs_axis(ch, vs, is_vert);
             let glyph_id = glyf_data.glyph_id;
             let layout = self.get_layout(glyph_id as usize, is_vert);
@@ -917,7 +1070,31 @@ impl Font {
         }
     }
 
-    fn current_mvar(&self) -> Option<&mvar::MVAR> {
+    fn current_os2(&self) -> Option<&os2::OS2> {
+        if self.current_font == 0 {
+            self.os2.as_ref()
+        } else {
+            self.more_fonts[self.current_font - 1].os2.as_ref()
+        }
+    }
+
+    fn current_post(&self) -> Option<&post::POST> {
+        if self.current_font == 0 {
+            self.post.as_ref()
+        } else {
+            self.more_fonts[self.current_font - 1].post.as_ref()
+        }
+    }
+
+    pub(crate) fn current_stat(&self) -> Option<&stat::STAT> {
+        if self.current_font == 0 {
+            self.stat.as_ref()
+        } else {
+            self.more_fonts[self.current_font - 1].stat.as_ref()
+        }
+    }
+
+    pub(crate) fn current_mvar(&self) -> Option<&mvar::MVAR> {
         if self.current_font == 0 {
             self.mvar.as_ref()
         } else {
@@ -933,14 +1110,24 @@ impl Font {
         }
     }
 
-    fn current_glyf(&self) -> Option<&glyf::GLYF> {
+    /// The selected face: `self`, or a collection face `set_font` has parsed.
+    pub(crate) fn current(&self) -> &Font {
         if self.current_font == 0 {
-            self.glyf.as_ref()
+            self
         } else {
-            self.more_fonts[self.current_font - 1].glyf.as_ref()
+            &self.more_fonts[self.current_font - 1]
         }
     }
 
+    pub(crate) fn current_glyf(&self) -> Option<&glyf::GLYF> {
+        let font = self.current();
+        let loca = font.loca.as_ref()?;
+        font.glyf
+            .as_ref()?
+            .get(|reader, length| Ok(glyf::GLYF::new(reader, 0, length, loca)))
+            .ok()
+    }
+
     fn current_outline_format(&self) -> GlyphFormat {
         if self.current_font == 0 {
             self.outline_format
@@ -949,15 +1136,15 @@ impl Font {
         }
     }
 
-    fn current_colr(&self) -> Option<&colr::COLR> {
-        if self.current_font == 0 {
-            self.colr.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].colr.as_ref()
-        }
+    pub(crate) fn current_colr(&self) -> Option<&colr::COLR> {
+        self.current()
+            .colr
+            .as_ref()?
+            .get(|reader, length| colr::COLR::new(reader, 0, length))
+            .ok()
     }
 
-    fn current_cpal(&self) -> Option<&cpal::CPAL> {
+    pub(crate) fn current_cpal(&self) -> Option<&cpal::CPAL> {
         if self.current_font == 0 {
             self.cpal.as_ref()
         } else {
@@ -966,80 +1153,201 @@ impl Font {
     }
 
     fn current_sbix(&self) -> Option<&sbix::SBIX> {
-        if self.current_font == 0 {
-            self.sbix.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].sbix.as_ref()
-        }
+        let font = self.current();
+        let num_glyphs = font.maxp.as_ref()?.num_glyphs as u32;
+        font.sbix
+            .as_ref()?
+            .get(|reader, length| sbix::SBIX::new(reader, 0, length, num_glyphs))
+            .ok()
     }
 
-    fn current_svg_table(&self) -> Option<&svg::SVG> {
-        if self.current_font == 0 {
-            self.svg.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].svg.as_ref()
-        }
+    fn current_cblc(&self) -> Option<&cblc::CBLC> {
+        self.current()
+            .cblc
+            .as_ref()?
+            .get(|reader, length| cblc::CBLC::new(reader, 0, length))
+            .ok()
+    }
+
+    fn current_cbdt(&self) -> Option<&cbdt::CBDT> {
+        self.current()
+            .cbdt
+            .as_ref()?
+            .get(|reader, length| cbdt::CBDT::new(reader, 0, length))
+            .ok()
+    }
+
+    fn current_eblc(&self) -> Option<&cblc::CBLC> {
+        self.current()
+            .eblc
+            .as_ref()?
+            .get(|reader, length| cblc::CBLC::new(reader, 0, length))
+            .ok()
+    }
+
+    fn current_ebdt(&self) -> Option<&ebdt::EBDT> {
+        self.current()
+            .ebdt
+            .as_ref()?
+            .get(|reader, length| ebdt::EBDT::new(reader, 0, length))
+            .ok()
+    }
+
+    pub(crate) fn current_svg_table(&self) -> Option<&svg::SVG> {
+        self.current()
+            .svg
+            .as_ref()?
+            .get(|reader, length| svg::SVG::new(reader, 0, length))
+            .ok()
     }
 
     #[cfg(feature = "layout")]
-    fn current_gpos(&self) -> Option<&gpos::GPOS> {
-        if self.current_font == 0 {
-            self.gpos.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].gpos.as_ref()
-        }
+    pub(crate) fn current_gpos(&self) -> Option<&gpos::GPOS> {
+        self.current()
+            .gpos
+            .as_ref()?
+            .get(|reader, length| gpos::GPOS::new(reader, 0, length))
+            .ok()
     }
 
     #[cfg(feature = "layout")]
-    fn current_gsub(&self) -> Option<&gsub::GSUB> {
-        if self.current_font == 0 {
-            self.gsub.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].gsub.as_ref()
-        }
+    pub(crate) fn current_gsub(&self) -> Option<&gsub::GSUB> {
+        self.current()
+            .gsub
+            .as_ref()?
+            .get(|reader, length| gsub::GSUB::new(reader, 0, length))
+            .ok()
     }
 
     #[cfg(feature = "layout")]
-    fn current_gdef(&self) -> Option<&gdef::GDEF> {
-        if self.current_font == 0 {
-            self.gdef.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].gdef.as_ref()
-        }
+    fn current_math(&self) -> Option<&math::MATH> {
+        self.current()
+            .math
+            .as_ref()?
+            .get(|reader, length| math::MATH::new(reader, 0, length as usize))
+            .ok()
+    }
+
+    #[cfg(feature = "layout")]
+    fn current_base(&self) -> Option<&base::BASE> {
+        self.current()
+            .base
+            .as_ref()?
+            .get(|reader, length| base::BASE::new(reader, 0, length as usize))
+            .ok()
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn current_gdef(&self) -> Option<&gdef::GDEF> {
+        self.current()
+            .gdef
+            .as_ref()?
+            .get(|reader, length| gdef::GDEF::new(reader, 0, length as usize))
+            .ok()
     }
 
     #[cfg(feature = "cff")]
     fn current_cff(&self) -> Option<&cff::CFF> {
-        if self.current_font == 0 {
-            self.cff.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].cff.as_ref()
+        self.current()
+            .cff
+            .as_ref()?
+            .get(|reader, length| {
+                cff::CFF::new(reader, 0, length)
+                    .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))
+            })
+            .ok()
+    }
+
+    /// Parses every table of the selected face that is otherwise parsed on
+    /// first use, and returns the first one that is malformed. Rendering and
+    /// shaping skip such a table instead of failing.
+    pub fn check_tables(&self) -> Result<(), Error> {
+        let font = self.current();
+        let _ = (
+            self.current_glyf(),
+            self.current_colr(),
+            self.current_svg_table(),
+            self.current_sbix(),
+            self.current_cblc(),
+            self.current_cbdt(),
+            self.current_eblc(),
+            self.current_ebdt(),
+        );
+        let errors = vec![
+            ("glyf", font.glyf.as_ref().and_then(LazyTable::error)),
+            ("COLR", font.colr.as_ref().and_then(LazyTable::error)),
+            ("SVG ", font.svg.as_ref().and_then(LazyTable::error)),
+            ("sbix", font.sbix.as_ref().and_then(LazyTable::error)),
+            ("CBLC", font.cblc.as_ref().and_then(LazyTable::error)),
+            ("CBDT", font.cbdt.as_ref().and_then(LazyTable::error)),
+            ("EBLC", font.eblc.as_ref().and_then(LazyTable::error)),
+            ("EBDT", font.ebdt.as_ref().and_then(LazyTable::error)),
+        ];
+        #[cfg(feature = "cff")]
+        let errors = {
+            let _ = self.current_cff();
+            let mut errors = errors;
+            errors.push(("CFF ", font.cff.as_ref().and_then(LazyTable::error)));
+            errors
+        };
+        #[cfg(feature = "layout")]
+        let errors = {
+            let _ = (
+                self.current_gdef(),
+                self.current_gsub(),
+                self.current_gpos(),
+                self.current_base(),
+                self.current_math(),
+            );
+            let mut errors = errors;
+            errors.extend([
+                ("GDEF", font.gdef.as_ref().and_then(LazyTable::error)),
+                ("GSUB", font.gsub.as_ref().and_then(LazyTable::error)),
+                ("GPOS", font.gpos.as_ref().and_then(LazyTable::error)),
+                ("BASE", font.base.as_ref().and_then(LazyTable::error)),
+                ("MATH", font.math.as_ref().and_then(LazyTable::error)),
+            ]);
+            errors
+        };
+        match errors
+            .into_iter()
+            .find_map(|(tag, error)| Some((tag, error?)))
+        {
+            Some((tag, error)) => Err(Error::new(
+                error.kind(),
+                format!("malformed {} table: {error}", tag.trim_end()),
+            )),
+            None => Ok(()),
         }
     }
 
-    fn normalized_variation_coords(&self, options: &crate::commands::FontOptions<'_>) -> Vec<f32> {
+    pub(crate) fn normalized_variation_coords(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Vec<f32> {
         let Some(fvar) = self.current_fvar() else {
             return Vec::new();
         };
 
+        let has_italic_axis = fvar.axes.iter().any(|axis| axis.tag == tag4("ital"));
         let mut coordinates = fvar
             .axes
             .iter()
             .map(|axis| {
+                // Explicit settings win over the CSS properties.
                 let value = options
                     .variations
                     .iter()
                     .find(|setting| u32::from_be_bytes(setting.tag) == axis.tag)
                     .map(|setting| setting.value)
+                    .or_else(|| options.css_variation(axis.tag.to_be_bytes(), has_italic_axis))
                     .unwrap_or(axis.default_value);
                 axis.normalized_value(value)
             })
             .collect::<Vec<_>>();
 
         if let Some(avar) = self.current_avar() {
-            for index in 0..coordinates.len() {
-                avar.map_coordinate(&mut coordinates, index);
-            }
+            avar.map_coordinates(&mut coordinates);
         }
 
         coordinates
@@ -1066,24 +1374,56 @@ impl Font {
         options: &crate::commands::FontOptions<'_>,
     ) -> GriphData {
         let coordinates = self.normalized_variation_coords(options);
+        GriphData {
+            glyph_id,
+            open_type_glyf: Some(self.get_glyph_from_id_with_coords(
+                glyph_id,
+                is_vert,
+                coordinates,
+            )),
+        }
+    }
+
+    /// Resolves a glyph through `outlines` when the face keeps an outline cache.
+    fn resolve_outline(
+        &self,
+        glyph_id: usize,
+        is_vert: bool,
+        options: &crate::commands::FontOptions<'_>,
+        outlines: Option<&OutlineCache>,
+    ) -> Arc<CachedOutline> {
+        let coordinates = self.normalized_variation_coords(options);
+        let Some(outlines) = outlines else {
+            return Arc::new(CachedOutline::new(self.get_glyph_from_id_with_coords(
+                glyph_id,
+                is_vert,
+                coordinates,
+            )));
+        };
+        outlines.get_or_insert(OutlineKey::new(glyph_id, is_vert, &coordinates), || {
+            CachedOutline::new(self.get_glyph_from_id_with_coords(glyph_id, is_vert, coordinates))
+        })
+    }
+
+    fn get_glyph_from_id_with_coords(
+        &self,
+        glyph_id: usize,
+        is_vert: bool,
+        coordinates: Vec<f32>,
+    ) -> OpenTypeGlyph {
         let mut layout = self.get_layout_with_coords(glyph_id, is_vert, &coordinates);
 
         #[cfg(feature = "cff")]
         if let Some(cff) = self.current_cff() {
             let string = cff.to_code_with_coords(glyph_id, &layout, &coordinates);
-            let open_type_glyf = Some(OpenTypeGlyph {
+            return OpenTypeGlyph {
                 layout,
                 glyph: FontData::CFF(string.as_bytes().to_vec()),
-                variation_coords: coordinates.clone(),
-            });
-
-            return GriphData {
-                glyph_id,
-                open_type_glyf,
+                variation_coords: coordinates,
             };
         }
 
-        let open_type_glyph = match self.current_outline_format() {
+        match self.current_outline_format() {
             GlyphFormat::OpenTypeGlyph => {
                 let glyf = self
                     .current_glyf()
@@ -1113,25 +1453,92 @@ impl Font {
                 OpenTypeGlyph {
                     layout,
                     glyph,
-                    variation_coords: coordinates.clone(),
+                    variation_coords: coordinates,
                 }
             }
             GlyphFormat::CFF2 => OpenTypeGlyph {
                 layout,
                 glyph: FontData::CFF2(Vec::new()),
-                variation_coords: coordinates.clone(),
+                variation_coords: coordinates,
             },
             _ => OpenTypeGlyph {
                 layout,
                 glyph: FontData::CFF2(Vec::new()),
-                variation_coords: coordinates.clone(),
+                variation_coords: coordinates,
             },
+        }
+    }
+
+    /// Outline and metrics of one glyph at normalized `coordinates`, for
+    /// writing a static instance. Composite glyphs come back flattened.
+    pub(crate) fn instance_glyph(
+        &self,
+        glyph_id: usize,
+        coordinates: &[f32],
+    ) -> Result<crate::instancer::InstanceGlyph, Error> {
+        use crate::instancer::{InstanceGlyph, InstanceOutline};
+
+        let glyph = self.get_glyph_from_id_with_coords(glyph_id, false, coordinates.to_vec());
+        let FontLayout::Horizontal(horizontal) = &glyph.layout else {
+            return Err(Error::new(ErrorKind::InvalidData, "hmtx is missing"));
+        };
+        let vertical = if self.current_vhea().is_some() {
+            match self
+                .get_glyph_from_id_with_coords(glyph_id, true, coordinates.to_vec())
+                .layout
+            {
+                FontLayout::Vertical(vertical) => Some((vertical.advance_height, vertical.tsb)),
+                _ => None,
+            }
+        } else {
+            None
         };
 
-        GriphData {
-            glyph_id,
-            open_type_glyf: Some(open_type_glyph),
+        #[cfg(feature = "cff")]
+        if let Some(cff) = self.current_cff() {
+            // The CFF path flips y for screen space; instances stay y-up.
+            let commands = cff
+                .to_path_commands_with_coords(glyph_id, 1.0, coordinates)?
+                .into_iter()
+                .map(|command| match command {
+                    DrawCommand::MoveTo(x, y) => DrawCommand::MoveTo(x, -y),
+                    DrawCommand::Line(x, y) => DrawCommand::Line(x, -y),
+                    DrawCommand::Bezier((cx, cy), (x, y)) => {
+                        DrawCommand::Bezier((cx, -cy), (x, -y))
+                    }
+                    DrawCommand::CubicBezier((xa, ya), (xb, yb), (xc, yc)) => {
+                        DrawCommand::CubicBezier((xa, -ya), (xb, -yb), (xc, -yc))
+                    }
+                    DrawCommand::Close => DrawCommand::Close,
+                })
+                .collect();
+            return Ok(InstanceGlyph {
+                outline: InstanceOutline::Cubic(commands),
+                advance_width: horizontal.advance_width,
+                lsb: horizontal.lsb,
+                vertical,
+            });
         }
+
+        let outline = match glyph.glyph {
+            FontData::ParsedGlyph(parsed) => InstanceOutline::TrueType(parsed),
+            FontData::Glyph(_) => self
+                .current_glyf()
+                .and_then(|glyf| glyf.parse_glyph(glyph_id))
+                .map_or(InstanceOutline::Empty, InstanceOutline::TrueType),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "static instances need glyf or CFF outlines",
+                ))
+            }
+        };
+        Ok(InstanceGlyph {
+            outline,
+            advance_width: horizontal.advance_width,
+            lsb: horizontal.lsb,
+            vertical,
+        })
     }
 
     fn current_gvar_variation(
@@ -1231,6 +1638,36 @@ impl Font {
         Ok(glyph_id)
     }
 
+    /// Resolves `ch` through cmap, then the plan's vertical and `locl` lookups.
+    fn resolve_glyph_id_with_plan(
+        &self,
+        ch: char,
+        vs: char,
+        plan: &ShapePlan,
+    ) -> Result<usize, Error> {
+        let glyph_id = self
+            .current_cmap()?
+            .get_glyph_position_from_uvs(ch as u32, vs as u32) as usize;
+
+        #[cfg(feature = "layout")]
+        if let Some(gsub) = self.current_gsub() {
+            let glyph_id = if plan.direction().is_vertical() {
+                gsub.lookup_single_with_lookups(glyph_id, plan.gsub_lookups(GsubStage::Vertical))
+                    .map_or(glyph_id, |glyph_id| glyph_id as u16 as usize)
+            } else {
+                glyph_id
+            };
+            return Ok(gsub
+                .lookup_single_with_lookups(glyph_id, plan.gsub_lookups(GsubStage::Locale))
+                .unwrap_or(glyph_id));
+        }
+
+        #[cfg(not(feature = "layout"))]
+        let _ = plan;
+
+        Ok(glyph_id)
+    }
+
     fn is_variation_selector(ch: char) -> bool {
         (0xfe00..=0xfe0f).contains(&(ch as u32)) || (0xE0100..=0xE01EF).contains(&(ch as u32))
     }
@@ -1365,6 +1802,14 @@ impl Font {
     }
 
     fn parse_text_units(text: &str) -> Vec<ParsedTextUnit> {
+        Self::parse_text_units_with_offsets(text)
+            .into_iter()
+            .map(|(_, unit)| unit)
+            .collect()
+    }
+
+    /// Like `parse_text_units`, paired with the char index each unit starts at.
+    pub(crate) fn parse_text_units_with_offsets(text: &str) -> Vec<(usize, ParsedTextUnit)> {
         let chars: Vec<char> = text.chars().collect();
         let mut units = Vec::new();
         let mut index = 0;
@@ -1376,17 +1821,18 @@ impl Font {
                     index += 1;
                 }
                 '\n' => {
-                    units.push(ParsedTextUnit::Newline);
+                    units.push((index, ParsedTextUnit::Newline));
                     index += 1;
                 }
                 '\t' => {
-                    units.push(ParsedTextUnit::Tab);
+                    units.push((index, ParsedTextUnit::Tab));
                     index += 1;
                 }
                 _ if Self::is_variation_selector(ch) => {
                     index += 1;
                 }
                 _ => {
+                    let start = index;
                     let mut text = String::new();
                     text.push(ch);
                     let mut variation_selector = '\0';
@@ -1419,11 +1865,14 @@ impl Font {
                         Self::extend_cluster_suffix(&chars, &mut index, &mut text);
                     }
 
-                    units.push(ParsedTextUnit::Glyph {
-                        text,
-                        ch,
-                        variation_selector,
-                    });
+                    units.push((
+                        start,
+                        ParsedTextUnit::Glyph {
+                            text,
+                            ch,
+                            variation_selector,
+                        },
+                    ));
                 }
             }
         }
@@ -1431,6 +1880,7 @@ impl Font {
         units
     }
 
+    #[cfg(test)]
     pub(crate) fn parse_text_units_for_fallback(text: &str) -> Vec<ParsedTextUnit> {
         Self::parse_text_units(text)
     }
@@ -1439,23 +1889,48 @@ impl Font {
     fn apply_gsub_sequence_stages(
         &self,
         glyphs: &mut Vec<(usize, usize)>,
-        locale: Option<&str>,
-        is_right_to_left: bool,
-        font_variant: crate::commands::FontVariant,
+        plan: &ShapePlan,
+        source_chars: &[char],
     ) {
         let Some(gsub) = self.current_gsub() else {
             return;
         };
 
         // Keep the shaping order explicit:
+        // 0. `rvrn`, which swaps glyphs for the variation instance
         // 1. canonical composition / decomposition
-        // 2. locale / variant specific substitutions
-        // 3. RTL joining and contextual forms
-        gsub.apply_ccmp_sequence(glyphs);
-        gsub.apply_variant_sequence(glyphs, locale, font_variant);
-        if is_right_to_left {
-            gsub.apply_joining_sequence(glyphs, locale);
-            gsub.apply_rtl_contextual_sequence(glyphs, locale);
+        // 2. fractions, which only apply around a fraction slash
+        // 3. locale / variant / requested feature substitutions
+        // 4. RTL joining and contextual forms
+        gsub.apply_lookups(glyphs, plan.gsub_lookups(GsubStage::RequiredVariation));
+        gsub.apply_lookups(glyphs, plan.gsub_lookups(GsubStage::Ccmp));
+        if plan.requests_feature(*b"frac") {
+            let source_char = |source_index: usize| source_chars.get(source_index).copied();
+            gsub.apply_fraction_with_lookups(
+                glyphs,
+                [
+                    plan.gsub_lookups(GsubStage::Numerator),
+                    plan.gsub_lookups(GsubStage::Denominator),
+                    plan.gsub_lookups(GsubStage::Fraction),
+                ],
+                |source_index| source_char(source_index).is_some_and(|ch| ch.is_ascii_digit()),
+                |source_index| {
+                    source_char(source_index).is_some_and(|ch| ch == '/' || ch == '\u{2044}')
+                },
+            );
+        }
+        gsub.apply_lookups(glyphs, plan.gsub_lookups(GsubStage::Features));
+        if plan.direction().is_right_to_left() {
+            gsub.apply_joining_with_lookups(
+                glyphs,
+                [
+                    plan.gsub_lookups(GsubStage::Isolated),
+                    plan.gsub_lookups(GsubStage::Initial),
+                    plan.gsub_lookups(GsubStage::Medial),
+                    plan.gsub_lookups(GsubStage::Final),
+                ],
+            );
+            gsub.apply_lookups(glyphs, plan.gsub_lookups(GsubStage::RtlContextual));
         }
     }
 
@@ -1464,8 +1939,7 @@ impl Font {
         &self,
         output: &mut Vec<ResolvedTextUnit>,
         expanded_glyphs: &[ResolvedGlyph],
-        locale: Option<&str>,
-        is_right_to_left: bool,
+        plan: &ShapePlan,
     ) {
         let Some(gsub) = self.current_gsub() else {
             output.extend(expanded_glyphs.iter().copied().map(ResolvedTextUnit::Glyph));
@@ -1473,6 +1947,7 @@ impl Font {
         };
 
         const MAX_LIGATURE_COMPONENTS: usize = 8;
+        let is_right_to_left = plan.direction().is_right_to_left();
         let glyph_ids: Vec<usize> = expanded_glyphs.iter().map(|glyph| glyph.glyph_id).collect();
         let mut index = 0;
         while index < expanded_glyphs.len() {
@@ -1480,14 +1955,18 @@ impl Font {
             let mut matched = None;
             for len in (2..=max_len).rev() {
                 if is_right_to_left {
-                    if let Some(glyph_id) =
-                        gsub.lookup_rlig_sequence(&glyph_ids[index..index + len], locale)
-                    {
+                    if let Some(glyph_id) = gsub.lookup_ligature_with_lookups(
+                        &glyph_ids[index..index + len],
+                        plan.gsub_lookups(GsubStage::RequiredLigature),
+                    ) {
                         matched = Some((glyph_id, len));
                         break;
                     }
                 }
-                if let Some(glyph_id) = gsub.lookup_liga_sequence(&glyph_ids[index..index + len]) {
+                if let Some(glyph_id) = gsub.lookup_ligature_with_lookups(
+                    &glyph_ids[index..index + len],
+                    plan.gsub_lookups(GsubStage::Ligature),
+                ) {
                     matched = Some((glyph_id, len));
                     break;
                 }
@@ -1496,9 +1975,11 @@ impl Font {
             if let Some((glyph_id, len)) = matched {
                 output.push(ResolvedTextUnit::Glyph(ResolvedGlyph {
                     ch: expanded_glyphs[index].ch,
+                    cluster: expanded_glyphs[index].cluster,
                     glyph_id,
                     prefer_color: expanded_glyphs[index].prefer_color,
                     ligature_components: len as u16,
+                    synthesis: None,
                 }));
                 index += len;
             } else {
@@ -1512,12 +1993,10 @@ impl Font {
         &self,
         output: &mut Vec<ResolvedTextUnit>,
         glyphs: &mut Vec<ResolvedGlyph>,
-        locale: Option<&str>,
-        is_right_to_left: bool,
-        font_variant: crate::commands::FontVariant,
+        plan: &ShapePlan,
     ) {
         #[cfg(not(feature = "layout"))]
-        let _ = (locale, is_right_to_left, font_variant);
+        let _ = plan;
 
         if glyphs.is_empty() {
             return;
@@ -1530,22 +2009,20 @@ impl Font {
                 .enumerate()
                 .map(|(source_index, glyph)| (glyph.glyph_id, source_index))
                 .collect::<Vec<_>>();
-            self.apply_gsub_sequence_stages(
-                &mut ccmp_glyphs,
-                locale,
-                is_right_to_left,
-                font_variant,
-            );
+            let source_chars = glyphs.iter().map(|glyph| glyph.ch).collect::<Vec<_>>();
+            self.apply_gsub_sequence_stages(&mut ccmp_glyphs, plan, &source_chars);
             let expanded_glyphs = ccmp_glyphs
                 .into_iter()
                 .map(|(glyph_id, source_index)| ResolvedGlyph {
                     ch: glyphs[source_index].ch,
+                    cluster: glyphs[source_index].cluster,
                     glyph_id,
                     prefer_color: glyphs[source_index].prefer_color,
                     ligature_components: glyphs[source_index].ligature_components,
+                    synthesis: glyphs[source_index].synthesis,
                 })
                 .collect::<Vec<_>>();
-            self.apply_gsub_ligature_stage(output, &expanded_glyphs, locale, is_right_to_left);
+            self.apply_gsub_ligature_stage(output, &expanded_glyphs, plan);
             glyphs.clear();
             return;
         }
@@ -1557,108 +2034,269 @@ impl Font {
     fn shape_text_units(
         &self,
         text: &str,
-        is_vert: bool,
-        is_right_to_left: bool,
-        locale: Option<&str>,
-        font_variant: crate::commands::FontVariant,
+        plan: &ShapePlan,
     ) -> Result<Vec<ResolvedTextUnit>, Error> {
-        #[cfg(not(feature = "layout"))]
-        let _ = (locale, is_right_to_left, font_variant);
-
         let mut output = Vec::new();
         let mut pending_glyphs = Vec::new();
 
-        for unit in Self::parse_text_units(text) {
+        for (cluster, unit) in Self::parse_text_units_with_offsets(text) {
             match unit {
                 ParsedTextUnit::Newline => {
-                    self.flush_shaped_glyphs(
-                        &mut output,
-                        &mut pending_glyphs,
-                        locale,
-                        is_right_to_left,
-                        font_variant,
-                    );
+                    self.flush_shaped_glyphs(&mut output, &mut pending_glyphs, plan);
                     output.push(ResolvedTextUnit::Newline);
                 }
                 ParsedTextUnit::Tab => {
-                    self.flush_shaped_glyphs(
-                        &mut output,
-                        &mut pending_glyphs,
-                        locale,
-                        is_right_to_left,
-                        font_variant,
-                    );
+                    self.flush_shaped_glyphs(&mut output, &mut pending_glyphs, plan);
                     output.push(ResolvedTextUnit::Tab);
                 }
                 ParsedTextUnit::Glyph { text, .. } => {
                     let prefer_color = Self::text_prefers_color_glyph(&text);
                     for (ch, variation_selector) in Self::cluster_glyph_scalars(&text) {
                         let glyph_id =
-                            self.resolve_glyph_id_with_uvs(ch, variation_selector, is_vert)?;
-                        #[cfg(feature = "layout")]
-                        let glyph_id = if let Some(locale) = locale {
-                            if let Some(gsub) = self.current_gsub() {
-                                gsub.lookup_locale(glyph_id, locale)
-                            } else {
-                                glyph_id
-                            }
-                        } else {
-                            glyph_id
-                        };
+                            self.resolve_glyph_id_with_plan(ch, variation_selector, plan)?;
                         pending_glyphs.push(ResolvedGlyph {
                             ch,
+                            cluster,
                             glyph_id,
                             prefer_color,
                             ligature_components: 1,
+                            synthesis: None,
                         });
                     }
                 }
             }
         }
 
-        self.flush_shaped_glyphs(
-            &mut output,
-            &mut pending_glyphs,
-            locale,
-            is_right_to_left,
-            font_variant,
-        );
+        self.flush_shaped_glyphs(&mut output, &mut pending_glyphs, plan);
         Ok(output)
     }
 
-    pub(crate) fn supports_text_unit(
+    /// Replaces caps and position forms that GSUB cannot provide with scaled
+    /// default glyphs, the way CSS synthesizes `font-variant-caps` and
+    /// `font-variant-position`.
+    fn apply_variant_synthesis(
         &self,
-        unit: &ParsedTextUnit,
-        text_direction: crate::commands::TextDirection,
-        locale: Option<&str>,
-        font_variant: crate::commands::FontVariant,
-    ) -> bool {
-        self.text_unit_support(unit, text_direction, locale, font_variant)
-            .is_supported()
-    }
+        units: &mut [ResolvedTextUnit],
+        options: &crate::commands::FontOptions<'_>,
+        plan: &ShapePlan,
+    ) -> Result<(), Error> {
+        use crate::commands::{FontVariantCaps, FontVariantPosition};
 
-    pub(crate) fn text_unit_support(
-        &self,
-        unit: &ParsedTextUnit,
-        text_direction: crate::commands::TextDirection,
-        locale: Option<&str>,
-        font_variant: crate::commands::FontVariant,
-    ) -> TextUnitSupport {
-        #[cfg(not(feature = "layout"))]
-        let _ = (locale, font_variant);
+        let caps = options.effective_caps();
+        let position = if plan.direction().is_vertical() {
+            FontVariantPosition::Normal
+        } else {
+            options.font_variant_position
+        };
+        if caps == FontVariantCaps::Normal && position == FontVariantPosition::Normal {
+            return Ok(());
+        }
+
+        let coordinates = self.normalized_variation_coords(options);
+        let small_caps = self.small_caps_synthesis(&coordinates)?;
+        // CSS only uses `sups`/`subs` when every character in the run has the form.
+        let position_stage = match position {
+            FontVariantPosition::Normal => None,
+            FontVariantPosition::Super => Some(GsubStage::Superscript),
+            FontVariantPosition::Sub => Some(GsubStage::Subscript),
+        };
+        let synthesize_position = match position_stage {
+            Some(stage) => {
+                let mut covered = true;
+                for unit in units.iter() {
+                    if let ResolvedTextUnit::Glyph(glyph) = unit {
+                        if !glyph.ch.is_whitespace()
+                            && !self.has_variant_form(glyph.ch, plan, stage)?
+                        {
+                            covered = false;
+                            break;
+                        }
+                    }
+                }
+                !covered
+            }
+            None => false,
+        };
+        let position_synthesis = match position {
+            _ if !synthesize_position => None,
+            FontVariantPosition::Normal => None,
+            FontVariantPosition::Super => Some(self.script_position_synthesis(true, &coordinates)?),
+            FontVariantPosition::Sub => Some(self.script_position_synthesis(false, &coordinates)?),
+        };
+
+        for unit in units.iter_mut() {
+            let ResolvedTextUnit::Glyph(glyph) = unit else {
+                continue;
+            };
+            if glyph.ligature_components != 1 {
+                continue;
+            }
+
+            let mut synthesis = None;
+            if caps != FontVariantCaps::Normal {
+                let caps_stage = if glyph.ch.is_lowercase() {
+                    Some(GsubStage::SmallCaps)
+                } else if caps == FontVariantCaps::AllSmallCaps && glyph.ch.is_uppercase() {
+                    Some(GsubStage::CapitalsToSmallCaps)
+                } else {
+                    None
+                };
+                if let Some(stage) = caps_stage {
+                    if !self.has_variant_form(glyph.ch, plan, stage)? {
+                        let mut upper = glyph.ch.to_uppercase();
+                        let upper = match (upper.next(), upper.next()) {
+                            (Some(upper), None) => upper,
+                            _ => glyph.ch,
+                        };
+                        let glyph_id = self.resolve_glyph_id_with_plan(upper, '\0', plan)?;
+                        if glyph_id != 0 {
+                            glyph.glyph_id = glyph_id;
+                            synthesis = Some(small_caps);
+                        }
+                    }
+                }
+            }
+            if let (Some(position_synthesis), Some(stage)) = (position_synthesis, position_stage) {
+                if synthesis.is_none() && self.has_variant_form(glyph.ch, plan, stage)? {
+                    // Undo the partial `sups`/`subs` substitution before scaling.
+                    glyph.glyph_id = self.resolve_glyph_id_with_plan(glyph.ch, '\0', plan)?;
+                }
+                synthesis = Some(
+                    synthesis.map_or(position_synthesis, |caps| caps.then(position_synthesis)),
+                );
+            }
+            glyph.synthesis = synthesis;
+        }
+        Ok(())
+    }
+
+    fn has_variant_form(
+        &self,
+        ch: char,
+        plan: &ShapePlan,
+        stage: GsubStage,
+    ) -> Result<bool, Error> {
+        #[cfg(feature = "layout")]
+        if let Some(gsub) = self.current_gsub() {
+            let glyph_id = self.resolve_glyph_id_with_plan(ch, '\0', plan)?;
+            return Ok(gsub
+                .lookup_single_with_lookups(glyph_id, plan.gsub_lookups(stage))
+                .is_some());
+        }
+        let _ = (ch, plan, stage);
+        Ok(false)
+    }
+
+    /// Scales capitals to the x-height and strokes them back to roughly the
+    /// stem weight of the full-size capitals.
+    fn small_caps_synthesis(&self, coordinates: &[f32]) -> Result<GlyphSynthesis, Error> {
+        let units_per_em = self.current_head()?.units_per_em.max(1) as f32;
+        let (x_height, cap_height, weight_class) = self
+            .current_os2()
+            .map(|os2| (os2.x_height(), os2.cap_height(), os2.weight_class()))
+            .unwrap_or((0, 0, 400));
+        let x_height = self.metric_value_i16(tag4("xhgt"), x_height, coordinates);
+        let cap_height = self.metric_value_i16(tag4("cpht"), cap_height, coordinates);
+        let scale = if x_height > 0 && cap_height > 0 {
+            (x_height as f32 / cap_height as f32).clamp(0.5, 1.0)
+        } else {
+            0.7
+        };
+        // A regular stem is roughly 6% of the em, growing with the weight class.
+        let stem = units_per_em * (0.02 + weight_class.clamp(100, 900) as f32 / 10_000.0);
+
+        Ok(GlyphSynthesis {
+            scale_x: scale,
+            scale_y: scale,
+            shift_x: 0.0,
+            shift_y: 0.0,
+            embolden: stem * (1.0 - scale),
+        })
+    }
 
+    /// Uses the OS/2 (and MVAR) superscript or subscript size and offset.
+    fn script_position_synthesis(
+        &self,
+        superscript: bool,
+        coordinates: &[f32],
+    ) -> Result<GlyphSynthesis, Error> {
+        let units_per_em = self.current_head()?.units_per_em.max(1) as i16;
+        let (fallback, tags) = if superscript {
+            (
+                (
+                    units_per_em * 2 / 3,
+                    units_per_em * 2 / 3,
+                    0,
+                    units_per_em / 3,
+                ),
+                ["spxs", "spys", "spxo", "spyo"],
+            )
+        } else {
+            (
+                (
+                    units_per_em * 2 / 3,
+                    units_per_em * 2 / 3,
+                    0,
+                    units_per_em / 7,
+                ),
+                ["sbxs", "sbys", "sbxo", "sbyo"],
+            )
+        };
+        let (x_size, y_size, x_offset, y_offset) = self
+            .current_os2()
+            .map(|os2| {
+                if superscript {
+                    os2.superscript_metrics()
+                } else {
+                    os2.subscript_metrics()
+                }
+            })
+            .filter(|(x_size, y_size, _, _)| *x_size > 0 && *y_size > 0)
+            .unwrap_or(fallback);
+        let x_size = self.metric_value_i16(tag4(tags[0]), x_size, coordinates);
+        let y_size = self.metric_value_i16(tag4(tags[1]), y_size, coordinates);
+        let x_offset = self.metric_value_i16(tag4(tags[2]), x_offset, coordinates);
+        let y_offset = self.metric_value_i16(tag4(tags[3]), y_offset, coordinates);
+        let units_per_em = units_per_em as f32;
+
+        Ok(GlyphSynthesis {
+            scale_x: x_size.max(1) as f32 / units_per_em,
+            scale_y: y_size.max(1) as f32 / units_per_em,
+            shift_x: x_offset as f32,
+            // ySubscriptYOffset is positive below the baseline.
+            shift_y: if superscript {
+                y_offset as f32
+            } else {
+                -(y_offset as f32)
+            },
+            embolden: 0.0,
+        })
+    }
+
+    pub(crate) fn supports_text_unit(
+        &self,
+        unit: &ParsedTextUnit,
+        plan: &ShapePlan,
+        font_size: f32,
+    ) -> bool {
+        self.text_unit_support(unit, plan, font_size).is_supported()
+    }
+
+    /// What `unit` can be drawn with; bitmap strikes are probed at `font_size`.
+    pub(crate) fn text_unit_support(
+        &self,
+        unit: &ParsedTextUnit,
+        plan: &ShapePlan,
+        font_size: f32,
+    ) -> TextUnitSupport {
         match unit {
             ParsedTextUnit::Newline | ParsedTextUnit::Tab => TextUnitSupport {
                 has_glyph: true,
                 has_outline: true,
-                has_color: false,
+                ..Default::default()
             },
             ParsedTextUnit::Glyph { text, .. } => {
-                let is_vert = text_direction.is_vertical();
-                let is_right_to_left = text_direction.is_right_to_left();
-                let Ok(shaped_units) =
-                    self.shape_text_units(text, is_vert, is_right_to_left, locale, font_variant)
-                else {
+                let Ok(shaped_units) = self.shape_text_units(text, plan) else {
                     return TextUnitSupport::default();
                 };
 
@@ -1693,9 +2331,17 @@ impl Font {
                         support.has_outline = true;
                     }
 
+                    if self
+                        .current_eblc()
+                        .and_then(|eblc| eblc.select_strike(glyph.glyph_id as u16, font_size))
+                        .is_some()
+                    {
+                        support.has_bitmap = true;
+                    }
+
                     if self
                         .current_colr()
-                        .map(|colr| !colr.get_layer_record(glyph.glyph_id as u16).is_empty())
+                        .map(|colr| colr.has_glyph(glyph.glyph_id as u16))
                         .unwrap_or(false)
                     {
                         support.has_color = true;
@@ -1703,8 +2349,14 @@ impl Font {
 
                     if self
                         .current_sbix()
-                        .and_then(|sbix| sbix.get_raster_glyph(glyph.glyph_id as u32, 16.0, "px"))
+                        .and_then(|sbix| {
+                            sbix.get_raster_glyph(glyph.glyph_id as u32, font_size, "px")
+                        })
                         .is_some()
+                        || self
+                            .current_cblc()
+                            .and_then(|cblc| cblc.select_strike(glyph.glyph_id as u16, font_size))
+                            .is_some()
                     {
                         support.has_color = true;
                     }
@@ -1717,7 +2369,7 @@ impl Font {
                         support.has_color = true;
                     }
 
-                    if !support.has_outline && !support.has_color {
+                    if !support.has_outline && !support.has_color && !support.has_bitmap {
                         return TextUnitSupport::default();
                     }
                 }
@@ -1774,13 +2426,15 @@ impl Font {
         locale: Option<&str>,
     ) -> Result<Vec<usize>, Error> {
         let mut glyph_ids = Vec::new();
-        for unit in self.shape_text_units(
-            text,
-            false,
-            false,
+        let plan = ShapePlan::resolve(
+            self,
             locale,
-            crate::commands::FontVariant::Normal,
-        )? {
+            crate::commands::TextDirection::LeftToRight,
+            &[],
+            &[],
+            &[],
+        );
+        for unit in self.shape_text_units(text, &plan)? {
             if let ResolvedTextUnit::Glyph(glyph) = unit {
                 glyph_ids.push(glyph.glyph_id);
             }
@@ -1797,13 +2451,13 @@ impl Font {
         is_right_to_left: bool,
     ) -> Result<Vec<usize>, Error> {
         let mut glyph_ids = Vec::new();
-        for unit in self.shape_text_units(
-            text,
-            false,
-            is_right_to_left,
-            locale,
-            crate::commands::FontVariant::Normal,
-        )? {
+        let direction = if is_right_to_left {
+            crate::commands::TextDirection::RightToLeft
+        } else {
+            crate::commands::TextDirection::LeftToRight
+        };
+        let plan = ShapePlan::resolve(self, locale, direction, &[], &[], &[]);
+        for unit in self.shape_text_units(text, &plan)? {
             if let ResolvedTextUnit::Glyph(glyph) = unit {
                 glyph_ids.push(glyph.glyph_id);
             }
@@ -1820,7 +2474,15 @@ impl Font {
         font_variant: crate::commands::FontVariant,
     ) -> Result<Vec<usize>, Error> {
         let mut glyph_ids = Vec::new();
-        for unit in self.shape_text_units(text, false, false, locale, font_variant)? {
+        let plan = ShapePlan::resolve(
+            self,
+            locale,
+            crate::commands::TextDirection::LeftToRight,
+            font_variant.gsub_feature_tags(),
+            &[],
+            &[],
+        );
+        for unit in self.shape_text_units(text, &plan)? {
             if let ResolvedTextUnit::Glyph(glyph) = unit {
                 glyph_ids.push(glyph.glyph_id);
             }
@@ -1859,7 +2521,7 @@ impl Font {
     ) -> bool {
         if self
             .current_colr()
-            .map(|colr| !colr.get_layer_record(glyph_id as u16).is_empty())
+            .map(|colr| colr.has_glyph(glyph_id as u16))
             .unwrap_or(false)
         {
             return true;
@@ -1894,18 +2556,38 @@ impl Font {
         Some(svg_document_to_glyph_layers(&document, scale_x, scale_y))
     }
 
+    /// Instance and size that GPOS device and variation deltas resolve for;
+    /// `scale` is pixels per font unit.
+    #[cfg(feature = "layout")]
+    fn device_context<'a>(&'a self, plan: &'a ShapePlan, scale: f32) -> DeviceContext<'a> {
+        let units_per_em = self
+            .current_head()
+            .map(|head| head.units_per_em)
+            .unwrap_or(1000);
+        DeviceContext {
+            coordinates: plan.coordinates(),
+            variation_store: self
+                .current_gdef()
+                .and_then(|gdef| gdef.item_var_store.as_ref()),
+            ppem: Some((units_per_em as f32 * scale).round() as u16),
+            units_per_em,
+        }
+    }
+
+    /// Kerning of the glyph at `index` scaled by `scale_x` / `scale_y`;
+    /// `device_scale` is the pixels per font unit that device deltas resolve for.
     fn pair_adjustment_for_index(
         &self,
         units: &[ResolvedTextUnit],
         index: usize,
-        locale: Option<&str>,
-        is_vertical: bool,
+        plan: &ShapePlan,
         scale_x: f32,
         scale_y: f32,
+        device_scale: f32,
     ) -> GlyphPositionAdjustment {
         #[cfg(not(feature = "layout"))]
         {
-            let _ = (units, index, locale, is_vertical, scale_x, scale_y);
+            let _ = (units, index, plan, scale_x, scale_y, device_scale);
             GlyphPositionAdjustment::default()
         }
 
@@ -1918,17 +2600,19 @@ impl Font {
                 return GlyphPositionAdjustment::default();
             };
 
+            let lookups = plan.gpos_lookups(GposStage::Kerning);
+            let context = self.device_context(plan, device_scale);
             let mut adjustment = GlyphPositionAdjustment::default();
             let previous_index = self.find_previous_spacing_glyph_index(units, index);
             let next_index = self.find_next_spacing_glyph_index(units, index);
 
             if let Some(previous_index) = previous_index {
                 if let Some(previous) = Self::glyph_unit_at(units, previous_index) {
-                    if let Some(pair) = gpos.lookup_pair_adjustment(
+                    if let Some(pair) = gpos.lookup_pair_adjustment_with_lookups(
                         previous.glyph_id as u16,
                         current.glyph_id as u16,
-                        is_vertical,
-                        locale,
+                        lookups,
+                        &context,
                     ) {
                         adjustment.placement_x += pair.second.x_placement as f32 * scale_x;
                         adjustment.placement_y += pair.second.y_placement as f32 * scale_y;
@@ -1940,11 +2624,11 @@ impl Font {
 
             if let Some(next_index) = next_index {
                 if let Some(next) = Self::glyph_unit_at(units, next_index) {
-                    if let Some(pair) = gpos.lookup_pair_adjustment(
+                    if let Some(pair) = gpos.lookup_pair_adjustment_with_lookups(
                         current.glyph_id as u16,
                         next.glyph_id as u16,
-                        is_vertical,
-                        locale,
+                        lookups,
+                        &context,
                     ) {
                         adjustment.placement_x += pair.first.x_placement as f32 * scale_x;
                         adjustment.placement_y += pair.first.y_placement as f32 * scale_y;
@@ -1964,21 +2648,24 @@ impl Font {
         units: &[ResolvedTextUnit],
         unit_glyph_indices: &[Option<usize>],
         index: usize,
-        locale: Option<&str>,
+        plan: &ShapePlan,
         scale_x: f32,
         scale_y: f32,
     ) -> Option<GlyphAttachmentPlacement> {
         let gpos = self.current_gpos()?;
+        let mark_lookups = plan.gpos_lookups(GposStage::Mark);
+        let context = self.device_context(plan, scale_y);
         let current = Self::glyph_unit_at(units, index)?;
         if let Some(previous_mark_unit_index) = self.find_previous_mark_glyph_index(units, index) {
             let previous_mark = Self::glyph_unit_at(units, previous_mark_unit_index)?;
             let glyph_index = unit_glyph_indices
                 .get(previous_mark_unit_index)
                 .and_then(|glyph_index| *glyph_index)?;
-            if let Some(adjustment) = gpos.lookup_mark_to_mark_adjustment(
+            if let Some(adjustment) = gpos.lookup_mark_to_mark_adjustment_with_lookups(
                 previous_mark.glyph_id as u16,
                 current.glyph_id as u16,
-                locale,
+                plan.gpos_lookups(GposStage::MarkToMark),
+                &context,
             ) {
                 return Some(GlyphAttachmentPlacement {
                     glyph_index,
@@ -1999,24 +2686,27 @@ impl Font {
             .and_then(|glyph_index| *glyph_index)?;
         let ligature_component_index = base.ligature_components.saturating_sub(1) as usize;
         let adjustment = if base.ligature_components > 1 {
-            gpos.lookup_mark_to_ligature_adjustment(
+            gpos.lookup_mark_to_ligature_adjustment_with_lookups(
                 base.glyph_id as u16,
                 current.glyph_id as u16,
                 ligature_component_index,
-                locale,
+                mark_lookups,
+                &context,
             )
             .or_else(|| {
-                gpos.lookup_mark_to_base_adjustment(
+                gpos.lookup_mark_to_base_adjustment_with_lookups(
                     base.glyph_id as u16,
                     current.glyph_id as u16,
-                    locale,
+                    mark_lookups,
+                    &context,
                 )
             })?
         } else {
-            gpos.lookup_mark_to_base_adjustment(
+            gpos.lookup_mark_to_base_adjustment_with_lookups(
                 base.glyph_id as u16,
                 current.glyph_id as u16,
-                locale,
+                mark_lookups,
+                &context,
             )?
         };
 
@@ -2037,11 +2727,11 @@ impl Font {
         units: &[ResolvedTextUnit],
         unit_glyph_indices: &[Option<usize>],
         index: usize,
-        locale: Option<&str>,
+        plan: &ShapePlan,
         scale_x: f32,
         scale_y: f32,
     ) -> Option<GlyphAttachmentPlacement> {
-        let _ = (units, unit_glyph_indices, index, locale, scale_x, scale_y);
+        let _ = (units, unit_glyph_indices, index, plan, scale_x, scale_y);
         None
     }
 
@@ -2135,6 +2825,34 @@ impl Font {
         text: &str,
         options: &crate::commands::FontOptions<'_>,
     ) -> Result<GlyphRun, Error> {
+        self.text2glyph_run_with_plan(text, options, &ShapePlan::new(self, options), None)
+    }
+
+    pub(crate) fn text2glyph_run_with_plan(
+        &self,
+        text: &str,
+        options: &crate::commands::FontOptions<'_>,
+        plan: &ShapePlan,
+        outlines: Option<&OutlineCache>,
+    ) -> Result<GlyphRun, Error> {
+        Ok(self
+            .text2clustered_run(text, options, plan, outlines, &[])?
+            .0)
+    }
+
+    /// Shapes `text` and returns the run with the cluster of every glyph;
+    /// decoration glyphs appended after the shaped ones have none.
+    ///
+    /// `sizes` overrides `options.font_size` for the glyphs whose cluster
+    /// falls in a range, without splitting the run for GSUB and GPOS.
+    pub(crate) fn text2clustered_run(
+        &self,
+        text: &str,
+        options: &crate::commands::FontOptions<'_>,
+        plan: &ShapePlan,
+        outlines: Option<&OutlineCache>,
+        sizes: &[ClusterSize],
+    ) -> Result<(GlyphRun, Vec<Option<usize>>), Error> {
         let _ = self.current_head()?;
 
         if !options.font_size.is_finite() || options.font_size <= 0.0 {
@@ -2157,22 +2875,42 @@ impl Font {
             ));
         }
 
+        if sizes
+            .iter()
+            .any(|size| !size.font_size.is_finite() || size.font_size <= 0.0)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "font_size must be a positive finite value",
+            ));
+        }
+        let sized_options = sizes
+            .iter()
+            .map(|size| options.clone().with_font_size(size.font_size))
+            .collect::<Vec<_>>();
+
         let mut glyphs: Vec<PositionedGlyph> = Vec::new();
+        let mut clusters = Vec::new();
         let mut cursor_x = 0.0f32;
         let mut cursor_y = 0.0f32;
+        let mut decorated_lines = Vec::new();
+        let mut line_glyph_start = 0usize;
         let tab_advance = line_height;
-        let shaped_units = self.shape_text_units(
-            text,
-            is_vertical,
-            is_right_to_left,
-            options.locale,
-            options.font_variant,
-        )?;
+        let mut shaped_units = self.shape_text_units(text, plan)?;
+        self.apply_variant_synthesis(&mut shaped_units, options, plan)?;
         let mut unit_glyph_indices = vec![None; shaped_units.len()];
 
         for (index, unit) in shaped_units.iter().enumerate() {
             match *unit {
                 ResolvedTextUnit::Newline => {
+                    decorated_lines.push(decorated_line(
+                        line_glyph_start..glyphs.len(),
+                        cursor_x,
+                        cursor_y,
+                        is_vertical,
+                        is_right_to_left,
+                    ));
+                    line_glyph_start = glyphs.len();
                     if is_vertical {
                         cursor_x -= line_height;
                         cursor_y = 0.0;
@@ -2191,16 +2929,15 @@ impl Font {
                     }
                 }
                 ResolvedTextUnit::Glyph(resolved) => {
-                    let glyph_data = self.get_glyph_from_id_with_options(
-                        resolved.glyph_id,
-                        is_vertical,
-                        options,
-                    );
-                    let open_type_glyph = glyph_data
-                        .open_type_glyf
-                        .as_ref()
-                        .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "glyph is none"))?;
-                    let glyph_id = glyph_data.glyph_id;
+                    let options = sizes
+                        .iter()
+                        .position(|size| size.chars.contains(&resolved.cluster))
+                        .map_or(options, |index| &sized_options[index]);
+                    let scale_y = options.font_size / default_line_height.max(1.0);
+                    let scale_x = scale_y * options.font_stretch.0.max(0.0);
+                    let glyph_id = resolved.glyph_id;
+                    let outline = self.resolve_outline(glyph_id, is_vertical, options, outlines);
+                    let open_type_glyph = &outline.glyph;
                     let can_use_outline =
                         self.resolved_glyph_can_use_outline(open_type_glyph, glyph_id);
                     #[cfg(feature = "svg-fonts")]
@@ -2209,66 +2946,34 @@ impl Font {
                         .map(|svg| svg.has_glyph(glyph_id as u32))
                         .unwrap_or(false);
 
-                    let layers = if let Some(sbix) = self.current_sbix() {
-                        if let Some(bitmap) =
-                            sbix.get_raster_glyph(glyph_id as u32, options.font_size, "px")
-                        {
-                            if resolved.prefer_color || !can_use_outline {
-                                let mut raster = RasterGlyphLayer::from_encoded(bitmap.glyph_data);
-                                raster.offset_x = bitmap.offset_x * options.font_stretch.0.max(0.0);
-                                raster.offset_y = bitmap.offset_y;
-                                raster.width = bitmap.width;
-                                raster.height = bitmap.height;
-                                vec![GlyphLayer::Raster(raster)]
-                            } else {
-                                self.build_outline_layers(
-                                    glyph_id,
-                                    open_type_glyph,
-                                    scale_x,
-                                    scale_y,
-                                    resolved.ch,
-                                )?
-                            }
+                    let synthesis = resolved.synthesis;
+                    let (glyph_scale_x, glyph_scale_y) = synthesis
+                        .map_or((scale_x, scale_y), |s| {
+                            (scale_x * s.scale_x, scale_y * s.scale_y)
+                        });
+                    let raster = if resolved.prefer_color || !can_use_outline {
+                        self.raster_glyph_layer(glyph_id, options)
+                    } else {
+                        None
+                    }
+                    .or_else(|| {
+                        self.embedded_bitmap_layer(glyph_id, options, can_use_outline)
+                            .map(|raster| (raster, false))
+                    });
+                    let mut layers = if let Some((raster, draw_outline)) = raster {
+                        let mut layers = if draw_outline && can_use_outline {
+                            self.build_outline_layers(
+                                glyph_id,
+                                &outline,
+                                glyph_scale_x,
+                                glyph_scale_y,
+                                options,
+                            )?
                         } else {
-                            #[cfg(feature = "svg-fonts")]
-                            {
-                                if can_use_svg && (resolved.prefer_color || !can_use_outline) {
-                                    self.build_svg_layers(
-                                        glyph_id,
-                                        &open_type_glyph.layout,
-                                        scale_x,
-                                        scale_y,
-                                    )
-                                    .ok_or_else(|| {
-                                        Error::new(
-                                            ErrorKind::Unsupported,
-                                            format!(
-                                                "failed to extract SVG glyph layer for {:?}",
-                                                resolved.ch
-                                            ),
-                                        )
-                                    })?
-                                } else {
-                                    self.build_outline_layers(
-                                        glyph_id,
-                                        open_type_glyph,
-                                        scale_x,
-                                        scale_y,
-                                        resolved.ch,
-                                    )?
-                                }
-                            }
-                            #[cfg(not(feature = "svg-fonts"))]
-                            {
-                                self.build_outline_layers(
-                                    glyph_id,
-                                    open_type_glyph,
-                                    scale_x,
-                                    scale_y,
-                                    resolved.ch,
-                                )?
-                            }
-                        }
+                            Vec::new()
+                        };
+                        layers.push(GlyphLayer::Raster(raster));
+                        layers
                     } else {
                         #[cfg(feature = "svg-fonts")]
                         {
@@ -2276,8 +2981,8 @@ impl Font {
                                 self.build_svg_layers(
                                     glyph_id,
                                     &open_type_glyph.layout,
-                                    scale_x,
-                                    scale_y,
+                                    glyph_scale_x,
+                                    glyph_scale_y,
                                 )
                                 .ok_or_else(|| {
                                     Error::new(
@@ -2291,10 +2996,10 @@ impl Font {
                             } else {
                                 self.build_outline_layers(
                                     glyph_id,
-                                    open_type_glyph,
-                                    scale_x,
-                                    scale_y,
-                                    resolved.ch,
+                                    &outline,
+                                    glyph_scale_x,
+                                    glyph_scale_y,
+                                    options,
                                 )?
                             }
                         }
@@ -2302,29 +3007,41 @@ impl Font {
                         {
                             self.build_outline_layers(
                                 glyph_id,
-                                open_type_glyph,
-                                scale_x,
-                                scale_y,
-                                resolved.ch,
+                                &outline,
+                                glyph_scale_x,
+                                glyph_scale_y,
+                                options,
                             )?
                         }
                     };
 
-                    let mut metrics =
-                        glyph_metrics_from_layout(&open_type_glyph.layout, scale_x, scale_y);
+                    if let Some(synthesis) = synthesis.filter(|s| s.embolden > 0.0) {
+                        embolden_path_layers(&mut layers, synthesis.embolden * scale_y);
+                    }
+                    let mut metrics = glyph_metrics_from_layout(
+                        &open_type_glyph.layout,
+                        glyph_scale_x,
+                        glyph_scale_y,
+                    );
+                    if let Some(synthesis) = synthesis {
+                        let (advance_x, advance_y) =
+                            synthesis.advance(layout_advance(&open_type_glyph.layout), is_vertical);
+                        metrics.advance_x = advance_x * scale_x;
+                        metrics.advance_y = advance_y * scale_y;
+                    }
                     let adjustment = self.pair_adjustment_for_index(
                         &shaped_units,
                         index,
-                        options.locale,
-                        is_vertical,
+                        plan,
                         scale_x,
                         scale_y,
+                        scale_y,
                     );
                     let mark_attachment = self.mark_attachment_for_index(
                         &shaped_units,
                         &unit_glyph_indices,
                         index,
-                        options.locale,
+                        plan,
                         scale_x,
                         scale_y,
                     );
@@ -2374,12 +3091,19 @@ impl Font {
                         metrics.advance_x = 0.0;
                         metrics.advance_y = 0.0;
                     }
+                    let (origin_x, origin_y) = synthesis.map_or((origin_x, origin_y), |s| {
+                        (
+                            origin_x + s.shift_x * scale_x,
+                            origin_y - s.shift_y * scale_y,
+                        )
+                    });
                     let glyph = Glyph {
                         font: Some(font_metrics_from_layout(&open_type_glyph.layout, scale_y)),
                         metrics,
                         layers,
                     };
                     glyphs.push(PositionedGlyph::new(glyph, origin_x, origin_y));
+                    clusters.push(Some(resolved.cluster));
                     unit_glyph_indices[index] = Some(glyphs.len() - 1);
                     if !uses_mark_attachment {
                         if is_right_to_left && !is_vertical {
@@ -2393,32 +3117,621 @@ impl Font {
             }
         }
 
-        Ok(GlyphRun::new(glyphs))
+        if !options.decorations.is_empty() {
+            decorated_lines.push(decorated_line(
+                line_glyph_start..glyphs.len(),
+                cursor_x,
+                cursor_y,
+                is_vertical,
+                is_right_to_left,
+            ));
+            let metrics = self.decoration_metrics(options, scale_x, scale_y)?;
+            let decorations = crate::decoration::build_decoration_glyphs(
+                &glyphs,
+                &decorated_lines,
+                &options.decorations,
+                &metrics,
+                is_vertical,
+            );
+            glyphs.extend(decorations);
+        }
+        clusters.resize(glyphs.len(), None);
+
+        Ok((GlyphRun::new(glyphs), clusters))
+    }
+
+    /// Decoration metrics at the scale a run shaped with `options` uses.
+    pub(crate) fn scaled_decoration_metrics(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<crate::decoration::DecorationMetrics, Error> {
+        let default_line_height = self.default_line_height_with_options(options)? as f32;
+        let scale_y = options.font_size / default_line_height.max(1.0);
+        self.decoration_metrics(options, scale_y * options.font_stretch.0.max(0.0), scale_y)
+    }
+
+    /// Scales `post` underline and `OS/2` strikeout metrics, falling back to em-based defaults.
+    fn decoration_metrics(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+        scale_x: f32,
+        scale_y: f32,
+    ) -> Result<crate::decoration::DecorationMetrics, Error> {
+        let hhea = self.current_hhea()?;
+        let units_per_em = self.current_head()?.units_per_em.max(1) as i16;
+        let coordinates = self.normalized_variation_coords(options);
+        let ascender = self.metric_value_i16(tag4("hasc"), hhea.get_accender(), &coordinates);
+        let descender = self.metric_value_i16(tag4("hdsc"), hhea.get_descender(), &coordinates);
+        let (underline_position, underline_thickness) = self
+            .current_post()
+            .map(|post| (post.underline_position, post.underline_thickness))
+            .filter(|(_, thickness)| *thickness > 0)
+            .unwrap_or((-units_per_em / 10, units_per_em / 20));
+        let (strikeout_position, strikeout_thickness) = self
+            .current_os2()
+            .map(|os2| (os2.strikeout_position(), os2.strikeout_size()))
+            .filter(|(_, size)| *size > 0)
+            .unwrap_or((units_per_em / 4, underline_thickness));
+        let underline_position =
+            self.metric_value_i16(tag4("undo"), underline_position, &coordinates);
+        let underline_thickness =
+            self.metric_value_i16(tag4("unds"), underline_thickness, &coordinates);
+        let strikeout_position =
+            self.metric_value_i16(tag4("stro"), strikeout_position, &coordinates);
+        let strikeout_thickness =
+            self.metric_value_i16(tag4("strs"), strikeout_thickness, &coordinates);
+
+        Ok(crate::decoration::DecorationMetrics {
+            underline_position: underline_position as f32 * scale_y,
+            underline_thickness: underline_thickness.max(1) as f32 * scale_y,
+            strikeout_position: strikeout_position as f32 * scale_y,
+            strikeout_thickness: strikeout_thickness.max(1) as f32 * scale_y,
+            ascent: ascender as f32 * scale_y,
+            descent: -(descender as f32) * scale_y,
+            em_size: units_per_em as f32 * scale_x,
+        })
+    }
+
+    /// Returns the position of `baseline` in px, measured upward from the
+    /// alphabetic origin, or rightward from the vertical origin in vertical flow.
+    pub(crate) fn baseline_offset(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+        baseline: crate::commands::Baseline,
+        script_tag: u32,
+    ) -> Result<f32, Error> {
+        let (scale_x, scale_y) = self.cross_axis_scales(options)?;
+        let is_vertical = options.text_direction.is_vertical();
+        let tag = u32::from_be_bytes(baseline.tag());
+        let value = match self.base_baseline_value(is_vertical, script_tag, tag) {
+            Some(value) => value as f32,
+            None => self.estimated_baseline_value(options, baseline, is_vertical)?,
+        };
+        Ok(if is_vertical {
+            value * scale_x
+        } else {
+            value * scale_y
+        })
+    }
+
+    /// Returns the BASE min/max extent of a script in px.
+    pub(crate) fn script_extent(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+        script_tag: u32,
+        language_tag: Option<u32>,
+    ) -> Result<Option<crate::commands::ScriptExtent>, Error> {
+        let (scale_x, scale_y) = self.cross_axis_scales(options)?;
+        let is_vertical = options.text_direction.is_vertical();
+        let scale = if is_vertical { scale_x } else { scale_y };
+        Ok(self
+            .base_min_max(is_vertical, script_tag, language_tag)
+            .map(|(min, max)| crate::commands::ScriptExtent {
+                min: min.map(|value| value as f32 * scale),
+                max: max.map(|value| value as f32 * scale),
+            }))
+    }
+
+    fn cross_axis_scales(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<(f32, f32), Error> {
+        let default_line_height = self.default_line_height_with_options(options)? as f32;
+        let scale_y = options.font_size / default_line_height.max(1.0);
+        Ok((scale_y * options.font_stretch.0.max(0.0), scale_y))
+    }
+
+    #[cfg(feature = "layout")]
+    fn base_baseline_value(&self, is_vertical: bool, script_tag: u32, tag: u32) -> Option<i16> {
+        self.current_base()?
+            .axis(is_vertical)?
+            .baseline(script_tag, tag)
+    }
+
+    #[cfg(not(feature = "layout"))]
+    fn base_baseline_value(&self, is_vertical: bool, script_tag: u32, tag: u32) -> Option<i16> {
+        let _ = (is_vertical, script_tag, tag);
+        None
+    }
+
+    #[cfg(feature = "layout")]
+    fn base_min_max(
+        &self,
+        is_vertical: bool,
+        script_tag: u32,
+        language_tag: Option<u32>,
+    ) -> Option<(Option<i16>, Option<i16>)> {
+        let min_max = self
+            .current_base()?
+            .axis(is_vertical)?
+            .min_max(script_tag, language_tag)?;
+        Some((
+            min_max.min_coord.map(|coord| coord.coordinate),
+            min_max.max_coord.map(|coord| coord.coordinate),
+        ))
+    }
+
+    #[cfg(not(feature = "layout"))]
+    fn base_min_max(
+        &self,
+        is_vertical: bool,
+        script_tag: u32,
+        language_tag: Option<u32>,
+    ) -> Option<(Option<i16>, Option<i16>)> {
+        let _ = (is_vertical, script_tag, language_tag);
+        None
+    }
+
+    /// Returns the glyph id mapped to `ch`, or `None` for `.notdef`.
+    pub(crate) fn glyph_id_for_char(&self, ch: char) -> Result<Option<u16>, Error> {
+        let glyph_id = self.current_cmap()?.get_glyph_position(ch as u32);
+        Ok((glyph_id != 0).then_some(glyph_id as u16))
+    }
+
+    #[cfg(feature = "layout")]
+    fn current_num_glyphs(&self) -> Result<u16, Error> {
+        let maxp = if self.current_font == 0 {
+            self.maxp.as_ref()
+        } else {
+            self.more_fonts[self.current_font - 1].maxp.as_ref()
+        };
+        maxp.map(|maxp| maxp.num_glyphs)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "maxp is none"))
+    }
+
+    /// Builds one horizontal outline glyph at the origin.
+    #[cfg(feature = "layout")]
+    fn glyph_for_id(
+        &self,
+        glyph_id: u16,
+        options: &crate::commands::FontOptions<'_>,
+        scale_x: f32,
+        scale_y: f32,
+    ) -> Result<Glyph, Error> {
+        if glyph_id >= self.current_num_glyphs()? {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("glyph id {glyph_id} is out of range"),
+            ));
+        }
+        let outline = self.resolve_outline(glyph_id as usize, false, options, None);
+        let open_type_glyph = &outline.glyph;
+        let layers =
+            self.build_outline_layers(glyph_id as usize, &outline, scale_x, scale_y, options)?;
+        let mut metrics = glyph_metrics_from_layout(&open_type_glyph.layout, scale_x, scale_y);
+        metrics.bounds = glyph_layers_bounds(&layers);
+        Ok(Glyph {
+            font: Some(font_metrics_from_layout(&open_type_glyph.layout, scale_y)),
+            metrics,
+            layers,
+        })
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn math_constants(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<Option<crate::commands::MathConstants>, Error> {
+        let Some(constants) = self.current_math().and_then(|math| math.constants.as_ref()) else {
+            return Ok(None);
+        };
+        let (scale_x, scale_y) = self.cross_axis_scales(options)?;
+        let values = &constants.values;
+        Ok(Some(crate::commands::MathConstants {
+            script_percent_scale_down: constants.script_percent_scale_down,
+            script_script_percent_scale_down: constants.script_script_percent_scale_down,
+            delimited_sub_formula_min_height: constants.delimited_sub_formula_min_height as f32
+                * scale_y,
+            display_operator_min_height: constants.display_operator_min_height as f32 * scale_y,
+            math_leading: values[0] as f32 * scale_y,
+            axis_height: values[1] as f32 * scale_y,
+            accent_base_height: values[2] as f32 * scale_y,
+            flattened_accent_base_height: values[3] as f32 * scale_y,
+            subscript_shift_down: values[4] as f32 * scale_y,
+            subscript_top_max: values[5] as f32 * scale_y,
+            subscript_baseline_drop_min: values[6] as f32 * scale_y,
+            superscript_shift_up: values[7] as f32 * scale_y,
+            superscript_shift_up_cramped: values[8] as f32 * scale_y,
+            superscript_bottom_min: values[9] as f32 * scale_y,
+            superscript_baseline_drop_max: values[10] as f32 * scale_y,
+            sub_superscript_gap_min: values[11] as f32 * scale_y,
+            superscript_bottom_max_with_subscript: values[12] as f32 * scale_y,
+            space_after_script: values[13] as f32 * scale_x,
+            upper_limit_gap_min: values[14] as f32 * scale_y,
+            upper_limit_baseline_rise_min: values[15] as f32 * scale_y,
+            lower_limit_gap_min: values[16] as f32 * scale_y,
+            lower_limit_baseline_drop_min: values[17] as f32 * scale_y,
+            stack_top_shift_up: values[18] as f32 * scale_y,
+            stack_top_display_style_shift_up: values[19] as f32 * scale_y,
+            stack_bottom_shift_down: values[20] as f32 * scale_y,
+            stack_bottom_display_style_shift_down: values[21] as f32 * scale_y,
+            stack_gap_min: values[22] as f32 * scale_y,
+            stack_display_style_gap_min: values[23] as f32 * scale_y,
+            stretch_stack_top_shift_up: values[24] as f32 * scale_y,
+            stretch_stack_bottom_shift_down: values[25] as f32 * scale_y,
+            stretch_stack_gap_above_min: values[26] as f32 * scale_y,
+            stretch_stack_gap_below_min: values[27] as f32 * scale_y,
+            fraction_numerator_shift_up: values[28] as f32 * scale_y,
+            fraction_numerator_display_style_shift_up: values[29] as f32 * scale_y,
+            fraction_denominator_shift_down: values[30] as f32 * scale_y,
+            fraction_denominator_display_style_shift_down: values[31] as f32 * scale_y,
+            fraction_numerator_gap_min: values[32] as f32 * scale_y,
+            fraction_num_display_style_gap_min: values[33] as f32 * scale_y,
+            fraction_rule_thickness: values[34] as f32 * scale_y,
+            fraction_denominator_gap_min: values[35] as f32 * scale_y,
+            fraction_denom_display_style_gap_min: values[36] as f32 * scale_y,
+            skewed_fraction_horizontal_gap: values[37] as f32 * scale_x,
+            skewed_fraction_vertical_gap: values[38] as f32 * scale_y,
+            overbar_vertical_gap: values[39] as f32 * scale_y,
+            overbar_rule_thickness: values[40] as f32 * scale_y,
+            overbar_extra_ascender: values[41] as f32 * scale_y,
+            underbar_vertical_gap: values[42] as f32 * scale_y,
+            underbar_rule_thickness: values[43] as f32 * scale_y,
+            underbar_extra_descender: values[44] as f32 * scale_y,
+            radical_vertical_gap: values[45] as f32 * scale_y,
+            radical_display_style_vertical_gap: values[46] as f32 * scale_y,
+            radical_rule_thickness: values[47] as f32 * scale_y,
+            radical_extra_ascender: values[48] as f32 * scale_y,
+            radical_kern_before_degree: values[49] as f32 * scale_x,
+            radical_kern_after_degree: values[50] as f32 * scale_x,
+            radical_degree_bottom_raise_percent: constants.radical_degree_bottom_raise_percent,
+        }))
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn math_italic_correction(
+        &self,
+        glyph_id: u16,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<Option<f32>, Error> {
+        let (scale_x, _) = self.cross_axis_scales(options)?;
+        Ok(self
+            .current_math()
+            .and_then(|math| math.italics_correction(glyph_id))
+            .map(|value| value as f32 * scale_x))
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn math_top_accent_attachment(
+        &self,
+        glyph_id: u16,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<Option<f32>, Error> {
+        let (scale_x, _) = self.cross_axis_scales(options)?;
+        Ok(self
+            .current_math()
+            .and_then(|math| math.top_accent_attachment(glyph_id))
+            .map(|value| value as f32 * scale_x))
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn math_kern(
+        &self,
+        glyph_id: u16,
+        corner: crate::commands::MathKernCorner,
+        height: f32,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<Option<f32>, Error> {
+        use crate::commands::MathKernCorner;
+
+        let (scale_x, scale_y) = self.cross_axis_scales(options)?;
+        let corner = match corner {
+            MathKernCorner::TopRight => 0,
+            MathKernCorner::TopLeft => 1,
+            MathKernCorner::BottomRight => 2,
+            MathKernCorner::BottomLeft => 3,
+        };
+        let height = (height / scale_y.max(f32::EPSILON))
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        Ok(self
+            .current_math()
+            .and_then(|math| math.kern(glyph_id, corner, height))
+            .map(|value| value as f32 * scale_x))
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn is_math_extended_shape(&self, glyph_id: u16) -> bool {
+        self.current_math()
+            .map(|math| math.is_extended_shape(glyph_id))
+            .unwrap_or(false)
+    }
+
+    #[cfg(feature = "layout")]
+    pub(crate) fn math_variants(
+        &self,
+        glyph_id: u16,
+        vertical: bool,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<Vec<crate::commands::MathGlyphVariant>, Error> {
+        let (scale_x, scale_y) = self.cross_axis_scales(options)?;
+        let scale = if vertical { scale_y } else { scale_x };
+        Ok(self
+            .current_math()
+            .and_then(|math| math.construction(glyph_id, vertical))
+            .map(|construction| {
+                construction
+                    .variants
+                    .iter()
+                    .map(|variant| crate::commands::MathGlyphVariant {
+                        glyph_id: variant.variant_glyph,
+                        advance: variant.advance_measurement as f32 * scale,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Stretches `glyph_id` to at least `target_size` px along one axis.
+    ///
+    /// The smallest pre-built variant that is long enough wins; otherwise the glyph
+    /// assembly is used, and the largest variant is the last resort. Vertical
+    /// assemblies grow upward from the baseline.
+    #[cfg(feature = "layout")]
+    pub(crate) fn math_stretch_glyph(
+        &self,
+        glyph_id: u16,
+        target_size: f32,
+        vertical: bool,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Result<GlyphRun, Error> {
+        if !target_size.is_finite() || target_size < 0.0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "target_size must be a non-negative finite value",
+            ));
+        }
+        let (scale_x, scale_y) = self.cross_axis_scales(options)?;
+        let scale = if vertical { scale_y } else { scale_x };
+        let target = target_size / scale.max(f32::EPSILON);
+        let Some(math) = self.current_math() else {
+            return Err(Error::new(ErrorKind::Unsupported, "font has no MATH table"));
+        };
+        let single = |glyph_id: u16| -> Result<GlyphRun, Error> {
+            let glyph = self.glyph_for_id(glyph_id, options, scale_x, scale_y)?;
+            Ok(GlyphRun::new(vec![PositionedGlyph::new(glyph, 0.0, 0.0)]))
+        };
+        let Some(construction) = math.construction(glyph_id, vertical) else {
+            return single(glyph_id);
+        };
+        if let Some(variant) = construction
+            .variants
+            .iter()
+            .find(|variant| variant.advance_measurement as f32 >= target)
+        {
+            return single(variant.variant_glyph);
+        }
+        let Some(assembly) = construction.assembly.as_ref() else {
+            return single(
+                construction
+                    .variants
+                    .last()
+                    .map(|variant| variant.variant_glyph)
+                    .unwrap_or(glyph_id),
+            );
+        };
+
+        let parts = assembly.layout(target, math.min_connector_overlap());
+        let mut glyphs = Vec::with_capacity(parts.len());
+        for (index, part) in parts.iter().enumerate() {
+            let mut glyph = self.glyph_for_id(part.glyph_id, options, scale_x, scale_y)?;
+            if vertical {
+                // Only the first part carries the horizontal advance of the delimiter.
+                if index > 0 {
+                    glyph.metrics.advance_x = 0.0;
+                }
+                glyph.metrics.advance_y = 0.0;
+                glyphs.push(PositionedGlyph::new(glyph, 0.0, -part.offset * scale_y));
+            } else {
+                glyph.metrics.advance_x = part.advance * scale_x;
+                glyphs.push(PositionedGlyph::new(glyph, part.offset * scale_x, 0.0));
+            }
+        }
+        Ok(GlyphRun::new(glyphs))
+    }
+
+    /// Estimates a baseline in design units for fonts without a BASE table.
+    fn estimated_baseline_value(
+        &self,
+        options: &crate::commands::FontOptions<'_>,
+        baseline: crate::commands::Baseline,
+        is_vertical: bool,
+    ) -> Result<f32, Error> {
+        use crate::commands::Baseline;
+
+        let units_per_em = self.current_head()?.units_per_em.max(1) as f32;
+        let face_inset = units_per_em / 20.0;
+        if is_vertical {
+            // Vertical baselines are x positions; the em box spans 0..units_per_em.
+            return Ok(match baseline {
+                Baseline::Ideographic => 0.0,
+                Baseline::IdeographicEmTop => units_per_em,
+                Baseline::IdeographicFaceBottom => face_inset,
+                Baseline::IdeographicFaceTop => units_per_em - face_inset,
+                Baseline::Alphabetic => units_per_em * 0.12,
+                Baseline::Hanging => units_per_em * 0.8,
+                Baseline::Math => units_per_em / 2.0,
+            });
+        }
+
+        let hhea = self.current_hhea()?;
+        let coordinates = self.normalized_variation_coords(options);
+        let ascender =
+            self.metric_value_i16(tag4("hasc"), hhea.get_accender(), &coordinates) as f32;
+        let descender =
+            self.metric_value_i16(tag4("hdsc"), hhea.get_descender(), &coordinates) as f32;
+        let ideographic = descender;
+        Ok(match baseline {
+            Baseline::Alphabetic => 0.0,
+            Baseline::Ideographic => ideographic,
+            Baseline::IdeographicEmTop => ideographic + units_per_em,
+            Baseline::IdeographicFaceBottom => ideographic + face_inset,
+            Baseline::IdeographicFaceTop => ideographic + units_per_em - face_inset,
+            Baseline::Hanging => ascender * 0.8,
+            Baseline::Math => ascender / 2.0,
+        })
+    }
+
+    /// Embedded color bitmap for `glyph_id` from sbix, else CBDT, and
+    /// whether the outline should be drawn under it.
+    fn raster_glyph_layer(
+        &self,
+        glyph_id: usize,
+        options: &crate::commands::FontOptions<'_>,
+    ) -> Option<(RasterGlyphLayer, bool)> {
+        let stretch = options.font_stretch.0.max(0.0);
+        if let Some(bitmap) = self
+            .current_sbix()
+            .and_then(|sbix| sbix.get_raster_glyph(glyph_id as u32, options.font_size, "px"))
+        {
+            let top = sbix_top(&bitmap, bitmap.height.unwrap_or(0) as f32);
+            let mut raster = RasterGlyphLayer::from_encoded(bitmap.glyph_data);
+            raster.offset_x = bitmap.offset_x * stretch;
+            raster.offset_y = top;
+            raster.width = bitmap.width;
+            raster.height = bitmap.height;
+            return Some((raster, bitmap.draw_outline));
+        }
+        self.cbdt_raster_layer(glyph_id as u16, options.font_size, stretch)
+            .map(|raster| (raster, false))
+    }
+
+    /// Places a CBDT PNG with its bitmap bearings, scaled from the strike ppem.
+    fn cbdt_raster_layer(
+        &self,
+        glyph_id: u16,
+        font_size: f32,
+        stretch: f32,
+    ) -> Option<RasterGlyphLayer> {
+        let strike = self.current_cblc()?.select_strike(glyph_id, font_size)?;
+        let bitmap = self.current_cbdt()?.glyph(&strike.locate(glyph_id)?)?;
+        let scale = if strike.ppem_y == 0 {
+            1.0
+        } else {
+            font_size / strike.ppem_y as f32
+        };
+        let metrics = bitmap.metrics;
+        let mut raster = RasterGlyphLayer::from_encoded(bitmap.data);
+        raster.offset_x = metrics.hori_bearing_x as f32 * scale * stretch;
+        raster.offset_y = -(metrics.hori_bearing_y as f32) * scale;
+        raster.width = Some(((metrics.width as f32 * scale * stretch).round() as u32).max(1));
+        raster.height = Some(((metrics.height as f32 * scale).round() as u32).max(1));
+        Some(raster)
+    }
+
+    /// EBDT bitmap for `glyph_id` tinted with the foreground color. With
+    /// outlines available it is only used when `embedded_bitmaps` is set and a
+    /// strike matches the font size; bitmap-only fonts scale the nearest strike.
+    fn embedded_bitmap_layer(
+        &self,
+        glyph_id: usize,
+        options: &crate::commands::FontOptions<'_>,
+        can_use_outline: bool,
+    ) -> Option<RasterGlyphLayer> {
+        if can_use_outline && !options.embedded_bitmaps {
+            return None;
+        }
+        let glyph_id = glyph_id as u16;
+        let strike = self
+            .current_eblc()?
+            .select_strike(glyph_id, options.font_size)?;
+        if can_use_outline && (strike.ppem_y as f32 - options.font_size).abs() >= 0.5 {
+            return None;
+        }
+        let bitmap = self.current_ebdt()?.glyph(strike, glyph_id)?;
+        let scale = if strike.ppem_y == 0 {
+            1.0
+        } else {
+            options.font_size / strike.ppem_y as f32
+        };
+        let stretch = options.font_stretch.0.max(0.0);
+        let [alpha, red, green, blue] = options
+            .foreground_color
+            .unwrap_or(0xFF00_0000)
+            .to_be_bytes();
+        let rgba = bitmap
+            .coverage
+            .iter()
+            .flat_map(|&coverage| {
+                let alpha = (alpha as u32 * coverage as u32 / 255) as u8;
+                [red, green, blue, alpha]
+            })
+            .collect();
+        let metrics = bitmap.metrics;
+        let mut raster = RasterGlyphLayer::from_rgba(bitmap.width, bitmap.height, rgba);
+        raster.offset_x = metrics.hori_bearing_x as f32 * scale * stretch;
+        raster.offset_y = -(metrics.hori_bearing_y as f32) * scale;
+        raster.width = Some(((metrics.width as f32 * scale * stretch).round() as u32).max(1));
+        raster.height = Some(((metrics.height as f32 * scale).round() as u32).max(1));
+        Some(raster)
     }
 
     fn build_outline_layers(
         &self,
         glyph_id: usize,
-        open_type_glyph: &OpenTypeGlyph,
+        outline: &CachedOutline,
         scale_x: f32,
         scale_y: f32,
-        _ch: char,
+        options: &crate::commands::FontOptions<'_>,
     ) -> Result<Vec<GlyphLayer>, Error> {
+        let open_type_glyph = &outline.glyph;
         let color_layers =
-            self.build_colr_layers(glyph_id, &open_type_glyph.layout, scale_x, scale_y);
+            self.build_colr_layers(glyph_id, open_type_glyph, options, scale_x, scale_y);
         if !color_layers.is_empty() {
             return Ok(color_layers);
         }
 
+        let path_layer = |commands: &[DrawCommand]| {
+            vec![GlyphLayer::Path(PathGlyphLayer::new(
+                scale_draw_commands(commands, scale_x, scale_y),
+                GlyphPaint::CurrentColor,
+            ))]
+        };
+        match outline.outline(|| {
+            self.unscaled_outline_commands(glyph_id, open_type_glyph)
+                .ok()
+        }) {
+            Some(commands) => Ok(path_layer(commands)),
+            // Failures are not cached; building again reports the error.
+            None => self
+                .unscaled_outline_commands(glyph_id, open_type_glyph)
+                .map(|commands| path_layer(&commands)),
+        }
+    }
+
+    /// Outline commands in font units, with the baseline shift applied.
+    fn unscaled_outline_commands(
+        &self,
+        glyph_id: usize,
+        open_type_glyph: &OpenTypeGlyph,
+    ) -> Result<Vec<DrawCommand>, Error> {
         #[cfg(feature = "cff")]
         if let Some(cff) = self.current_cff() {
-            let commands =
-                cff.to_path_commands_with_coords(glyph_id, 1.0, &open_type_glyph.variation_coords)?;
-            let commands = transform_cff_commands(&commands, scale_x, scale_y);
-            return Ok(vec![GlyphLayer::Path(PathGlyphLayer::new(
-                commands,
-                GlyphPaint::CurrentColor,
-            ))]);
+            return cff.to_path_commands_with_coords(
+                glyph_id,
+                1.0,
+                &open_type_glyph.variation_coords,
+            );
         }
 
         if self.current_outline_format() == GlyphFormat::CFF2 {
@@ -2434,26 +3747,26 @@ impl Font {
                     .current_glyf()
                     .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "glyf is none"))?;
                 let commands = glyf.to_path_commands(glyph_id, &open_type_glyph.layout, 0.0, 0.0);
-                let commands =
-                    transform_glyf_commands(&commands, &open_type_glyph.layout, scale_x, scale_y);
-                Ok(vec![GlyphLayer::Path(PathGlyphLayer::new(
-                    commands,
-                    GlyphPaint::CurrentColor,
-                ))])
+                Ok(transform_glyf_commands(
+                    &commands,
+                    &open_type_glyph.layout,
+                    1.0,
+                    1.0,
+                ))
             }
             FontData::ParsedGlyph(parsed) => {
                 let commands =
                     glyf::Glyph::to_path_commands_parsed(parsed, &open_type_glyph.layout, 0.0, 0.0);
-                let commands =
-                    transform_glyf_commands(&commands, &open_type_glyph.layout, scale_x, scale_y);
-                Ok(vec![GlyphLayer::Path(PathGlyphLayer::new(
-                    commands,
-                    GlyphPaint::CurrentColor,
-                ))])
+                Ok(transform_glyf_commands(
+                    &commands,
+                    &open_type_glyph.layout,
+                    1.0,
+                    1.0,
+                ))
             }
             FontData::Bitmap(_, _) => Err(Error::new(
                 ErrorKind::Unsupported,
-                "bitmap glyphs are only supported through sbix raster layers",
+                "bitmap glyphs are only supported through sbix, CBDT and EBDT raster layers",
             )),
             FontData::SVG(_) => Err(Error::new(
                 ErrorKind::Unsupported,
@@ -2469,15 +3782,18 @@ impl Font {
     fn build_colr_layers(
         &self,
         glyph_id: usize,
-        layout: &FontLayout,
+        open_type_glyph: &OpenTypeGlyph,
+        options: &crate::commands::FontOptions<'_>,
         scale_x: f32,
         scale_y: f32,
     ) -> Vec<GlyphLayer> {
-        let (Some(colr), Some(cpal), Some(glyf)) = (
-            self.current_colr(),
-            self.current_cpal(),
-            self.current_glyf(),
-        ) else {
+        let layout = &open_type_glyph.layout;
+        if let Some(layers) =
+            self.build_colr_paint_layers(glyph_id, open_type_glyph, options, scale_x, scale_y)
+        {
+            return layers;
+        }
+        let (Some(colr), Some(glyf)) = (self.current_colr(), self.current_glyf()) else {
             return Vec::new();
         };
 
@@ -2488,20 +3804,78 @@ impl Font {
             }
             let commands = glyf.to_path_commands(layer.glyph_id as usize, layout, 0.0, 0.0);
             let commands = transform_glyf_commands(&commands, layout, scale_x, scale_y);
-            let color = cpal.get_pallet(layer.palette_index as usize);
-            let argb = ((color.alpha as u32) << 24)
-                | ((color.red as u32) << 16)
-                | ((color.green as u32) << 8)
-                | color.blue as u32;
-            layers.push(GlyphLayer::Path(PathGlyphLayer::new(
-                commands,
-                GlyphPaint::Solid(argb),
-            )));
+            let paint = self
+                .palette_color(options, layer.palette_index)
+                .map_or(GlyphPaint::CurrentColor, GlyphPaint::Solid);
+            layers.push(GlyphLayer::Path(PathGlyphLayer::new(commands, paint)));
         }
 
         layers
     }
 
+    /// `0xAARRGGBB` of palette entry `index` under the options' palette
+    /// selection and overrides; `None` draws with `GlyphPaint::CurrentColor`.
+    fn palette_color(&self, options: &crate::commands::FontOptions<'_>, index: u16) -> Option<u32> {
+        use crate::commands::FontPaletteType;
+
+        if index == cpal::FOREGROUND_ENTRY {
+            return options.foreground_color;
+        }
+        if let Some((_, color)) = options
+            .palette_overrides
+            .iter()
+            .find(|(entry, _)| *entry == index)
+        {
+            return Some(*color);
+        }
+        let cpal = self.current_cpal()?;
+        let flag = options.palette_type.map(|palette_type| match palette_type {
+            FontPaletteType::Light => cpal::USABLE_WITH_LIGHT_BACKGROUND,
+            FontPaletteType::Dark => cpal::USABLE_WITH_DARK_BACKGROUND,
+        });
+        let palette = cpal.select_palette(options.palette, flag);
+        cpal.color(palette, index).map(|color| color.argb())
+    }
+
+    /// Layers of a COLRv1 glyph, or `None` when the glyph has no paint graph.
+    fn build_colr_paint_layers(
+        &self,
+        glyph_id: usize,
+        open_type_glyph: &OpenTypeGlyph,
+        options: &crate::commands::FontOptions<'_>,
+        scale_x: f32,
+        scale_y: f32,
+    ) -> Option<Vec<GlyphLayer>> {
+        let colr = self.current_colr()?;
+        let coordinates = &open_type_glyph.variation_coords;
+        let mut paint = colr.paint(glyph_id as u16, coordinates)?;
+        let color = |index: u16| self.palette_color(options, index);
+        let mut outline = |layer_glyph_id: u16| {
+            let glyph = self.get_glyph_from_id_with_coords(
+                layer_glyph_id as usize,
+                false,
+                coordinates.clone(),
+            );
+            self.unscaled_outline_commands(layer_glyph_id as usize, &glyph)
+                .ok()
+        };
+        // Paint space is y up; glyph space is y down.
+        let matrix = [scale_x, 0.0, 0.0, -scale_y, 0.0, 0.0];
+        if let Some(clip_box) = colr.clip_box(glyph_id as u16, coordinates) {
+            paint = colr::Paint::ColrGlyph {
+                glyph_id: glyph_id as u16,
+                clip_box: Some(clip_box),
+                paint: Box::new(paint),
+            };
+        }
+        Some(
+            colr::paint_to_layers(&paint, matrix, &mut outline, &color)
+                .into_iter()
+                .map(GlyphLayer::Path)
+                .collect(),
+        )
+    }
+
     fn legacy_colr_commands(
         &self,
         glyph_id: usize,
@@ -2528,6 +3902,48 @@ impl Font {
         commands
     }
 
+    /// glyf outline of one glyph for the legacy command output, with COLRv0
+    /// layers merged into one path.
+    fn legacy_outline_commands(
+        &self,
+        glyph_id: usize,
+        open_type_glyph: &OpenTypeGlyph,
+        origin_x: f64,
+        origin_y: f64,
+    ) -> Result<Vec<PathCommand>, Error> {
+        match &open_type_glyph.glyph {
+            FontData::Glyph(_) => {
+                let commands = self.legacy_colr_commands(
+                    glyph_id,
+                    &open_type_glyph.layout,
+                    origin_x,
+                    origin_y,
+                );
+                if !commands.is_empty() {
+                    return Ok(commands);
+                }
+                let glyf = self
+                    .current_glyf()
+                    .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "glyf is none"))?;
+                Ok(glyf.to_path_commands(glyph_id, &open_type_glyph.layout, origin_x, origin_y))
+            }
+            FontData::ParsedGlyph(parsed) => Ok(glyf::Glyph::to_path_commands_parsed(
+                parsed,
+                &open_type_glyph.layout,
+                origin_x,
+                origin_y,
+            )),
+            FontData::CFF(_) | FontData::CFF2(_) => Err(Error::new(
+                ErrorKind::Unsupported,
+                "legacy text2commands does not support CFF/CFF2 outlines",
+            )),
+            _ => Err(Error::new(
+                std::io::ErrorKind::Other,
+                "text2commands supports glyf outlines and sbix bitmap glyphs only",
+            )),
+        }
+    }
+
     pub(crate) fn text2commands(&self, text: &str) -> Result<Vec<GlyphCommands>, Error> {
         let mut result = Vec::new();
         let mut cursor_x = 0.0;
@@ -2535,13 +3951,19 @@ impl Font {
         let line_height = self
             .default_line_height_with_options(&crate::commands::FontOptions::from_parsed(self))?;
         let tab_advance = line_height;
-        let shaped_units = self.shape_text_units(
-            text,
-            false,
-            false,
+        // sbix strikes are requested at one em so bitmaps share the font units.
+        let units_per_em = self
+            .current_head()
+            .map_or(1.0, |head| head.units_per_em.max(1) as f32);
+        let plan = ShapePlan::resolve(
+            self,
             None,
-            crate::commands::FontVariant::Normal,
-        )?;
+            crate::commands::TextDirection::LeftToRight,
+            &[],
+            &[],
+            &[],
+        );
+        let shaped_units = self.shape_text_units(text, &plan)?;
 
         for (index, unit) in shaped_units.iter().enumerate() {
             match *unit {
@@ -2563,7 +3985,7 @@ impl Font {
                         .as_ref()
                         .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "glyph is none"))?;
                     let adjustment =
-                        self.pair_adjustment_for_index(&shaped_units, index, None, false, 1.0, 1.0);
+                        self.pair_adjustment_for_index(&shaped_units, index, &plan, 1.0, 1.0, 1.0);
                     let origin_y =
                         -(line_index as f64 * line_height) + adjustment.placement_y as f64;
                     let advance_width = match &open_type_glyph.layout {
@@ -2575,122 +3997,81 @@ impl Font {
                     let can_use_outline =
                         self.resolved_glyph_can_use_outline(open_type_glyph, glyph_data.glyph_id);
 
-                    if let Some(sbix) = self.current_sbix() {
-                        if let Some(bitmap) = sbix.get_raster_glyph(
-                            glyph_data.glyph_id as u32,
-                            line_height as f32,
-                            "px",
-                        ) {
-                            if resolved.prefer_color || !can_use_outline {
-                                let format = if bitmap.graphic_type == u32::from_be_bytes(*b"png ")
-                                {
-                                    BitmapGlyphFormat::Png
-                                } else if bitmap.graphic_type == u32::from_be_bytes(*b"jpg ") {
-                                    BitmapGlyphFormat::Jpeg
-                                } else {
-                                    return Err(Error::new(
-                                        std::io::ErrorKind::Unsupported,
-                                        "unsupported sbix image format",
-                                    ));
-                                };
-                                let sniffed_dimensions =
-                                    sniff_encoded_image_dimensions(&bitmap.glyph_data);
-                                result.push(GlyphCommands {
-                                    ch: resolved.ch,
-                                    glyph_id: glyph_data.glyph_id,
-                                    origin_x,
-                                    origin_y,
-                                    advance_width,
-                                    commands: Vec::new(),
-                                    bitmap: Some(BitmapGlyphCommands {
-                                        offset_x: bitmap.offset_x as f64,
-                                        offset_y: bitmap.offset_y as f64,
-                                        width: bitmap
-                                            .width
-                                            .map(|width| width as f64)
-                                            .or_else(|| {
-                                                sniffed_dimensions.map(|(_, width, _)| width as f64)
-                                            })
-                                            .unwrap_or(line_height),
-                                        height: bitmap
-                                            .height
-                                            .map(|height| height as f64)
-                                            .or_else(|| {
-                                                sniffed_dimensions
-                                                    .map(|(_, _, height)| height as f64)
-                                            })
-                                            .unwrap_or(line_height),
-                                        format,
-                                        data: bitmap.glyph_data,
-                                    }),
-                                });
-                                cursor_x += advance_width;
-                                continue;
+                    let bitmap = self
+                        .current_sbix()
+                        .and_then(|sbix| {
+                            sbix.get_raster_glyph(glyph_data.glyph_id as u32, units_per_em, "px")
+                        })
+                        .filter(|_| resolved.prefer_color || !can_use_outline);
+                    if let Some(bitmap) = bitmap {
+                        let format = match bitmap.mime_type() {
+                            Some("image/png") => BitmapGlyphFormat::Png,
+                            Some("image/jpeg") => BitmapGlyphFormat::Jpeg,
+                            Some("image/tiff") => BitmapGlyphFormat::Tiff,
+                            _ => {
+                                return Err(Error::new(
+                                    std::io::ErrorKind::Unsupported,
+                                    "unsupported sbix image format",
+                                ))
                             }
-                        }
-                    }
-
-                    match &open_type_glyph.glyph {
-                        FontData::Glyph(_) => {
-                            let mut commands = self.legacy_colr_commands(
+                        };
+                        let sniffed_dimensions = sniff_encoded_image_dimensions(&bitmap.glyph_data);
+                        let width = bitmap
+                            .width
+                            .or_else(|| sniffed_dimensions.map(|(_, width, _)| width))
+                            .map_or(units_per_em as f64, f64::from);
+                        let height = bitmap
+                            .height
+                            .or_else(|| sniffed_dimensions.map(|(_, _, height)| height))
+                            .map_or(units_per_em as f64, f64::from);
+                        let commands = if bitmap.draw_outline && can_use_outline {
+                            self.legacy_outline_commands(
                                 glyph_data.glyph_id,
-                                &open_type_glyph.layout,
-                                origin_x,
-                                origin_y,
-                            );
-                            if commands.is_empty() {
-                                let glyf = self.current_glyf().ok_or_else(|| {
-                                    Error::new(std::io::ErrorKind::Other, "glyf is none")
-                                })?;
-                                commands = glyf.to_path_commands(
-                                    glyph_data.glyph_id,
-                                    &open_type_glyph.layout,
-                                    origin_x,
-                                    origin_y,
-                                );
-                            }
-                            result.push(GlyphCommands {
-                                ch: resolved.ch,
-                                glyph_id: glyph_data.glyph_id,
-                                origin_x,
-                                origin_y,
-                                advance_width,
-                                commands,
-                                bitmap: None,
-                            });
-                            cursor_x += advance_width;
-                        }
-                        FontData::ParsedGlyph(parsed) => {
-                            let commands = glyf::Glyph::to_path_commands_parsed(
-                                parsed,
-                                &open_type_glyph.layout,
-                                origin_x,
-                                origin_y,
-                            );
-                            result.push(GlyphCommands {
-                                ch: resolved.ch,
-                                glyph_id: glyph_data.glyph_id,
+                                open_type_glyph,
                                 origin_x,
                                 origin_y,
-                                advance_width,
-                                commands,
-                                bitmap: None,
-                            });
-                            cursor_x += advance_width;
-                        }
-                        FontData::CFF(_) | FontData::CFF2(_) => {
-                            return Err(Error::new(
-                                ErrorKind::Unsupported,
-                                "legacy text2commands does not support CFF/CFF2 outlines",
-                            ));
-                        }
-                        _ => {
-                            return Err(Error::new(
-                                std::io::ErrorKind::Other,
-                                "text2commands supports glyf outlines and sbix bitmap glyphs only",
-                            ));
-                        }
+                            )?
+                        } else {
+                            Vec::new()
+                        };
+                        // Commands put the baseline at the top of the em box.
+                        let baseline = glyph_baseline_shift(&open_type_glyph.layout) as f64;
+                        result.push(GlyphCommands {
+                            ch: resolved.ch,
+                            glyph_id: glyph_data.glyph_id,
+                            origin_x,
+                            origin_y,
+                            advance_width,
+                            commands,
+                            bitmap: Some(BitmapGlyphCommands {
+                                offset_x: bitmap.offset_x as f64,
+                                offset_y: baseline + sbix_top(&bitmap, height as f32) as f64,
+                                width,
+                                height,
+                                format,
+                                data: bitmap.glyph_data,
+                            }),
+                        });
+                        cursor_x += advance_width;
+                        continue;
                     }
+
+                    let commands = self.legacy_outline_commands(
+                        glyph_data.glyph_id,
+                        open_type_glyph,
+                        origin_x,
+                        origin_y,
+                    )?;
+                    result.push(GlyphCommands {
+                        ch: resolved.ch,
+                        glyph_id: glyph_data.glyph_id,
+                        origin_x,
+                        origin_y,
+                        advance_width,
+                        commands,
+                        bitmap: None,
+                    });
+                    cursor_x += advance_width;
                 }
             }
         }
@@ -2711,21 +4092,29 @@ impl Font {
         &self,
         text: &str,
         options: &crate::commands::FontOptions<'_>,
+    ) -> Result<f64, Error> {
+        self.measure_with_plan(text, options, &ShapePlan::new(self, options), None)
+    }
+
+    pub(crate) fn measure_with_plan(
+        &self,
+        text: &str,
+        options: &crate::commands::FontOptions<'_>,
+        plan: &ShapePlan,
+        outlines: Option<&OutlineCache>,
     ) -> Result<f64, Error> {
         let mut cursor_x = 0.0;
         let mut cursor_y = 0.0;
         let mut max_line_width: f64 = 0.0;
         let line_height = self.default_line_height_with_options(options)?;
         let tab_advance = line_height;
+        // Advances stay in font units, but device deltas resolve at the size
+        // `text2glyph_run` draws `options.font_size` at.
+        let device_scale = options.font_size / (line_height as f32).max(1.0);
         let is_vertical = options.text_direction.is_vertical();
         let is_right_to_left = options.text_direction.is_right_to_left();
-        let shaped_units = self.shape_text_units(
-            text,
-            is_vertical,
-            is_right_to_left,
-            options.locale,
-            options.font_variant,
-        )?;
+        let mut shaped_units = self.shape_text_units(text, plan)?;
+        self.apply_variant_synthesis(&mut shaped_units, options, plan)?;
 
         for (index, unit) in shaped_units.iter().enumerate() {
             match *unit {
@@ -2754,29 +4143,23 @@ impl Font {
                     }
                 }
                 ResolvedTextUnit::Glyph(resolved) => {
-                    let glyph_data = self.get_glyph_from_id_with_options(
-                        resolved.glyph_id,
-                        is_vertical,
-                        options,
-                    );
-                    let open_type_glyph = glyph_data
-                        .open_type_glyf
-                        .as_ref()
-                        .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "glyph is none"))?;
+                    let outline =
+                        self.resolve_outline(resolved.glyph_id, is_vertical, options, outlines);
+                    let open_type_glyph = &outline.glyph;
 
                     let adjustment = self.pair_adjustment_for_index(
                         &shaped_units,
                         index,
-                        options.locale,
-                        is_vertical,
+                        plan,
                         1.0,
                         1.0,
+                        device_scale,
                     );
-                    let (advance_x, advance_y) = match &open_type_glyph.layout {
-                        FontLayout::Horizontal(layout) => (layout.advance_width as f64, 0.0),
-                        FontLayout::Vertical(layout) => (0.0, layout.advance_height as f64),
-                        FontLayout::Unknown => (0.0, 0.0),
-                    };
+                    let advance = layout_advance(&open_type_glyph.layout);
+                    let (advance_x, advance_y) = resolved
+                        .synthesis
+                        .map_or(advance, |s| s.advance(advance, is_vertical));
+                    let (advance_x, advance_y) = (advance_x as f64, advance_y as f64);
                     if is_right_to_left && !is_vertical {
                         cursor_x -= advance_x + adjustment.advance_x as f64;
                     } else {
@@ -2833,7 +4216,7 @@ impl Font {
 
             if let Some(bitmap) = glyph.bitmap.as_ref() {
                 let glyph_min_x = glyph.origin_x + bitmap.offset_x;
-                let glyph_min_y = glyph.origin_y + bitmap.offset_y;
+                let glyph_min_y = bitmap.offset_y - glyph.origin_y;
                 let glyph_max_x = glyph_min_x + bitmap.width;
                 let glyph_max_y = glyph_min_y + bitmap.height;
                 if !has_point {
@@ -2889,7 +4272,12 @@ impl Font {
     }
 
     pub(crate) fn face_name_by_id(&self, name_id: u16) -> Option<String> {
-        let locale = "en-US".to_string();
+        self.face_name_by_id_for_locale(name_id, "en-US")
+    }
+
+    /// Looks a name up in `locale`, falling back to the English names.
+    pub(crate) fn face_name_by_id_for_locale(&self, name_id: u16, locale: &str) -> Option<String> {
+        let locale = locale.to_string();
         let name_table = if self.current_font == 0 {
             self.name_table.as_ref()?
         } else {
@@ -2910,6 +4298,23 @@ impl Font {
             .unwrap_or_default()
     }
 
+    pub(crate) fn face_named_instances(&self) -> Vec<fvar::InstanceRecord> {
+        self.current_fvar()
+            .map(|fvar| fvar.instances.clone())
+            .unwrap_or_default()
+    }
+
+    /// Number of `fvar` named instances in face `index` of the file.
+    pub(crate) fn named_instance_count_at(&self, index: usize) -> usize {
+        let font = if index == 0 {
+            Some(self)
+        } else {
+            self.more_fonts.get(index - 1)
+        };
+        font.and_then(|font| font.fvar.as_ref())
+            .map_or(0, |fvar| fvar.instances.len())
+    }
+
     pub(crate) fn face_family_name(&self) -> String {
         let locale = "en-US".to_string();
         self.get_name(NameID::TypographicFamilyName, &locale)
@@ -3069,38 +4474,16 @@ impl Font {
 
     #[cfg(debug_assertions)]
     pub fn get_sbix_raw(&self) -> String {
-        let sbix = if self.current_font == 0 {
-            if let Some(sbix) = &self.sbix {
-                sbix
-            } else {
-                return "sbix is none".to_string();
-            }
-        } else {
-            let sbix = self.more_fonts[self.current_font - 1].sbix.as_ref();
-            if let Some(sbix) = sbix {
-                sbix
-            } else {
-                return "sbix is none".to_string();
-            }
+        let Some(sbix) = self.current_sbix() else {
+            return "sbix is none".to_string();
         };
         sbix.to_string()
     }
 
     #[cfg(debug_assertions)]
     pub fn get_svg_raw(&self) -> String {
-        let svg = if self.current_font == 0 {
-            if let Some(svg) = &self.svg {
-                svg
-            } else {
-                return "svg is none".to_string();
-            }
-        } else {
-            let svg = self.more_fonts[self.current_font - 1].svg.as_ref();
-            if let Some(svg) = svg {
-                svg
-            } else {
-                return "svg is none".to_string();
-            }
+        let Some(svg) = self.current_svg_table() else {
+            return "svg is none".to_string();
         };
         svg.to_string()
     }
@@ -3139,11 +4522,7 @@ impl Font {
 
     #[cfg(debug_assertions)]
     pub fn get_colr_raw(&self) -> String {
-        let colr = if self.current_font == 0 {
-            self.colr.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].colr.as_ref()
-        };
+        let colr = self.current_colr();
         Self::debug_optional_table_string(colr, "colr", |colr| colr.to_string())
     }
     #[cfg(debug_assertions)]
@@ -3160,22 +4539,14 @@ impl Font {
     #[cfg(debug_assertions)]
     #[cfg(feature = "layout")]
     pub fn get_gdef_raw(&self) -> String {
-        let gdef = if self.current_font == 0 {
-            self.gdef.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].gdef.as_ref()
-        };
+        let gdef = self.current_gdef();
         Self::debug_optional_table_string(gdef, "gdef", |gdef| gdef.to_string())
     }
 
     #[cfg(debug_assertions)]
     #[cfg(feature = "layout")]
     pub fn get_gsub_raw(&self) -> String {
-        let gsub = if self.current_font == 0 {
-            self.gsub.as_ref()
-        } else {
-            self.more_fonts[self.current_font - 1].gsub.as_ref()
-        };
+        let gsub = self.current_gsub();
         Self::debug_optional_table_string(gsub, "gsub", |gsub| gsub.to_string())
     }
 
@@ -3255,12 +4626,14 @@ impl Font {
     }
 
     pub fn set_font(&mut self, number: usize) -> Result<(), String> {
-        if number <= self.more_fonts.len() {
-            self.current_font = number;
-            Ok(())
-        } else {
-            Err("font number is out of range".to_owned())
+        if number > self.more_fonts.len() {
+            return Err("font number is out of range".to_owned());
+        }
+        if number > 0 && self.more_fonts.get(number - 1).is_none() {
+            return Err(format!("font number {number} could not be parsed"));
         }
+        self.current_font = number;
+        Ok(())
     }
 }
 
@@ -3305,7 +4678,7 @@ fn font_load_from_file(filename: &PathBuf) -> Result<Font, Error> {
     #[cfg(not(target_arch = "wasm32"))]
     {
         let fontdata = std::fs::read(filename)?;
-        Font::get_font_from_buffer(&fontdata)
+        Font::get_font_from_bytes(FontBytes::from(fontdata))
     }
 }
 
@@ -3358,16 +4731,23 @@ fn path_command_bounds(commands: &[PathCommand]) -> (f64, f64, f64, f64) {
     (min_x, min_y, max_x, max_y)
 }
 
+/// Top of an sbix bitmap `height` tall relative to the baseline, y down;
+/// sbix offsets locate the lower-left corner, y up.
+fn sbix_top(bitmap: &sbix::RasterGlyphData, height: f32) -> f32 {
+    -(bitmap.offset_y + height)
+}
+
 fn bitmap_glyph_to_svg_image(glyph: &GlyphCommands, bitmap: &BitmapGlyphCommands) -> String {
     let mime = match bitmap.format {
         BitmapGlyphFormat::Png => "image/png",
         BitmapGlyphFormat::Jpeg => "image/jpeg",
+        BitmapGlyphFormat::Tiff => "image/tiff",
     };
     let encoded = general_purpose::STANDARD.encode(&bitmap.data);
     format!(
         "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"data:{};base64,{}\"/>",
         glyph.origin_x + bitmap.offset_x,
-        glyph.origin_y + bitmap.offset_y,
+        bitmap.offset_y - glyph.origin_y,
         bitmap.width,
         bitmap.height,
         mime,
@@ -3411,11 +4791,7 @@ fn transform_glyf_commands(
         .collect()
 }
 
-fn transform_cff_commands(
-    commands: &[DrawCommand],
-    scale_x: f32,
-    scale_y: f32,
-) -> Vec<DrawCommand> {
+fn scale_draw_commands(commands: &[DrawCommand], scale_x: f32, scale_y: f32) -> Vec<DrawCommand> {
     commands
         .iter()
         .map(|command| match command {
@@ -3434,6 +4810,51 @@ fn transform_cff_commands(
         .collect()
 }
 
+/// Picks the BASE script tag for a character, or `DFLT` when unknown.
+pub(crate) fn baseline_script_tag(ch: char) -> u32 {
+    let tag = match ch as u32 {
+        0x0041..=0x024F => *b"latn",
+        0x0370..=0x03FF => *b"grek",
+        0x0400..=0x04FF => *b"cyrl",
+        0x0590..=0x05FF => *b"hebr",
+        0x0600..=0x06FF | 0x0750..=0x077F => *b"arab",
+        0x0900..=0x097F => *b"dev2",
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => *b"hang",
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => *b"kana",
+        0x2E80..=0x2FDF
+        | 0x3000..=0x303F
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xF900..=0xFAFF
+        | 0xFF00..=0xFF65
+        | 0x20000..=0x3FFFF => *b"hani",
+        _ => *b"DFLT",
+    };
+    u32::from_be_bytes(tag)
+}
+
+pub(crate) fn decorated_line(
+    glyphs: std::ops::Range<usize>,
+    cursor_x: f32,
+    cursor_y: f32,
+    is_vertical: bool,
+    is_right_to_left: bool,
+) -> crate::decoration::DecoratedLine {
+    let (cross_origin, inline_start, inline_end) = if is_vertical {
+        (cursor_x, 0.0, cursor_y)
+    } else if is_right_to_left {
+        (cursor_y, cursor_x, 0.0)
+    } else {
+        (cursor_y, 0.0, cursor_x)
+    };
+    crate::decoration::DecoratedLine {
+        glyphs,
+        cross_origin,
+        inline_start,
+        inline_end,
+    }
+}
+
 fn font_metrics_from_layout(layout: &FontLayout, scale_y: f32) -> DrawFontMetrics {
     match layout {
         FontLayout::Horizontal(layout) => DrawFontMetrics {
@@ -3477,6 +4898,32 @@ fn glyph_metrics_from_layout(layout: &FontLayout, scale_x: f32, scale_y: f32) ->
     }
 }
 
+/// Unscaled advance of a glyph layout in font units.
+fn layout_advance(layout: &FontLayout) -> (f32, f32) {
+    match layout {
+        FontLayout::Horizontal(layout) => (layout.advance_width as f32, 0.0),
+        FontLayout::Vertical(layout) => (0.0, layout.advance_height as f32),
+        FontLayout::Unknown => (0.0, 0.0),
+    }
+}
+
+/// Adds a stroke of `width` px around every filled path layer.
+fn embolden_path_layers(layers: &mut Vec<GlyphLayer>, width: f32) {
+    let strokes = layers
+        .iter()
+        .filter_map(|layer| match layer {
+            GlyphLayer::Path(path) if path.paint_mode == PathPaintMode::Fill => {
+                let mut stroke = path.clone();
+                stroke.paint_mode = PathPaintMode::Stroke;
+                stroke.stroke_width = width;
+                Some(GlyphLayer::Path(stroke))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    layers.extend(strokes);
+}
+
 fn glyph_layers_bounds(layers: &[GlyphLayer]) -> Option<GlyphBounds> {
     let mut bounds = None;
 
@@ -3579,7 +5026,7 @@ fn font_debug(_font: &Font) {
         let _ = writeln!(&mut writer, "loca is none. it is not glyf font.");
         return;
     };
-    let Some(glyf) = _font.glyf.as_ref() else {
+    let Some(glyf) = _font.current_glyf() else {
         return;
     };
 
@@ -3605,7 +5052,7 @@ fn font_debug(_font: &Font) {
     if let Some(cpal) = _font.cpal.as_ref() {
         let _ = writeln!(&mut writer, "{}", cpal.to_string());
     }
-    if let Some(colr) = _font.colr.as_ref() {
+    if let Some(colr) = _font.current_colr() {
         let _ = writeln!(&mut writer, "{}", colr.to_string());
     }
 
@@ -3667,10 +5114,14 @@ fn tag4(tag: &str) -> u32 {
     u32::from_be_bytes(bytes)
 }
 
-fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
-    match fontheader::get_font_type(file)? {
+fn woff_lazy_table<T>(data: &FontBytes) -> LazyTable<T> {
+    LazyTable::new(data.clone(), 0, data.len() as u32)
+}
+
+fn font_load(data: &FontBytes, font_type: fontheader::FontHeaders) -> Result<Font, Error> {
+    match font_type {
         fontheader::FontHeaders::OTF(header) => {
-            let font = from_opentype(file, &header);
+            let font = from_opentype(data, &header);
             #[cfg(debug_assertions)]
             {
                 // font_debug(font.as_ref().unwrap());
@@ -3678,24 +5129,18 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
             font
         }
         fontheader::FontHeaders::TTC(header) => {
-            let num_fonts = header.num_fonts;
             let font_collection = header.font_collection.as_ref();
-            let table = &font_collection[0];
-            let mut font = from_opentype(file, table);
+            let table = font_collection
+                .first()
+                .ok_or_else(|| Error::new(std::io::ErrorKind::InvalidData, "TTC has no fonts"))?;
+            let mut font = from_opentype(data, table);
             #[cfg(debug_assertions)]
             {
                 // font_debug(font.as_ref().unwrap());
             }
 
-            let mut fonts = Vec::new();
-            for i in 1..num_fonts {
-                let table = &font_collection[i as usize];
-                if let Ok(font) = from_opentype(file, table) {
-                    fonts.push(font);
-                }
-            }
             if let Ok(font) = font.as_mut() {
-                font.more_fonts = Box::new(fonts);
+                font.more_fonts = CollectionFaces::new(data, &font_collection[1..]);
                 #[cfg(debug_assertions)]
                 {
                     //    font_debug(font.as_ref().unwrap());
@@ -3706,14 +5151,23 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
         fontheader::FontHeaders::WOFF(header) => {
             let mut font = Font::empty();
             font.font_type = fontheader::FontHeaders::WOFF(header.clone());
-            let woff = crate::woff::WOFF::from(file, header)?;
+            let mut file = BytesReader::new(data.as_bytes());
+            file.set_endian(Endian::BigEndian);
+            // The table directory follows the 44-byte WOFF header.
+            file.seek(SeekFrom::Start(44))?;
+            let woff = crate::woff::WOFF::from(&mut file, header)?;
 
             let mut hmtx_table = None;
             let mut loca_table = None;
-            let mut glyf_table = None;
-            let mut sbix_table = None;
             let mut vmtx_table = None;
             for table in woff.tables {
+                let bytes = FontBytes::from(table.data);
+                font.raw_tables.push(RawTable::new(
+                    table.tag,
+                    bytes.clone(),
+                    0,
+                    bytes.len() as u32,
+                ));
                 let tag: [u8; 4] = [
                     (table.tag >> 24) as u8,
                     (table.tag >> 16) as u8,
@@ -3723,142 +5177,154 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
                 // println!("tag: {}", crate::util::u32_to_string(table.tag));
                 match &tag {
                     b"cmap" => {
-                        let mut reader = BytesReader::new(&table.data);
+                        let mut reader = BytesReader::new(bytes.as_bytes());
                         let cmap_encodings =
-                            CmapEncodings::new(&mut reader, 0, table.data.len() as u32)?;
+                            CmapEncodings::new(&mut reader, 0, bytes.len() as u32)?;
                         font.cmap = Some(cmap_encodings);
                     }
                     b"head" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let head = head::HEAD::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let head = head::HEAD::new(&mut reader, 0, bytes.len() as u32)?;
                         font.head = Some(head);
                     }
                     b"OS/2" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let os2 = os2::OS2::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let os2 = os2::OS2::new(&mut reader, 0, bytes.len() as u32)?;
                         font.os2 = Some(os2);
                     }
                     b"fvar" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let fvar = fvar::FVAR::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let fvar = fvar::FVAR::new(&mut reader, 0, bytes.len() as u32)?;
                         font.fvar = Some(fvar);
                     }
                     b"gvar" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let gvar = gvar::GVAR::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let gvar = gvar::GVAR::new(&mut reader, 0, bytes.len() as u32)?;
                         font.gvar = Some(gvar);
                     }
                     b"avar" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let avar = avar::AVAR::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let avar = avar::AVAR::new(&mut reader, 0, bytes.len() as u32)?;
                         font.avar = Some(avar);
                     }
                     b"hhea" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let hhea = hhea::HHEA::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let hhea = hhea::HHEA::new(&mut reader, 0, bytes.len() as u32)?;
                         font.hhea = Some(hhea);
                     }
                     b"maxp" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let maxp = maxp::MAXP::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let maxp = maxp::MAXP::new(&mut reader, 0, bytes.len() as u32)?;
                         font.maxp = Some(maxp);
                     }
                     b"hmtx" => {
-                        hmtx_table = Some(table);
+                        hmtx_table = Some(bytes);
                     }
                     b"name" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let name = name::NAME::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let name = name::NAME::new(&mut reader, 0, bytes.len() as u32)?;
                         let name_table = name::NameTable::new(&name);
                         font.name = Some(name);
                         font.name_table = Some(name_table);
                     }
                     b"post" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let post = post::POST::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let post = post::POST::new(&mut reader, 0, bytes.len() as u32)?;
                         font.post = Some(post);
                     }
                     b"loca" => {
-                        loca_table = Some(table);
+                        loca_table = Some(bytes);
                     }
                     b"glyf" => {
-                        glyf_table = Some(table);
+                        font.glyf = Some(woff_lazy_table(&bytes));
                     }
                     b"COLR" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let colr = colr::COLR::new(&mut reader, 0, table.data.len() as u32)?;
-                        font.colr = Some(colr);
+                        font.colr = Some(woff_lazy_table(&bytes));
                     }
                     b"HVAR" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let hvar = hvar::HVAR::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let hvar = hvar::HVAR::new(&mut reader, 0, bytes.len() as u32)?;
                         font.hvar = Some(hvar);
                     }
                     b"MVAR" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let mvar = mvar::MVAR::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let mvar = mvar::MVAR::new(&mut reader, 0, bytes.len() as u32)?;
                         font.mvar = Some(mvar);
                     }
+                    b"STAT" => {
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        font.stat = stat::STAT::new(&mut reader, 0, bytes.len() as u32).ok();
+                    }
                     b"CPAL" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let cpal = cpal::CPAL::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let cpal = cpal::CPAL::new(&mut reader, 0, bytes.len() as u32)?;
                         font.cpal = Some(cpal);
                     }
                     b"sbix" => {
-                        sbix_table = Some(table);
+                        font.sbix = Some(woff_lazy_table(&bytes));
+                    }
+                    b"CBLC" => {
+                        font.cblc = Some(woff_lazy_table(&bytes));
+                    }
+                    b"CBDT" => {
+                        font.cbdt = Some(woff_lazy_table(&bytes));
+                    }
+                    b"EBLC" => {
+                        font.eblc = Some(woff_lazy_table(&bytes));
+                    }
+                    b"EBDT" => {
+                        font.ebdt = Some(woff_lazy_table(&bytes));
+                    }
+                    b"bloc" if font.eblc.is_none() => {
+                        font.eblc = Some(woff_lazy_table(&bytes));
+                    }
+                    b"bdat" if font.ebdt.is_none() => {
+                        font.ebdt = Some(woff_lazy_table(&bytes));
                     }
                     b"SVG " => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let svg = svg::SVG::new(&mut reader, 0, table.data.len() as u32)?;
-                        font.svg = Some(svg);
+                        font.svg = Some(woff_lazy_table(&bytes));
                     }
                     #[cfg(feature = "cff")]
                     b"CFF " => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let cff = cff::CFF::new(&mut reader, 0, table.data.len() as u32).map_err(
-                            |err| Error::new(std::io::ErrorKind::InvalidData, err.to_string()),
-                        )?;
-                        font.cff = Some(cff);
+                        font.cff = Some(woff_lazy_table(&bytes));
                         font.outline_format = GlyphFormat::CFF;
                     }
                     #[cfg(feature = "cff")]
                     b"CFF2" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let cff = cff::CFF::new(&mut reader, 0, table.data.len() as u32).map_err(
-                            |err| Error::new(std::io::ErrorKind::InvalidData, err.to_string()),
-                        )?;
-                        font.cff = Some(cff);
+                        font.cff = Some(woff_lazy_table(&bytes));
                         font.outline_format = GlyphFormat::CFF2;
                     }
                     #[cfg(feature = "layout")]
                     b"GPOS" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let gpos = gpos::GPOS::new(&mut reader, 0, table.data.len() as u32)?;
-                        font.gpos = Some(gpos);
+                        font.gpos = Some(woff_lazy_table(&bytes));
                     }
                     #[cfg(feature = "layout")]
                     b"GSUB" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let gsub = gsub::GSUB::new(&mut reader, 0, table.data.len() as u32)?;
-                        font.gsub = Some(gsub);
+                        font.gsub = Some(woff_lazy_table(&bytes));
                     }
                     #[cfg(feature = "layout")]
                     b"GDEF" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let gdef = gdef::GDEF::new(&mut reader, 0, table.data.len() as usize)?;
-                        font.gdef = Some(gdef);
+                        font.gdef = Some(woff_lazy_table(&bytes));
+                    }
+                    #[cfg(feature = "layout")]
+                    b"BASE" => {
+                        font.base = Some(woff_lazy_table(&bytes));
+                    }
+                    #[cfg(feature = "layout")]
+                    b"MATH" => {
+                        font.math = Some(woff_lazy_table(&bytes));
                     }
                     b"vhea" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let vhea = vhea::VHEA::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let vhea = vhea::VHEA::new(&mut reader, 0, bytes.len() as u32)?;
                         font.vhea = Some(vhea);
                     }
                     b"vmtx" => {
-                        vmtx_table = Some(table);
+                        vmtx_table = Some(bytes);
                     }
                     b"VVAR" => {
-                        let mut reader = BytesReader::new(&table.data);
-                        let vvar = vvar::VVAR::new(&mut reader, 0, table.data.len() as u32)?;
+                        let mut reader = BytesReader::new(bytes.as_bytes());
+                        let vvar = vvar::VVAR::new(&mut reader, 0, bytes.len() as u32)?;
                         font.vvar = Some(vvar);
                     }
                     _ => {}
@@ -3875,17 +5341,17 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
                 .maxp
                 .as_ref()
                 .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "No maxp table"))?;
-            let mut reader = BytesReader::new(&hmtx_table.data);
+            let mut reader = BytesReader::new(hmtx_table.as_bytes());
             let hmtx = hmtx::HMTX::new(
                 &mut reader,
                 0,
-                hmtx_table.data.len() as u32,
+                hmtx_table.len() as u32,
                 hhea.number_of_hmetrics,
                 maxp.num_glyphs,
             )?;
             font.hmtx = Some(hmtx);
             if let Some(vmtx_table) = vmtx_table {
-                let mut reader = BytesReader::new(&vmtx_table.data);
+                let mut reader = BytesReader::new(vmtx_table.as_bytes());
                 let vhea = font
                     .vhea
                     .as_ref()
@@ -3893,15 +5359,14 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
                 let vmtx = vmtx::VMTX::new(
                     &mut reader,
                     0,
-                    vmtx_table.data.len() as u32,
+                    vmtx_table.len() as u32,
                     vhea.number_of_vmetrics,
                     maxp.num_glyphs,
                 )?;
                 font.vmtx = Some(vmtx);
             }
-            if let (Some(loca_table), Some(glyf_table)) = (loca_table.as_ref(), glyf_table.as_ref())
-            {
-                let mut reader = BytesReader::new(&loca_table.data);
+            if let Some(loca_table) = loca_table.filter(|_| font.glyf.is_some()) {
+                let mut reader = BytesReader::new(loca_table.as_bytes());
                 let head = font
                     .head
                     .as_ref()
@@ -3910,27 +5375,12 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
                 let loca = loca::LOCA::new_by_size(
                     &mut reader,
                     0,
-                    loca_table.data.len() as u32,
+                    loca_table.len() as u32,
                     index_to_loc_format,
                 )?;
                 font.loca = Some(loca);
-                let mut reader = BytesReader::new(&glyf_table.data);
-                let loca = font
-                    .loca
-                    .as_ref()
-                    .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "No loca table"))?;
-                let glyf = glyf::GLYF::new(&mut reader, 0, glyf_table.data.len() as u32, loca);
-                font.glyf = Some(glyf);
                 font.outline_format = GlyphFormat::OpenTypeGlyph;
             }
-
-            if let Some(sbix_table) = sbix_table {
-                let mut reader = BytesReader::new(&sbix_table.data);
-                let num_glyphs = maxp.num_glyphs as u32;
-                let sbix =
-                    sbix::SBIX::new(&mut reader, 0, sbix_table.data.len() as u32, num_glyphs)?;
-                font.sbix = Some(sbix);
-            }
             #[cfg(debug_assertions)]
             {
                 // font_debug(&font);
@@ -3948,7 +5398,7 @@ fn font_load<R: BinaryReader>(file: &mut R) -> Result<Font, Error> {
     }
 }
 
-fn from_opentype<R: BinaryReader>(file: &mut R, header: &OTFHeader) -> Result<Font, Error> {
+fn from_opentype(data: &FontBytes, header: &OTFHeader) -> Result<Font, Error> {
     let mut font = Font::empty();
     font.font_type = fontheader::FontHeaders::OTF(header.clone());
 
@@ -3956,149 +5406,162 @@ fn from_opentype<R: BinaryReader>(file: &mut R, header: &OTFHeader) -> Result<Fo
 
     for record in records.iter() {
         let tag: [u8; 4] = record.table_tag.to_be_bytes();
+        let length = record.length;
+        font.raw_tables.push(RawTable::new(
+            record.table_tag,
+            data.clone(),
+            record.offset,
+            length,
+        ));
+        let table = || data.table_reader(record.offset, length);
         match &tag {
             b"cmap" => {
-                let cmap_encodings = CmapEncodings::new(file, record.offset, record.length)?;
+                let cmap_encodings = CmapEncodings::new(&mut table()?, 0, length)?;
                 font.cmap = Some(cmap_encodings);
             }
             b"head" => {
-                let head = head::HEAD::new(file, record.offset, record.length)?;
+                let head = head::HEAD::new(&mut table()?, 0, length)?;
                 font.head = Some(head);
             }
             b"fvar" => {
-                let fvar = fvar::FVAR::new(file, record.offset, record.length)?;
+                let fvar = fvar::FVAR::new(&mut table()?, 0, length)?;
                 font.fvar = Some(fvar);
             }
             b"gvar" => {
-                let gvar = gvar::GVAR::new(file, record.offset, record.length)?;
+                let gvar = gvar::GVAR::new(&mut table()?, 0, length)?;
                 font.gvar = Some(gvar);
             }
             b"avar" => {
-                let avar = avar::AVAR::new(file, record.offset, record.length)?;
+                let avar = avar::AVAR::new(&mut table()?, 0, length)?;
                 font.avar = Some(avar);
             }
             b"hhea" => {
-                let hhea = hhea::HHEA::new(file, record.offset, record.length)?;
+                let hhea = hhea::HHEA::new(&mut table()?, 0, length)?;
                 font.hhea = Some(hhea);
             }
             b"hmtx" => {
                 let htmx_pos = Pointer {
                     offset: record.offset,
-                    length: record.length,
+                    length,
                 };
                 font.hmtx_pos = Some(htmx_pos);
             }
             b"maxp" => {
-                let maxp = maxp::MAXP::new(file, record.offset, record.length)?;
+                let maxp = maxp::MAXP::new(&mut table()?, 0, length)?;
                 font.maxp = Some(maxp);
             }
             b"name" => {
-                let name = name::NAME::new(file, record.offset, record.length)?;
+                let name = name::NAME::new(&mut table()?, 0, length)?;
                 let name_table = name::NameTable::new(&name);
                 font.name = Some(name);
                 font.name_table = Some(name_table);
             }
             b"OS/2" => {
-                let os2 = os2::OS2::new(file, record.offset, record.length)?;
+                let os2 = os2::OS2::new(&mut table()?, 0, length)?;
                 font.os2 = Some(os2);
             }
             b"post" => {
-                let post = post::POST::new(file, record.offset, record.length)?;
+                let post = post::POST::new(&mut table()?, 0, length)?;
                 font.post = Some(post);
             }
             b"loca" => {
                 let loca_pos = Pointer {
                     offset: record.offset,
-                    length: record.length,
+                    length,
                 };
                 font.loca_pos = Some(loca_pos);
             }
             b"glyf" => {
-                let glyf_pos = Pointer {
-                    offset: record.offset,
-                    length: record.length,
-                };
-                font.glyf_pos = Some(glyf_pos);
+                font.glyf = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             b"COLR" => {
-                let colr = colr::COLR::new(file, record.offset, record.length)?;
-                font.colr = Some(colr);
+                font.colr = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             b"HVAR" => {
-                let hvar = hvar::HVAR::new(file, record.offset, record.length)?;
+                let hvar = hvar::HVAR::new(&mut table()?, 0, length)?;
                 font.hvar = Some(hvar);
             }
             b"MVAR" => {
-                let mvar = mvar::MVAR::new(file, record.offset, record.length)?;
+                let mvar = mvar::MVAR::new(&mut table()?, 0, length)?;
                 font.mvar = Some(mvar);
             }
+            b"STAT" => {
+                // STAT only affects naming, so a malformed table is ignored.
+                font.stat = stat::STAT::new(&mut table()?, 0, length).ok();
+            }
             b"CPAL" => {
-                let cpal = cpal::CPAL::new(file, record.offset, record.length)?;
+                let cpal = cpal::CPAL::new(&mut table()?, 0, length)?;
                 font.cpal = Some(cpal);
             }
             b"sbix" => {
-                let sbix_pos = Pointer {
-                    offset: record.offset,
-                    length: record.length,
-                };
-                font.sbix_pos = Some(sbix_pos);
+                font.sbix = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            b"CBLC" => {
+                font.cblc = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            b"CBDT" => {
+                font.cbdt = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            b"EBLC" => {
+                font.eblc = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            b"EBDT" => {
+                font.ebdt = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            b"bloc" if font.eblc.is_none() => {
+                font.eblc = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            b"bdat" if font.ebdt.is_none() => {
+                font.ebdt = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             b"SVG " => {
-                let svg = svg::SVG::new(file, record.offset, record.length)?;
-                font.svg = Some(svg);
+                font.svg = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             #[cfg(feature = "cff")]
             b"CFF " => {
-                let cff = cff::CFF::new(file, record.offset, record.length)
-                    .map_err(|err| Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
-                font.cff = Some(cff);
+                font.cff = Some(LazyTable::new(data.clone(), record.offset, length));
                 font.outline_format = GlyphFormat::CFF;
             }
             #[cfg(feature = "cff")]
             b"CFF2" => {
-                let cff = cff::CFF::new(file, record.offset, record.length)
-                    .map_err(|err| Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
-                font.cff = Some(cff);
+                font.cff = Some(LazyTable::new(data.clone(), record.offset, length));
                 font.outline_format = GlyphFormat::CFF2;
             }
             #[cfg(feature = "layout")]
             b"GPOS" => {
-                let gpos = gpos::GPOS::new(file, record.offset, record.length)?;
-                font.gpos = Some(gpos);
+                font.gpos = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             #[cfg(feature = "layout")]
             b"GSUB" => {
-                let gsub = gsub::GSUB::new(file, record.offset, record.length)?;
-                font.gsub = Some(gsub);
-                #[cfg(debug_assertions)]
-                {
-                    // println!("{}", &font.gsub.as_ref().unwrap().to_string());
-                }
+                font.gsub = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             #[cfg(feature = "layout")]
             b"GDEF" => {
-                let gdef = gdef::GDEF::new(file, record.offset as u64, record.length as usize)?;
-                font.gdef = Some(gdef);
-                #[cfg(debug_assertions)]
-                {
-                    // println!("{}", &font.gdef.as_ref().unwrap().to_string());
-                }
+                font.gdef = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            #[cfg(feature = "layout")]
+            b"BASE" => {
+                font.base = Some(LazyTable::new(data.clone(), record.offset, length));
+            }
+            #[cfg(feature = "layout")]
+            b"MATH" => {
+                font.math = Some(LazyTable::new(data.clone(), record.offset, length));
             }
             #[cfg(feature = "layout")]
             b"vhea" => {
-                let vhea = vhea::VHEA::new(file, record.offset, record.length)?;
+                let vhea = vhea::VHEA::new(&mut table()?, 0, length)?;
                 font.vhea = Some(vhea);
             }
             #[cfg(feature = "layout")]
             b"vmtx" => {
                 let vmtx_pos = Pointer {
                     offset: record.offset,
-                    length: record.length,
+                    length,
                 };
                 font.vmtx_pos = Some(vmtx_pos);
             }
             b"VVAR" => {
-                let vvar = vvar::VVAR::new(file, record.offset, record.length)?;
+                let vvar = vvar::VVAR::new(&mut table()?, 0, length)?;
                 font.vvar = Some(vvar);
             }
             _ => {
@@ -4124,7 +5587,8 @@ fn from_opentype<R: BinaryReader>(file: &mut R, header: &OTFHeader) -> Result<Fo
     let offset = hmtx_pointer.offset;
     let length = hmtx_pointer.length;
 
-    let hmtx = hmtx::HMTX::new(file, offset, length, number_of_hmetrics, num_glyphs)?;
+    let mut reader = data.table_reader(offset, length)?;
+    let hmtx = hmtx::HMTX::new(&mut reader, 0, length, number_of_hmetrics, num_glyphs)?;
     font.hmtx = Some(hmtx);
 
     if font.vmtx_pos.is_some() {
@@ -4139,7 +5603,8 @@ fn from_opentype<R: BinaryReader>(file: &mut R, header: &OTFHeader) -> Result<Fo
             .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "No vmtx table pointer"))?;
         let offset = vmtx_pointer.offset;
         let length = vmtx_pointer.length;
-        let vmtx = vmtx::VMTX::new(file, offset, length, number_of_vmetrics, num_glyphs)?;
+        let mut reader = data.table_reader(offset, length)?;
+        let vmtx = vmtx::VMTX::new(&mut reader, 0, length, number_of_vmetrics, num_glyphs)?;
         font.vmtx = Some(vmtx);
     }
 
@@ -4150,26 +5615,17 @@ fn from_opentype<R: BinaryReader>(file: &mut R, header: &OTFHeader) -> Result<Fo
             .as_ref()
             .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "No head table"))?
             .index_to_loc_format as usize;
-        let loca = loca::LOCA::new_by_size(file, offset.offset, length, index_to_loc_format)?;
+        let mut reader = data.table_reader(offset.offset, length)?;
+        let loca = loca::LOCA::new_by_size(&mut reader, 0, length, index_to_loc_format)?;
         font.loca = Some(loca);
-        let glyf_pointer = font
-            .glyf_pos
-            .as_ref()
-            .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "No glyf table pointer"))?;
-        let offset = glyf_pointer.offset;
-        let length = glyf_pointer.length;
-        let loca = font
-            .loca
-            .as_ref()
-            .ok_or_else(|| Error::new(std::io::ErrorKind::Other, "No loca table"))?;
-        let glyf = glyf::GLYF::new(file, offset, length, loca);
-        font.glyf = Some(glyf);
+        if font.glyf.is_none() {
+            return Err(Error::new(
+                std::io::ErrorKind::Other,
+                "No glyf table pointer",
+            ));
+        }
         font.outline_format = GlyphFormat::OpenTypeGlyph;
     }
-    if let Some(offset) = font.sbix_pos.as_ref() {
-        let sbix = sbix::SBIX::new(file, offset.offset, offset.length, num_glyphs as u32)?;
-        font.sbix = Some(sbix);
-    }
 
     if font.cmap.is_none() {
         debug_assert!(true, "No cmap table");
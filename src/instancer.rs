@@ -0,0 +1,1566 @@
+//! Static instances cut from a variable font at fixed axis values.
+//!
+//! Outlines and metrics are resolved through the same gvar / HVAR / VVAR /
+//! CFF2 blend code used for rendering, then written out as a plain sfnt.
+//! Composite glyphs are flattened and hinting is dropped, since neither the
+//! instructions nor `cvar` can be varied here. GPOS and GDEF device deltas
+//! are added to their values and the GDEF ItemVariationStore is removed.
+
+#[cfg(feature = "cff")]
+use crate::commands::Command;
+use crate::commands::FontVariationSetting;
+use crate::fontreader::Font;
+use crate::opentype::outline::glyf::ParsedGlyph;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+/// Outline and metrics of one glyph at the instance coordinates.
+pub(crate) struct InstanceGlyph {
+    pub(crate) outline: InstanceOutline,
+    pub(crate) advance_width: isize,
+    pub(crate) lsb: isize,
+    /// Advance height and top side bearing when the font has `vhea`.
+    pub(crate) vertical: Option<(isize, isize)>,
+}
+
+pub(crate) enum InstanceOutline {
+    Empty,
+    /// Flattened glyf points, with coordinates stored as deltas.
+    TrueType(ParsedGlyph),
+    /// CFF / CFF2 outline in font units, y up.
+    #[cfg(feature = "cff")]
+    Cubic(Vec<Command>),
+}
+
+/// Names written into the instance's `name` table.
+pub(crate) struct InstanceNames {
+    /// Typographic family of the variable font.
+    pub(crate) family: String,
+    /// Style of the instance, e.g. "SemiBold Condensed".
+    pub(crate) style: String,
+    /// PostScript name of a matching named instance.
+    pub(crate) postscript_name: Option<String>,
+}
+
+/// Tables that only hold variation data or hinting for the default instance.
+const DROPPED_TABLES: [&[u8; 4]; 14] = [
+    b"fvar", b"gvar", b"avar", b"cvar", b"HVAR", b"VVAR", b"MVAR", b"hdmx", b"LTSH", b"VDMX",
+    b"fpgm", b"prep", b"cvt ", b"DSIG",
+];
+
+/// Writes the current face of `font` as a static font. `user` holds one value
+/// per `fvar` axis and `coordinates` the matching normalized values.
+pub(crate) fn instantiate(
+    font: &Font,
+    user: &[FontVariationSetting],
+    coordinates: &[f32],
+    names: &InstanceNames,
+) -> Result<Vec<u8>, Error> {
+    let mut tables: BTreeMap<[u8; 4], Vec<u8>> = font
+        .current()
+        .raw_tables
+        .iter()
+        .map(|table| (table.tag.to_be_bytes(), table.bytes().to_vec()))
+        .collect();
+    for tag in DROPPED_TABLES {
+        tables.remove(tag);
+    }
+
+    let num_glyphs = tables
+        .get(b"maxp")
+        .and_then(|maxp| read_u16(maxp, 4))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "maxp is missing"))?
+        as usize;
+    let glyph_ids = (0..num_glyphs).collect::<Vec<_>>();
+    let glyphs = crate::util::parallel_map(&glyph_ids, |glyph_id| {
+        font.instance_glyph(*glyph_id, coordinates)
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>, Error>>()?;
+    let bounds = glyphs
+        .iter()
+        .map(|glyph| match &glyph.outline {
+            InstanceOutline::Empty => None,
+            InstanceOutline::TrueType(parsed) => points_bounds(parsed),
+            #[cfg(feature = "cff")]
+            InstanceOutline::Cubic(commands) => commands_bounds(commands),
+        })
+        .collect::<Vec<_>>();
+
+    let is_truetype = tables.contains_key(b"glyf");
+    if is_truetype {
+        let (glyf, loca) = encode_glyf(&glyphs, &bounds);
+        tables.insert(*b"glyf", glyf);
+        tables.insert(*b"loca", loca);
+        if let Some(maxp) = tables.get_mut(b"maxp") {
+            update_maxp(maxp, &glyphs);
+        }
+    } else if tables.contains_key(b"CFF2") {
+        #[cfg(not(feature = "cff"))]
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "CFF2 instances need the cff feature",
+        ));
+        #[cfg(feature = "cff")]
+        {
+            let charstrings = glyphs
+                .iter()
+                .map(|glyph| match &glyph.outline {
+                    InstanceOutline::Cubic(commands) => encode_charstring(commands),
+                    _ => Vec::new(),
+                })
+                .collect::<Vec<_>>();
+            tables.insert(*b"CFF2", encode_cff2(&charstrings));
+        }
+    } else if !tables.contains_key(b"CFF ") {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "static instances need glyf or CFF outlines",
+        ));
+    }
+
+    let mvar = font.current_mvar().filter(|_| !coordinates.is_empty());
+    let metric = |tag: &[u8; 4]| {
+        mvar.and_then(|mvar| mvar.metric_offset(u32::from_be_bytes(*tag), coordinates))
+            .map_or(0, |delta| delta.round() as i32)
+    };
+
+    let lsbs = glyphs
+        .iter()
+        .zip(&bounds)
+        .map(|(glyph, bounds)| bounds.map_or(glyph.lsb as i32, |(x_min, ..)| x_min))
+        .collect::<Vec<_>>();
+    let mut hmtx = Vec::with_capacity(num_glyphs * 4);
+    for (glyph, lsb) in glyphs.iter().zip(&lsbs) {
+        push_u16(
+            &mut hmtx,
+            glyph.advance_width.clamp(0, u16::MAX as isize) as u16,
+        );
+        push_i16(&mut hmtx, clamp_i16(*lsb));
+    }
+    tables.insert(*b"hmtx", hmtx);
+    if let Some(hhea) = tables.get_mut(b"hhea") {
+        update_hhea(hhea, &glyphs, &bounds, &lsbs, &metric);
+    }
+
+    let vertical = glyphs
+        .iter()
+        .map(|glyph| glyph.vertical)
+        .collect::<Option<Vec<_>>>();
+    match vertical {
+        Some(vertical) if tables.contains_key(b"vhea") => {
+            let mut vmtx = Vec::with_capacity(num_glyphs * 4);
+            for (advance, tsb) in &vertical {
+                push_u16(&mut vmtx, (*advance).clamp(0, u16::MAX as isize) as u16);
+                push_i16(&mut vmtx, clamp_i16(*tsb as i32));
+            }
+            tables.insert(*b"vmtx", vmtx);
+            if let Some(vhea) = tables.get_mut(b"vhea") {
+                update_vhea(vhea, &vertical, &bounds, &metric);
+            }
+        }
+        _ => {
+            tables.remove(b"vhea");
+            tables.remove(b"vmtx");
+        }
+    }
+
+    if let Some(os2) = tables.get_mut(b"OS/2") {
+        update_os2(os2, user, &glyphs, &metric);
+    }
+    if let Some(post) = tables.get_mut(b"post") {
+        add_i16(post, 8, metric(b"undo"));
+        add_i16(post, 10, metric(b"unds"));
+    }
+    if let Some(head) = tables.get_mut(b"head") {
+        update_head(head, &bounds, is_truetype);
+    }
+    #[cfg(feature = "layout")]
+    bake_layout_deltas(font, &mut tables, coordinates);
+    if let Some(name) = tables.get(b"name").and_then(|name| rename(name, names)) {
+        tables.insert(*b"name", name);
+    }
+    for tag in [b"GSUB", b"GPOS"] {
+        if let Some(table) = tables.get(tag) {
+            let resolved = resolve_feature_variations(table, coordinates)?;
+            tables.insert(*tag, resolved);
+        }
+    }
+
+    let sfnt_version = if is_truetype {
+        0x0001_0000
+    } else {
+        u32::from_be_bytes(*b"OTTO")
+    };
+    Ok(write_sfnt(sfnt_version, &tables))
+}
+
+/// Picks the first matching `FeatureVariations` record, swaps in its
+/// alternate feature tables and writes the table as version 1.0.
+///
+/// The new FeatureList is placed in front of an untouched copy of the original
+/// table, so script and lookup offsets only move by the prefix length.
+pub(crate) fn resolve_feature_variations(
+    table: &[u8],
+    coordinates: &[f32],
+) -> Result<Vec<u8>, Error> {
+    let truncated = || Error::new(ErrorKind::InvalidData, "layout table is truncated");
+    let minor_version = read_u16(table, 2).ok_or_else(truncated)?;
+    let feature_variations = if minor_version >= 1 {
+        read_u32(table, 10).ok_or_else(truncated)? as usize
+    } else {
+        0
+    };
+    if feature_variations == 0 {
+        return Ok(table.to_vec());
+    }
+    let script_list = read_u16(table, 4).ok_or_else(truncated)? as usize;
+    let feature_list = read_u16(table, 6).ok_or_else(truncated)? as usize;
+    let lookup_list = read_u16(table, 8).ok_or_else(truncated)? as usize;
+
+    let feature_count = read_u16(table, feature_list).ok_or_else(truncated)? as usize;
+    let mut features = Vec::with_capacity(feature_count);
+    for index in 0..feature_count {
+        let record = feature_list + 2 + index * 6;
+        let tag = table.get(record..record + 4).ok_or_else(truncated)?;
+        let offset = read_u16(table, record + 4).ok_or_else(truncated)? as usize;
+        features.push((tag.to_vec(), feature_list + offset));
+    }
+
+    if let Some(substitution) = matching_substitution(table, feature_variations, coordinates) {
+        let count = read_u16(table, substitution + 4).ok_or_else(truncated)? as usize;
+        for index in 0..count {
+            let record = substitution + 6 + index * 6;
+            let feature_index = read_u16(table, record).ok_or_else(truncated)? as usize;
+            let alternate = read_u32(table, record + 2).ok_or_else(truncated)? as usize;
+            if let Some(feature) = features.get_mut(feature_index) {
+                feature.1 = substitution + alternate;
+            }
+        }
+    }
+
+    let mut feature_tables = Vec::with_capacity(features.len());
+    for (_, offset) in &features {
+        let lookup_count = read_u16(table, offset + 2).ok_or_else(truncated)? as usize;
+        let size = 4 + lookup_count * 2;
+        feature_tables.push(table.get(*offset..offset + size).ok_or_else(truncated)?);
+    }
+    let list_header = 2 + features.len() * 6;
+    let prefix = 10 + list_header + feature_tables.iter().map(|t| t.len()).sum::<usize>();
+    let relocated = |offset: usize| {
+        u16::try_from(prefix + offset).map_err(|_| {
+            Error::new(
+                ErrorKind::Unsupported,
+                "layout table is too large to resolve FeatureVariations",
+            )
+        })
+    };
+
+    let mut out = Vec::with_capacity(prefix + table.len());
+    push_u16(&mut out, 1);
+    push_u16(&mut out, 0);
+    push_u16(&mut out, relocated(script_list)?);
+    push_u16(&mut out, 10);
+    push_u16(&mut out, relocated(lookup_list)?);
+    push_u16(&mut out, features.len() as u16);
+    let mut feature_offset = list_header;
+    for ((tag, _), feature_table) in features.iter().zip(&feature_tables) {
+        out.extend_from_slice(tag);
+        push_u16(&mut out, feature_offset as u16);
+        feature_offset += feature_table.len();
+    }
+    for ((_, offset), feature_table) in features.iter().zip(&feature_tables) {
+        let position = out.len();
+        // FeatureParams stay in the copied original; drop them if out of reach.
+        let params = read_u16(feature_table, 0).unwrap_or(0) as usize;
+        let params = if params == 0 {
+            0
+        } else {
+            u16::try_from(prefix + offset + params - position).unwrap_or(0)
+        };
+        push_u16(&mut out, params);
+        out.extend_from_slice(&feature_table[2..]);
+    }
+    out.extend_from_slice(table);
+    Ok(out)
+}
+
+/// Absolute offset of the FeatureTableSubstitution of the first record whose
+/// condition set matches `coordinates`.
+fn matching_substitution(table: &[u8], base: usize, coordinates: &[f32]) -> Option<usize> {
+    let record_count = read_u32(table, base + 4)? as usize;
+    (0..record_count).find_map(|index| {
+        let record = base + 8 + index * 8;
+        let condition_set = read_u32(table, record)? as usize;
+        let substitution = read_u32(table, record + 4)? as usize;
+        let matches =
+            condition_set == 0 || condition_set_matches(table, base + condition_set, coordinates)?;
+        (matches && substitution != 0).then_some(base + substitution)
+    })
+}
+
+fn condition_set_matches(table: &[u8], base: usize, coordinates: &[f32]) -> Option<bool> {
+    let count = read_u16(table, base)? as usize;
+    for index in 0..count {
+        let condition = base + read_u32(table, base + 2 + index * 4)? as usize;
+        // Only format 1 (axis range) is defined; other formats never match.
+        if read_u16(table, condition)? != 1 {
+            return Some(false);
+        }
+        let axis_index = read_u16(table, condition + 2)? as usize;
+        let min = f2dot14(read_u16(table, condition + 4)?);
+        let max = f2dot14(read_u16(table, condition + 6)?);
+        let value = coordinates.get(axis_index).copied().unwrap_or(0.0);
+        if value < min || value > max {
+            return Some(false);
+        }
+    }
+    Some(true)
+}
+
+fn encode_glyf(
+    glyphs: &[InstanceGlyph],
+    bounds: &[Option<(i32, i32, i32, i32)>],
+) -> (Vec<u8>, Vec<u8>) {
+    let mut glyf = Vec::new();
+    let mut loca = Vec::with_capacity((glyphs.len() + 1) * 4);
+    for (glyph, bounds) in glyphs.iter().zip(bounds) {
+        push_u32(&mut loca, glyf.len() as u32);
+        if let (InstanceOutline::TrueType(parsed), Some(bounds)) = (&glyph.outline, bounds) {
+            encode_simple_glyph(&mut glyf, parsed, *bounds);
+            while glyf.len() % 4 != 0 {
+                glyf.push(0);
+            }
+        }
+    }
+    push_u32(&mut loca, glyf.len() as u32);
+    (glyf, loca)
+}
+
+fn encode_simple_glyph(out: &mut Vec<u8>, parsed: &ParsedGlyph, bounds: (i32, i32, i32, i32)) {
+    let (x_min, y_min, x_max, y_max) = bounds;
+    push_i16(out, parsed.end_pts_of_contours.len() as i16);
+    for value in [x_min, y_min, x_max, y_max] {
+        push_i16(out, clamp_i16(value));
+    }
+    for end in &parsed.end_pts_of_contours {
+        push_u16(out, *end as u16);
+    }
+    push_u16(out, 0);
+
+    let overlap = parsed.flags.first().map_or(0, |flag| flag & 0x40);
+    let mut flags = Vec::with_capacity(parsed.xs.len());
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    for (index, (dx, dy)) in parsed.xs.iter().zip(&parsed.ys).enumerate() {
+        let mut flag = u8::from(parsed.on_curves.get(index).copied().unwrap_or(true));
+        flag |= encode_coordinate(&mut xs, *dx, 0x02, 0x10);
+        flag |= encode_coordinate(&mut ys, *dy, 0x04, 0x20);
+        if index == 0 {
+            flag |= overlap;
+        }
+        flags.push(flag);
+    }
+
+    let mut index = 0;
+    while index < flags.len() {
+        let flag = flags[index];
+        let mut repeat = 0usize;
+        while index + repeat + 1 < flags.len() && flags[index + repeat + 1] == flag && repeat < 255
+        {
+            repeat += 1;
+        }
+        if repeat > 0 {
+            out.push(flag | 0x08);
+            out.push(repeat as u8);
+        } else {
+            out.push(flag);
+        }
+        index += repeat + 1;
+    }
+    out.extend_from_slice(&xs);
+    out.extend_from_slice(&ys);
+}
+
+/// Writes one glyf coordinate delta and returns its flag bits.
+fn encode_coordinate(out: &mut Vec<u8>, delta: i16, short: u8, same_or_positive: u8) -> u8 {
+    if delta == 0 {
+        same_or_positive
+    } else if delta.unsigned_abs() <= 255 {
+        out.push(delta.unsigned_abs() as u8);
+        if delta > 0 {
+            short | same_or_positive
+        } else {
+            short
+        }
+    } else {
+        push_i16(out, delta);
+        0
+    }
+}
+
+fn update_maxp(maxp: &mut [u8], glyphs: &[InstanceGlyph]) {
+    if read_u32(maxp, 0) != Some(0x0001_0000) || maxp.len() < 32 {
+        return;
+    }
+    let (points, contours) = glyphs
+        .iter()
+        .filter_map(|glyph| match &glyph.outline {
+            InstanceOutline::TrueType(parsed) => {
+                Some((parsed.xs.len(), parsed.end_pts_of_contours.len()))
+            }
+            _ => None,
+        })
+        .fold((0, 0), |(points, contours), (p, c)| {
+            (points.max(p), contours.max(c))
+        });
+    write_u16(maxp, 6, points.min(u16::MAX as usize) as u16);
+    write_u16(maxp, 8, contours.min(u16::MAX as usize) as u16);
+    // Flattened composites and no hinting programs.
+    for offset in [10, 12, 20, 22, 26, 28, 30] {
+        write_u16(maxp, offset, 0);
+    }
+}
+
+fn update_hhea(
+    hhea: &mut [u8],
+    glyphs: &[InstanceGlyph],
+    bounds: &[Option<(i32, i32, i32, i32)>],
+    lsbs: &[i32],
+    metric: &dyn Fn(&[u8; 4]) -> i32,
+) {
+    let advance_max = glyphs.iter().map(|g| g.advance_width).max().unwrap_or(0);
+    let mut min_lsb = i32::MAX;
+    let mut min_rsb = i32::MAX;
+    let mut max_extent = i32::MIN;
+    for ((glyph, bounds), lsb) in glyphs.iter().zip(bounds).zip(lsbs) {
+        if let Some((x_min, _, x_max, _)) = bounds {
+            min_lsb = min_lsb.min(*lsb);
+            min_rsb = min_rsb.min(glyph.advance_width as i32 - lsb - (x_max - x_min));
+            max_extent = max_extent.max(lsb + (x_max - x_min));
+        }
+    }
+    if min_lsb == i32::MAX {
+        (min_lsb, min_rsb, max_extent) = (0, 0, 0);
+    }
+    write_u16(hhea, 10, advance_max.clamp(0, u16::MAX as isize) as u16);
+    write_i16(hhea, 12, clamp_i16(min_lsb));
+    write_i16(hhea, 14, clamp_i16(min_rsb));
+    write_i16(hhea, 16, clamp_i16(max_extent));
+    add_i16(hhea, 18, metric(b"hcrs"));
+    add_i16(hhea, 20, metric(b"hcrn"));
+    add_i16(hhea, 22, metric(b"hcof"));
+    write_u16(hhea, 34, glyphs.len() as u16);
+}
+
+fn update_vhea(
+    vhea: &mut [u8],
+    vertical: &[(isize, isize)],
+    bounds: &[Option<(i32, i32, i32, i32)>],
+    metric: &dyn Fn(&[u8; 4]) -> i32,
+) {
+    let advance_max = vertical
+        .iter()
+        .map(|(advance, _)| *advance)
+        .max()
+        .unwrap_or(0);
+    let mut min_tsb = i32::MAX;
+    let mut min_bsb = i32::MAX;
+    let mut max_extent = i32::MIN;
+    for ((advance, tsb), bounds) in vertical.iter().zip(bounds) {
+        if let Some((_, y_min, _, y_max)) = bounds {
+            let tsb = *tsb as i32;
+            min_tsb = min_tsb.min(tsb);
+            min_bsb = min_bsb.min(*advance as i32 - tsb - (y_max - y_min));
+            max_extent = max_extent.max(tsb + (y_max - y_min));
+        }
+    }
+    if min_tsb == i32::MAX {
+        (min_tsb, min_bsb, max_extent) = (0, 0, 0);
+    }
+    add_i16(vhea, 4, metric(b"vasc"));
+    add_i16(vhea, 6, metric(b"vdsc"));
+    add_i16(vhea, 8, metric(b"vlgp"));
+    write_u16(vhea, 10, advance_max.clamp(0, u16::MAX as isize) as u16);
+    write_i16(vhea, 12, clamp_i16(min_tsb));
+    write_i16(vhea, 14, clamp_i16(min_bsb));
+    write_i16(vhea, 16, clamp_i16(max_extent));
+    add_i16(vhea, 18, metric(b"vcrs"));
+    add_i16(vhea, 20, metric(b"vcrn"));
+    add_i16(vhea, 22, metric(b"vcof"));
+    write_u16(vhea, 34, vertical.len() as u16);
+}
+
+fn update_os2(
+    os2: &mut [u8],
+    user: &[FontVariationSetting],
+    glyphs: &[InstanceGlyph],
+    metric: &dyn Fn(&[u8; 4]) -> i32,
+) {
+    let advances = glyphs
+        .iter()
+        .map(|glyph| glyph.advance_width as i64)
+        .filter(|advance| *advance > 0)
+        .collect::<Vec<_>>();
+    if !advances.is_empty() {
+        let average = advances.iter().sum::<i64>() as f64 / advances.len() as f64;
+        write_i16(os2, 2, clamp_i16(average.round() as i32));
+    }
+    for setting in user {
+        match &setting.tag {
+            b"wght" => write_u16(os2, 4, setting.value.round().clamp(1.0, 1000.0) as u16),
+            b"wdth" => write_u16(os2, 6, width_class(setting.value)),
+            _ => {}
+        }
+    }
+    for (offset, tag) in [
+        (10, b"sbxs"),
+        (12, b"sbys"),
+        (14, b"sbxo"),
+        (16, b"sbyo"),
+        (18, b"spxs"),
+        (20, b"spys"),
+        (22, b"spxo"),
+        (24, b"spyo"),
+        (26, b"strs"),
+        (28, b"stro"),
+        (68, b"hasc"),
+        (70, b"hdsc"),
+        (72, b"hlgp"),
+        (86, b"xhgt"),
+        (88, b"cpht"),
+    ] {
+        add_i16(os2, offset, metric(tag));
+    }
+    for (offset, tag) in [(74, b"hcla"), (76, b"hcld")] {
+        if let Some(value) = read_u16(os2, offset) {
+            let value = (value as i32 + metric(tag)).clamp(0, u16::MAX as i32);
+            write_u16(os2, offset, value as u16);
+        }
+    }
+}
+
+/// Nearest OS/2 width class for a `wdth` percentage.
+fn width_class(width: f32) -> u16 {
+    const PERCENTAGES: [f32; 9] = [50.0, 62.5, 75.0, 87.5, 100.0, 112.5, 125.0, 150.0, 200.0];
+    PERCENTAGES
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - width).abs().total_cmp(&(*b - width).abs()))
+        .map_or(5, |(index, _)| index as u16 + 1)
+}
+
+fn update_head(head: &mut [u8], bounds: &[Option<(i32, i32, i32, i32)>], long_loca: bool) {
+    let mut union: Option<(i32, i32, i32, i32)> = None;
+    for (x_min, y_min, x_max, y_max) in bounds.iter().flatten() {
+        union = Some(match union {
+            Some((a, b, c, d)) => (a.min(*x_min), b.min(*y_min), c.max(*x_max), d.max(*y_max)),
+            None => (*x_min, *y_min, *x_max, *y_max),
+        });
+    }
+    let (x_min, y_min, x_max, y_max) = union.unwrap_or_default();
+    write_u32(head, 8, 0);
+    write_i16(head, 36, clamp_i16(x_min));
+    write_i16(head, 38, clamp_i16(y_min));
+    write_i16(head, 40, clamp_i16(x_max));
+    write_i16(head, 42, clamp_i16(y_max));
+    if long_loca {
+        write_u16(head, 50, 1);
+    }
+}
+
+fn points_bounds(parsed: &ParsedGlyph) -> Option<(i32, i32, i32, i32)> {
+    let mut x = 0i32;
+    let mut y = 0i32;
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for (dx, dy) in parsed.xs.iter().zip(&parsed.ys) {
+        x += *dx as i32;
+        y += *dy as i32;
+        bounds = Some(match bounds {
+            Some((a, b, c, d)) => (a.min(x), b.min(y), c.max(x), d.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+    bounds
+}
+
+/// Exact bounds of a CFF outline, including cubic extrema between points.
+#[cfg(feature = "cff")]
+fn commands_bounds(commands: &[Command]) -> Option<(i32, i32, i32, i32)> {
+    let mut bounds: Option<(f32, f32, f32, f32)> = None;
+    let mut include = |x: f32, y: f32| {
+        bounds = Some(match bounds {
+            Some((a, b, c, d)) => (a.min(x), b.min(y), c.max(x), d.max(y)),
+            None => (x, y, x, y),
+        });
+    };
+    let mut current = (0.0f32, 0.0f32);
+    for command in commands {
+        match *command {
+            Command::MoveTo(x, y) | Command::Line(x, y) => {
+                include(x, y);
+                current = (x, y);
+            }
+            Command::Bezier((cx, cy), (x, y)) => {
+                let ((xa, ya), (xb, yb)) = quad_to_cubic(current, (cx, cy), (x, y));
+                for t in cubic_extrema(current, (xa, ya), (xb, yb), (x, y)) {
+                    let (px, py) = cubic_point(current, (xa, ya), (xb, yb), (x, y), t);
+                    include(px, py);
+                }
+                include(x, y);
+                current = (x, y);
+            }
+            Command::CubicBezier(a, b, (x, y)) => {
+                for t in cubic_extrema(current, a, b, (x, y)) {
+                    let (px, py) = cubic_point(current, a, b, (x, y), t);
+                    include(px, py);
+                }
+                include(x, y);
+                current = (x, y);
+            }
+            Command::Close => {}
+        }
+    }
+    bounds.map(|(a, b, c, d)| {
+        (
+            a.floor() as i32,
+            b.floor() as i32,
+            c.ceil() as i32,
+            d.ceil() as i32,
+        )
+    })
+}
+
+/// Parameters in (0, 1) where either coordinate of a cubic has a turning point.
+#[cfg(feature = "cff")]
+fn cubic_extrema(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) -> Vec<f32> {
+    let mut roots = Vec::new();
+    for (a0, a1, a2, a3) in [(p0.0, p1.0, p2.0, p3.0), (p0.1, p1.1, p2.1, p3.1)] {
+        // Derivative: 3(a t^2 + b t + c) with the coefficients below.
+        let a = -a0 + 3.0 * a1 - 3.0 * a2 + a3;
+        let b = 2.0 * (a0 - 2.0 * a1 + a2);
+        let c = a1 - a0;
+        if a.abs() < 1e-6 {
+            if b.abs() > 1e-6 {
+                roots.push(-c / b);
+            }
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant >= 0.0 {
+                let sqrt = discriminant.sqrt();
+                roots.push((-b + sqrt) / (2.0 * a));
+                roots.push((-b - sqrt) / (2.0 * a));
+            }
+        }
+    }
+    roots.retain(|t| *t > 0.0 && *t < 1.0);
+    roots
+}
+
+#[cfg(feature = "cff")]
+fn cubic_point(
+    p0: (f32, f32),
+    p1: (f32, f32),
+    p2: (f32, f32),
+    p3: (f32, f32),
+    t: f32,
+) -> (f32, f32) {
+    let mt = 1.0 - t;
+    let (w0, w1, w2, w3) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+    (
+        w0 * p0.0 + w1 * p1.0 + w2 * p2.0 + w3 * p3.0,
+        w0 * p0.1 + w1 * p1.1 + w2 * p2.1 + w3 * p3.1,
+    )
+}
+
+#[cfg(feature = "cff")]
+fn quad_to_cubic(
+    start: (f32, f32),
+    control: (f32, f32),
+    end: (f32, f32),
+) -> ((f32, f32), (f32, f32)) {
+    (
+        (
+            start.0 + (control.0 - start.0) * 2.0 / 3.0,
+            start.1 + (control.1 - start.1) * 2.0 / 3.0,
+        ),
+        (
+            end.0 + (control.0 - end.0) * 2.0 / 3.0,
+            end.1 + (control.1 - end.1) * 2.0 / 3.0,
+        ),
+    )
+}
+
+/// Encodes a CFF2 charstring with rmoveto / rlineto / rrcurveto on whole units.
+#[cfg(feature = "cff")]
+fn encode_charstring(commands: &[Command]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut current = (0i32, 0i32);
+    let mut float_current = (0.0f32, 0.0f32);
+    let point = |out: &mut Vec<u8>, current: &mut (i32, i32), (x, y): (f32, f32)| {
+        let (x, y) = (x.round() as i32, y.round() as i32);
+        push_charstring_number(out, x - current.0);
+        push_charstring_number(out, y - current.1);
+        *current = (x, y);
+    };
+    for command in commands {
+        match *command {
+            Command::MoveTo(x, y) => {
+                point(&mut out, &mut current, (x, y));
+                out.push(21);
+                float_current = (x, y);
+            }
+            Command::Line(x, y) => {
+                point(&mut out, &mut current, (x, y));
+                out.push(5);
+                float_current = (x, y);
+            }
+            Command::Bezier(control, end) => {
+                let (a, b) = quad_to_cubic(float_current, control, end);
+                for p in [a, b, end] {
+                    point(&mut out, &mut current, p);
+                }
+                out.push(8);
+                float_current = end;
+            }
+            Command::CubicBezier(a, b, end) => {
+                for p in [a, b, end] {
+                    point(&mut out, &mut current, p);
+                }
+                out.push(8);
+                float_current = end;
+            }
+            Command::Close => {}
+        }
+    }
+    out
+}
+
+#[cfg(feature = "cff")]
+fn push_charstring_number(out: &mut Vec<u8>, value: i32) {
+    match value {
+        -107..=107 => out.push((value + 139) as u8),
+        108..=1131 => {
+            let value = value - 108;
+            out.push((value / 256 + 247) as u8);
+            out.push((value % 256) as u8);
+        }
+        -1131..=-108 => {
+            let value = -value - 108;
+            out.push((value / 256 + 251) as u8);
+            out.push((value % 256) as u8);
+        }
+        -32768..=32767 => {
+            out.push(28);
+            push_i16(out, value as i16);
+        }
+        _ => {
+            out.push(255);
+            push_u32(out, (value << 16) as u32);
+        }
+    }
+}
+
+/// Writes a CFF2 table without a variation store: one Font DICT, an empty
+/// Private DICT and no subroutines.
+#[cfg(feature = "cff")]
+fn encode_cff2(charstrings: &[Vec<u8>]) -> Vec<u8> {
+    // Offsets are written as 5-byte integers so the Top DICT size is fixed.
+    let dict_offset = |out: &mut Vec<u8>, value: usize| {
+        out.push(29);
+        push_u32(out, value as u32);
+    };
+    const HEADER_SIZE: usize = 5;
+    const TOP_DICT_SIZE: usize = 5 + 1 + 5 + 2;
+    let global_subrs = cff2_index(&[]);
+    let charstrings = cff2_index(charstrings);
+    let charstrings_offset = HEADER_SIZE + TOP_DICT_SIZE + global_subrs.len();
+    let fd_array_offset = charstrings_offset + charstrings.len();
+    let mut font_dict = Vec::new();
+    // Private DICT: size 0, placed right after the FDArray.
+    font_dict.push(139);
+    let font_dict_size = 1 + 5 + 1;
+    let fd_array_size = cff2_index(&[vec![0; font_dict_size]]).len();
+    dict_offset(&mut font_dict, fd_array_offset + fd_array_size);
+    font_dict.push(18);
+    let fd_array = cff2_index(&[font_dict]);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&[2, 0, HEADER_SIZE as u8]);
+    push_u16(&mut out, TOP_DICT_SIZE as u16);
+    dict_offset(&mut out, charstrings_offset);
+    out.push(17);
+    dict_offset(&mut out, fd_array_offset);
+    out.extend_from_slice(&[12, 36]);
+    out.extend_from_slice(&global_subrs);
+    out.extend_from_slice(&charstrings);
+    out.extend_from_slice(&fd_array);
+    out
+}
+
+/// CFF2 INDEX: u32 count, offset size, 1-based offsets, then the data.
+#[cfg(feature = "cff")]
+fn cff2_index(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    push_u32(&mut out, items.len() as u32);
+    if items.is_empty() {
+        return out;
+    }
+    let data_size = items.iter().map(|item| item.len()).sum::<usize>() + 1;
+    let offset_size = match data_size {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+    out.push(offset_size as u8);
+    let mut offset = 1usize;
+    for item in items.iter().map(Vec::len).chain(std::iter::once(0)) {
+        out.extend_from_slice(&(offset as u32).to_be_bytes()[4 - offset_size..]);
+        offset += item;
+    }
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+/// Writes the table directory and tables, then fixes `head.checkSumAdjustment`.
+fn write_sfnt(sfnt_version: u32, tables: &BTreeMap<[u8; 4], Vec<u8>>) -> Vec<u8> {
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.max(1).leading_zeros() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+    let mut out = Vec::new();
+    push_u32(&mut out, sfnt_version);
+    push_u16(&mut out, num_tables);
+    push_u16(&mut out, search_range);
+    push_u16(&mut out, entry_selector);
+    push_u16(&mut out, num_tables * 16 - search_range);
+
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_offset = None;
+    for (tag, data) in tables {
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        out.extend_from_slice(tag);
+        push_u32(&mut out, checksum(data));
+        push_u32(&mut out, offset as u32);
+        push_u32(&mut out, data.len() as u32);
+        offset += data.len().next_multiple_of(4);
+    }
+    for data in tables.values() {
+        out.extend_from_slice(data);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&out));
+        write_u32(&mut out, head_offset + 8, adjustment);
+    }
+    out
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Bakes the GPOS and GDEF deltas resolved through the GDEF ItemVariationStore.
+#[cfg(feature = "layout")]
+fn bake_layout_deltas(font: &Font, tables: &mut BTreeMap<[u8; 4], Vec<u8>>, coordinates: &[f32]) {
+    let store = font
+        .current_gdef()
+        .and_then(|gdef| gdef.item_var_store.as_ref())
+        .filter(|_| !coordinates.is_empty());
+    let delta = |outer: u16, inner: u16| {
+        store
+            .and_then(|store| store.parse_delta(outer, inner, coordinates))
+            .map_or(0, |delta| delta.round() as i32)
+    };
+    if let Some(gpos) = tables.get_mut(b"GPOS") {
+        bake_gpos_deltas(gpos, &delta);
+    }
+    if let Some(gdef) = tables.get_mut(b"GDEF") {
+        bake_gdef_deltas(gdef, &delta);
+    }
+}
+
+/// Adds the VariationIndex deltas of GPOS value records and anchors to
+/// their values and clears the device offsets. Clearing makes shared
+/// subtables and anchors safe to visit twice.
+#[cfg(feature = "layout")]
+fn bake_gpos_deltas(gpos: &mut [u8], delta: &dyn Fn(u16, u16) -> i32) {
+    let Some(lookup_list) = read_u16(gpos, 8).map(usize::from) else {
+        return;
+    };
+    let lookup_count = read_u16(gpos, lookup_list).unwrap_or(0) as usize;
+    for index in 0..lookup_count {
+        let Some(lookup) = read_u16(gpos, lookup_list + 2 + index * 2) else {
+            break;
+        };
+        let lookup = lookup_list + lookup as usize;
+        let (Some(lookup_type), Some(subtable_count)) =
+            (read_u16(gpos, lookup), read_u16(gpos, lookup + 4))
+        else {
+            continue;
+        };
+        for subtable in 0..subtable_count as usize {
+            if let Some(offset) = read_u16(gpos, lookup + 6 + subtable * 2) {
+                bake_gpos_subtable(gpos, lookup_type, lookup + offset as usize, delta);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "layout")]
+fn bake_gpos_subtable(
+    gpos: &mut [u8],
+    lookup_type: u16,
+    subtable: usize,
+    delta: &dyn Fn(u16, u16) -> i32,
+) {
+    let field = |gpos: &[u8], at: usize| read_u16(gpos, subtable + at).unwrap_or(0) as usize;
+    let Some(format) = read_u16(gpos, subtable) else {
+        return;
+    };
+    match (lookup_type, format) {
+        (1, 1) => {
+            let value_format = field(gpos, 4) as u16;
+            bake_value_record(gpos, subtable + 6, value_format, subtable, delta);
+        }
+        (1, 2) => {
+            let value_format = field(gpos, 4) as u16;
+            let size = value_record_size(value_format);
+            for index in 0..field(gpos, 6) {
+                let record = subtable + 8 + index * size;
+                if record >= gpos.len() {
+                    break;
+                }
+                bake_value_record(gpos, record, value_format, subtable, delta);
+            }
+        }
+        (2, 1) => {
+            let (format1, format2) = (field(gpos, 4) as u16, field(gpos, 6) as u16);
+            let size1 = value_record_size(format1);
+            let record_size = 2 + size1 + value_record_size(format2);
+            for index in 0..field(gpos, 8) {
+                let pair_set = subtable + field(gpos, 10 + index * 2);
+                let pair_count = read_u16(gpos, pair_set).unwrap_or(0) as usize;
+                for pair in 0..pair_count {
+                    let record = pair_set + 2 + pair * record_size + 2;
+                    if record >= gpos.len() {
+                        break;
+                    }
+                    bake_value_record(gpos, record, format1, subtable, delta);
+                    bake_value_record(gpos, record + size1, format2, subtable, delta);
+                }
+            }
+        }
+        (2, 2) => {
+            let (format1, format2) = (field(gpos, 4) as u16, field(gpos, 6) as u16);
+            let size1 = value_record_size(format1);
+            let record_size = size1 + value_record_size(format2);
+            let count = field(gpos, 12) * field(gpos, 14);
+            for index in 0..count {
+                let record = subtable + 16 + index * record_size;
+                if record >= gpos.len() {
+                    break;
+                }
+                bake_value_record(gpos, record, format1, subtable, delta);
+                bake_value_record(gpos, record + size1, format2, subtable, delta);
+            }
+        }
+        (3, 1) => {
+            for index in 0..field(gpos, 4) {
+                let record = subtable + 6 + index * 4;
+                if record >= gpos.len() {
+                    break;
+                }
+                bake_anchor(gpos, record, subtable, delta);
+                bake_anchor(gpos, record + 2, subtable, delta);
+            }
+        }
+        (4..=6, 1) => {
+            let class_count = field(gpos, 6);
+            let mark_array = subtable + field(gpos, 8);
+            for index in 0..read_u16(gpos, mark_array).unwrap_or(0) as usize {
+                bake_anchor(gpos, mark_array + 4 + index * 4, mark_array, delta);
+            }
+            let array = subtable + field(gpos, 10);
+            let count = read_u16(gpos, array).unwrap_or(0) as usize;
+            if lookup_type == 5 {
+                for index in 0..count {
+                    let attach =
+                        array + read_u16(gpos, array + 2 + index * 2).unwrap_or(0) as usize;
+                    let components = read_u16(gpos, attach).unwrap_or(0) as usize;
+                    bake_anchor_array(gpos, attach, components * class_count, delta);
+                }
+            } else {
+                bake_anchor_array(gpos, array, count * class_count, delta);
+            }
+        }
+        (9, 1) => {
+            let extension_type = field(gpos, 2) as u16;
+            if let Some(offset) = read_u32(gpos, subtable + 4).filter(|_| extension_type != 9) {
+                bake_gpos_subtable(gpos, extension_type, subtable + offset as usize, delta);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(feature = "layout")]
+fn value_record_size(value_format: u16) -> usize {
+    (value_format & 0xFF).count_ones() as usize * 2
+}
+
+/// Bakes one ValueRecord whose device offsets are relative to `base`.
+#[cfg(feature = "layout")]
+fn bake_value_record(
+    gpos: &mut [u8],
+    record: usize,
+    value_format: u16,
+    base: usize,
+    delta: &dyn Fn(u16, u16) -> i32,
+) {
+    let mut cursor = record;
+    let mut values = [None; 4];
+    for (bit, value) in values.iter_mut().enumerate() {
+        if value_format & (1 << bit) != 0 {
+            *value = Some(cursor);
+            cursor += 2;
+        }
+    }
+    for (bit, value) in values.into_iter().enumerate() {
+        if value_format & (0x10 << bit) != 0 {
+            bake_device(gpos, value, cursor, base, delta);
+            cursor += 2;
+        }
+    }
+}
+
+/// Bakes `count` anchor offsets that follow the u16 count at `array`.
+#[cfg(feature = "layout")]
+fn bake_anchor_array(gpos: &mut [u8], array: usize, count: usize, delta: &dyn Fn(u16, u16) -> i32) {
+    for index in 0..count {
+        let offset_at = array + 2 + index * 2;
+        if offset_at >= gpos.len() {
+            break;
+        }
+        bake_anchor(gpos, offset_at, array, delta);
+    }
+}
+
+/// Bakes the anchor whose offset from `base` is stored at `offset_at`.
+#[cfg(feature = "layout")]
+fn bake_anchor(gpos: &mut [u8], offset_at: usize, base: usize, delta: &dyn Fn(u16, u16) -> i32) {
+    let Some(offset) = read_u16(gpos, offset_at).filter(|offset| *offset != 0) else {
+        return;
+    };
+    let anchor = base + offset as usize;
+    if read_u16(gpos, anchor) == Some(3) {
+        bake_device(gpos, Some(anchor + 2), anchor + 6, anchor, delta);
+        bake_device(gpos, Some(anchor + 4), anchor + 8, anchor, delta);
+    }
+}
+
+/// Adds the VariationIndex delta to the value at `value` and clears the
+/// device offset at `offset_at`. Hinting devices are kept.
+#[cfg(feature = "layout")]
+fn bake_device(
+    table: &mut [u8],
+    value: Option<usize>,
+    offset_at: usize,
+    base: usize,
+    delta: &dyn Fn(u16, u16) -> i32,
+) {
+    let Some(offset) = read_u16(table, offset_at).filter(|offset| *offset != 0) else {
+        return;
+    };
+    let device = base + offset as usize;
+    if read_u16(table, device + 4) != Some(0x8000) {
+        return;
+    }
+    let (Some(outer), Some(inner)) = (read_u16(table, device), read_u16(table, device + 2)) else {
+        return;
+    };
+    if let Some(value) = value {
+        add_i16(table, value, delta(outer, inner));
+    }
+    write_u16(table, offset_at, 0);
+}
+
+/// Bakes the ligature caret deltas and drops the ItemVariationStore.
+#[cfg(feature = "layout")]
+fn bake_gdef_deltas(gdef: &mut [u8], delta: &dyn Fn(u16, u16) -> i32) {
+    let lig_caret_list = read_u16(gdef, 8).unwrap_or(0) as usize;
+    if lig_caret_list != 0 {
+        let count = read_u16(gdef, lig_caret_list + 2).unwrap_or(0) as usize;
+        for index in 0..count {
+            let Some(offset) = read_u16(gdef, lig_caret_list + 4 + index * 2) else {
+                break;
+            };
+            let lig_glyph = lig_caret_list + offset as usize;
+            let caret_count = read_u16(gdef, lig_glyph).unwrap_or(0) as usize;
+            for caret in 0..caret_count {
+                let Some(offset) = read_u16(gdef, lig_glyph + 2 + caret * 2) else {
+                    break;
+                };
+                let caret = lig_glyph + offset as usize;
+                if read_u16(gdef, caret) == Some(3) {
+                    bake_device(gdef, Some(caret + 2), caret + 4, caret, delta);
+                }
+            }
+        }
+    }
+    if read_u16(gdef, 0) == Some(1) && read_u16(gdef, 2).is_some_and(|minor| minor >= 3) {
+        write_u32(gdef, 14, 0);
+    }
+}
+
+/// Rewrites the family, style, full and PostScript names for the instance
+/// and drops the variations PostScript name prefix (ID 25). Localized
+/// families keep their own family name. Returns `None` for unknown formats.
+fn rename(name: &[u8], names: &InstanceNames) -> Option<Vec<u8>> {
+    let version = read_u16(name, 0)?;
+    let count = read_u16(name, 2)? as usize;
+    let storage = read_u16(name, 4)? as usize;
+    if version > 1 {
+        return None;
+    }
+    let mut records = Vec::with_capacity(count);
+    for index in 0..count {
+        let record = 6 + index * 12;
+        let field = |at: usize| read_u16(name, record + at);
+        let (platform, encoding, language, name_id) = (field(0)?, field(2)?, field(4)?, field(6)?);
+        let (length, offset) = (field(8)? as usize, field(10)? as usize);
+        let bytes = name
+            .get(storage + offset..storage + offset + length)?
+            .to_vec();
+        records.push((platform, encoding, language, name_id, bytes));
+    }
+    let mut lang_tags = Vec::new();
+    if version == 1 {
+        let lang_tag_count = read_u16(name, 6 + count * 12)? as usize;
+        for index in 0..lang_tag_count {
+            let record = 8 + count * 12 + index * 4;
+            let (length, offset) = (
+                read_u16(name, record)? as usize,
+                read_u16(name, record + 2)? as usize,
+            );
+            lang_tags.push(
+                name.get(storage + offset..storage + offset + length)?
+                    .to_vec(),
+            );
+        }
+    }
+
+    let style = names.style.as_str();
+    let ribbi = matches!(style, "Regular" | "Italic" | "Bold" | "Bold Italic");
+    let postscript = names.postscript_name.clone().unwrap_or_else(|| {
+        let part = |text: &str| {
+            text.chars()
+                .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+                .collect::<String>()
+        };
+        let mut postscript = format!("{}-{}", part(&names.family), part(style));
+        postscript.truncate(63);
+        postscript
+    });
+    let mut groups = records
+        .iter()
+        .filter(|record| matches!(record.3, 1 | 16) && is_writable_name(record.0, record.1))
+        .map(|record| (record.0, record.1, record.2))
+        .collect::<Vec<_>>();
+    groups.sort_unstable();
+    groups.dedup();
+    let original = records.clone();
+    records.retain(|record| {
+        record.3 != 25
+            && !(matches!(record.3, 1 | 2 | 4 | 6 | 16 | 17)
+                && groups.contains(&(record.0, record.1, record.2)))
+    });
+    for (platform, encoding, language) in groups {
+        let decoded = |name_id: u16| {
+            original
+                .iter()
+                .find(|record| {
+                    (record.0, record.1, record.2, record.3)
+                        == (platform, encoding, language, name_id)
+                })
+                .and_then(|record| decode_name(platform, &record.4))
+        };
+        let family = decoded(16)
+            .or_else(|| decoded(1))
+            .unwrap_or_else(|| names.family.clone());
+        let full = if style == "Regular" {
+            family.clone()
+        } else {
+            format!("{family} {style}")
+        };
+        let mut strings = vec![(4, full.clone()), (6, postscript.clone())];
+        if ribbi {
+            strings.extend([(1, family), (2, style.to_string())]);
+        } else {
+            let legacy_style = if style.contains("Italic") {
+                "Italic"
+            } else {
+                "Regular"
+            };
+            strings.extend([
+                (1, full),
+                (2, legacy_style.to_string()),
+                (16, family),
+                (17, style.to_string()),
+            ]);
+        }
+        for (name_id, text) in strings {
+            if let Some(bytes) = encode_name(platform, &text) {
+                records.push((platform, encoding, language, name_id, bytes));
+            }
+        }
+    }
+    records.sort_by_key(|record| (record.0, record.1, record.2, record.3));
+
+    let header = 6
+        + records.len() * 12
+        + if version == 1 {
+            2 + lang_tags.len() * 4
+        } else {
+            0
+        };
+    let mut out = Vec::new();
+    let mut strings = Vec::new();
+    push_u16(&mut out, version);
+    push_u16(&mut out, records.len() as u16);
+    push_u16(&mut out, header as u16);
+    for (platform, encoding, language, name_id, bytes) in &records {
+        for value in [
+            *platform,
+            *encoding,
+            *language,
+            *name_id,
+            bytes.len() as u16,
+        ] {
+            push_u16(&mut out, value);
+        }
+        push_u16(&mut out, strings.len() as u16);
+        strings.extend_from_slice(bytes);
+    }
+    if version == 1 {
+        push_u16(&mut out, lang_tags.len() as u16);
+        for tag in &lang_tags {
+            push_u16(&mut out, tag.len() as u16);
+            push_u16(&mut out, strings.len() as u16);
+            strings.extend_from_slice(tag);
+        }
+    }
+    u16::try_from(strings.len()).ok()?;
+    out.extend_from_slice(&strings);
+    Some(out)
+}
+
+/// Unicode and Windows Unicode records, and Mac Roman.
+fn is_writable_name(platform: u16, encoding: u16) -> bool {
+    matches!((platform, encoding), (0, _) | (3, 0 | 1 | 10) | (1, 0))
+}
+
+/// UTF-16BE for Unicode and Windows records, ASCII for Mac Roman.
+fn encode_name(platform: u16, text: &str) -> Option<Vec<u8>> {
+    if platform == 1 {
+        return text.is_ascii().then(|| text.as_bytes().to_vec());
+    }
+    Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect())
+}
+
+fn decode_name(platform: u16, bytes: &[u8]) -> Option<String> {
+    if platform == 1 {
+        return bytes
+            .is_ascii()
+            .then(|| String::from_utf8_lossy(bytes).into_owned());
+    }
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect::<Vec<_>>();
+    String::from_utf16(&units).ok()
+}
+
+fn f2dot14(value: u16) -> f32 {
+    value as i16 as f32 / 16384.0
+}
+
+fn clamp_i16(value: i32) -> i16 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    if let Some(slot) = data.get_mut(offset..offset + 2) {
+        slot.copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn write_i16(data: &mut [u8], offset: usize, value: i16) {
+    write_u16(data, offset, value as u16);
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    if let Some(slot) = data.get_mut(offset..offset + 4) {
+        slot.copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Adds an MVAR delta to an FWORD field when the table is long enough.
+fn add_i16(data: &mut [u8], offset: usize, delta: i32) {
+    if delta == 0 {
+        return;
+    }
+    if let Some(value) = read_u16(data, offset) {
+        write_i16(data, offset, clamp_i16(value as i16 as i32 + delta));
+    }
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_i16(out: &mut Vec<u8>, value: i16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_glyph_packs_repeated_flags_and_short_deltas() {
+        let parsed = ParsedGlyph {
+            number_of_contours: 1,
+            x_min: 0,
+            y_min: 0,
+            x_max: 0,
+            y_max: 0,
+            offset: 0,
+            length: 0,
+            end_pts_of_contours: vec![3],
+            instructions: Vec::new(),
+            flags: vec![0x41, 0x01, 0x01, 0x01],
+            xs: vec![10, 0, 0, -300],
+            ys: vec![0, 20, 20, 0],
+            on_curves: vec![true; 4],
+        };
+        let mut out = Vec::new();
+        encode_simple_glyph(&mut out, &parsed, points_bounds(&parsed).unwrap());
+        assert_eq!(
+            out,
+            [
+                0, 1, 0xFE, 0xDE, 0, 0, 0, 10, 0, 40, // contours, bbox
+                0, 3, 0, 0, // end point, no instructions
+                0x73, 0x3D, 0x01, 0x21, // flags, one repeat
+                10, 0xFE, 0xD4, // xs
+                20, 20, // ys
+            ]
+        );
+    }
+
+    #[cfg(feature = "cff")]
+    #[test]
+    fn cff2_table_round_trips_through_the_parser() {
+        use bin_rs::reader::BytesReader;
+        let commands = vec![
+            Command::MoveTo(10.0, 20.0),
+            Command::Line(500.0, 20.0),
+            Command::CubicBezier((600.0, 100.0), (600.0, 600.0), (-1200.0, 700.0)),
+            Command::Close,
+        ];
+        let table = encode_cff2(&[Vec::new(), encode_charstring(&commands)]);
+        let mut reader = BytesReader::new(&table);
+        let cff =
+            crate::opentype::outline::cff::CFF::new(&mut reader, 0, table.len() as u32).unwrap();
+        let parsed = cff.to_path_commands_with_coords(1, 1.0, &[]).unwrap();
+        let points = parsed
+            .iter()
+            .filter_map(|command| match *command {
+                Command::MoveTo(x, y) | Command::Line(x, y) => Some((x, -y)),
+                Command::CubicBezier(_, _, (x, y)) => Some((x, -y)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(points, [(10.0, 20.0), (500.0, 20.0), (-1200.0, 700.0)]);
+        assert_eq!(
+            commands_bounds(&commands),
+            Some((-1200, 20, 535, 700)),
+            "the curve bulges past its end points"
+        );
+    }
+
+    /// GSUB 1.1 with one `rvrn` feature whose lookup is swapped on wght >= 0.5.
+    fn layout_with_feature_variations() -> Vec<u8> {
+        let mut table = Vec::new();
+        push_u16(&mut table, 1);
+        push_u16(&mut table, 1);
+        push_u16(&mut table, 14); // ScriptList
+        push_u16(&mut table, 16); // FeatureList
+        push_u16(&mut table, 30); // LookupList
+        push_u32(&mut table, 32); // FeatureVariations
+        push_u16(&mut table, 0); // empty ScriptList
+        push_u16(&mut table, 1); // FeatureList: one record
+        table.extend_from_slice(b"rvrn");
+        push_u16(&mut table, 8);
+        for value in [0, 1, 0] {
+            push_u16(&mut table, value); // params, lookup count, lookup 0
+        }
+        push_u16(&mut table, 0); // empty LookupList
+                                 // FeatureVariations at 32.
+        for value in [1, 0] {
+            push_u16(&mut table, value);
+        }
+        push_u32(&mut table, 1);
+        push_u32(&mut table, 16); // ConditionSet
+        push_u32(&mut table, 30); // FeatureTableSubstitution
+        push_u16(&mut table, 1); // ConditionSet: one condition
+        push_u32(&mut table, 6);
+        for value in [1, 0, 0x2000, 0x4000] {
+            push_u16(&mut table, value); // format, axis, min 0.5, max 1.0
+        }
+        for value in [1, 0, 1, 0] {
+            push_u16(&mut table, value); // version, count, feature index
+        }
+        push_u32(&mut table, 12);
+        for value in [0, 1, 1] {
+            push_u16(&mut table, value); // params, lookup count, lookup 1
+        }
+        table
+    }
+
+    #[test]
+    fn feature_variations_are_resolved_into_the_feature_list() {
+        let table = layout_with_feature_variations();
+        for (coordinate, lookup) in [(0.0, 0), (0.75, 1)] {
+            let resolved = resolve_feature_variations(&table, &[coordinate]).unwrap();
+            assert_eq!(read_u16(&resolved, 2), Some(0));
+            let feature_list = read_u16(&resolved, 6).unwrap() as usize;
+            assert_eq!(&resolved[feature_list + 2..feature_list + 6], b"rvrn");
+            let feature = feature_list + read_u16(&resolved, feature_list + 6).unwrap() as usize;
+            assert_eq!(read_u16(&resolved, feature + 4), Some(lookup));
+            let script_list = read_u16(&resolved, 4).unwrap() as usize;
+            assert_eq!(&resolved[script_list..script_list + 2], &table[14..16]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "layout")]
+    fn gpos_value_record_deltas_are_baked_once() {
+        let mut gpos = Vec::new();
+        for value in [1, 0, 0, 0, 10] {
+            push_u16(&mut gpos, value); // version, ScriptList, FeatureList, LookupList
+        }
+        for value in [1, 4, 1, 0, 1, 8] {
+            push_u16(&mut gpos, value); // LookupList, SinglePos lookup
+        }
+        for value in [1, 0, 0x0044, 100, 10] {
+            push_u16(&mut gpos, value); // format, coverage, XAdvance + device, value
+        }
+        for value in [0, 2, 0x8000] {
+            push_u16(&mut gpos, value); // VariationIndex device
+        }
+        let delta = |outer, inner| if (outer, inner) == (0, 2) { 25 } else { 0 };
+        for _ in 0..2 {
+            bake_gpos_deltas(&mut gpos, &delta);
+            assert_eq!(read_u16(&gpos, 28), Some(125));
+            assert_eq!(read_u16(&gpos, 30), Some(0));
+        }
+    }
+
+    #[test]
+    fn names_follow_the_instance_style() {
+        let strings = [
+            (1, "Fam"),
+            (2, "Regular"),
+            (4, "Fam"),
+            (6, "Fam-Regular"),
+            (25, "FamVF"),
+        ];
+        let mut name = Vec::new();
+        let mut storage = Vec::new();
+        push_u16(&mut name, 0);
+        push_u16(&mut name, strings.len() as u16);
+        push_u16(&mut name, 6 + strings.len() as u16 * 12);
+        for (name_id, text) in strings {
+            let bytes = text
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect::<Vec<_>>();
+            for value in [
+                3,
+                1,
+                0x409,
+                name_id,
+                bytes.len() as u16,
+                storage.len() as u16,
+            ] {
+                push_u16(&mut name, value);
+            }
+            storage.extend_from_slice(&bytes);
+        }
+        name.extend_from_slice(&storage);
+        let names = InstanceNames {
+            family: "Fam".to_string(),
+            style: "SemiBold".to_string(),
+            postscript_name: None,
+        };
+
+        let renamed = rename(&name, &names).unwrap();
+        let storage = read_u16(&renamed, 4).unwrap() as usize;
+        let records = (0..read_u16(&renamed, 2).unwrap() as usize)
+            .map(|index| {
+                let field = |at| read_u16(&renamed, 6 + index * 12 + at).unwrap();
+                let start = storage + field(10) as usize;
+                let text = decode_name(3, &renamed[start..start + field(8) as usize]).unwrap();
+                (field(6), text)
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            (1, "Fam SemiBold"),
+            (2, "Regular"),
+            (4, "Fam SemiBold"),
+            (6, "Fam-SemiBold"),
+            (16, "Fam"),
+            (17, "SemiBold"),
+        ];
+        assert_eq!(
+            records,
+            expected.map(|(name_id, text)| (name_id, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn sfnt_checksum_adjustment_balances_the_file() {
+        let mut tables = BTreeMap::new();
+        tables.insert(*b"head", vec![0; 54]);
+        tables.insert(*b"name", vec![1, 2, 3]);
+        let font = write_sfnt(0x0001_0000, &tables);
+        assert_eq!(checksum(&font), 0xB1B0_AFBA);
+        assert_eq!(width_class(100.0), 5);
+        assert_eq!(width_class(70.0), 3);
+    }
+}
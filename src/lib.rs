@@ -36,9 +36,17 @@ pub mod opentype;
 mod opentype;
 
 pub mod commands;
+pub(crate) mod decoration;
+pub(crate) mod fontbytes;
 pub mod fontengine;
 pub mod fontface;
 pub mod fontfile;
+pub(crate) mod instancer;
+pub(crate) mod outlinecache;
+pub(crate) mod png;
+pub(crate) mod richtext;
+pub(crate) mod ruby;
+pub(crate) mod shapeplan;
 #[cfg(feature = "svg-fonts")]
 pub(crate) mod svgparse;
 pub(crate) mod util;
@@ -49,19 +57,41 @@ pub use commands as commads;
 #[cfg(feature = "svg-fonts")]
 pub use commands::SvgGlyphLayer;
 pub use commands::{
-    text2commands, Command, FillRule, FontMetrics, FontOptions, FontRef, FontStretch, FontStyle,
-    FontVariant, FontVariationSetting, FontWeight, Glyph, GlyphBounds, GlyphFlow,
-    GlyphGradientSpread, GlyphGradientStop, GlyphGradientUnits, GlyphLayer, GlyphLinearGradient,
-    GlyphMetrics, GlyphPaint, GlyphRadialGradient, GlyphRun, PathGlyphLayer, PathPaintMode,
-    PositionedGlyph, RasterGlyphLayer, RasterGlyphSource, TextDirection,
+    parse_rich_text, rich_text2commands, shape_parallel, spans2commands, text2commands, Baseline,
+    Command, FillRule, FontFeatureSetting, FontMetrics, FontOpticalSizing, FontOptions,
+    FontPaletteType, FontRef, FontStretch, FontStyle, FontVariant, FontVariantCaps,
+    FontVariantPosition, FontVariationSetting, FontWeight, Glyph, GlyphBlendMode, GlyphBounds,
+    GlyphFilter, GlyphFlow, GlyphGradientSpread, GlyphGradientStop, GlyphGradientUnits, GlyphLayer,
+    GlyphLinearGradient, GlyphMask, GlyphMaskMode, GlyphMetrics, GlyphPaint, GlyphRadialGradient,
+    GlyphRun, GlyphSweepGradient, GlyphWidth, MathConstants, MathGlyphVariant, MathKernCorner,
+    NumericFigure, NumericSpacing, PathGlyphLayer, PathPaintMode, PositionedGlyph,
+    RasterGlyphLayer, RasterGlyphSource, RichTextSpan, RubyKind, RubyText, ScriptExtent,
+    TextDecoration, TextDecorationLine, TextDecorationStyle, TextDirection,
 };
+pub use fontbytes::FontBytes;
 pub use fontengine::{FontEngine, ShapingPolicy};
-pub use fontface::{FontFace, FontFaceDescriptor, FontFamily, FontVariationAxis};
+pub use fontface::{
+    FontAxisValue, FontFace, FontFaceDescriptor, FontFamily, FontNamedInstance, FontPalette,
+    FontStyleAxis, FontVariationAxis,
+};
 pub use fontfile::{
     load_font, load_font_from_buffer, load_font_from_file, load_font_from_net, open_font,
     open_font_from_buffer, open_font_from_file, open_font_from_net, ChunkedFontBuffer, FontFile,
     FontSource,
 };
+pub use shapeplan::ShapePlan;
+
+// Faces, families and engines are shared across threads without cloning the
+// parsed font; internal caches use `Mutex` / `OnceLock`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FontFile>();
+    assert_send_sync::<FontFace>();
+    assert_send_sync::<FontFamily>();
+    assert_send_sync::<FontEngine<'static>>();
+    assert_send_sync::<FontOptions<'static>>();
+    assert_send_sync::<GlyphRun>();
+};
 
 #[cfg(feature = "raw")]
 #[allow(deprecated)]
@@ -0,0 +1,53 @@
+// CBDT: color bitmap glyph data located through CBLC.
+
+use std::io::{Error, SeekFrom};
+
+use bin_rs::reader::BinaryReader;
+
+use super::cblc::{BigGlyphMetrics, BitmapLocation};
+
+#[derive(Debug, Clone)]
+pub(crate) struct CBDT {
+    data: Vec<u8>,
+}
+
+/// One decoded bitmap glyph record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BitmapGlyph {
+    pub(crate) metrics: BigGlyphMetrics,
+    /// Encoded PNG data.
+    pub(crate) data: Vec<u8>,
+}
+
+impl CBDT {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u32,
+        length: u32,
+    ) -> Result<Self, Error> {
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        let data = reader.read_bytes_as_vec(length as usize)?;
+        Ok(Self { data })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_bytes(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    /// Reads the glyph at `location`; formats 17, 18 and 19 carry PNG data.
+    pub(crate) fn glyph(&self, location: &BitmapLocation) -> Option<BitmapGlyph> {
+        let record = self
+            .data
+            .get(location.offset..location.offset.checked_add(location.length)?)?;
+        let (metrics, rest) = match location.image_format {
+            17 => (BigGlyphMetrics::from_small_bytes(record)?, &record[5..]),
+            18 => (BigGlyphMetrics::from_bytes(record)?, &record[8..]),
+            19 => (location.metrics?, record),
+            _ => return None,
+        };
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let data = rest.get(4..4usize.checked_add(length)?)?.to_vec();
+        Some(BitmapGlyph { metrics, data })
+    }
+}
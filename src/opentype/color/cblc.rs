@@ -0,0 +1,434 @@
+// CBLC / EBLC / bloc: bitmap strikes and where each glyph sits in the data table.
+
+use std::io::{Error, ErrorKind, SeekFrom};
+
+use bin_rs::reader::BinaryReader;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CBLC {
+    pub(crate) strikes: Vec<BitmapStrike>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BitmapStrike {
+    pub(crate) ppem_y: u8,
+    pub(crate) bit_depth: u8,
+    subtables: Vec<IndexSubtable>,
+}
+
+#[derive(Debug, Clone)]
+struct IndexSubtable {
+    first_glyph: u16,
+    last_glyph: u16,
+    image_format: u16,
+    image_data_offset: u32,
+    kind: IndexSubtableKind,
+}
+
+#[derive(Debug, Clone)]
+enum IndexSubtableKind {
+    /// Formats 1 and 3: one offset per glyph plus an end offset.
+    Offsets(Vec<u32>),
+    /// Format 2: fixed-size images sharing one set of metrics.
+    Constant {
+        image_size: u32,
+        metrics: BigGlyphMetrics,
+    },
+    /// Format 4: sorted `(glyph, offset)` pairs plus an end pair.
+    SparseOffsets(Vec<(u16, u16)>),
+    /// Format 5: fixed-size images for a sorted glyph list.
+    Sparse {
+        image_size: u32,
+        metrics: BigGlyphMetrics,
+        glyph_ids: Vec<u16>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct BigGlyphMetrics {
+    pub(crate) height: u8,
+    pub(crate) width: u8,
+    pub(crate) hori_bearing_x: i8,
+    pub(crate) hori_bearing_y: i8,
+    pub(crate) hori_advance: u8,
+    pub(crate) vert_bearing_x: i8,
+    pub(crate) vert_bearing_y: i8,
+    pub(crate) vert_advance: u8,
+}
+
+impl BigGlyphMetrics {
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; 8] = bytes.get(..8)?.try_into().ok()?;
+        Some(Self {
+            height: bytes[0],
+            width: bytes[1],
+            hori_bearing_x: bytes[2] as i8,
+            hori_bearing_y: bytes[3] as i8,
+            hori_advance: bytes[4],
+            vert_bearing_x: bytes[5] as i8,
+            vert_bearing_y: bytes[6] as i8,
+            vert_advance: bytes[7],
+        })
+    }
+
+    /// Small metrics use the same bearings for both directions.
+    pub(crate) fn from_small_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; 5] = bytes.get(..5)?.try_into().ok()?;
+        Some(Self {
+            height: bytes[0],
+            width: bytes[1],
+            hori_bearing_x: bytes[2] as i8,
+            hori_bearing_y: bytes[3] as i8,
+            hori_advance: bytes[4],
+            vert_bearing_x: bytes[2] as i8,
+            vert_bearing_y: bytes[3] as i8,
+            vert_advance: bytes[4],
+        })
+    }
+
+    fn read<R: BinaryReader>(reader: &mut R) -> Result<Self, Error> {
+        let bytes = reader.read_bytes_as_vec(8)?;
+        Self::from_bytes(&bytes)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "truncated bitmap metrics"))
+    }
+}
+
+/// Where one glyph's bitmap lives in the data table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BitmapLocation {
+    pub(crate) image_format: u16,
+    /// Offset from the start of the data table.
+    pub(crate) offset: usize,
+    pub(crate) length: usize,
+    /// Metrics from the index subtable (formats 2 and 5).
+    pub(crate) metrics: Option<BigGlyphMetrics>,
+}
+
+impl CBLC {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u32,
+        length: u32,
+    ) -> Result<Self, Error> {
+        let offset = offset as u64;
+        reader.seek(SeekFrom::Start(offset))?;
+        // Major and minor version.
+        reader.read_u32_be()?;
+        let num_sizes = reader.read_u32_be()?;
+        if (num_sizes as u64).saturating_mul(48) > length as u64 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "bitmap size records exceed the table",
+            ));
+        }
+
+        let mut records = Vec::new();
+        for _ in 0..num_sizes {
+            let index_subtable_array_offset = reader.read_u32_be()?;
+            let _index_tables_size = reader.read_u32_be()?;
+            let number_of_index_subtables = reader.read_u32_be()?;
+            let _color_ref = reader.read_u32_be()?;
+            // Horizontal and vertical SbitLineMetrics, the glyph range and
+            // ppemX are not needed for placement; each subtable has its range.
+            reader.read_bytes_as_vec(29)?;
+            let ppem_y = reader.read_u8()?;
+            let bit_depth = reader.read_u8()?;
+            // Flags.
+            reader.read_u8()?;
+            records.push((
+                index_subtable_array_offset,
+                number_of_index_subtables,
+                BitmapStrike {
+                    ppem_y,
+                    bit_depth,
+                    subtables: Vec::new(),
+                },
+            ));
+        }
+
+        let mut strikes = Vec::new();
+        for (array_offset, count, mut strike) in records {
+            if array_offset as u64 >= length as u64 {
+                continue;
+            }
+            let array_start = offset + array_offset as u64;
+            // An array that runs past the table only loses its own strike.
+            if array_offset as u64 + count as u64 * 8 > length as u64 {
+                continue;
+            }
+            let Ok(entries) = read_index_array(reader, array_start, count) else {
+                continue;
+            };
+            for (first_glyph, last_glyph, additional_offset) in entries {
+                if last_glyph < first_glyph {
+                    continue;
+                }
+                // A broken subtable only loses its own glyphs.
+                let subtable = reader
+                    .seek(SeekFrom::Start(array_start + additional_offset as u64))
+                    .and_then(|_| IndexSubtable::read(reader, first_glyph, last_glyph));
+                if let Ok(subtable) = subtable {
+                    strike.subtables.push(subtable);
+                }
+            }
+            strikes.push(strike);
+        }
+
+        Ok(Self { strikes })
+    }
+
+    /// The strike to use for `glyph_id` at `ppem`: the smallest strike that
+    /// is at least that large, else the largest one that has the glyph.
+    pub(crate) fn select_strike(&self, glyph_id: u16, ppem: f32) -> Option<&BitmapStrike> {
+        let mut candidates = self
+            .strikes
+            .iter()
+            .filter(|strike| strike.locate(glyph_id).is_some())
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|strike| strike.ppem_y);
+        candidates
+            .iter()
+            .find(|strike| strike.ppem_y as f32 >= ppem)
+            .or(candidates.last())
+            .copied()
+    }
+}
+
+/// `(first glyph, last glyph, subtable offset)` entries of an index subtable array.
+fn read_index_array<R: BinaryReader>(
+    reader: &mut R,
+    array_start: u64,
+    count: u32,
+) -> Result<Vec<(u16, u16, u32)>, Error> {
+    reader.seek(SeekFrom::Start(array_start))?;
+    (0..count)
+        .map(|_| {
+            Ok((
+                reader.read_u16_be()?,
+                reader.read_u16_be()?,
+                reader.read_u32_be()?,
+            ))
+        })
+        .collect()
+}
+
+impl BitmapStrike {
+    pub(crate) fn locate(&self, glyph_id: u16) -> Option<BitmapLocation> {
+        let subtable = self
+            .subtables
+            .iter()
+            .find(|subtable| (subtable.first_glyph..=subtable.last_glyph).contains(&glyph_id))?;
+        let base = subtable.image_data_offset as usize;
+        let index = (glyph_id - subtable.first_glyph) as usize;
+        let (start, end, metrics) = match &subtable.kind {
+            IndexSubtableKind::Offsets(offsets) => {
+                let start = *offsets.get(index)? as usize;
+                let end = *offsets.get(index + 1)? as usize;
+                (start, end, None)
+            }
+            IndexSubtableKind::Constant {
+                image_size,
+                metrics,
+            } => {
+                let size = *image_size as usize;
+                (index * size, (index + 1) * size, Some(*metrics))
+            }
+            IndexSubtableKind::SparseOffsets(pairs) => {
+                let position = pairs[..pairs.len().saturating_sub(1)]
+                    .binary_search_by_key(&glyph_id, |(glyph, _)| *glyph)
+                    .ok()?;
+                let start = pairs[position].1 as usize;
+                let end = pairs.get(position + 1)?.1 as usize;
+                (start, end, None)
+            }
+            IndexSubtableKind::Sparse {
+                image_size,
+                metrics,
+                glyph_ids,
+            } => {
+                let position = glyph_ids.binary_search(&glyph_id).ok()?;
+                let size = *image_size as usize;
+                (position * size, (position + 1) * size, Some(*metrics))
+            }
+        };
+        // Equal offsets mean the glyph has no bitmap in this strike.
+        if end <= start {
+            return None;
+        }
+        Some(BitmapLocation {
+            image_format: subtable.image_format,
+            offset: base + start,
+            length: end - start,
+            metrics,
+        })
+    }
+}
+
+impl IndexSubtable {
+    fn read<R: BinaryReader>(
+        reader: &mut R,
+        first_glyph: u16,
+        last_glyph: u16,
+    ) -> Result<Self, Error> {
+        let index_format = reader.read_u16_be()?;
+        let image_format = reader.read_u16_be()?;
+        let image_data_offset = reader.read_u32_be()?;
+        let glyph_count = (last_glyph - first_glyph) as usize + 1;
+        let kind = match index_format {
+            1 => IndexSubtableKind::Offsets(
+                (0..=glyph_count)
+                    .map(|_| reader.read_u32_be())
+                    .collect::<Result<_, _>>()?,
+            ),
+            2 => IndexSubtableKind::Constant {
+                image_size: reader.read_u32_be()?,
+                metrics: BigGlyphMetrics::read(reader)?,
+            },
+            3 => IndexSubtableKind::Offsets(
+                (0..=glyph_count)
+                    .map(|_| reader.read_u16_be().map(u32::from))
+                    .collect::<Result<_, _>>()?,
+            ),
+            4 => {
+                let num_glyphs = reader.read_u32_be()?.min(u16::MAX as u32);
+                IndexSubtableKind::SparseOffsets(
+                    (0..=num_glyphs)
+                        .map(|_| Ok((reader.read_u16_be()?, reader.read_u16_be()?)))
+                        .collect::<Result<_, Error>>()?,
+                )
+            }
+            5 => {
+                let image_size = reader.read_u32_be()?;
+                let metrics = BigGlyphMetrics::read(reader)?;
+                let num_glyphs = reader.read_u32_be()?.min(u16::MAX as u32);
+                IndexSubtableKind::Sparse {
+                    image_size,
+                    metrics,
+                    glyph_ids: (0..num_glyphs)
+                        .map(|_| reader.read_u16_be())
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("unsupported bitmap index format {index_format}"),
+                ))
+            }
+        };
+        Ok(Self {
+            first_glyph,
+            last_glyph,
+            image_format,
+            image_data_offset,
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opentype::color::cbdt::CBDT;
+    use bin_rs::reader::BytesReader;
+
+    fn size_record(array_offset: u32, subtables: u32, glyphs: (u16, u16), ppem: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&array_offset.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes.extend_from_slice(&subtables.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        bytes.extend_from_slice(&glyphs.0.to_be_bytes());
+        bytes.extend_from_slice(&glyphs.1.to_be_bytes());
+        bytes.extend_from_slice(&[ppem, ppem, 32, 1]);
+        bytes
+    }
+
+    /// Strike 20 ppem: format 1 index over glyphs 3..=4 with format 17 data
+    /// (glyph 4 empty). Strike 40 ppem: format 5 index for glyphs 3 and 7
+    /// with format 19 data.
+    fn tables() -> (CBLC, CBDT) {
+        let png = b"\x89PNG".to_vec();
+        let mut cbdt = vec![0, 3, 0, 0];
+        // Format 17: small metrics, length, data.
+        cbdt.extend_from_slice(&[10, 12, 1, 9, 14]);
+        cbdt.extend_from_slice(&(png.len() as u32).to_be_bytes());
+        cbdt.extend_from_slice(&png);
+        let format17_len = cbdt.len() as u32 - 4;
+        // Format 19 records: length and data, fixed size.
+        let format19_offset = cbdt.len() as u32;
+        for _ in 0..2 {
+            cbdt.extend_from_slice(&(png.len() as u32).to_be_bytes());
+            cbdt.extend_from_slice(&png);
+        }
+
+        let mut cblc = vec![0, 3, 0, 0, 0, 0, 0, 2];
+        cblc.extend(size_record(104, 1, (3, 4), 20));
+        cblc.extend(size_record(132, 1, (3, 7), 40));
+        // Strike 0 array and format 1 subtable.
+        cblc.extend_from_slice(&[0, 3, 0, 4, 0, 0, 0, 8]);
+        cblc.extend_from_slice(&[0, 1, 0, 17, 0, 0, 0, 4]);
+        for offset in [0, format17_len, format17_len] {
+            cblc.extend_from_slice(&offset.to_be_bytes());
+        }
+        // Strike 1 array and format 5 subtable.
+        cblc.extend_from_slice(&[0, 3, 0, 7, 0, 0, 0, 8]);
+        cblc.extend_from_slice(&[0, 5, 0, 19]);
+        cblc.extend_from_slice(&format19_offset.to_be_bytes());
+        cblc.extend_from_slice(&(4 + png.len() as u32).to_be_bytes());
+        cblc.extend_from_slice(&[20, 24, 2, 18, 28, 0, 0, 28]);
+        cblc.extend_from_slice(&2u32.to_be_bytes());
+        cblc.extend_from_slice(&[0, 3, 0, 7]);
+
+        let mut reader = BytesReader::new(&cblc);
+        let cblc = CBLC::new(&mut reader, 0, cblc.len() as u32).unwrap();
+        (cblc, CBDT::from_bytes(cbdt))
+    }
+
+    #[test]
+    fn cblc_selects_the_smallest_strike_at_least_the_requested_size() {
+        let (cblc, _) = tables();
+        assert_eq!(cblc.strikes.len(), 2);
+        assert_eq!(cblc.select_strike(3, 16.0).unwrap().ppem_y, 20);
+        assert_eq!(cblc.select_strike(3, 32.0).unwrap().ppem_y, 40);
+        assert_eq!(cblc.select_strike(3, 96.0).unwrap().ppem_y, 40);
+        // Glyph 4 has no bitmap in the 20 ppem strike, glyph 7 only in 40.
+        assert_eq!(cblc.select_strike(7, 16.0).unwrap().ppem_y, 40);
+        assert!(cblc.select_strike(4, 16.0).is_none());
+        assert!(cblc.select_strike(5, 16.0).is_none());
+    }
+
+    #[test]
+    fn cblc_skips_strikes_whose_index_array_runs_past_the_table() {
+        let mut cblc = vec![0, 3, 0, 0, 0, 0, 0, 2];
+        cblc.extend(size_record(104, 1, (3, 4), 20));
+        cblc.extend(size_record(104, 50, (3, 4), 40));
+        cblc.extend_from_slice(&[0, 3, 0, 4, 0, 0, 0, 8]);
+        cblc.extend_from_slice(&[0, 1, 0, 17, 0, 0, 0, 4]);
+        for offset in [0u32, 10, 10] {
+            cblc.extend_from_slice(&offset.to_be_bytes());
+        }
+
+        let mut reader = BytesReader::new(&cblc);
+        let cblc = CBLC::new(&mut reader, 0, cblc.len() as u32).unwrap();
+        assert_eq!(cblc.strikes.len(), 1);
+        assert_eq!(cblc.select_strike(3, 40.0).unwrap().ppem_y, 20);
+    }
+
+    #[test]
+    fn cbdt_reads_small_metrics_and_index_metrics() {
+        let (cblc, cbdt) = tables();
+        let small = cbdt.glyph(&cblc.strikes[0].locate(3).unwrap()).unwrap();
+        assert_eq!(small.data, b"\x89PNG");
+        assert_eq!((small.metrics.width, small.metrics.height), (12, 10));
+        assert_eq!(small.metrics.hori_bearing_y, 9);
+
+        let location = cblc.strikes[1].locate(7).unwrap();
+        assert_eq!(location.image_format, 19);
+        let indexed = cbdt.glyph(&location).unwrap();
+        assert_eq!(indexed.data, b"\x89PNG");
+        assert_eq!(indexed.metrics.width, 24);
+        assert_eq!(indexed.metrics.hori_bearing_x, 2);
+    }
+}
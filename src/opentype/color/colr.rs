@@ -4,6 +4,14 @@ use std::{io::Error, io::SeekFrom};
 
 use bin_rs::reader::BinaryReader;
 
+use crate::commands::{
+    Command, GlyphBlendMode, GlyphGradientSpread, GlyphGradientStop, GlyphGradientUnits,
+    GlyphLinearGradient, GlyphMask, GlyphMaskMode, GlyphPaint, GlyphRadialGradient,
+    GlyphSweepGradient, PathGlyphLayer,
+};
+use crate::opentype::requires::delta_set::DeltaSetIndexMap;
+use crate::opentype::requires::var_store::ItemVariationStore;
+
 #[derive(Debug, Clone)]
 pub(crate) struct COLR {
     version: u16,
@@ -12,22 +20,200 @@ pub(crate) struct COLR {
     layer_records: Vec<LayerRecord>,
     num_layers: u16,
     // version 1
-    /*
-    layers: Vec<Layer>,
-    clip_list: Vec<u16>,
-    ver_index_maps: Vec<VerIndexMap>,
-    item_variations_store: ItemVariationsStore,
-     */
+    paint_graph: Option<PaintGraph>,
+}
+
+/// Raw COLRv1 data; paints are resolved on demand for one instance.
+#[derive(Debug, Clone)]
+struct PaintGraph {
+    data: Vec<u8>,
+    base_glyph_list_offset: usize,
+    layer_list_offset: usize,
+    clip_list_offset: usize,
+    var_index_map_offset: usize,
+    variation_store: Option<ItemVariationStore>,
+}
+
+/// One COLRv1 paint with its variation deltas applied. Coordinates are in
+/// font units, y up.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Paint {
+    Layers(Vec<Paint>),
+    Solid {
+        palette_index: u16,
+        alpha: f32,
+    },
+    LinearGradient {
+        color_line: ColorLine,
+        p0: (f32, f32),
+        p1: (f32, f32),
+        /// Rotation point; the gradient runs perpendicular to `p0`-`p2`.
+        p2: (f32, f32),
+    },
+    RadialGradient {
+        color_line: ColorLine,
+        c0: (f32, f32),
+        r0: f32,
+        c1: (f32, f32),
+        r1: f32,
+    },
+    SweepGradient {
+        color_line: ColorLine,
+        center: (f32, f32),
+        /// Degrees, counter-clockwise.
+        start_angle: f32,
+        end_angle: f32,
+    },
+    Glyph {
+        glyph_id: u16,
+        paint: Box<Paint>,
+    },
+    /// A PaintColrGlyph, resolved to the referenced glyph's paint and clip box.
+    ColrGlyph {
+        glyph_id: u16,
+        clip_box: Option<ClipBox>,
+        paint: Box<Paint>,
+    },
+    /// Affine `[xx, yx, xy, yy, dx, dy]`; every transform format maps to one.
+    Transform {
+        matrix: [f32; 6],
+        paint: Box<Paint>,
+    },
+    Composite {
+        source: Box<Paint>,
+        mode: CompositeMode,
+        backdrop: Box<Paint>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ColorLine {
+    pub(crate) extend: Extend,
+    pub(crate) stops: Vec<ColorStop>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ColorStop {
+    pub(crate) offset: f32,
+    pub(crate) palette_index: u16,
+    pub(crate) alpha: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Extend {
+    Pad,
+    Repeat,
+    Reflect,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompositeMode {
+    Clear,
+    Src,
+    Dest,
+    SrcOver,
+    DestOver,
+    SrcIn,
+    DestIn,
+    SrcOut,
+    DestOut,
+    SrcAtop,
+    DestAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositeMode {
+    fn from_u8(value: u8) -> Option<Self> {
+        const MODES: [CompositeMode; 28] = [
+            CompositeMode::Clear,
+            CompositeMode::Src,
+            CompositeMode::Dest,
+            CompositeMode::SrcOver,
+            CompositeMode::DestOver,
+            CompositeMode::SrcIn,
+            CompositeMode::DestIn,
+            CompositeMode::SrcOut,
+            CompositeMode::DestOut,
+            CompositeMode::SrcAtop,
+            CompositeMode::DestAtop,
+            CompositeMode::Xor,
+            CompositeMode::Plus,
+            CompositeMode::Screen,
+            CompositeMode::Overlay,
+            CompositeMode::Darken,
+            CompositeMode::Lighten,
+            CompositeMode::ColorDodge,
+            CompositeMode::ColorBurn,
+            CompositeMode::HardLight,
+            CompositeMode::SoftLight,
+            CompositeMode::Difference,
+            CompositeMode::Exclusion,
+            CompositeMode::Multiply,
+            CompositeMode::Hue,
+            CompositeMode::Saturation,
+            CompositeMode::Color,
+            CompositeMode::Luminosity,
+        ];
+        MODES.get(value as usize).copied()
+    }
+}
+
+/// Clip rectangle in font units, y up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ClipBox {
+    pub(crate) x_min: f32,
+    pub(crate) y_min: f32,
+    pub(crate) x_max: f32,
+    pub(crate) y_max: f32,
+}
+
+/// Nesting limit for paint graphs; deeper graphs are treated as malformed.
+const MAX_PAINT_DEPTH: usize = 64;
+/// Upper bound on paints visited per glyph, so shared sub-graphs cannot blow up.
+const MAX_PAINT_COUNT: usize = 10_000;
+
 impl COLR {
-    pub(crate) fn new<R: BinaryReader>(reader: &mut R, offset: u32, _: u32) -> Result<Self, Error> {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u32,
+        length: u32,
+    ) -> Result<Self, Error> {
         reader.seek(SeekFrom::Start(offset as u64))?;
         let version = reader.read_u16_be()?;
         let num_base_glyphs = reader.read_u16_be()?;
         let base_glyph_records_offset = reader.read_u32_be()?;
         let layer_records_offset = reader.read_u32_be()?;
         let num_layers = reader.read_u16_be()?;
+        let paint_graph = if version >= 1 {
+            let offsets = [
+                reader.read_u32_be()? as usize,
+                reader.read_u32_be()? as usize,
+                reader.read_u32_be()? as usize,
+                reader.read_u32_be()? as usize,
+                reader.read_u32_be()? as usize,
+            ];
+            reader.seek(SeekFrom::Start(offset as u64))?;
+            let data = reader.read_bytes_as_vec(length as usize)?;
+            Some(PaintGraph::new(data, offsets))
+        } else {
+            None
+        };
         reader.seek(SeekFrom::Start((offset + base_glyph_records_offset) as u64))?;
         let mut base_glyph_records = Vec::new();
         for _ in 0..num_base_glyphs {
@@ -53,9 +239,39 @@ impl COLR {
             base_glyph_records,
             layer_records,
             num_layers,
+            paint_graph,
         })
     }
 
+    /// Whether `glyph_id` has color layers or a paint graph.
+    pub(crate) fn has_glyph(&self, glyph_id: u16) -> bool {
+        self.has_paint(glyph_id) || !self.get_layer_record(glyph_id).is_empty()
+    }
+
+    /// Whether `glyph_id` has a COLRv1 paint graph.
+    pub(crate) fn has_paint(&self, glyph_id: u16) -> bool {
+        self.paint_graph
+            .as_ref()
+            .is_some_and(|graph| graph.base_paint_offset(glyph_id).is_some())
+    }
+
+    /// The COLRv1 paint graph of `glyph_id` at normalized `coordinates`.
+    pub(crate) fn paint(&self, glyph_id: u16, coordinates: &[f32]) -> Option<Paint> {
+        let graph = self.paint_graph.as_ref()?;
+        let mut context = PaintContext {
+            coordinates,
+            depth: 0,
+            visited: 0,
+            glyph_stack: vec![glyph_id],
+        };
+        graph.parse_paint(graph.base_paint_offset(glyph_id)?, &mut context)
+    }
+
+    /// The COLRv1 ClipBox of `glyph_id` at normalized `coordinates`.
+    pub(crate) fn clip_box(&self, glyph_id: u16, coordinates: &[f32]) -> Option<ClipBox> {
+        self.paint_graph.as_ref()?.clip_box(glyph_id, coordinates)
+    }
+
     pub(crate) fn to_string(&self) -> String {
         let mut string = "COLR Table\n".to_string();
         string.push_str(&format!("version: {}\n", self.version));
@@ -110,6 +326,910 @@ impl COLR {
     }
 }
 
+struct PaintContext<'a> {
+    coordinates: &'a [f32],
+    depth: usize,
+    visited: usize,
+    /// PaintColrGlyph chain, to stop cycles.
+    glyph_stack: Vec<u16>,
+}
+
+impl PaintGraph {
+    fn new(data: Vec<u8>, offsets: [usize; 5]) -> Self {
+        let [base_glyph_list_offset, layer_list_offset, clip_list_offset, var_index_map_offset, variation_store_offset] =
+            offsets;
+        let variation_store = (variation_store_offset != 0)
+            .then(|| data.get(variation_store_offset..))
+            .flatten()
+            .and_then(|store| ItemVariationStore::parse(store).ok());
+        Self {
+            data,
+            base_glyph_list_offset,
+            layer_list_offset,
+            clip_list_offset,
+            var_index_map_offset,
+            variation_store,
+        }
+    }
+
+    fn base_paint_offset(&self, glyph_id: u16) -> Option<usize> {
+        if self.base_glyph_list_offset == 0 {
+            return None;
+        }
+        let list = self.base_glyph_list_offset;
+        let count = self.u32(list)? as usize;
+        let (mut low, mut high) = (0usize, count);
+        while low < high {
+            let middle = (low + high) / 2;
+            let record = list + 4 + middle * 6;
+            let record_glyph = self.u16(record)?;
+            if record_glyph == glyph_id {
+                return Some(list + self.u32(record + 2)? as usize);
+            } else if record_glyph < glyph_id {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        None
+    }
+
+    fn layer_paint_offset(&self, index: usize) -> Option<usize> {
+        if self.layer_list_offset == 0 {
+            return None;
+        }
+        let list = self.layer_list_offset;
+        if index >= self.u32(list)? as usize {
+            return None;
+        }
+        Some(list + self.u32(list + 4 + index * 4)? as usize)
+    }
+
+    fn clip_box(&self, glyph_id: u16, coordinates: &[f32]) -> Option<ClipBox> {
+        if self.clip_list_offset == 0 {
+            return None;
+        }
+        let list = self.clip_list_offset;
+        let count = self.u32(list + 1)? as usize;
+        for index in 0..count {
+            let record = list + 5 + index * 7;
+            let (start, end) = (self.u16(record)?, self.u16(record + 2)?);
+            if (start..=end).contains(&glyph_id) {
+                let offset = list + self.u24(record + 4)? as usize;
+                let format = *self.data.get(offset)?;
+                let var_index_base = if format == 2 {
+                    self.u32(offset + 9)?
+                } else {
+                    u32::MAX
+                };
+                let value = |field: usize| {
+                    Some(
+                        self.i16(offset + 1 + field * 2)? as f32
+                            + self.delta(var_index_base, field as u32, coordinates),
+                    )
+                };
+                return Some(ClipBox {
+                    x_min: value(0)?,
+                    y_min: value(1)?,
+                    x_max: value(2)?,
+                    y_max: value(3)?,
+                });
+            }
+        }
+        None
+    }
+
+    fn parse_paint(&self, offset: usize, context: &mut PaintContext<'_>) -> Option<Paint> {
+        if context.depth >= MAX_PAINT_DEPTH || context.visited >= MAX_PAINT_COUNT {
+            return None;
+        }
+        context.depth += 1;
+        context.visited += 1;
+        let paint = self.parse_paint_inner(offset, context);
+        context.depth -= 1;
+        paint
+    }
+
+    fn parse_paint_inner(&self, offset: usize, context: &mut PaintContext<'_>) -> Option<Paint> {
+        let format = *self.data.get(offset)?;
+        let coordinates = context.coordinates;
+        // Variable formats are odd and carry a trailing VarIndexBase.
+        let var_index_base = |size: usize| {
+            if format % 2 == 1 {
+                self.u32(offset + size)
+            } else {
+                Some(u32::MAX)
+            }
+        };
+        let child = |context: &mut PaintContext<'_>| {
+            let child_offset = offset + self.u24(offset + 1)? as usize;
+            self.parse_paint(child_offset, context).map(Box::new)
+        };
+        let fword = |at: usize, base: u32, index: u32| {
+            Some(self.i16(offset + at)? as f32 + self.delta(base, index, coordinates))
+        };
+        let f2dot14 = |at: usize, base: u32, index: u32| {
+            Some((self.i16(offset + at)? as f32 + self.delta(base, index, coordinates)) / 16384.0)
+        };
+
+        match format {
+            1 => {
+                let count = *self.data.get(offset + 1)? as usize;
+                let first = self.u32(offset + 2)? as usize;
+                let layers = (first..first + count)
+                    .filter_map(|index| self.parse_paint(self.layer_paint_offset(index)?, context))
+                    .collect();
+                Some(Paint::Layers(layers))
+            }
+            2 | 3 => {
+                let base = var_index_base(5)?;
+                Some(Paint::Solid {
+                    palette_index: self.u16(offset + 1)?,
+                    alpha: f2dot14(3, base, 0)?,
+                })
+            }
+            4 | 5 => {
+                let base = var_index_base(16)?;
+                let point = |index: u32| {
+                    let at = 4 + index as usize * 4;
+                    Some((
+                        fword(at, base, index * 2)?,
+                        fword(at + 2, base, index * 2 + 1)?,
+                    ))
+                };
+                Some(Paint::LinearGradient {
+                    color_line: self.color_line(offset, format == 5, coordinates)?,
+                    p0: point(0)?,
+                    p1: point(1)?,
+                    p2: point(2)?,
+                })
+            }
+            6 | 7 => {
+                let base = var_index_base(16)?;
+                Some(Paint::RadialGradient {
+                    color_line: self.color_line(offset, format == 7, coordinates)?,
+                    c0: (fword(4, base, 0)?, fword(6, base, 1)?),
+                    r0: self.u16(offset + 8)? as f32 + self.delta(base, 2, coordinates),
+                    c1: (fword(10, base, 3)?, fword(12, base, 4)?),
+                    r1: self.u16(offset + 14)? as f32 + self.delta(base, 5, coordinates),
+                })
+            }
+            8 | 9 => {
+                let base = var_index_base(12)?;
+                // Angles are biased by 1.0 so that -1..1 covers a full turn.
+                Some(Paint::SweepGradient {
+                    color_line: self.color_line(offset, format == 9, coordinates)?,
+                    center: (fword(4, base, 0)?, fword(6, base, 1)?),
+                    start_angle: (f2dot14(8, base, 2)? + 1.0) * 180.0,
+                    end_angle: (f2dot14(10, base, 3)? + 1.0) * 180.0,
+                })
+            }
+            10 => Some(Paint::Glyph {
+                glyph_id: self.u16(offset + 4)?,
+                paint: child(context)?,
+            }),
+            11 => {
+                let glyph_id = self.u16(offset + 1)?;
+                if context.glyph_stack.contains(&glyph_id) {
+                    return None;
+                }
+                context.glyph_stack.push(glyph_id);
+                let paint = self
+                    .base_paint_offset(glyph_id)
+                    .and_then(|paint_offset| self.parse_paint(paint_offset, context));
+                context.glyph_stack.pop();
+                Some(Paint::ColrGlyph {
+                    glyph_id,
+                    clip_box: self.clip_box(glyph_id, coordinates),
+                    paint: Box::new(paint?),
+                })
+            }
+            12 | 13 => {
+                let affine = offset + self.u24(offset + 4)? as usize;
+                let base = if format == 13 {
+                    self.u32(affine + 24)?
+                } else {
+                    u32::MAX
+                };
+                let mut matrix = [0.0f32; 6];
+                for (index, value) in matrix.iter_mut().enumerate() {
+                    *value = (self.u32(affine + index * 4)? as i32 as f32
+                        + self.delta(base, index as u32, coordinates))
+                        / 65536.0;
+                }
+                Some(Paint::Transform {
+                    matrix,
+                    paint: child(context)?,
+                })
+            }
+            14 | 15 => {
+                let base = var_index_base(8)?;
+                Some(Paint::Transform {
+                    matrix: [1.0, 0.0, 0.0, 1.0, fword(4, base, 0)?, fword(6, base, 1)?],
+                    paint: child(context)?,
+                })
+            }
+            16..=23 => {
+                let uniform = format >= 20;
+                let around_center = matches!(format, 18 | 19 | 22 | 23);
+                let scale_count = if uniform { 1 } else { 2 };
+                let size = 4 + scale_count * 2 + if around_center { 4 } else { 0 };
+                let base = var_index_base(size)?;
+                let scale_x = f2dot14(4, base, 0)?;
+                let scale_y = if uniform {
+                    scale_x
+                } else {
+                    f2dot14(6, base, 1)?
+                };
+                let center = if around_center {
+                    let at = 4 + scale_count * 2;
+                    let index = scale_count as u32;
+                    (fword(at, base, index)?, fword(at + 2, base, index + 1)?)
+                } else {
+                    (0.0, 0.0)
+                };
+                Some(Paint::Transform {
+                    matrix: around(center, [scale_x, 0.0, 0.0, scale_y, 0.0, 0.0]),
+                    paint: child(context)?,
+                })
+            }
+            24..=27 => {
+                let around_center = format >= 26;
+                let base = var_index_base(if around_center { 10 } else { 6 })?;
+                let angle = f2dot14(4, base, 0)? * std::f32::consts::PI;
+                let center = if around_center {
+                    (fword(6, base, 1)?, fword(8, base, 2)?)
+                } else {
+                    (0.0, 0.0)
+                };
+                let (sin, cos) = angle.sin_cos();
+                Some(Paint::Transform {
+                    matrix: around(center, [cos, sin, -sin, cos, 0.0, 0.0]),
+                    paint: child(context)?,
+                })
+            }
+            28..=31 => {
+                let around_center = format >= 30;
+                let base = var_index_base(if around_center { 12 } else { 8 })?;
+                let x_skew = f2dot14(4, base, 0)? * std::f32::consts::PI;
+                let y_skew = f2dot14(6, base, 1)? * std::f32::consts::PI;
+                let center = if around_center {
+                    (fword(8, base, 2)?, fword(10, base, 3)?)
+                } else {
+                    (0.0, 0.0)
+                };
+                Some(Paint::Transform {
+                    matrix: around(center, [1.0, y_skew.tan(), (-x_skew).tan(), 1.0, 0.0, 0.0]),
+                    paint: child(context)?,
+                })
+            }
+            32 => {
+                let mode = CompositeMode::from_u8(*self.data.get(offset + 4)?)?;
+                let backdrop_offset = offset + self.u24(offset + 5)? as usize;
+                Some(Paint::Composite {
+                    source: child(context)?,
+                    mode,
+                    backdrop: Box::new(self.parse_paint(backdrop_offset, context)?),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads the (Var)ColorLine a gradient paint at `paint_offset` points to.
+    fn color_line(
+        &self,
+        paint_offset: usize,
+        variable: bool,
+        coordinates: &[f32],
+    ) -> Option<ColorLine> {
+        let offset = paint_offset + self.u24(paint_offset + 1)? as usize;
+        let extend = match *self.data.get(offset)? {
+            1 => Extend::Repeat,
+            2 => Extend::Reflect,
+            _ => Extend::Pad,
+        };
+        let count = self.u16(offset + 1)? as usize;
+        let stride = if variable { 10 } else { 6 };
+        let stops = (0..count)
+            .map(|index| {
+                let stop = offset + 3 + index * stride;
+                let base = if variable {
+                    self.u32(stop + 6)?
+                } else {
+                    u32::MAX
+                };
+                Some(ColorStop {
+                    offset: (self.i16(stop)? as f32 + self.delta(base, 0, coordinates)) / 16384.0,
+                    palette_index: self.u16(stop + 2)?,
+                    alpha: (self.i16(stop + 4)? as f32 + self.delta(base, 1, coordinates))
+                        / 16384.0,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(ColorLine { extend, stops })
+    }
+
+    /// Delta for field `index` of a variable paint; 0 without variations.
+    fn delta(&self, var_index_base: u32, index: u32, coordinates: &[f32]) -> f32 {
+        if var_index_base == u32::MAX || coordinates.iter().all(|value| *value == 0.0) {
+            return 0.0;
+        }
+        let Some(store) = self.variation_store.as_ref() else {
+            return 0.0;
+        };
+        let var_index = var_index_base.saturating_add(index);
+        let mapped = if self.var_index_map_offset != 0 {
+            self.data
+                .get(self.var_index_map_offset..)
+                .and_then(|data| DeltaSetIndexMap::new(data).map(var_index))
+        } else {
+            Some(((var_index >> 16) as u16, var_index as u16))
+        };
+        mapped
+            .and_then(|(outer, inner)| store.parse_delta(outer, inner, coordinates))
+            .unwrap_or(0.0)
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        Some(u16::from_be_bytes(
+            self.data.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    }
+
+    fn i16(&self, offset: usize) -> Option<i16> {
+        self.u16(offset).map(|value| value as i16)
+    }
+
+    fn u24(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 3)?;
+        Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        Some(u32::from_be_bytes(
+            self.data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    }
+}
+
+/// `matrix` applied around `center` instead of the origin.
+fn around(center: (f32, f32), matrix: [f32; 6]) -> [f32; 6] {
+    let [xx, yx, xy, yy, _, _] = matrix;
+    let (cx, cy) = center;
+    [
+        xx,
+        yx,
+        xy,
+        yy,
+        cx - (xx * cx + xy * cy),
+        cy - (yx * cx + yy * cy),
+    ]
+}
+
+/// Flattens a paint graph into path layers.
+///
+/// `matrix` maps paint space (font units, y up) to glyph space, `outline`
+/// returns glyph outlines in font units with y down, and `color` resolves a
+/// palette index to `0xAARRGGBB` (`None` for the foreground color).
+///
+/// Porter-Duff composites and clips nested deeper than a layer's own clip
+/// become layer masks.
+pub(crate) fn paint_to_layers(
+    paint: &Paint,
+    matrix: [f32; 6],
+    outline: &mut dyn FnMut(u16) -> Option<Vec<Command>>,
+    color: &dyn Fn(u16) -> Option<u32>,
+) -> Vec<PathGlyphLayer> {
+    let mut flattener = PaintFlattener {
+        outline,
+        color,
+        layers: Vec::new(),
+        clips: Vec::new(),
+        masks: Vec::new(),
+    };
+    flattener.walk(paint, matrix, GlyphBlendMode::Normal);
+    flattener.layers
+}
+
+struct PaintFlattener<'a> {
+    outline: &'a mut dyn FnMut(u16) -> Option<Vec<Command>>,
+    color: &'a dyn Fn(u16) -> Option<u32>,
+    layers: Vec<PathGlyphLayer>,
+    /// Active clip outlines in glyph space, outermost first.
+    clips: Vec<Vec<Command>>,
+    /// Active composite masks, outermost first.
+    masks: Vec<GlyphMask>,
+}
+
+/// Gradient stop with a normalized offset; `color` is `None` for an
+/// unresolved foreground entry.
+struct ResolvedStop {
+    offset: f32,
+    color: Option<u32>,
+    alpha: f32,
+}
+
+/// Mask content wider than any glyph, used to invert coverage.
+const MASK_EXTENT: f32 = 100_000.0;
+
+impl PaintFlattener<'_> {
+    fn walk(&mut self, paint: &Paint, matrix: [f32; 6], blend_mode: GlyphBlendMode) {
+        match paint {
+            Paint::Layers(layers) => {
+                for layer in layers {
+                    self.walk(layer, matrix, blend_mode);
+                }
+            }
+            Paint::Glyph { glyph_id, paint } => {
+                let Some(commands) = (self.outline)(*glyph_id) else {
+                    return;
+                };
+                // Outlines come y down; paint space is y up.
+                let flip = multiply(matrix, [1.0, 0.0, 0.0, -1.0, 0.0, 0.0]);
+                self.clips.push(transform_commands(&commands, flip));
+                self.walk(paint, matrix, blend_mode);
+                self.clips.pop();
+            }
+            Paint::ColrGlyph {
+                clip_box, paint, ..
+            } => match clip_box {
+                Some(clip_box) => {
+                    let rectangle = vec![
+                        Command::MoveTo(clip_box.x_min, clip_box.y_min),
+                        Command::Line(clip_box.x_max, clip_box.y_min),
+                        Command::Line(clip_box.x_max, clip_box.y_max),
+                        Command::Line(clip_box.x_min, clip_box.y_max),
+                        Command::Close,
+                    ];
+                    self.clips.push(transform_commands(&rectangle, matrix));
+                    self.walk(paint, matrix, blend_mode);
+                    self.clips.pop();
+                }
+                None => self.walk(paint, matrix, blend_mode),
+            },
+            Paint::Transform {
+                matrix: transform,
+                paint,
+            } => self.walk(paint, multiply(matrix, *transform), blend_mode),
+            Paint::Composite {
+                source,
+                mode,
+                backdrop,
+            } => match mode {
+                CompositeMode::Clear => {}
+                CompositeMode::Src => self.walk(source, matrix, blend_mode),
+                CompositeMode::Dest => self.walk(backdrop, matrix, blend_mode),
+                CompositeMode::DestOver => {
+                    self.walk(source, matrix, blend_mode);
+                    self.walk(backdrop, matrix, blend_mode);
+                }
+                CompositeMode::SrcIn | CompositeMode::SrcOut => {
+                    let inside = *mode == CompositeMode::SrcIn;
+                    let mask = self.coverage_mask(backdrop, matrix, inside);
+                    self.walk_masked(source, matrix, blend_mode, mask);
+                }
+                CompositeMode::DestIn | CompositeMode::DestOut => {
+                    let inside = *mode == CompositeMode::DestIn;
+                    let mask = self.coverage_mask(source, matrix, inside);
+                    self.walk_masked(backdrop, matrix, blend_mode, mask);
+                }
+                CompositeMode::SrcAtop => {
+                    self.walk(backdrop, matrix, blend_mode);
+                    let mask = self.coverage_mask(backdrop, matrix, true);
+                    self.walk_masked(source, matrix, blend_mode, mask);
+                }
+                // The two halves are drawn one over the other, which is exact
+                // where coverage is fully opaque or empty.
+                CompositeMode::DestAtop | CompositeMode::Xor => {
+                    let mask = self.coverage_mask(backdrop, matrix, false);
+                    self.walk_masked(source, matrix, blend_mode, mask);
+                    let inside = *mode == CompositeMode::DestAtop;
+                    let mask = self.coverage_mask(source, matrix, inside);
+                    self.walk_masked(backdrop, matrix, blend_mode, mask);
+                }
+                // Plus is drawn as source-over; separable and non-separable
+                // blends map to the layer blend mode.
+                mode => {
+                    self.walk(backdrop, matrix, blend_mode);
+                    self.walk(source, matrix, blend_mode_for(*mode).unwrap_or(blend_mode));
+                }
+            },
+            Paint::Solid {
+                palette_index,
+                alpha,
+            } => match (self.color)(*palette_index) {
+                Some(color) => {
+                    self.fill(GlyphPaint::Solid(with_alpha(color, *alpha)), blend_mode);
+                }
+                None => {
+                    let alpha = alpha.clamp(0.0, 1.0);
+                    if let Some(layer) = self.fill(GlyphPaint::CurrentColor, blend_mode) {
+                        layer.opacity = alpha;
+                    }
+                }
+            },
+            Paint::LinearGradient {
+                color_line,
+                p0,
+                p1,
+                p2,
+            } => {
+                // The gradient line runs from p0 to p1 projected onto the
+                // normal of p0-p2.
+                let normal = (p2.1 - p0.1, p0.0 - p2.0);
+                let length = normal.0 * normal.0 + normal.1 * normal.1;
+                let p3 = if length > 0.0 {
+                    let scale = ((p1.0 - p0.0) * normal.0 + (p1.1 - p0.1) * normal.1) / length;
+                    (p0.0 + normal.0 * scale, p0.1 + normal.1 * scale)
+                } else {
+                    *p1
+                };
+                let Some((stops, first, last)) = self.stops(color_line) else {
+                    return;
+                };
+                let at = |t: f32| (p0.0 + (p3.0 - p0.0) * t, p0.1 + (p3.1 - p0.1) * t);
+                let ((x1, y1), (x2, y2)) = (at(first), at(last));
+                self.fill_gradient(&stops, blend_mode, |stops| {
+                    GlyphPaint::LinearGradient(GlyphLinearGradient {
+                        x1,
+                        y1,
+                        x2,
+                        y2,
+                        units: GlyphGradientUnits::UserSpaceOnUse,
+                        transform: matrix,
+                        spread: spread(color_line.extend),
+                        stops,
+                    })
+                });
+            }
+            Paint::RadialGradient {
+                color_line,
+                c0,
+                r0,
+                c1,
+                r1,
+            } => {
+                let Some((stops, first, last)) = self.stops(color_line) else {
+                    return;
+                };
+                let at = |t: f32| {
+                    (
+                        c0.0 + (c1.0 - c0.0) * t,
+                        c0.1 + (c1.1 - c0.1) * t,
+                        (r0 + (r1 - r0) * t).max(0.0),
+                    )
+                };
+                let ((fx, fy, fr), (cx, cy, r)) = (at(first), at(last));
+                self.fill_gradient(&stops, blend_mode, |stops| {
+                    GlyphPaint::RadialGradient(GlyphRadialGradient {
+                        cx,
+                        cy,
+                        r,
+                        fx,
+                        fy,
+                        fr,
+                        units: GlyphGradientUnits::UserSpaceOnUse,
+                        transform: matrix,
+                        spread: spread(color_line.extend),
+                        stops,
+                    })
+                });
+            }
+            Paint::SweepGradient {
+                color_line,
+                center,
+                start_angle,
+                end_angle,
+            } => {
+                let Some((stops, first, last)) = self.stops(color_line) else {
+                    return;
+                };
+                let at = |t: f32| start_angle + (end_angle - start_angle) * t;
+                self.fill_gradient(&stops, blend_mode, |stops| {
+                    GlyphPaint::SweepGradient(GlyphSweepGradient {
+                        cx: center.0,
+                        cy: center.1,
+                        start_angle: at(first),
+                        end_angle: at(last),
+                        transform: matrix,
+                        spread: spread(color_line.extend),
+                        stops,
+                    })
+                });
+            }
+        }
+    }
+
+    /// Walks `paint` with `mask` applied to every layer it draws.
+    fn walk_masked(
+        &mut self,
+        paint: &Paint,
+        matrix: [f32; 6],
+        blend_mode: GlyphBlendMode,
+        mask: Option<GlyphMask>,
+    ) {
+        // Nothing is inside an empty backdrop.
+        let Some(mask) = mask else {
+            return;
+        };
+        self.masks.push(mask);
+        self.walk(paint, matrix, blend_mode);
+        self.masks.pop();
+    }
+
+    /// Mask covering what `paint` draws, or its complement when
+    /// `inside` is false. `None` when `paint` draws nothing and `inside`.
+    fn coverage_mask(
+        &mut self,
+        paint: &Paint,
+        matrix: [f32; 6],
+        inside: bool,
+    ) -> Option<GlyphMask> {
+        let drawn = std::mem::take(&mut self.layers);
+        self.walk(paint, matrix, GlyphBlendMode::Normal);
+        let mut layers = std::mem::replace(&mut self.layers, drawn);
+        for layer in &mut layers {
+            layer.blend_mode = GlyphBlendMode::Normal;
+        }
+        if inside {
+            return (!layers.is_empty()).then_some(GlyphMask {
+                mode: GlyphMaskMode::Alpha,
+                layers,
+            });
+        }
+        // White everywhere, with the coverage painted black on top.
+        let extent = vec![
+            Command::MoveTo(-MASK_EXTENT, -MASK_EXTENT),
+            Command::Line(MASK_EXTENT, -MASK_EXTENT),
+            Command::Line(MASK_EXTENT, MASK_EXTENT),
+            Command::Line(-MASK_EXTENT, MASK_EXTENT),
+            Command::Close,
+        ];
+        let mut inverted = vec![PathGlyphLayer::new(extent, GlyphPaint::Solid(0xffff_ffff))];
+        inverted.extend(layers.into_iter().map(|mut layer| {
+            layer.paint = black(layer.paint);
+            layer
+        }));
+        Some(GlyphMask {
+            mode: GlyphMaskMode::Luminance,
+            layers: inverted,
+        })
+    }
+
+    /// Fills the innermost clip; the next one out becomes the layer clip and
+    /// the rest, with the composite masks, become its mask.
+    fn fill(
+        &mut self,
+        paint: GlyphPaint,
+        blend_mode: GlyphBlendMode,
+    ) -> Option<&mut PathGlyphLayer> {
+        let count = self.clips.len();
+        let commands = self.clips.last()?;
+        let mut layer = PathGlyphLayer::new(commands.clone(), paint);
+        if count > 1 {
+            layer.clip_commands = self.clips[count - 2].clone();
+        }
+        let outer_clips = self.clips[..count.saturating_sub(2)]
+            .iter()
+            .map(|clip| GlyphMask {
+                mode: GlyphMaskMode::Alpha,
+                layers: vec![PathGlyphLayer::new(
+                    clip.clone(),
+                    GlyphPaint::Solid(0xff00_0000),
+                )],
+            });
+        for mask in outer_clips.chain(self.masks.iter().cloned()) {
+            attach_mask(&mut layer, mask);
+        }
+        layer.blend_mode = blend_mode;
+        self.layers.push(layer);
+        self.layers.last_mut()
+    }
+
+    /// Fills a gradient. Foreground stops without a color are drawn as
+    /// `CurrentColor` through an alpha mask of their stops, over the other
+    /// stops; the result matches the gradient at every stop.
+    fn fill_gradient(
+        &mut self,
+        stops: &[ResolvedStop],
+        blend_mode: GlyphBlendMode,
+        gradient: impl Fn(Vec<GlyphGradientStop>) -> GlyphPaint,
+    ) {
+        let stop = |resolved: &ResolvedStop, color: u32| GlyphGradientStop {
+            offset: resolved.offset,
+            color,
+        };
+        if stops.iter().all(|stop| stop.color.is_some()) {
+            let stops = stops
+                .iter()
+                .map(|resolved| {
+                    stop(
+                        resolved,
+                        with_alpha(resolved.color.unwrap_or(0), resolved.alpha),
+                    )
+                })
+                .collect();
+            self.fill(gradient(stops), blend_mode);
+            return;
+        }
+        if stops.iter().any(|stop| stop.color.is_some()) {
+            // Clear stops take the color of the nearest colored stop so the
+            // interpolation does not fade through black.
+            let nearest = |index: usize| {
+                (0..stops.len())
+                    .filter_map(|other| Some((index.abs_diff(other), stops[other].color?)))
+                    .min_by_key(|(distance, _)| *distance)
+                    .map_or(0, |(_, color)| color & 0x00ff_ffff)
+            };
+            let colored = stops
+                .iter()
+                .enumerate()
+                .map(|(index, resolved)| match resolved.color {
+                    Some(color) => stop(resolved, with_alpha(color, resolved.alpha)),
+                    None => stop(resolved, nearest(index)),
+                })
+                .collect();
+            self.fill(gradient(colored), blend_mode);
+        }
+        let Some(commands) = self.clips.last() else {
+            return;
+        };
+        let coverage = stops
+            .iter()
+            .map(|resolved| match resolved.color {
+                Some(_) => stop(resolved, 0),
+                None => stop(resolved, with_alpha(0xff00_0000, resolved.alpha)),
+            })
+            .collect();
+        let mask = GlyphMask {
+            mode: GlyphMaskMode::Alpha,
+            layers: vec![PathGlyphLayer::new(commands.clone(), gradient(coverage))],
+        };
+        if let Some(layer) = self.fill(GlyphPaint::CurrentColor, blend_mode) {
+            attach_mask(layer, mask);
+        }
+    }
+
+    /// Sorted stops normalized to 0..1, with the original offset range.
+    fn stops(&self, color_line: &ColorLine) -> Option<(Vec<ResolvedStop>, f32, f32)> {
+        let mut stops = color_line.stops.clone();
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+        let first = stops.first()?.offset;
+        let last = stops.last()?.offset;
+        let span = last - first;
+        let stops = stops
+            .iter()
+            .map(|stop| {
+                let offset = if span > 0.0 {
+                    (stop.offset - first) / span
+                } else {
+                    0.0
+                };
+                ResolvedStop {
+                    offset,
+                    color: (self.color)(stop.palette_index),
+                    alpha: stop.alpha,
+                }
+            })
+            .collect();
+        Some((stops, first, if span > 0.0 { last } else { first + 1.0 }))
+    }
+}
+
+/// Intersects `mask` with the layer's mask; a layer that is already masked
+/// passes it on to its mask content.
+fn attach_mask(layer: &mut PathGlyphLayer, mask: GlyphMask) {
+    match &mut layer.mask {
+        Some(existing) => {
+            for content in &mut existing.layers {
+                attach_mask(content, mask.clone());
+            }
+        }
+        None => layer.mask = Some(mask),
+    }
+}
+
+/// The same paint in black, keeping its alpha.
+fn black(paint: GlyphPaint) -> GlyphPaint {
+    let black = |color: u32| color & 0xff00_0000;
+    let stops = |stops: &mut Vec<GlyphGradientStop>| {
+        for stop in stops {
+            stop.color = black(stop.color);
+        }
+    };
+    match paint {
+        GlyphPaint::Solid(color) => GlyphPaint::Solid(black(color)),
+        GlyphPaint::CurrentColor => GlyphPaint::Solid(0xff00_0000),
+        GlyphPaint::LinearGradient(mut gradient) => {
+            stops(&mut gradient.stops);
+            GlyphPaint::LinearGradient(gradient)
+        }
+        GlyphPaint::RadialGradient(mut gradient) => {
+            stops(&mut gradient.stops);
+            GlyphPaint::RadialGradient(gradient)
+        }
+        GlyphPaint::SweepGradient(mut gradient) => {
+            stops(&mut gradient.stops);
+            GlyphPaint::SweepGradient(gradient)
+        }
+    }
+}
+
+fn blend_mode_for(mode: CompositeMode) -> Option<GlyphBlendMode> {
+    Some(match mode {
+        CompositeMode::Multiply => GlyphBlendMode::Multiply,
+        CompositeMode::Screen => GlyphBlendMode::Screen,
+        CompositeMode::Overlay => GlyphBlendMode::Overlay,
+        CompositeMode::Darken => GlyphBlendMode::Darken,
+        CompositeMode::Lighten => GlyphBlendMode::Lighten,
+        CompositeMode::ColorDodge => GlyphBlendMode::ColorDodge,
+        CompositeMode::ColorBurn => GlyphBlendMode::ColorBurn,
+        CompositeMode::HardLight => GlyphBlendMode::HardLight,
+        CompositeMode::SoftLight => GlyphBlendMode::SoftLight,
+        CompositeMode::Difference => GlyphBlendMode::Difference,
+        CompositeMode::Exclusion => GlyphBlendMode::Exclusion,
+        CompositeMode::Hue => GlyphBlendMode::Hue,
+        CompositeMode::Saturation => GlyphBlendMode::Saturation,
+        CompositeMode::Color => GlyphBlendMode::Color,
+        CompositeMode::Luminosity => GlyphBlendMode::Luminosity,
+        _ => return None,
+    })
+}
+
+fn spread(extend: Extend) -> GlyphGradientSpread {
+    match extend {
+        Extend::Pad => GlyphGradientSpread::Pad,
+        Extend::Repeat => GlyphGradientSpread::Repeat,
+        Extend::Reflect => GlyphGradientSpread::Reflect,
+    }
+}
+
+fn with_alpha(color: u32, alpha: f32) -> u32 {
+    let value = ((color >> 24) as f32 * alpha.clamp(0.0, 1.0)).round() as u32;
+    (value << 24) | (color & 0x00ff_ffff)
+}
+
+/// `first` applied after `second`.
+fn multiply(first: [f32; 6], second: [f32; 6]) -> [f32; 6] {
+    let [a, b, c, d, e, f] = first;
+    let [na, nb, nc, nd, ne, nf] = second;
+    [
+        a * na + c * nb,
+        b * na + d * nb,
+        a * nc + c * nd,
+        b * nc + d * nd,
+        a * ne + c * nf + e,
+        b * ne + d * nf + f,
+    ]
+}
+
+fn transform_commands(commands: &[Command], matrix: [f32; 6]) -> Vec<Command> {
+    let [a, b, c, d, e, f] = matrix;
+    let point = |(x, y): (f32, f32)| (a * x + c * y + e, b * x + d * y + f);
+    commands
+        .iter()
+        .map(|command| match *command {
+            Command::MoveTo(x, y) => {
+                let (x, y) = point((x, y));
+                Command::MoveTo(x, y)
+            }
+            Command::Line(x, y) => {
+                let (x, y) = point((x, y));
+                Command::Line(x, y)
+            }
+            Command::Bezier(control, (x, y)) => {
+                let (x, y) = point((x, y));
+                Command::Bezier(point(control), (x, y))
+            }
+            Command::CubicBezier(first, second, end) => {
+                Command::CubicBezier(point(first), point(second), point(end))
+            }
+            Command::Close => Command::Close,
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 
 pub(crate) struct BaseGlyphRecord {
@@ -151,8 +1271,228 @@ mod tests {
                 palette_index: 0,
             }],
             num_layers: 1,
+            paint_graph: None,
         };
 
         assert!(colr.get_layer_record(7).is_empty());
     }
+
+    /// COLRv1 table: glyph 5 -> PaintGlyph(9) -> PaintTranslate -> sweep,
+    /// with a ClipBox for glyph 5.
+    fn colr_v1_table() -> Vec<u8> {
+        let mut data = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for offset in [34u32, 0, 85, 0, 0] {
+            data.extend_from_slice(&offset.to_be_bytes());
+        }
+        // BaseGlyphList
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 5, 0, 0, 0, 10]);
+        // PaintGlyph, PaintTranslate(10, -5), PaintSweepGradient
+        data.extend_from_slice(&[10, 0, 0, 6, 0, 9]);
+        data.extend_from_slice(&[14, 0, 0, 8, 0, 10, 0xff, 0xfb]);
+        data.extend_from_slice(&[8, 0, 0, 12, 0, 50, 0, 60, 0, 0, 0x40, 0]);
+        // ColorLine
+        data.extend_from_slice(&[0, 0, 2, 0, 0, 0, 0, 0x40, 0, 0x40, 0, 0, 1, 0x20, 0]);
+        // ClipList
+        data.extend_from_slice(&[1, 0, 0, 0, 1, 0, 5, 0, 5, 0, 0, 12]);
+        data.extend_from_slice(&[1, 0xff, 0xf6, 0xff, 0xec, 0, 100, 0, 200]);
+        data
+    }
+
+    #[test]
+    fn colr_v1_parses_paint_graph_and_clip_box() {
+        let data = colr_v1_table();
+        let mut reader = BytesReader::new(&data);
+        let colr = COLR::new(&mut reader, 0, data.len() as u32).expect("COLRv1");
+
+        assert!(colr.has_glyph(5));
+        assert!(!colr.has_glyph(6));
+        let sweep = Paint::SweepGradient {
+            color_line: ColorLine {
+                extend: Extend::Pad,
+                stops: vec![
+                    ColorStop {
+                        offset: 0.0,
+                        palette_index: 0,
+                        alpha: 1.0,
+                    },
+                    ColorStop {
+                        offset: 1.0,
+                        palette_index: 1,
+                        alpha: 0.5,
+                    },
+                ],
+            },
+            center: (50.0, 60.0),
+            start_angle: 180.0,
+            end_angle: 360.0,
+        };
+        assert_eq!(
+            colr.paint(5, &[]),
+            Some(Paint::Glyph {
+                glyph_id: 9,
+                paint: Box::new(Paint::Transform {
+                    matrix: [1.0, 0.0, 0.0, 1.0, 10.0, -5.0],
+                    paint: Box::new(sweep),
+                }),
+            })
+        );
+        assert_eq!(
+            colr.clip_box(5, &[]),
+            Some(ClipBox {
+                x_min: -10.0,
+                y_min: -20.0,
+                x_max: 100.0,
+                y_max: 200.0,
+            })
+        );
+    }
+
+    #[test]
+    fn colr_v1_stops_colr_glyph_cycles() {
+        let mut data = vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        for offset in [34u32, 0, 0, 0, 0] {
+            data.extend_from_slice(&offset.to_be_bytes());
+        }
+        // Glyph 5 paints glyph 5 through PaintColrGlyph.
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 5, 0, 0, 0, 10, 11, 0, 5]);
+        let mut reader = BytesReader::new(&data);
+        let colr = COLR::new(&mut reader, 0, data.len() as u32).expect("COLRv1");
+
+        assert_eq!(colr.paint(5, &[]), None);
+    }
+
+    #[test]
+    fn paint_to_layers_clips_solid_fill_to_glyph_outline() {
+        let paint = Paint::Composite {
+            source: Box::new(Paint::Glyph {
+                glyph_id: 3,
+                paint: Box::new(Paint::Solid {
+                    palette_index: 0,
+                    alpha: 0.5,
+                }),
+            }),
+            mode: CompositeMode::Multiply,
+            backdrop: Box::new(Paint::Glyph {
+                glyph_id: 3,
+                paint: Box::new(Paint::Solid {
+                    palette_index: 0xffff,
+                    alpha: 1.0,
+                }),
+            }),
+        };
+        let mut outline = |_| Some(vec![Command::MoveTo(1.0, -2.0), Command::Close]);
+        let color = |index: u16| (index == 0).then_some(0xff11_2233);
+
+        let layers = paint_to_layers(
+            &paint,
+            [2.0, 0.0, 0.0, -2.0, 0.0, 0.0],
+            &mut outline,
+            &color,
+        );
+
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].paint, GlyphPaint::CurrentColor);
+        assert_eq!(layers[1].paint, GlyphPaint::Solid(0x8011_2233));
+        assert_eq!(layers[1].blend_mode, GlyphBlendMode::Multiply);
+        assert!(matches!(layers[1].commands[0], Command::MoveTo(x, y) if x == 2.0 && y == -4.0));
+    }
+
+    fn solid_glyph(glyph_id: u16, palette_index: u16, paint: Option<Paint>) -> Paint {
+        Paint::Glyph {
+            glyph_id,
+            paint: Box::new(paint.unwrap_or(Paint::Solid {
+                palette_index,
+                alpha: 1.0,
+            })),
+        }
+    }
+
+    fn layers_of(paint: &Paint) -> Vec<PathGlyphLayer> {
+        let mut outline = |glyph_id: u16| Some(vec![Command::MoveTo(glyph_id as f32, 0.0)]);
+        let color = |index: u16| (index != 0xffff).then_some(0xff00_0000 | index as u32);
+        paint_to_layers(paint, [1.0, 0.0, 0.0, -1.0, 0.0, 0.0], &mut outline, &color)
+    }
+
+    fn first_x(commands: &[Command]) -> Option<f32> {
+        match commands.first()? {
+            Command::MoveTo(x, _) => Some(*x),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn paint_to_layers_masks_clips_below_the_layer_clip() {
+        let inner = solid_glyph(3, 7, None);
+        let paint = solid_glyph(1, 0, Some(solid_glyph(2, 0, Some(inner))));
+
+        let layers = layers_of(&paint);
+
+        assert_eq!(layers.len(), 1);
+        assert_eq!(first_x(&layers[0].commands), Some(3.0));
+        assert_eq!(first_x(&layers[0].clip_commands), Some(2.0));
+        let mask = layers[0].mask.as_ref().expect("outer clip mask");
+        assert_eq!(first_x(&mask.layers[0].commands), Some(1.0));
+    }
+
+    #[test]
+    fn paint_to_layers_masks_porter_duff_composites() {
+        let composite = |mode| Paint::Composite {
+            source: Box::new(solid_glyph(1, 1, None)),
+            mode,
+            backdrop: Box::new(solid_glyph(2, 2, None)),
+        };
+
+        let layers = layers_of(&composite(CompositeMode::SrcIn));
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].paint, GlyphPaint::Solid(0xff00_0001));
+        let mask = layers[0].mask.as_ref().expect("backdrop mask");
+        assert_eq!(mask.mode, GlyphMaskMode::Alpha);
+        assert_eq!(first_x(&mask.layers[0].commands), Some(2.0));
+
+        let layers = layers_of(&composite(CompositeMode::DestOut));
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].paint, GlyphPaint::Solid(0xff00_0002));
+        let mask = layers[0].mask.as_ref().expect("inverted source mask");
+        assert_eq!(mask.mode, GlyphMaskMode::Luminance);
+        assert_eq!(mask.layers[1].paint, GlyphPaint::Solid(0xff00_0000));
+        assert_eq!(first_x(&mask.layers[1].commands), Some(1.0));
+
+        let layers = layers_of(&composite(CompositeMode::Xor));
+        assert_eq!(layers.len(), 2);
+        assert!(layers.iter().all(|layer| layer.mask.is_some()));
+    }
+
+    #[test]
+    fn paint_to_layers_draws_foreground_gradient_stops_with_current_color() {
+        let stop = |offset, palette_index| ColorStop {
+            offset,
+            palette_index,
+            alpha: 1.0,
+        };
+        let gradient = Paint::LinearGradient {
+            color_line: ColorLine {
+                extend: Extend::Pad,
+                stops: vec![stop(0.0, 5), stop(1.0, 0xffff)],
+            },
+            p0: (0.0, 0.0),
+            p1: (10.0, 0.0),
+            p2: (0.0, 10.0),
+        };
+
+        let layers = layers_of(&solid_glyph(1, 0, Some(gradient)));
+
+        assert_eq!(layers.len(), 2);
+        let GlyphPaint::LinearGradient(colored) = &layers[0].paint else {
+            panic!("palette stops stay a gradient");
+        };
+        let colors = colored.stops.iter().map(|stop| stop.color);
+        assert_eq!(colors.collect::<Vec<_>>(), [0xff00_0005, 0x0000_0005]);
+        assert_eq!(layers[1].paint, GlyphPaint::CurrentColor);
+        let mask = layers[1].mask.as_ref().expect("foreground coverage");
+        let GlyphPaint::LinearGradient(coverage) = &mask.layers[0].paint else {
+            panic!("coverage follows the gradient");
+        };
+        let alphas = coverage.stops.iter().map(|stop| stop.color >> 24);
+        assert_eq!(alphas.collect::<Vec<_>>(), [0, 0xff]);
+    }
 }
@@ -14,13 +14,18 @@ pub(crate) struct CPAL {
     color_records: Vec<ColorRecord>,
     color_record_indices: Vec<u16>,
     // version 1
-    /*
-    palette_types: Vec<PaletteType>,
-    palette_labels: Vec<PaletteLabel>,
-    palette_entry_labels: Vec<PaletteEntryLabel>,
-    */
+    palette_types: Vec<u32>,
+    palette_labels: Vec<u16>,
+    palette_entry_labels: Vec<u16>,
 }
 
+/// Palette type flag: usable with a light background.
+pub(crate) const USABLE_WITH_LIGHT_BACKGROUND: u32 = 0x0001;
+/// Palette type flag: usable with a dark background.
+pub(crate) const USABLE_WITH_DARK_BACKGROUND: u32 = 0x0002;
+/// Palette entry index that stands for the text color.
+pub(crate) const FOREGROUND_ENTRY: u16 = 0xFFFF;
+
 impl CPAL {
     pub(crate) fn new<R: BinaryReader>(
         reader: &mut R,
@@ -34,14 +39,19 @@ impl CPAL {
         let num_color_records = reader.read_u16_be()?;
         let color_records_array_offset = reader.read_u32_be()?;
         let mut color_record_indices = Vec::new();
-        let mut max_color_record_index = 0;
         for _ in 0..num_palettes {
-            let color_record_index = reader.read_u16_be()?;
-            if max_color_record_index < color_record_index {
-                max_color_record_index = color_record_index;
-            }
             color_record_indices.push(reader.read_u16_be()?);
         }
+        let (palette_types_offset, palette_labels_offset, palette_entry_labels_offset) =
+            if version >= 1 {
+                (
+                    reader.read_u32_be()?,
+                    reader.read_u32_be()?,
+                    reader.read_u32_be()?,
+                )
+            } else {
+                (0, 0, 0)
+            };
         reader.seek(SeekFrom::Start(
             (offset + color_records_array_offset) as u64,
         ))?;
@@ -57,6 +67,30 @@ impl CPAL {
             color_records.push(color_record);
         }
 
+        let mut palette_types = Vec::new();
+        if palette_types_offset != 0 {
+            reader.seek(SeekFrom::Start((offset + palette_types_offset) as u64))?;
+            for _ in 0..num_palettes {
+                palette_types.push(reader.read_u32_be()?);
+            }
+        }
+        let mut palette_labels = Vec::new();
+        if palette_labels_offset != 0 {
+            reader.seek(SeekFrom::Start((offset + palette_labels_offset) as u64))?;
+            for _ in 0..num_palettes {
+                palette_labels.push(reader.read_u16_be()?);
+            }
+        }
+        let mut palette_entry_labels = Vec::new();
+        if palette_entry_labels_offset != 0 {
+            reader.seek(SeekFrom::Start(
+                (offset + palette_entry_labels_offset) as u64,
+            ))?;
+            for _ in 0..num_palette_entries {
+                palette_entry_labels.push(reader.read_u16_be()?);
+            }
+        }
+
         Ok(Self {
             version,
             num_palette_entries,
@@ -64,6 +98,9 @@ impl CPAL {
             num_color_records,
             color_records,
             color_record_indices,
+            palette_types,
+            palette_labels,
+            palette_entry_labels,
         })
     }
 
@@ -109,8 +146,72 @@ impl CPAL {
 }
 
 impl CPAL {
+    /// Entry `index` of the first palette; missing entries read as opaque black.
     pub(crate) fn get_pallet(&self, index: usize) -> ColorRecord {
-        self.color_records[index].clone()
+        self.color(0, index as u16).cloned().unwrap_or(ColorRecord {
+            red: 0,
+            green: 0,
+            blue: 0,
+            alpha: 0xff,
+        })
+    }
+
+    /// Entry `index` of `palette`, or `None` when either is out of range.
+    pub(crate) fn color(&self, palette: usize, index: u16) -> Option<&ColorRecord> {
+        if index >= self.num_palette_entries {
+            return None;
+        }
+        let first = *self.color_record_indices.get(palette)? as usize;
+        self.color_records.get(first + index as usize)
+    }
+
+    pub(crate) fn num_palettes(&self) -> usize {
+        self.color_record_indices.len()
+    }
+
+    pub(crate) fn num_palette_entries(&self) -> u16 {
+        self.num_palette_entries
+    }
+
+    /// `USABLE_WITH_*` flags of `palette`; 0 for version 0 tables.
+    pub(crate) fn palette_type(&self, palette: usize) -> u32 {
+        self.palette_types.get(palette).copied().unwrap_or(0)
+    }
+
+    /// `name` ID of the palette label, if any.
+    pub(crate) fn palette_label(&self, palette: usize) -> Option<u16> {
+        self.palette_labels
+            .get(palette)
+            .copied()
+            .filter(|name_id| *name_id != 0xFFFF)
+    }
+
+    /// `name` ID of the entry label, if any.
+    pub(crate) fn entry_label(&self, index: u16) -> Option<u16> {
+        self.palette_entry_labels
+            .get(index as usize)
+            .copied()
+            .filter(|name_id| *name_id != 0xFFFF)
+    }
+
+    /// Palette chosen by an explicit index, else by background type, else 0.
+    pub(crate) fn select_palette(&self, palette: Option<usize>, flag: Option<u32>) -> usize {
+        if let Some(palette) = palette.filter(|palette| *palette < self.num_palettes()) {
+            return palette;
+        }
+        flag.and_then(|flag| {
+            (0..self.num_palettes()).find(|palette| self.palette_type(*palette) & flag != 0)
+        })
+        .unwrap_or(0)
+    }
+}
+
+impl ColorRecord {
+    pub(crate) fn argb(&self) -> u32 {
+        ((self.alpha as u32) << 24)
+            | ((self.red as u32) << 16)
+            | ((self.green as u32) << 8)
+            | self.blue as u32
     }
 }
 
@@ -122,3 +223,46 @@ pub(crate) struct ColorRecord {
     pub(crate) blue: u8,
     pub(crate) alpha: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bin_rs::reader::BytesReader;
+
+    /// CPAL 1 with two palettes of two entries; palette 1 is for dark backgrounds.
+    fn cpal_v1_table() -> Vec<u8> {
+        let mut data = vec![0, 1, 0, 2, 0, 2, 0, 4, 0, 0, 0, 30, 0, 0, 0, 2];
+        for offset in [46u32, 54, 58] {
+            data.extend_from_slice(&offset.to_be_bytes());
+        }
+        data.extend_from_slice(&[0, 0]);
+        // Color records (BGRA)
+        data.extend_from_slice(&[
+            1, 2, 3, 0xff, 4, 5, 6, 0xff, 7, 8, 9, 0xff, 10, 11, 12, 0x80,
+        ]);
+        // Palette types, labels and entry labels
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2]);
+        data.extend_from_slice(&[1, 0, 0xff, 0xff]);
+        data.extend_from_slice(&[1, 1, 0xff, 0xff]);
+        data
+    }
+
+    #[test]
+    fn cpal_v1_reads_palettes_types_and_labels() {
+        let data = cpal_v1_table();
+        let mut reader = BytesReader::new(&data);
+        let cpal = CPAL::new(&mut reader, 0, data.len() as u32).expect("CPAL");
+
+        assert_eq!(cpal.num_palettes(), 2);
+        assert_eq!(cpal.color(1, 1).map(ColorRecord::argb), Some(0x800c_0b0a));
+        assert_eq!(cpal.color(0, FOREGROUND_ENTRY).map(ColorRecord::argb), None);
+        assert_eq!(cpal.palette_label(0), Some(256));
+        assert_eq!(cpal.palette_label(1), None);
+        assert_eq!(cpal.entry_label(0), Some(257));
+        assert_eq!(
+            cpal.select_palette(None, Some(USABLE_WITH_DARK_BACKGROUND)),
+            1
+        );
+        assert_eq!(cpal.select_palette(Some(5), None), 0);
+    }
+}
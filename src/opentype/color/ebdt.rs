@@ -0,0 +1,283 @@
+// EBDT / bdat: monochrome and grayscale bitmap glyph data located through EBLC / bloc.
+
+use std::io::{Error, SeekFrom};
+
+use bin_rs::reader::BinaryReader;
+
+use super::cblc::{BigGlyphMetrics, BitmapStrike};
+
+/// Component bitmaps (formats 8 and 9) nest at most this deep.
+const MAX_COMPONENT_DEPTH: usize = 8;
+/// Bitmaps decoded for one glyph, so shared components cannot multiply the work.
+const MAX_COMPONENT_COUNT: usize = 1_000;
+
+/// Glyphs being composed and bitmaps decoded so far for one glyph.
+#[derive(Debug, Default)]
+struct ComponentWalk {
+    glyph_stack: Vec<u16>,
+    visited: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct EBDT {
+    data: Vec<u8>,
+}
+
+/// A decoded glyph bitmap with one coverage byte (0 to 255) per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CoverageBitmap {
+    pub(crate) metrics: BigGlyphMetrics,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) coverage: Vec<u8>,
+}
+
+impl EBDT {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u32,
+        length: u32,
+    ) -> Result<Self, Error> {
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        let data = reader.read_bytes_as_vec(length as usize)?;
+        Ok(Self { data })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_bytes(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    /// Decodes `glyph_id` from `strike`, composing component bitmaps.
+    pub(crate) fn glyph(&self, strike: &BitmapStrike, glyph_id: u16) -> Option<CoverageBitmap> {
+        self.decode(strike, glyph_id, &mut ComponentWalk::default())
+    }
+
+    fn decode(
+        &self,
+        strike: &BitmapStrike,
+        glyph_id: u16,
+        walk: &mut ComponentWalk,
+    ) -> Option<CoverageBitmap> {
+        if walk.glyph_stack.len() > MAX_COMPONENT_DEPTH
+            || walk.visited >= MAX_COMPONENT_COUNT
+            || walk.glyph_stack.contains(&glyph_id)
+        {
+            return None;
+        }
+        walk.visited += 1;
+        walk.glyph_stack.push(glyph_id);
+        let bitmap = self.decode_record(strike, glyph_id, walk);
+        walk.glyph_stack.pop();
+        bitmap
+    }
+
+    fn decode_record(
+        &self,
+        strike: &BitmapStrike,
+        glyph_id: u16,
+        walk: &mut ComponentWalk,
+    ) -> Option<CoverageBitmap> {
+        let location = strike.locate(glyph_id)?;
+        let record = self
+            .data
+            .get(location.offset..location.offset.checked_add(location.length)?)?;
+        let bit_depth = strike.bit_depth;
+        match location.image_format {
+            1 => {
+                let metrics = BigGlyphMetrics::from_small_bytes(record)?;
+                unpack(metrics, &record[5..], bit_depth, true)
+            }
+            2 => {
+                let metrics = BigGlyphMetrics::from_small_bytes(record)?;
+                unpack(metrics, &record[5..], bit_depth, false)
+            }
+            5 => unpack(location.metrics?, record, bit_depth, false),
+            6 => {
+                let metrics = BigGlyphMetrics::from_bytes(record)?;
+                unpack(metrics, &record[8..], bit_depth, true)
+            }
+            7 => {
+                let metrics = BigGlyphMetrics::from_bytes(record)?;
+                unpack(metrics, &record[8..], bit_depth, false)
+            }
+            8 => {
+                // Small metrics are followed by one pad byte.
+                let metrics = BigGlyphMetrics::from_small_bytes(record)?;
+                self.compose(strike, metrics, record.get(6..)?, walk)
+            }
+            9 => {
+                let metrics = BigGlyphMetrics::from_bytes(record)?;
+                self.compose(strike, metrics, record.get(8..)?, walk)
+            }
+            _ => None,
+        }
+    }
+
+    fn compose(
+        &self,
+        strike: &BitmapStrike,
+        metrics: BigGlyphMetrics,
+        components: &[u8],
+        walk: &mut ComponentWalk,
+    ) -> Option<CoverageBitmap> {
+        let count = u16::from_be_bytes(components.get(..2)?.try_into().ok()?) as usize;
+        let width = metrics.width as u32;
+        let height = metrics.height as u32;
+        let mut coverage = vec![0u8; (width * height) as usize];
+        for index in 0..count {
+            let record = components.get(2 + index * 4..6 + index * 4)?;
+            let glyph_id = u16::from_be_bytes([record[0], record[1]]);
+            // Offsets place the component's top-left within the composite.
+            let x_offset = record[2] as i8 as i32;
+            let y_offset = record[3] as i8 as i32;
+            let Some(component) = self.decode(strike, glyph_id, walk) else {
+                continue;
+            };
+            for y in 0..component.height as i32 {
+                let target_y = y + y_offset;
+                if target_y < 0 || target_y >= height as i32 {
+                    continue;
+                }
+                for x in 0..component.width as i32 {
+                    let target_x = x + x_offset;
+                    if target_x < 0 || target_x >= width as i32 {
+                        continue;
+                    }
+                    let source =
+                        component.coverage[(y as u32 * component.width + x as u32) as usize];
+                    let target =
+                        &mut coverage[(target_y as u32 * width + target_x as u32) as usize];
+                    *target = (*target).max(source);
+                }
+            }
+        }
+        Some(CoverageBitmap {
+            metrics,
+            width,
+            height,
+            coverage,
+        })
+    }
+}
+
+/// Expands 1, 2, 4 or 8 bit pixels (most significant bits first) into
+/// coverage; byte-aligned images pad every row to a whole byte.
+fn unpack(
+    metrics: BigGlyphMetrics,
+    image: &[u8],
+    bit_depth: u8,
+    byte_aligned: bool,
+) -> Option<CoverageBitmap> {
+    if !matches!(bit_depth, 1 | 2 | 4 | 8) {
+        return None;
+    }
+    let width = metrics.width as usize;
+    let height = metrics.height as usize;
+    let depth = bit_depth as usize;
+    let row_bits = if byte_aligned {
+        (width * depth).div_ceil(8) * 8
+    } else {
+        width * depth
+    };
+    if row_bits * height > image.len() * 8 {
+        return None;
+    }
+    let max = (1u32 << depth) - 1;
+    let mut coverage = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let bit = y * row_bits + x * depth;
+            let byte = image[bit / 8] as u32;
+            let value = (byte >> (8 - depth - bit % 8)) & max;
+            coverage.push((value * 255 / max) as u8);
+        }
+    }
+    Some(CoverageBitmap {
+        metrics,
+        width: width as u32,
+        height: height as u32,
+        coverage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opentype::color::cblc::CBLC;
+    use bin_rs::reader::BytesReader;
+
+    fn metrics(width: u8, height: u8) -> BigGlyphMetrics {
+        BigGlyphMetrics {
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ebdt_unpacks_byte_aligned_grayscale_rows() {
+        let bitmap = unpack(metrics(3, 2), &[0xD0, 0x2C], 2, true).unwrap();
+        assert_eq!(bitmap.coverage, vec![255, 85, 0, 0, 170, 255]);
+        // Bit-aligned rows continue mid-byte.
+        let bitmap = unpack(metrics(3, 2), &[0b1010_1100], 1, false).unwrap();
+        assert_eq!(bitmap.coverage, vec![255, 0, 255, 0, 255, 255]);
+        assert!(unpack(metrics(3, 2), &[0xD0], 2, true).is_none());
+    }
+
+    /// Glyphs 1 and 2 as format 2 bitmaps, then `composite` as glyph 3 in format 8.
+    fn component_font(composite: &[u8]) -> (EBDT, CBLC) {
+        let mut ebdt = vec![0, 2, 0, 0];
+        // Glyph 1: format 2, 2x2 diagonal. Glyph 2: format 2, 1x3 bar.
+        ebdt.extend_from_slice(&[2, 2, 0, 2, 3, 0x90]);
+        ebdt.extend_from_slice(&[3, 1, 0, 3, 2, 0xE0]);
+        ebdt.extend_from_slice(composite);
+
+        let mut eblc = vec![0, 2, 0, 0, 0, 0, 0, 1];
+        eblc.extend_from_slice(&56u32.to_be_bytes());
+        eblc.extend_from_slice(&0u32.to_be_bytes());
+        eblc.extend_from_slice(&2u32.to_be_bytes());
+        eblc.extend_from_slice(&0u32.to_be_bytes());
+        eblc.extend_from_slice(&[0; 24]);
+        eblc.extend_from_slice(&[0, 1, 0, 3, 12, 12, 1, 1]);
+        eblc.extend_from_slice(&[0, 1, 0, 2, 0, 0, 0, 16]);
+        eblc.extend_from_slice(&[0, 3, 0, 3, 0, 0, 0, 36]);
+        eblc.extend_from_slice(&[0, 1, 0, 2, 0, 0, 0, 4]);
+        for offset in [0u32, 6, 12] {
+            eblc.extend_from_slice(&offset.to_be_bytes());
+        }
+        eblc.extend_from_slice(&[0, 1, 0, 8, 0, 0, 0, 16]);
+        for offset in [0u32, composite.len() as u32] {
+            eblc.extend_from_slice(&offset.to_be_bytes());
+        }
+
+        let mut reader = BytesReader::new(&eblc);
+        let eblc = CBLC::new(&mut reader, 0, eblc.len() as u32).unwrap();
+        (EBDT::from_bytes(ebdt), eblc)
+    }
+
+    #[test]
+    fn ebdt_composes_component_bitmaps() {
+        // Glyph 3: 3x3 made of glyph 1 at (0, 0) and glyph 2 at (2, 0).
+        let (ebdt, eblc) = component_font(&[3, 3, 0, 3, 4, 0, 0, 2, 0, 1, 0, 0, 0, 2, 2, 0]);
+        let strike = eblc.select_strike(3, 12.0).unwrap();
+        let bitmap = ebdt.glyph(strike, 3).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (3, 3));
+        assert_eq!(bitmap.metrics.hori_bearing_y, 3);
+        assert_eq!(bitmap.coverage, vec![255, 0, 255, 0, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn ebdt_skips_components_that_reference_the_glyph_itself() {
+        // Glyph 3 lists itself 64 times before glyphs 1 and 2.
+        let mut composite = vec![3, 3, 0, 3, 4, 0, 0, 66];
+        for _ in 0..64 {
+            composite.extend_from_slice(&[0, 3, 0, 0]);
+        }
+        composite.extend_from_slice(&[0, 1, 0, 0, 0, 2, 2, 0]);
+        let (ebdt, eblc) = component_font(&composite);
+        let strike = eblc.select_strike(3, 12.0).unwrap();
+        let bitmap = ebdt.glyph(strike, 3).unwrap();
+        assert_eq!(bitmap.coverage, vec![255, 0, 255, 0, 255, 255, 0, 0, 255]);
+    }
+}
@@ -1,7 +1,11 @@
+pub mod cbdt;
+pub mod cblc;
 pub mod colr;
 pub mod cpal;
+pub mod ebdt;
 pub mod sbix;
 pub mod svg;
 // Microsoft Emoji -> colr + cpal
 // Apple Emoji -> sbix (png)
+// Noto color emoji (bitmap) -> CBDT + CBLC (png)
 // Noto color emoji -> SVG
@@ -6,6 +6,9 @@ use crate::util::sniff_encoded_image_dimensions;
 use base64::{engine::general_purpose, Engine as _};
 use bin_rs::reader::BinaryReader;
 
+/// `flags` bit 1: draw the outline under the bitmaps.
+const DRAW_OUTLINES: u16 = 0x0002;
+
 #[derive(Debug, Clone)]
 pub(crate) struct SBIX {
     version: u16,
@@ -28,6 +31,8 @@ pub(crate) struct GlyphData {
     glyph_data: Vec<u8>,
 }
 
+/// A resolved sbix bitmap; offsets are the image's lower-left corner
+/// relative to the glyph origin, y up, scaled to the requested size.
 #[derive(Debug, Clone)]
 pub(crate) struct RasterGlyphData {
     pub(crate) offset_x: f32,
@@ -36,52 +41,71 @@ pub(crate) struct RasterGlyphData {
     pub(crate) height: Option<u32>,
     pub(crate) graphic_type: u32,
     pub(crate) glyph_data: Vec<u8>,
+    /// The glyph outline is drawn under the bitmap (`flags` bit 1 or a `mask` glyph).
+    pub(crate) draw_outline: bool,
+}
+
+impl RasterGlyphData {
+    /// MIME type of the payload, from its signature or else its graphic type.
+    pub(crate) fn mime_type(&self) -> Option<&'static str> {
+        if let Some((mime, _, _)) = sniff_encoded_image_dimensions(&self.glyph_data) {
+            return Some(mime);
+        }
+        match &self.graphic_type.to_be_bytes() {
+            b"png " => Some("image/png"),
+            b"jpg " => Some("image/jpeg"),
+            b"tiff" => Some("image/tiff"),
+            _ => None,
+        }
+    }
 }
 
 impl SBIX {
+    /// Follows `dupe` records, refusing cycles, and sizes the final image.
     fn resolve_raster_glyph_in_strike(
         strike: &Strike,
         gid: usize,
         scale: f32,
-        depth: usize,
+        visited: &mut Vec<usize>,
     ) -> Option<RasterGlyphData> {
-        if depth > strike.glyph_data.len() {
+        if visited.contains(&gid) {
             return None;
         }
+        visited.push(gid);
 
         let glyph_data = strike.glyph_data.get(gid)?.as_ref()?;
-        if glyph_data.graphic_type == u32::from_be_bytes(*b"dupe") {
-            if glyph_data.glyph_data.len() < 2 {
-                return None;
+        match &glyph_data.graphic_type.to_be_bytes() {
+            b"dupe" => {
+                let target = glyph_data.glyph_data.get(..2)?;
+                let target_gid = u16::from_be_bytes([target[0], target[1]]) as usize;
+                let mut raster =
+                    Self::resolve_raster_glyph_in_strike(strike, target_gid, scale, visited)?;
+                raster.offset_x = glyph_data.original_offset_x as f32 * scale;
+                raster.offset_y = glyph_data.original_offset_y as f32 * scale;
+                Some(raster)
             }
-            let target_gid =
-                u16::from_be_bytes([glyph_data.glyph_data[0], glyph_data.glyph_data[1]]) as usize;
-            if target_gid == gid {
-                return None;
+            b"png " | b"jpg " | b"tiff" | b"mask" => {
+                let (width, height) = sniff_encoded_image_dimensions(&glyph_data.glyph_data)
+                    .map(|(_, width, height)| {
+                        let width = ((width as f32) * scale).round().max(1.0) as u32;
+                        let height = ((height as f32) * scale).round().max(1.0) as u32;
+                        (width, height)
+                    })
+                    .map_or((None, None), |(width, height)| (Some(width), Some(height)));
+
+                Some(RasterGlyphData {
+                    offset_x: glyph_data.original_offset_x as f32 * scale,
+                    offset_y: glyph_data.original_offset_y as f32 * scale,
+                    width,
+                    height,
+                    graphic_type: glyph_data.graphic_type,
+                    glyph_data: glyph_data.glyph_data.clone(),
+                    draw_outline: glyph_data.graphic_type == u32::from_be_bytes(*b"mask"),
+                })
             }
-            let mut raster =
-                Self::resolve_raster_glyph_in_strike(strike, target_gid, scale, depth + 1)?;
-            raster.offset_x = glyph_data.original_offset_x as f32 * scale;
-            raster.offset_y = glyph_data.original_offset_y as f32 * scale;
-            return Some(raster);
+            // 'pdf ' and unknown types have no raster payload we can show.
+            _ => None,
         }
-
-        let (width, height) = sniff_encoded_image_dimensions(&glyph_data.glyph_data)
-            .map(|(_, width, height)| {
-                let width = ((width as f32) * scale).round().max(1.0) as u32;
-                let height = ((height as f32) * scale).round().max(1.0) as u32;
-                (width, height)
-            })
-            .map_or((None, None), |(width, height)| (Some(width), Some(height)));
-
-        Some(RasterGlyphData {
-            offset_x: glyph_data.original_offset_x as f32 * scale,
-            offset_y: glyph_data.original_offset_y as f32 * scale,
-            width,
-            height,
-            graphic_type: glyph_data.graphic_type,
-            glyph_data: glyph_data.glyph_data.clone(),
-        })
     }
 
     pub(crate) fn new<R: BinaryReader>(
@@ -200,7 +224,10 @@ impl SBIX {
         } else {
             requested_ppem / strike.ppem as f32
         };
-        Self::resolve_raster_glyph_in_strike(strike, gid as usize, scale, 0)
+        let mut raster =
+            Self::resolve_raster_glyph_in_strike(strike, gid as usize, scale, &mut Vec::new())?;
+        raster.draw_outline |= self.flags & DRAW_OUTLINES != 0;
+        Some(raster)
     }
 
     pub(crate) fn get_svg(
@@ -221,26 +248,12 @@ impl SBIX {
             .height
             .map(|value| value.to_string())
             .unwrap_or_else(|| width.clone());
-        let binary = &glyph_data.glyph_data;
-        let bytes = u32::to_be_bytes(glyph_data.graphic_type);
-        let mut base64 = general_purpose::STANDARD.encode(binary);
-        match &bytes {
-            b"png " => {
-                // base64
-                base64 = format!("data:image/png;base64,{}", base64);
-            }
-            b"jpg " => {
-                // base64
-                base64 = format!("data:image/jpeg;base64,{}", base64);
-            }
-            _ => {
-                let mut string = format!(
-                    "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" width=\"{}\" height=\"{}\" >\n",
-                    width, height);
-                string += "</svg>\n";
-                return None;
-            }
-        }
+        let mime = glyph_data.mime_type()?;
+        let base64 = format!(
+            "data:{};base64,{}",
+            mime,
+            general_purpose::STANDARD.encode(&glyph_data.glyph_data)
+        );
         let string = format!(
             "<img width=\"{}\" height=\"{}\" src=\"{}\" />\n",
             width, height, base64
@@ -341,3 +354,84 @@ impl GlyphData {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        data.extend_from_slice(&[0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data
+    }
+
+    fn glyph(graphic_type: &[u8; 4], x: i16, y: i16, data: Vec<u8>) -> Option<GlyphData> {
+        Some(GlyphData {
+            original_offset_x: x,
+            original_offset_y: y,
+            graphic_type: u32::from_be_bytes(*graphic_type),
+            glyph_data: data,
+        })
+    }
+
+    fn sbix(flags: u16, glyph_data: Vec<Option<GlyphData>>) -> SBIX {
+        SBIX {
+            version: 1,
+            flags,
+            strikes: vec![Strike {
+                ppem: 20,
+                ppi: 72,
+                glyph_data,
+            }],
+        }
+    }
+
+    #[test]
+    fn sbix_follows_dupe_chains_and_stops_on_cycles() {
+        let table = sbix(
+            1,
+            vec![
+                None,
+                glyph(b"png ", 1, -2, png(20, 10)),
+                glyph(b"dupe", 3, 4, vec![0, 1]),
+                glyph(b"dupe", 0, 0, vec![0, 2]),
+                glyph(b"dupe", 0, 0, vec![0, 5]),
+                glyph(b"dupe", 0, 0, vec![0, 4]),
+            ],
+        );
+
+        let raster = table.get_raster_glyph(3, 40.0, "px").unwrap();
+        assert_eq!(raster.mime_type(), Some("image/png"));
+        assert_eq!((raster.width, raster.height), (Some(40), Some(20)));
+        assert_eq!((raster.offset_x, raster.offset_y), (0.0, 0.0));
+        let raster = table.get_raster_glyph(2, 20.0, "px").unwrap();
+        assert_eq!((raster.offset_x, raster.offset_y), (3.0, 4.0));
+        assert!(!raster.draw_outline);
+        assert!(table.get_raster_glyph(4, 20.0, "px").is_none());
+    }
+
+    #[test]
+    fn sbix_draws_outline_for_flag_and_mask_glyphs() {
+        let glyphs = vec![
+            glyph(b"jpg ", 0, 0, vec![0xff, 0xd8]),
+            glyph(b"mask", 0, 0, png(4, 4)),
+            glyph(b"pdf ", 0, 0, vec![b'%', b'P']),
+        ];
+        let plain = sbix(1, glyphs.clone());
+        let raster = plain.get_raster_glyph(0, 20.0, "px").unwrap();
+        assert_eq!(raster.mime_type(), Some("image/jpeg"));
+        assert!(!raster.draw_outline);
+        assert!(plain.get_raster_glyph(1, 20.0, "px").unwrap().draw_outline);
+        assert!(plain.get_raster_glyph(2, 20.0, "px").is_none());
+
+        let with_outlines = sbix(1 | DRAW_OUTLINES, glyphs);
+        assert!(
+            with_outlines
+                .get_raster_glyph(0, 20.0, "px")
+                .unwrap()
+                .draw_outline
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use bin_rs::reader::BinaryReader;
+use std::io::SeekFrom;
+
+// BASE Baseline table
+// https://learn.microsoft.com/en-us/typography/opentype/spec/base
+
+#[derive(Debug, Clone)]
+pub(crate) struct BASE {
+    pub(crate) horizontal_axis: Option<BaseAxis>,
+    pub(crate) vertical_axis: Option<BaseAxis>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BaseAxis {
+    pub(crate) baseline_tags: Vec<u32>,
+    pub(crate) scripts: Vec<BaseScriptRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BaseScriptRecord {
+    pub(crate) script_tag: u32,
+    pub(crate) script: BaseScript,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BaseScript {
+    pub(crate) base_values: Option<BaseValues>,
+    pub(crate) default_min_max: Option<MinMax>,
+    pub(crate) lang_sys: Vec<(u32, MinMax)>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct BaseValues {
+    pub(crate) coords: Vec<BaseCoord>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MinMax {
+    pub(crate) min_coord: Option<BaseCoord>,
+    pub(crate) max_coord: Option<BaseCoord>,
+}
+
+/// Design-unit coordinate of any format; the glyph point of format 2 and
+/// the device table of format 3 are not applied.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BaseCoord {
+    pub(crate) coordinate: i16,
+}
+
+impl BASE {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u64,
+        length: usize,
+    ) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let _major_version = reader.read_u16_be()?;
+        let minor_version = reader.read_u16_be()?;
+        let horiz_axis_offset = reader.read_u16_be()?;
+        let vert_axis_offset = reader.read_u16_be()?;
+        let _item_var_store_offset = if minor_version >= 1 {
+            reader.read_u32_be()?
+        } else {
+            0
+        };
+
+        let horizontal_axis = if horiz_axis_offset != 0 && (horiz_axis_offset as usize) < length {
+            Some(BaseAxis::new(reader, offset + horiz_axis_offset as u64)?)
+        } else {
+            None
+        };
+        let vertical_axis = if vert_axis_offset != 0 && (vert_axis_offset as usize) < length {
+            Some(BaseAxis::new(reader, offset + vert_axis_offset as u64)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            horizontal_axis,
+            vertical_axis,
+        })
+    }
+
+    pub(crate) fn axis(&self, vertical: bool) -> Option<&BaseAxis> {
+        if vertical {
+            self.vertical_axis.as_ref()
+        } else {
+            self.horizontal_axis.as_ref()
+        }
+    }
+}
+
+impl BaseAxis {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let base_tag_list_offset = reader.read_u16_be()?;
+        let base_script_list_offset = reader.read_u16_be()?;
+
+        let mut baseline_tags = Vec::new();
+        if base_tag_list_offset != 0 {
+            reader.seek(SeekFrom::Start(offset + base_tag_list_offset as u64))?;
+            let count = reader.read_u16_be()?;
+            for _ in 0..count {
+                baseline_tags.push(reader.read_u32_be()?);
+            }
+        }
+
+        let mut scripts = Vec::new();
+        if base_script_list_offset != 0 {
+            let list_offset = offset + base_script_list_offset as u64;
+            reader.seek(SeekFrom::Start(list_offset))?;
+            let count = reader.read_u16_be()?;
+            let mut records = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let script_tag = reader.read_u32_be()?;
+                let script_offset = reader.read_u16_be()?;
+                records.push((script_tag, script_offset));
+            }
+            for (script_tag, script_offset) in records {
+                let script = BaseScript::new(reader, list_offset + script_offset as u64)?;
+                scripts.push(BaseScriptRecord { script_tag, script });
+            }
+        }
+
+        Ok(Self {
+            baseline_tags,
+            scripts,
+        })
+    }
+
+    /// Looks up `script_tag`, then `DFLT`.
+    pub(crate) fn script(&self, script_tag: u32) -> Option<&BaseScript> {
+        self.scripts
+            .iter()
+            .find(|record| record.script_tag == script_tag)
+            .or_else(|| {
+                self.scripts
+                    .iter()
+                    .find(|record| record.script_tag == u32::from_be_bytes(*b"DFLT"))
+            })
+            .map(|record| &record.script)
+    }
+
+    /// Returns the coordinate of `baseline_tag` for `script_tag` in design units.
+    pub(crate) fn baseline(&self, script_tag: u32, baseline_tag: u32) -> Option<i16> {
+        let index = self
+            .baseline_tags
+            .iter()
+            .position(|tag| *tag == baseline_tag)?;
+        let values = self.script(script_tag)?.base_values.as_ref()?;
+        values.coords.get(index).map(|coord| coord.coordinate)
+    }
+
+    /// Returns the language-specific or default min/max extent for a script.
+    pub(crate) fn min_max(&self, script_tag: u32, language_tag: Option<u32>) -> Option<&MinMax> {
+        let script = self.script(script_tag)?;
+        language_tag
+            .and_then(|language_tag| {
+                script
+                    .lang_sys
+                    .iter()
+                    .find(|(tag, _)| *tag == language_tag)
+                    .map(|(_, min_max)| min_max)
+            })
+            .or(script.default_min_max.as_ref())
+    }
+}
+
+impl BaseScript {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let base_values_offset = reader.read_u16_be()?;
+        let default_min_max_offset = reader.read_u16_be()?;
+        let lang_sys_count = reader.read_u16_be()?;
+        let mut lang_sys_records = Vec::with_capacity(lang_sys_count as usize);
+        for _ in 0..lang_sys_count {
+            let tag = reader.read_u32_be()?;
+            let min_max_offset = reader.read_u16_be()?;
+            lang_sys_records.push((tag, min_max_offset));
+        }
+
+        let base_values = if base_values_offset != 0 {
+            Some(BaseValues::new(reader, offset + base_values_offset as u64)?)
+        } else {
+            None
+        };
+        let default_min_max = if default_min_max_offset != 0 {
+            Some(MinMax::new(reader, offset + default_min_max_offset as u64)?)
+        } else {
+            None
+        };
+        let mut lang_sys = Vec::with_capacity(lang_sys_records.len());
+        for (tag, min_max_offset) in lang_sys_records {
+            if min_max_offset != 0 {
+                lang_sys.push((tag, MinMax::new(reader, offset + min_max_offset as u64)?));
+            }
+        }
+
+        Ok(Self {
+            base_values,
+            default_min_max,
+            lang_sys,
+        })
+    }
+}
+
+impl BaseValues {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let _default_baseline_index = reader.read_u16_be()?;
+        let count = reader.read_u16_be()?;
+        let mut offsets = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            offsets.push(reader.read_u16_be()?);
+        }
+        let mut coords = Vec::with_capacity(offsets.len());
+        for coord_offset in offsets {
+            coords.push(BaseCoord::new(reader, offset + coord_offset as u64)?);
+        }
+        Ok(Self { coords })
+    }
+}
+
+impl MinMax {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        // The per-feature FeatMinMax records that follow are not used.
+        let min_coord_offset = reader.read_u16_be()?;
+        let max_coord_offset = reader.read_u16_be()?;
+
+        Ok(Self {
+            min_coord: BaseCoord::optional(reader, offset, min_coord_offset)?,
+            max_coord: BaseCoord::optional(reader, offset, max_coord_offset)?,
+        })
+    }
+}
+
+impl BaseCoord {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let _format = reader.read_u16_be()?;
+        let coordinate = reader.read_i16_be()?;
+        Ok(Self { coordinate })
+    }
+
+    fn optional<R: BinaryReader>(
+        reader: &mut R,
+        base: u64,
+        offset: u16,
+    ) -> Result<Option<Self>, std::io::Error> {
+        if offset == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self::new(reader, base + offset as u64)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bin_rs::reader::BytesReader;
+
+    fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn push_coord(buffer: &mut Vec<u8>, value: i16) {
+        push_u16(buffer, 1);
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    // Horizontal axis only: hang/ideo/romn for `latn`, with a default and a `JAN ` extent.
+    fn build_base() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        // header
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, 8);
+        push_u16(&mut buffer, 0);
+        // axis at 8
+        push_u16(&mut buffer, 4);
+        push_u16(&mut buffer, 18);
+        // tag list at 12
+        push_u16(&mut buffer, 3);
+        buffer.extend_from_slice(b"hangideoromn");
+        // script list at 26
+        push_u16(&mut buffer, 1);
+        buffer.extend_from_slice(b"latn");
+        push_u16(&mut buffer, 8);
+        // script at 34
+        push_u16(&mut buffer, 12);
+        push_u16(&mut buffer, 34);
+        push_u16(&mut buffer, 1);
+        buffer.extend_from_slice(b"JAN ");
+        push_u16(&mut buffer, 48);
+        // base values at 46
+        push_u16(&mut buffer, 2);
+        push_u16(&mut buffer, 3);
+        push_u16(&mut buffer, 10);
+        push_u16(&mut buffer, 14);
+        push_u16(&mut buffer, 18);
+        push_coord(&mut buffer, 1500);
+        push_coord(&mut buffer, -120);
+        push_coord(&mut buffer, 0);
+        // default min/max at 68
+        push_u16(&mut buffer, 6);
+        push_u16(&mut buffer, 10);
+        push_u16(&mut buffer, 0);
+        push_coord(&mut buffer, -300);
+        push_coord(&mut buffer, 900);
+        // JAN min/max at 82
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, 6);
+        push_u16(&mut buffer, 0);
+        push_coord(&mut buffer, 1100);
+        buffer
+    }
+
+    fn tag(bytes: &[u8; 4]) -> u32 {
+        u32::from_be_bytes(*bytes)
+    }
+
+    #[test]
+    fn base_reads_horizontal_baselines() {
+        let data = build_base();
+        let mut reader = BytesReader::new(&data);
+        let base = BASE::new(&mut reader, 0, data.len()).unwrap();
+        assert!(base.vertical_axis.is_none());
+
+        let axis = base.axis(false).unwrap();
+        assert_eq!(axis.baseline(tag(b"latn"), tag(b"hang")), Some(1500));
+        assert_eq!(axis.baseline(tag(b"latn"), tag(b"ideo")), Some(-120));
+        assert_eq!(axis.baseline(tag(b"latn"), tag(b"math")), None);
+        // No DFLT record, so unknown scripts have no values.
+        assert_eq!(axis.baseline(tag(b"cyrl"), tag(b"ideo")), None);
+    }
+
+    #[test]
+    fn base_reads_script_extents() {
+        let data = build_base();
+        let mut reader = BytesReader::new(&data);
+        let base = BASE::new(&mut reader, 0, data.len()).unwrap();
+        let axis = base.axis(false).unwrap();
+
+        let default = axis.min_max(tag(b"latn"), None).unwrap();
+        assert_eq!(default.min_coord.map(|coord| coord.coordinate), Some(-300));
+        assert_eq!(default.max_coord.map(|coord| coord.coordinate), Some(900));
+
+        let japanese = axis.min_max(tag(b"latn"), Some(tag(b"JAN "))).unwrap();
+        assert!(japanese.min_coord.is_none());
+        assert_eq!(japanese.max_coord.map(|coord| coord.coordinate), Some(1100));
+
+        let fallback = axis.min_max(tag(b"latn"), Some(tag(b"KOR "))).unwrap();
+        assert_eq!(fallback.max_coord.map(|coord| coord.coordinate), Some(900));
+    }
+
+    #[test]
+    fn base_returns_error_on_truncated_input() {
+        let mut reader = BytesReader::new(&[0x00, 0x01, 0x00]);
+        assert!(BASE::new(&mut reader, 0, 3).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use crate::opentype::layouts::*;
+use crate::opentype::requires::var_store::ItemVariationStore;
 use bin_rs::reader::BinaryReader;
 use std::io::SeekFrom;
 
@@ -15,8 +16,7 @@ pub(crate) struct GDEF {
     // 1.2
     pub(crate) mark_glyph_sets_def: Option<MarkGlyphSetsDef>,
     // 1.3
-
-    // pub(crate) item_var_store: Option<VariationStore>,
+    pub(crate) item_var_store: Option<ItemVariationStore>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,8 +45,8 @@ impl GDEF {
         } else {
             0
         };
-        let _item_var_store_offset = if minor_version >= 3 {
-            reader.read_u16_be()?
+        let item_var_store_offset = if minor_version >= 3 {
+            reader.read_u32_be()?
         } else {
             0
         };
@@ -92,6 +92,18 @@ impl GDEF {
             None
         };
 
+        // A malformed store only loses variation deltas, so it is skipped.
+        let item_var_store =
+            if item_var_store_offset != 0 && (item_var_store_offset as usize) < length {
+                reader.seek(SeekFrom::Start(offset + item_var_store_offset as u64))?;
+                reader
+                    .read_bytes_as_vec(length - item_var_store_offset as usize)
+                    .ok()
+                    .and_then(|data| ItemVariationStore::parse(&data).ok())
+            } else {
+                None
+            };
+
         Ok(GDEF {
             major_versionn,
             minor_version,
@@ -100,7 +112,7 @@ impl GDEF {
             lig_caret_list,
             mark_attach_class_def,
             mark_glyph_sets_def,
-            // item_var_store: None,
+            item_var_store,
         })
     }
 
@@ -343,8 +355,6 @@ pub(crate) struct CaretValueFormat3 {
     pub(crate) device_table: Option<DeviceTable>,
 }
 
-pub(crate) struct VariationStore {}
-
 #[derive(Debug, Clone)]
 pub(crate) struct MarkGlyphSetsDef {
     pub(crate) mark_set_table_format: u16,
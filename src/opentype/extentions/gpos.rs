@@ -4,8 +4,9 @@ use std::collections::HashSet;
 use std::io::SeekFrom;
 
 use crate::opentype::layouts::{
-    classdef::ClassDef, coverage::Coverage, script::ParsedScript, FeatureList,
-    FeatureVariationList, ScriptList,
+    classdef::ClassDef, coverage::Coverage, feature::FeatureTableSubstitution,
+    script::ParsedScript, DeviceContext, DeviceTable, FeatureList, FeatureVariationList,
+    ScriptList,
 };
 use bin_rs::reader::BinaryReader;
 
@@ -29,7 +30,13 @@ impl ValueRecord {
         self.y_advance = self.y_advance.saturating_add(other.y_advance);
     }
 
-    fn parse<R: BinaryReader>(reader: &mut R, value_format: u16) -> Result<Self, std::io::Error> {
+    /// Reads one record; device offsets are relative to `base`, the PairPos
+    /// subtable. Malformed device tables are dropped.
+    fn parse<R: BinaryReader>(
+        reader: &mut R,
+        value_format: u16,
+        base: u64,
+    ) -> Result<(Self, Option<Box<ValueDevices>>), std::io::Error> {
         let mut value = Self::default();
 
         if value_format & 0x0001 != 0 {
@@ -45,18 +52,65 @@ impl ValueRecord {
             value.y_advance = reader.read_i16_be()?;
         }
 
-        for flag in [
-            0x0010u16, 0x0020, 0x0040, 0x0080, 0x0100, 0x0200, 0x0400, 0x0800,
-        ] {
+        let mut device_offsets = [0u16; 4];
+        for (index, flag) in [0x0010u16, 0x0020, 0x0040, 0x0080].into_iter().enumerate() {
             if value_format & flag != 0 {
-                let _ = reader.read_u16_be()?;
+                device_offsets[index] = reader.read_u16_be()?;
             }
         }
+        if device_offsets.iter().all(|offset| *offset == 0) {
+            return Ok((value, None));
+        }
 
-        Ok(value)
+        let position = reader.offset()?;
+        let mut devices = device_offsets.map(|offset| {
+            (offset != 0)
+                .then(|| DeviceTable::new(reader, base + offset as u64).ok())
+                .flatten()
+        });
+        reader.seek(SeekFrom::Start(position))?;
+        let [x_placement, y_placement, x_advance, y_advance] = std::mem::take(&mut devices);
+        Ok((
+            value,
+            Some(Box::new(ValueDevices {
+                x_placement,
+                y_placement,
+                x_advance,
+                y_advance,
+            })),
+        ))
+    }
+
+    /// This record with its device or variation deltas applied.
+    fn resolved(self, devices: Option<&ValueDevices>, context: &DeviceContext<'_>) -> Self {
+        let Some(devices) = devices else {
+            return self;
+        };
+        let add = |value: i16, device: &Option<DeviceTable>| {
+            device.as_ref().map_or(value, |device| {
+                (value as f32 + device.delta(context))
+                    .round()
+                    .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+            })
+        };
+        Self {
+            x_placement: add(self.x_placement, &devices.x_placement),
+            y_placement: add(self.y_placement, &devices.y_placement),
+            x_advance: add(self.x_advance, &devices.x_advance),
+            y_advance: add(self.y_advance, &devices.y_advance),
+        }
     }
 }
 
+/// Device or VariationIndex tables of one ValueRecord, per field.
+#[derive(Debug, Clone, Default)]
+struct ValueDevices {
+    x_placement: Option<DeviceTable>,
+    y_placement: Option<DeviceTable>,
+    x_advance: Option<DeviceTable>,
+    y_advance: Option<DeviceTable>,
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub(crate) struct PairAdjustment {
     pub(crate) first: ValueRecord,
@@ -79,6 +133,8 @@ struct PairValueRecord {
     second_glyph: u16,
     value1: ValueRecord,
     value2: ValueRecord,
+    devices1: Option<Box<ValueDevices>>,
+    devices2: Option<Box<ValueDevices>>,
 }
 
 #[derive(Debug, Clone)]
@@ -96,6 +152,8 @@ struct PairPosFormat1 {
 struct Class2Record {
     value1: ValueRecord,
     value2: ValueRecord,
+    devices1: Option<Box<ValueDevices>>,
+    devices2: Option<Box<ValueDevices>>,
 }
 
 #[derive(Debug, Clone)]
@@ -106,10 +164,36 @@ struct PairPosFormat2 {
     class1_records: Vec<Vec<Class2Record>>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 struct Anchor {
     x: i16,
     y: i16,
+    /// Format 3 device or VariationIndex tables.
+    x_device: Option<DeviceTable>,
+    y_device: Option<DeviceTable>,
+}
+
+impl Anchor {
+    fn new(x: i16, y: i16) -> Self {
+        Self {
+            x,
+            y,
+            x_device: None,
+            y_device: None,
+        }
+    }
+
+    /// Anchor position with device or variation deltas applied.
+    fn resolved(&self, context: &DeviceContext<'_>) -> (i16, i16) {
+        let add = |value: i16, device: &Option<DeviceTable>| {
+            device.as_ref().map_or(value, |device| {
+                (value as f32 + device.delta(context))
+                    .round()
+                    .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+            })
+        };
+        (add(self.x, &self.x_device), add(self.y, &self.y_device))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -188,6 +272,18 @@ pub(crate) struct MarkAttachmentAdjustment {
     pub(crate) y_placement: i16,
 }
 
+impl MarkAttachmentAdjustment {
+    /// Offset that moves `mark_anchor` onto `anchor`.
+    fn between(anchor: &Anchor, mark_anchor: &Anchor, context: &DeviceContext<'_>) -> Self {
+        let (x, y) = anchor.resolved(context);
+        let (mark_x, mark_y) = mark_anchor.resolved(context);
+        Self {
+            x_placement: x.saturating_sub(mark_x),
+            y_placement: y.saturating_sub(mark_y),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum PositioningSubtable {
     PairFormat1(PairPosFormat1),
@@ -200,7 +296,12 @@ enum PositioningSubtable {
 }
 
 impl PositioningSubtable {
-    fn lookup_pair_adjustment(&self, left: u16, right: u16) -> Option<PairAdjustment> {
+    fn lookup_pair_adjustment(
+        &self,
+        left: u16,
+        right: u16,
+        context: &DeviceContext<'_>,
+    ) -> Option<PairAdjustment> {
         match self {
             PositioningSubtable::PairFormat1(pair) => {
                 let coverage_index = pair.coverage.contains(left as usize)?;
@@ -210,8 +311,8 @@ impl PositioningSubtable {
                     .iter()
                     .find(|record| record.second_glyph == right)?;
                 Some(PairAdjustment {
-                    first: record.value1,
-                    second: record.value2,
+                    first: record.value1.resolved(record.devices1.as_deref(), context),
+                    second: record.value2.resolved(record.devices2.as_deref(), context),
                 })
             }
             PositioningSubtable::PairFormat2(pair) => {
@@ -223,12 +324,16 @@ impl PositioningSubtable {
                 let class1_record = pair.class1_records.get(class1)?;
                 let class2_record = class1_record.get(class2)?;
                 Some(PairAdjustment {
-                    first: class2_record.value1,
-                    second: class2_record.value2,
+                    first: class2_record
+                        .value1
+                        .resolved(class2_record.devices1.as_deref(), context),
+                    second: class2_record
+                        .value2
+                        .resolved(class2_record.devices2.as_deref(), context),
                 })
             }
             PositioningSubtable::Extension(extension) => {
-                extension.lookup_pair_adjustment(left, right)
+                extension.lookup_pair_adjustment(left, right, context)
             }
             PositioningSubtable::Unsupported
             | PositioningSubtable::MarkToBaseFormat1(_)
@@ -241,6 +346,7 @@ impl PositioningSubtable {
         &self,
         base: u16,
         mark: u16,
+        context: &DeviceContext<'_>,
     ) -> Option<MarkAttachmentAdjustment> {
         match self {
             PositioningSubtable::MarkToBaseFormat1(mark_to_base) => {
@@ -252,13 +358,14 @@ impl PositioningSubtable {
                     .base_anchors
                     .get(mark_record.mark_class as usize)?
                     .as_ref()?;
-                Some(MarkAttachmentAdjustment {
-                    x_placement: base_anchor.x.saturating_sub(mark_record.mark_anchor.x),
-                    y_placement: base_anchor.y.saturating_sub(mark_record.mark_anchor.y),
-                })
+                Some(MarkAttachmentAdjustment::between(
+                    base_anchor,
+                    &mark_record.mark_anchor,
+                    context,
+                ))
             }
             PositioningSubtable::Extension(extension) => {
-                extension.lookup_mark_to_base_adjustment(base, mark)
+                extension.lookup_mark_to_base_adjustment(base, mark, context)
             }
             PositioningSubtable::PairFormat1(_)
             | PositioningSubtable::PairFormat2(_)
@@ -273,6 +380,7 @@ impl PositioningSubtable {
         ligature: u16,
         mark: u16,
         component_index: usize,
+        context: &DeviceContext<'_>,
     ) -> Option<MarkAttachmentAdjustment> {
         match self {
             PositioningSubtable::MarkToLigatureFormat1(mark_to_ligature) => {
@@ -290,14 +398,14 @@ impl PositioningSubtable {
                     .ligature_anchors
                     .get(mark_record.mark_class as usize)?
                     .as_ref()?;
-                Some(MarkAttachmentAdjustment {
-                    x_placement: ligature_anchor.x.saturating_sub(mark_record.mark_anchor.x),
-                    y_placement: ligature_anchor.y.saturating_sub(mark_record.mark_anchor.y),
-                })
-            }
-            PositioningSubtable::Extension(extension) => {
-                extension.lookup_mark_to_ligature_adjustment(ligature, mark, component_index)
+                Some(MarkAttachmentAdjustment::between(
+                    ligature_anchor,
+                    &mark_record.mark_anchor,
+                    context,
+                ))
             }
+            PositioningSubtable::Extension(extension) => extension
+                .lookup_mark_to_ligature_adjustment(ligature, mark, component_index, context),
             PositioningSubtable::PairFormat1(_)
             | PositioningSubtable::PairFormat2(_)
             | PositioningSubtable::MarkToBaseFormat1(_)
@@ -310,6 +418,7 @@ impl PositioningSubtable {
         &self,
         base_mark: u16,
         combining_mark: u16,
+        context: &DeviceContext<'_>,
     ) -> Option<MarkAttachmentAdjustment> {
         match self {
             PositioningSubtable::MarkToMarkFormat1(mark_to_mark) => {
@@ -329,13 +438,14 @@ impl PositioningSubtable {
                     .mark2_anchors
                     .get(mark1_record.mark_class as usize)?
                     .as_ref()?;
-                Some(MarkAttachmentAdjustment {
-                    x_placement: mark2_anchor.x.saturating_sub(mark1_record.mark_anchor.x),
-                    y_placement: mark2_anchor.y.saturating_sub(mark1_record.mark_anchor.y),
-                })
+                Some(MarkAttachmentAdjustment::between(
+                    mark2_anchor,
+                    &mark1_record.mark_anchor,
+                    context,
+                ))
             }
             PositioningSubtable::Extension(extension) => {
-                extension.lookup_mark_to_mark_adjustment(base_mark, combining_mark)
+                extension.lookup_mark_to_mark_adjustment(base_mark, combining_mark, context)
             }
             PositioningSubtable::PairFormat1(_)
             | PositioningSubtable::PairFormat2(_)
@@ -375,8 +485,8 @@ impl GPOS {
         let script_list_offset = reader.read_u16_be()?;
         let feature_list_offset = reader.read_u16_be()?;
         let lookup_list_offset = reader.read_u16_be()?;
-        let feature_variations_offset = if major_version == 1 && minor_version == 1 {
-            reader.read_u16_be()?
+        let feature_variations_offset = if major_version == 1 && minor_version >= 1 {
+            reader.read_u32_be()?
         } else {
             0
         };
@@ -393,9 +503,14 @@ impl GPOS {
         )?);
         let lookups = Self::parse_lookups(reader, offset + lookup_list_offset as u64)?;
         let feature_variations = if feature_variations_offset > 0 {
-            FeatureVariationList::new(reader, offset + feature_variations_offset as u64, length)
-                .ok()
-                .map(Box::new)
+            FeatureVariationList::new(
+                reader,
+                offset + feature_variations_offset as u64,
+                offset + length as u64,
+                &features,
+            )
+            .ok()
+            .map(Box::new)
         } else {
             None
         };
@@ -491,12 +606,14 @@ impl GPOS {
                     let mut pair_value_records = Vec::with_capacity(pair_value_count as usize);
                     for _ in 0..pair_value_count {
                         let second_glyph = reader.read_u16_be()?;
-                        let value1 = ValueRecord::parse(reader, value_format1)?;
-                        let value2 = ValueRecord::parse(reader, value_format2)?;
+                        let (value1, devices1) = ValueRecord::parse(reader, value_format1, offset)?;
+                        let (value2, devices2) = ValueRecord::parse(reader, value_format2, offset)?;
                         pair_value_records.push(PairValueRecord {
                             second_glyph,
                             value1,
                             value2,
+                            devices1,
+                            devices2,
                         });
                     }
                     pair_sets.push(PairSet { pair_value_records });
@@ -517,9 +634,13 @@ impl GPOS {
                 for _ in 0..class1_count {
                     let mut class2_records = Vec::with_capacity(class2_count as usize);
                     for _ in 0..class2_count {
+                        let (value1, devices1) = ValueRecord::parse(reader, value_format1, offset)?;
+                        let (value2, devices2) = ValueRecord::parse(reader, value_format2, offset)?;
                         class2_records.push(Class2Record {
-                            value1: ValueRecord::parse(reader, value_format1)?,
-                            value2: ValueRecord::parse(reader, value_format2)?,
+                            value1,
+                            value2,
+                            devices1,
+                            devices2,
                         });
                     }
                     class1_records.push(class2_records);
@@ -787,15 +908,27 @@ impl GPOS {
         let y = reader.read_i16_be()?;
 
         match anchor_format {
-            1 => Ok(Some(Anchor { x, y })),
+            1 => Ok(Some(Anchor::new(x, y))),
             2 => {
                 let _anchor_point = reader.read_u16_be()?;
-                Ok(Some(Anchor { x, y }))
+                Ok(Some(Anchor::new(x, y)))
             }
             3 => {
-                let _x_device_offset = reader.read_u16_be()?;
-                let _y_device_offset = reader.read_u16_be()?;
-                Ok(Some(Anchor { x, y }))
+                let x_device_offset = reader.read_u16_be()?;
+                let y_device_offset = reader.read_u16_be()?;
+                let mut device = |device_offset: u16| {
+                    (device_offset != 0)
+                        .then(|| DeviceTable::new(reader, offset + device_offset as u64).ok())
+                        .flatten()
+                };
+                let x_device = device(x_device_offset);
+                let y_device = device(y_device_offset);
+                Ok(Some(Anchor {
+                    x,
+                    y,
+                    x_device,
+                    y_device,
+                }))
             }
             _ => Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -954,54 +1087,82 @@ impl GPOS {
         systems
     }
 
-    fn collect_lookups<'a>(
+    fn lookups_at<'a>(
         &'a self,
+        lookups: &'a [u16],
+    ) -> impl Iterator<Item = &'a PositioningLookup> + 'a {
+        lookups
+            .iter()
+            .filter_map(|lookup_index| self.lookups.get(*lookup_index as usize))
+    }
+
+    /// Resolves `feature_tags` for `locale` to LookupList indices in application order.
+    pub(crate) fn feature_lookup_indices(
+        &self,
+        locale: Option<&str>,
+        feature_tags: &[[u8; 4]],
+    ) -> Vec<u16> {
+        self.feature_lookup_indices_at(locale, feature_tags, &[])
+    }
+
+    /// Like [`Self::feature_lookup_indices`], with feature tables swapped by
+    /// FeatureVariations at normalized `coordinates`.
+    pub(crate) fn feature_lookup_indices_at(
+        &self,
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
-    ) -> Vec<&'a PositioningLookup> {
+        coordinates: &[f32],
+    ) -> Vec<u16> {
+        let substitution = self
+            .feature_variations
+            .as_ref()
+            .and_then(|variations| variations.substitution(coordinates));
         if locale.is_none() {
-            return self.collect_lookups_from_scripts(
+            return self.lookup_indices_from_scripts(
                 self.scripts.scripts.iter().collect(),
                 locale,
                 feature_tags,
+                substitution,
             );
         }
 
         let (preferred_scripts, default_scripts, other_scripts) = self.partition_scripts(locale);
         let mut result = Vec::new();
-        let mut seen_lookup_ptrs = HashSet::new();
+        let mut seen_lookup_indices = HashSet::new();
 
         for feature_tag in feature_tags {
             let lookups = {
-                let preferred = self.collect_lookups_from_scripts(
+                let preferred = self.lookup_indices_from_scripts(
                     preferred_scripts.clone(),
                     locale,
                     std::slice::from_ref(feature_tag),
+                    substitution,
                 );
                 if !preferred.is_empty() {
                     preferred
                 } else {
-                    let defaults = self.collect_lookups_from_scripts(
+                    let defaults = self.lookup_indices_from_scripts(
                         default_scripts.clone(),
                         locale,
                         std::slice::from_ref(feature_tag),
+                        substitution,
                     );
                     if !defaults.is_empty() {
                         defaults
                     } else {
-                        self.collect_lookups_from_scripts(
+                        self.lookup_indices_from_scripts(
                             other_scripts.clone(),
                             locale,
                             std::slice::from_ref(feature_tag),
+                            substitution,
                         )
                     }
                 }
             };
 
-            for lookup in lookups {
-                let ptr = lookup as *const PositioningLookup as usize;
-                if seen_lookup_ptrs.insert(ptr) {
-                    result.push(lookup);
+            for lookup_index in lookups {
+                if seen_lookup_indices.insert(lookup_index) {
+                    result.push(lookup_index);
                 }
             }
         }
@@ -1009,12 +1170,13 @@ impl GPOS {
         result
     }
 
-    fn collect_lookups_from_scripts<'a>(
-        &'a self,
-        scripts: Vec<&'a ParsedScript>,
+    fn lookup_indices_from_scripts(
+        &self,
+        scripts: Vec<&ParsedScript>,
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
-    ) -> Vec<&'a PositioningLookup> {
+        substitution: Option<&FeatureTableSubstitution>,
+    ) -> Vec<u16> {
         let mut result = Vec::new();
         let mut seen_lookup_indices = HashSet::new();
 
@@ -1023,7 +1185,9 @@ impl GPOS {
                 for feature_index in
                     Self::collect_language_system_feature_indices(&language_system.language_system)
                 {
-                    let feature = &self.features.features[feature_index as usize];
+                    let Some(feature) = self.features.feature(feature_index, substitution) else {
+                        continue;
+                    };
                     if !feature_tags
                         .iter()
                         .any(|tag| feature.feature_tag == u32::from_be_bytes(*tag))
@@ -1032,11 +1196,10 @@ impl GPOS {
                     }
 
                     for lookup_index in feature.lookup_list_indices.iter() {
-                        if !seen_lookup_indices.insert(*lookup_index) {
-                            continue;
-                        }
-                        if let Some(lookup) = self.lookups.get(*lookup_index as usize) {
-                            result.push(lookup);
+                        if (*lookup_index as usize) < self.lookups.len()
+                            && seen_lookup_indices.insert(*lookup_index)
+                        {
+                            result.push(*lookup_index);
                         }
                     }
                 }
@@ -1164,16 +1327,32 @@ impl GPOS {
         } else {
             &[*b"kern"]
         };
+        self.lookup_pair_adjustment_with_lookups(
+            left,
+            right,
+            &self.feature_lookup_indices(locale, feature_tags),
+            &DeviceContext::default(),
+        )
+    }
 
+    /// Sums pair adjustments for `left`/`right` from already resolved `lookups`,
+    /// with device and variation deltas resolved for `context`.
+    pub(crate) fn lookup_pair_adjustment_with_lookups(
+        &self,
+        left: u16,
+        right: u16,
+        lookups: &[u16],
+        context: &DeviceContext<'_>,
+    ) -> Option<PairAdjustment> {
         let mut adjustment = PairAdjustment::default();
         let mut matched = false;
 
-        for lookup in self.collect_lookups(locale, feature_tags) {
+        for lookup in self.lookups_at(lookups) {
             if lookup.lookup_type != 2 && lookup.lookup_type != 9 {
                 continue;
             }
             for subtable in &lookup.subtables {
-                if let Some(found) = subtable.lookup_pair_adjustment(left, right) {
+                if let Some(found) = subtable.lookup_pair_adjustment(left, right, context) {
                     adjustment.add_assign(found);
                     matched = true;
                 }
@@ -1193,14 +1372,28 @@ impl GPOS {
         mark: u16,
         locale: Option<&str>,
     ) -> Option<MarkAttachmentAdjustment> {
-        let feature_tags: &[[u8; 4]] = &[*b"mark"];
+        self.lookup_mark_to_base_adjustment_with_lookups(
+            base,
+            mark,
+            &self.feature_lookup_indices(locale, &[*b"mark"]),
+            &DeviceContext::default(),
+        )
+    }
 
-        for lookup in self.collect_lookups(locale, feature_tags) {
+    /// `lookup_mark_to_base_adjustment` with already resolved `mark` lookups.
+    pub(crate) fn lookup_mark_to_base_adjustment_with_lookups(
+        &self,
+        base: u16,
+        mark: u16,
+        lookups: &[u16],
+        context: &DeviceContext<'_>,
+    ) -> Option<MarkAttachmentAdjustment> {
+        for lookup in self.lookups_at(lookups) {
             if lookup.lookup_type != 4 && lookup.lookup_type != 9 {
                 continue;
             }
             for subtable in &lookup.subtables {
-                if let Some(found) = subtable.lookup_mark_to_base_adjustment(base, mark) {
+                if let Some(found) = subtable.lookup_mark_to_base_adjustment(base, mark, context) {
                     return Some(found);
                 }
             }
@@ -1216,16 +1409,35 @@ impl GPOS {
         component_index: usize,
         locale: Option<&str>,
     ) -> Option<MarkAttachmentAdjustment> {
-        let feature_tags: &[[u8; 4]] = &[*b"mark"];
+        self.lookup_mark_to_ligature_adjustment_with_lookups(
+            ligature,
+            mark,
+            component_index,
+            &self.feature_lookup_indices(locale, &[*b"mark"]),
+            &DeviceContext::default(),
+        )
+    }
 
-        for lookup in self.collect_lookups(locale, feature_tags) {
+    /// `lookup_mark_to_ligature_adjustment` with already resolved `mark` lookups.
+    pub(crate) fn lookup_mark_to_ligature_adjustment_with_lookups(
+        &self,
+        ligature: u16,
+        mark: u16,
+        component_index: usize,
+        lookups: &[u16],
+        context: &DeviceContext<'_>,
+    ) -> Option<MarkAttachmentAdjustment> {
+        for lookup in self.lookups_at(lookups) {
             if lookup.lookup_type != 5 && lookup.lookup_type != 9 {
                 continue;
             }
             for subtable in &lookup.subtables {
-                if let Some(found) =
-                    subtable.lookup_mark_to_ligature_adjustment(ligature, mark, component_index)
-                {
+                if let Some(found) = subtable.lookup_mark_to_ligature_adjustment(
+                    ligature,
+                    mark,
+                    component_index,
+                    context,
+                ) {
                     return Some(found);
                 }
             }
@@ -1240,15 +1452,29 @@ impl GPOS {
         combining_mark: u16,
         locale: Option<&str>,
     ) -> Option<MarkAttachmentAdjustment> {
-        let feature_tags: &[[u8; 4]] = &[*b"mkmk"];
+        self.lookup_mark_to_mark_adjustment_with_lookups(
+            base_mark,
+            combining_mark,
+            &self.feature_lookup_indices(locale, &[*b"mkmk"]),
+            &DeviceContext::default(),
+        )
+    }
 
-        for lookup in self.collect_lookups(locale, feature_tags) {
+    /// `lookup_mark_to_mark_adjustment` with already resolved `mkmk` lookups.
+    pub(crate) fn lookup_mark_to_mark_adjustment_with_lookups(
+        &self,
+        base_mark: u16,
+        combining_mark: u16,
+        lookups: &[u16],
+        context: &DeviceContext<'_>,
+    ) -> Option<MarkAttachmentAdjustment> {
+        for lookup in self.lookups_at(lookups) {
             if lookup.lookup_type != 6 && lookup.lookup_type != 9 {
                 continue;
             }
             for subtable in &lookup.subtables {
                 if let Some(found) =
-                    subtable.lookup_mark_to_mark_adjustment(base_mark, combining_mark)
+                    subtable.lookup_mark_to_mark_adjustment(base_mark, combining_mark, context)
                 {
                     return Some(found);
                 }
@@ -1262,8 +1488,66 @@ impl GPOS {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::opentype::requires::var_store::ItemVariationStore;
     use bin_rs::reader::BytesReader;
 
+    #[test]
+    fn pair_adjustment_applies_variation_index_delta() {
+        let bytes = [
+            0x00, 0x01, // posFormat
+            0x00, 0x0C, // coverageOffset
+            0x00, 0x44, // valueFormat1: xAdvance | xAdvDevice
+            0x00, 0x00, // valueFormat2
+            0x00, 0x01, // pairSetCount
+            0x00, 0x12, // pairSetOffset
+            0x00, 0x01, // coverage format
+            0x00, 0x01, // glyph count
+            0x00, 0x0A, // glyph 10
+            0x00, 0x01, // pairValueCount
+            0x00, 0x0B, // second glyph 11
+            0xFF, 0xCE, // xAdvance -50
+            0x00, 0x1A, // xAdvDevice offset
+            0x00, 0x00, // deltaSetOuterIndex
+            0x00, 0x00, // deltaSetInnerIndex
+            0x80, 0x00, // VariationIndex format
+        ];
+        let store = ItemVariationStore::parse(&[
+            0x00, 0x01, // format
+            0x00, 0x00, 0x00, 0x0C, // region list offset
+            0x00, 0x01, // data count
+            0x00, 0x00, 0x00, 0x16, // data offset
+            0x00, 0x01, // axis count
+            0x00, 0x01, // region count
+            0x00, 0x00, 0x40, 0x00, 0x40, 0x00, // region 0..1, peak 1
+            0x00, 0x01, // item count
+            0x00, 0x00, // word delta count
+            0x00, 0x01, // region index count
+            0x00, 0x00, // region index 0
+            0xD8, // delta -40
+        ])
+        .expect("parse ItemVariationStore");
+        let mut reader = BytesReader::new(&bytes);
+        let subtable = GPOS::parse_subtable(&mut reader, 2, 0).expect("parse pair pos");
+
+        let x_advance = |coordinates: &[f32]| {
+            let context = DeviceContext {
+                coordinates,
+                variation_store: Some(&store),
+                ppem: None,
+                units_per_em: 1000,
+            };
+            subtable
+                .lookup_pair_adjustment(10, 11, &context)
+                .expect("pair adjustment")
+                .first
+                .x_advance
+        };
+
+        assert_eq!(x_advance(&[]), -50);
+        assert_eq!(x_advance(&[0.5]), -70);
+        assert_eq!(x_advance(&[1.0]), -90);
+    }
+
     #[test]
     fn mark_to_base_format1_lookup_returns_anchor_delta() {
         let bytes = [
@@ -1295,7 +1579,7 @@ mod tests {
 
         let subtable = GPOS::parse_subtable(&mut reader, 4, 0).expect("parse mark-to-base");
         let adjustment = subtable
-            .lookup_mark_to_base_adjustment(10, 20)
+            .lookup_mark_to_base_adjustment(10, 20, &DeviceContext::default())
             .expect("mark attachment adjustment");
 
         assert_eq!(
@@ -1338,7 +1622,7 @@ mod tests {
 
         let subtable = GPOS::parse_subtable(&mut reader, 6, 0).expect("parse mark-to-mark");
         let adjustment = subtable
-            .lookup_mark_to_mark_adjustment(20, 30)
+            .lookup_mark_to_mark_adjustment(20, 30, &DeviceContext::default())
             .expect("mark-to-mark adjustment");
 
         assert_eq!(
@@ -1387,7 +1671,7 @@ mod tests {
 
         let subtable = GPOS::parse_subtable(&mut reader, 5, 0).expect("parse mark-to-ligature");
         let adjustment = subtable
-            .lookup_mark_to_ligature_adjustment(10, 20, 1)
+            .lookup_mark_to_ligature_adjustment(10, 20, 1, &DeviceContext::default())
             .expect("mark-to-ligature adjustment");
 
         assert_eq!(
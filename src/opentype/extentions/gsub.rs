@@ -8,13 +8,22 @@ use std::collections::HashSet;
 use std::io::SeekFrom;
 
 use crate::opentype::layouts::{
-    feature::Feature,
+    feature::{Feature, FeatureTableSubstitution},
     lookup::{Lookup, LookupResult},
     script::ParsedScript,
     *,
 };
 use bin_rs::reader::BinaryReader;
 
+/// Width features accepted by `GSUB::lookup_width`.
+pub(crate) const WIDTH_FEATURE_TAGS: [[u8; 4]; 6] =
+    [*b"fwid", *b"hwid", *b"pwid", *b"twid", *b"qwid", *b"pkna"];
+
+/// Number features accepted by `GSUB::lookup_number_feature`.
+pub(crate) const NUMBER_FEATURE_TAGS: [[u8; 4]; 9] = [
+    *b"tnum", *b"pnum", *b"onum", *b"lnum", *b"numr", *b"dnom", *b"sups", *b"subs", *b"zero",
+];
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub(crate) struct JoiningForms {
     pub(crate) isolated: Option<usize>,
@@ -78,8 +87,8 @@ impl GSUB {
         let script_list_offset = reader.read_u16_be()?;
         let feature_list_offset = reader.read_u16_be()?;
         let lookup_list_offset = reader.read_u16_be()?;
-        let feature_variations_offset = if major_version == 1 && minor_version == 1 {
-            reader.read_u16_be()?
+        let feature_variations_offset = if major_version == 1 && minor_version >= 1 {
+            reader.read_u32_be()?
         } else {
             0
         };
@@ -100,9 +109,14 @@ impl GSUB {
             length,
         )?);
         let feature_variations = if feature_variations_offset > 0 {
-            FeatureVariationList::new(reader, offset + feature_variations_offset as u64, length)
-                .ok()
-                .map(Box::new)
+            FeatureVariationList::new(
+                reader,
+                offset + feature_variations_offset as u64,
+                offset + length as u64,
+                &features,
+            )
+            .ok()
+            .map(Box::new)
         } else {
             None
         };
@@ -154,52 +168,71 @@ impl GSUB {
         lookups
     }
 
-    fn collect_feature_lookups<'a>(
-        &'a self,
+    /// Resolves `feature_tags` for `locale` to LookupList indices in application order.
+    pub(crate) fn feature_lookup_indices(
+        &self,
+        locale: Option<&str>,
+        feature_tags: &[[u8; 4]],
+    ) -> Vec<u16> {
+        self.feature_lookup_indices_at(locale, feature_tags, &[])
+    }
+
+    /// Like [`Self::feature_lookup_indices`], with feature tables swapped by
+    /// FeatureVariations at normalized `coordinates`.
+    pub(crate) fn feature_lookup_indices_at(
+        &self,
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
-    ) -> Vec<&'a Lookup> {
+        coordinates: &[f32],
+    ) -> Vec<u16> {
+        let substitution = self
+            .feature_variations
+            .as_ref()
+            .and_then(|variations| variations.substitution(coordinates));
         if locale.is_none() {
-            return self.collect_feature_lookups_from_scripts(
+            return self.feature_lookup_indices_from_scripts(
                 self.scripts.scripts.iter().collect(),
                 locale,
                 feature_tags,
+                substitution,
             );
         }
 
         let (preferred_scripts, default_scripts, other_scripts) = self.partition_scripts(locale);
         let mut lookups = Vec::new();
-        let mut seen_lookup_ptrs = HashSet::new();
+        let mut seen_lookup_indices = HashSet::new();
 
         for feature_tag in feature_tags {
-            let preferred = self.collect_feature_lookups_from_scripts(
+            let preferred = self.feature_lookup_indices_from_scripts(
                 preferred_scripts.clone(),
                 locale,
                 std::slice::from_ref(feature_tag),
+                substitution,
             );
             let script_lookups = if !preferred.is_empty() {
                 preferred
             } else {
-                let defaults = self.collect_feature_lookups_from_scripts(
+                let defaults = self.feature_lookup_indices_from_scripts(
                     default_scripts.clone(),
                     locale,
                     std::slice::from_ref(feature_tag),
+                    substitution,
                 );
                 if !defaults.is_empty() {
                     defaults
                 } else {
-                    self.collect_feature_lookups_from_scripts(
+                    self.feature_lookup_indices_from_scripts(
                         other_scripts.clone(),
                         locale,
                         std::slice::from_ref(feature_tag),
+                        substitution,
                     )
                 }
             };
 
-            for lookup in script_lookups {
-                let ptr = lookup as *const Lookup as usize;
-                if seen_lookup_ptrs.insert(ptr) {
-                    lookups.push(lookup);
+            for lookup_index in script_lookups {
+                if seen_lookup_indices.insert(lookup_index) {
+                    lookups.push(lookup_index);
                 }
             }
         }
@@ -207,12 +240,13 @@ impl GSUB {
         lookups
     }
 
-    fn collect_feature_lookups_from_scripts<'a>(
-        &'a self,
-        scripts: Vec<&'a ParsedScript>,
+    fn feature_lookup_indices_from_scripts(
+        &self,
+        scripts: Vec<&ParsedScript>,
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
-    ) -> Vec<&'a Lookup> {
+        substitution: Option<&FeatureTableSubstitution>,
+    ) -> Vec<u16> {
         let mut lookups = Vec::new();
         let mut seen_lookup_indices = HashSet::new();
 
@@ -221,7 +255,9 @@ impl GSUB {
                 for feature_index in
                     Self::collect_language_system_feature_indices(&language_system.language_system)
                 {
-                    let feature = &self.features.features[feature_index as usize];
+                    let Some(feature) = self.features.feature(feature_index, substitution) else {
+                        continue;
+                    };
                     let feature_tag = feature.feature_tag;
                     if !feature_tags
                         .iter()
@@ -231,10 +267,9 @@ impl GSUB {
                     }
 
                     for lookup_index in feature.lookup_list_indices.iter() {
-                        if !seen_lookup_indices.insert(*lookup_index) {
-                            continue;
+                        if seen_lookup_indices.insert(*lookup_index) {
+                            lookups.push(*lookup_index);
                         }
-                        lookups.push(&self.lookups.lookups[*lookup_index as usize]);
                     }
                 }
             }
@@ -316,7 +351,7 @@ impl GSUB {
         }
     }
 
-    fn locale_to_language_system_tags(locale: &str) -> Vec<u32> {
+    pub(crate) fn locale_to_language_system_tags(locale: &str) -> Vec<u32> {
         let subtags = Self::locale_subtags(locale);
         if subtags.is_empty() {
             return Vec::new();
@@ -370,7 +405,7 @@ impl GSUB {
         tags
     }
 
-    fn locale_to_script_tags(locale: &str) -> Vec<u32> {
+    pub(crate) fn locale_to_script_tags(locale: &str) -> Vec<u32> {
         let subtags = Self::locale_subtags(locale);
         if subtags.is_empty() {
             return Vec::new();
@@ -488,7 +523,19 @@ impl GSUB {
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
     ) -> Option<usize> {
-        for lookup in self.collect_feature_lookups(locale, feature_tags) {
+        self.lookup_single_with_lookups(
+            glyph_id,
+            &self.feature_lookup_indices(locale, feature_tags),
+        )
+    }
+
+    /// Returns the first substitute for `glyph_id` from already resolved `lookups`.
+    pub(crate) fn lookup_single_with_lookups(
+        &self,
+        glyph_id: usize,
+        lookups: &[u16],
+    ) -> Option<usize> {
+        for lookup in self.lookups_at(lookups) {
             for subtable in lookup.subtables.iter() {
                 match subtable.get_lookup(glyph_id) {
                     LookupResult::Single(result) => return Some(result as usize),
@@ -515,9 +562,21 @@ impl GSUB {
         glyph_ids: &[usize],
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
+    ) -> Option<usize> {
+        self.lookup_ligature_with_lookups(
+            glyph_ids,
+            &self.feature_lookup_indices(locale, feature_tags),
+        )
+    }
+
+    /// Returns the ligature for exactly `glyph_ids` from already resolved `lookups`.
+    pub(crate) fn lookup_ligature_with_lookups(
+        &self,
+        glyph_ids: &[usize],
+        lookups: &[u16],
     ) -> Option<usize> {
         let first_glyph = *glyph_ids.first()?;
-        for lookup in self.collect_feature_lookups(locale, feature_tags) {
+        for lookup in self.lookups_at(lookups) {
             for subtable in lookup.subtables.iter() {
                 if let LookupResult::Ligature(records) = subtable.get_lookup(first_glyph) {
                     for record in records.iter() {
@@ -541,6 +600,12 @@ impl GSUB {
         None
     }
 
+    fn lookups_at<'a>(&'a self, lookups: &'a [u16]) -> impl Iterator<Item = &'a Lookup> + 'a {
+        lookups
+            .iter()
+            .filter_map(|lookup_index| self.lookups.lookups.get(*lookup_index as usize))
+    }
+
     fn apply_subtable_at(
         subtable: &crate::opentype::layouts::lookup::LookupSubstitution,
         glyphs: &mut Vec<(usize, usize)>,
@@ -716,7 +781,7 @@ impl GSUB {
                 };
 
                 for rule in &rule_set.rules {
-                    if index + rule.input_sequence.len() >= glyphs.len() + 1 {
+                    if index + rule.input_sequence.len() >= glyphs.len() {
                         continue;
                     }
                     let matches =
@@ -752,7 +817,7 @@ impl GSUB {
                 };
 
                 for rule in &rule_set.class_seq_rules {
-                    if index + rule.input_sequences.len() >= glyphs.len() + 1 {
+                    if index + rule.input_sequences.len() >= glyphs.len() {
                         continue;
                     }
                     let matches =
@@ -795,7 +860,7 @@ impl GSUB {
                         continue;
                     }
                     if index + rule.input_glyph_ids.len() + rule.lookahead_glyph_ids.len()
-                        >= glyphs.len() + 1
+                        >= glyphs.len()
                     {
                         continue;
                     }
@@ -856,7 +921,7 @@ impl GSUB {
                         continue;
                     }
                     if index + rule.input_sequences.len() + rule.lookahead_class_ids.len()
-                        >= glyphs.len() + 1
+                        >= glyphs.len()
                     {
                         continue;
                     }
@@ -969,7 +1034,12 @@ impl GSUB {
         locale: Option<&str>,
         feature_tags: &[[u8; 4]],
     ) {
-        let lookups = self.collect_feature_lookups(locale, feature_tags);
+        self.apply_lookups(glyphs, &self.feature_lookup_indices(locale, feature_tags));
+    }
+
+    /// Applies already resolved `lookups` until the glyph sequence stops changing.
+    pub(crate) fn apply_lookups(&self, glyphs: &mut Vec<(usize, usize)>, lookups: &[u16]) {
+        let lookups = self.lookups_at(lookups).collect::<Vec<_>>();
         if lookups.is_empty() || glyphs.is_empty() {
             return;
         }
@@ -1012,6 +1082,20 @@ impl GSUB {
         &self,
         glyphs: &mut Vec<(usize, usize)>,
         locale: Option<&str>,
+    ) {
+        let lookups = [*b"isol", *b"init", *b"medi", *b"fina"]
+            .map(|tag| self.feature_lookup_indices(locale, &[tag]));
+        self.apply_joining_with_lookups(
+            glyphs,
+            [&lookups[0], &lookups[1], &lookups[2], &lookups[3]],
+        );
+    }
+
+    /// Applies joining forms from resolved `isol`, `init`, `medi` and `fina` lookups.
+    pub(crate) fn apply_joining_with_lookups(
+        &self,
+        glyphs: &mut Vec<(usize, usize)>,
+        [isolated, initial, medial, final_form]: [&[u16]; 4],
     ) {
         if glyphs.is_empty() {
             return;
@@ -1019,7 +1103,12 @@ impl GSUB {
 
         let forms: Vec<JoiningForms> = glyphs
             .iter()
-            .map(|(glyph_id, _)| self.lookup_joining_forms(*glyph_id, locale))
+            .map(|(glyph_id, _)| JoiningForms {
+                isolated: self.lookup_single_with_lookups(*glyph_id, isolated),
+                initial: self.lookup_single_with_lookups(*glyph_id, initial),
+                medial: self.lookup_single_with_lookups(*glyph_id, medial),
+                final_form: self.lookup_single_with_lookups(*glyph_id, final_form),
+            })
             .collect();
 
         for index in 0..glyphs.len() {
@@ -1107,13 +1196,101 @@ impl GSUB {
         self.lookup_standard_liga_sequence(griph_ids)
     }
 
-    // hwid, fwid, qwid, twid, pkna
-    pub fn lookup_width(&self, _griph_ids: usize, _tag: u32) -> usize {
-        todo!("lookup_width")
+    // fwid, hwid, pwid, twid, qwid, pkna
+    pub fn lookup_width(&self, griph_ids: usize, tag: u32) -> usize {
+        let tag = tag.to_be_bytes();
+        if !WIDTH_FEATURE_TAGS.contains(&tag) {
+            return griph_ids;
+        }
+        self.lookup_single_feature(griph_ids, None, &[tag])
+            .unwrap_or(griph_ids)
+    }
+
+    // frac
+    #[deprecated(note = "use `lookup_number_feature` to apply one number feature to a glyph")]
+    pub fn lookup_number(&self, griph_ids: Vec<usize>) -> usize {
+        self.lookup_ligature_feature(&griph_ids, None, &[*b"frac"])
+            .or_else(|| griph_ids.first().copied())
+            .unwrap_or_default()
+    }
+
+    // tnum, pnum, onum, lnum, numr, dnom, sups, subs, zero
+    pub fn lookup_number_feature(&self, griph_ids: usize, tag: u32) -> usize {
+        let tag = tag.to_be_bytes();
+        if !NUMBER_FEATURE_TAGS.contains(&tag) {
+            return griph_ids;
+        }
+        self.lookup_single_feature(griph_ids, None, &[tag])
+            .unwrap_or(griph_ids)
+    }
+
+    /// Applies `frac` around each fraction slash: `numr` to the digits before it,
+    /// `dnom` to the digits after it, and `frac` to the whole span.
+    ///
+    /// `is_digit` and `is_slash` classify the source character behind each glyph.
+    pub(crate) fn apply_fraction_sequence(
+        &self,
+        glyphs: &mut Vec<(usize, usize)>,
+        locale: Option<&str>,
+        is_digit: impl Fn(usize) -> bool,
+        is_slash: impl Fn(usize) -> bool,
+    ) {
+        let [numerator, denominator, fraction] =
+            [*b"numr", *b"dnom", *b"frac"].map(|tag| self.feature_lookup_indices(locale, &[tag]));
+        self.apply_fraction_with_lookups(
+            glyphs,
+            [&numerator, &denominator, &fraction],
+            is_digit,
+            is_slash,
+        );
     }
 
-    // dnom, numr, frac, subs, sups, zero
-    pub fn lookup_number(&self, _griph_ids: Vec<usize>) -> usize {
-        todo!("lookup_number")
+    /// `apply_fraction_sequence` with resolved `numr`, `dnom` and `frac` lookups.
+    pub(crate) fn apply_fraction_with_lookups(
+        &self,
+        glyphs: &mut Vec<(usize, usize)>,
+        [numerator, denominator, fraction]: [&[u16]; 3],
+        is_digit: impl Fn(usize) -> bool,
+        is_slash: impl Fn(usize) -> bool,
+    ) {
+        let mut index = 0usize;
+        while index < glyphs.len() {
+            if !is_slash(glyphs[index].1) {
+                index += 1;
+                continue;
+            }
+            let mut start = index;
+            while start > 0 && is_digit(glyphs[start - 1].1) {
+                start -= 1;
+            }
+            let mut end = index + 1;
+            while end < glyphs.len() && is_digit(glyphs[end].1) {
+                end += 1;
+            }
+            if start == index || end == index + 1 {
+                index += 1;
+                continue;
+            }
+
+            // Work back to front so earlier ranges keep their indices.
+            let denominator_end = self.apply_lookups_in_range(glyphs, index + 1..end, denominator);
+            let numerator_end = self.apply_lookups_in_range(glyphs, start..index, numerator);
+            let end = denominator_end + numerator_end - index;
+            index = self.apply_lookups_in_range(glyphs, start..end, fraction);
+        }
+    }
+
+    /// Runs `apply_lookups` on `glyphs[range]` and returns the new end of the range.
+    fn apply_lookups_in_range(
+        &self,
+        glyphs: &mut Vec<(usize, usize)>,
+        range: std::ops::Range<usize>,
+        lookups: &[u16],
+    ) -> usize {
+        let mut segment = glyphs[range.clone()].to_vec();
+        self.apply_lookups(&mut segment, lookups);
+        let end = range.start + segment.len();
+        glyphs.splice(range, segment);
+        end
     }
 }
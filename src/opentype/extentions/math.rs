@@ -0,0 +1,670 @@
+#![allow(dead_code)]
+
+use crate::opentype::layouts::coverage::Coverage;
+use bin_rs::reader::BinaryReader;
+use std::io::SeekFrom;
+
+// MATH Mathematical Typesetting table
+// https://learn.microsoft.com/en-us/typography/opentype/spec/math
+
+pub(crate) const MATH_VALUE_RECORD_COUNT: usize = 51;
+
+#[derive(Debug, Clone)]
+pub(crate) struct MATH {
+    pub(crate) major_version: u16,
+    pub(crate) minor_version: u16,
+    pub(crate) constants: Option<MathConstantsTable>,
+    pub(crate) glyph_info: Option<MathGlyphInfo>,
+    pub(crate) variants: Option<MathVariants>,
+}
+
+/// Raw MathConstants in spec order; `values` holds the 51 MathValueRecords.
+#[derive(Debug, Clone)]
+pub(crate) struct MathConstantsTable {
+    pub(crate) script_percent_scale_down: i16,
+    pub(crate) script_script_percent_scale_down: i16,
+    pub(crate) delimited_sub_formula_min_height: u16,
+    pub(crate) display_operator_min_height: u16,
+    pub(crate) values: [i16; MATH_VALUE_RECORD_COUNT],
+    pub(crate) radical_degree_bottom_raise_percent: i16,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MathGlyphInfo {
+    pub(crate) italics_correction: Option<MathValueTable>,
+    pub(crate) top_accent_attachment: Option<MathValueTable>,
+    pub(crate) extended_shape_coverage: Option<Coverage>,
+    pub(crate) kern_info: Option<MathKernInfo>,
+}
+
+/// Coverage-indexed MathValueRecords, shared by italics correction and top accent attachment.
+#[derive(Debug, Clone)]
+pub(crate) struct MathValueTable {
+    pub(crate) coverage: Coverage,
+    pub(crate) values: Vec<i16>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MathKernInfo {
+    pub(crate) coverage: Coverage,
+    pub(crate) records: Vec<[Option<MathKern>; 4]>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MathKern {
+    pub(crate) correction_heights: Vec<i16>,
+    pub(crate) kern_values: Vec<i16>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MathVariants {
+    pub(crate) min_connector_overlap: u16,
+    pub(crate) vertical_coverage: Option<Coverage>,
+    pub(crate) horizontal_coverage: Option<Coverage>,
+    pub(crate) vertical_constructions: Vec<MathGlyphConstruction>,
+    pub(crate) horizontal_constructions: Vec<MathGlyphConstruction>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MathGlyphConstruction {
+    pub(crate) assembly: Option<GlyphAssembly>,
+    pub(crate) variants: Vec<MathGlyphVariantRecord>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MathGlyphVariantRecord {
+    pub(crate) variant_glyph: u16,
+    pub(crate) advance_measurement: u16,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct GlyphAssembly {
+    pub(crate) italics_correction: i16,
+    pub(crate) parts: Vec<GlyphPart>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GlyphPart {
+    pub(crate) glyph_id: u16,
+    pub(crate) start_connector_length: u16,
+    pub(crate) end_connector_length: u16,
+    pub(crate) full_advance: u16,
+    pub(crate) part_flags: u16,
+}
+
+impl GlyphPart {
+    pub(crate) fn is_extender(&self) -> bool {
+        self.part_flags & 0x0001 != 0
+    }
+}
+
+/// One glyph of a laid-out assembly, in design units along the stretch axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AssembledPart {
+    pub(crate) glyph_id: u16,
+    pub(crate) offset: f32,
+    pub(crate) advance: f32,
+}
+
+impl MATH {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u64,
+        length: usize,
+    ) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let major_version = reader.read_u16_be()?;
+        let minor_version = reader.read_u16_be()?;
+        let constants_offset = reader.read_u16_be()?;
+        let glyph_info_offset = reader.read_u16_be()?;
+        let variants_offset = reader.read_u16_be()?;
+
+        let in_table = |sub_offset: u16| sub_offset != 0 && (sub_offset as usize) < length;
+        let constants = if in_table(constants_offset) {
+            Some(MathConstantsTable::new(
+                reader,
+                offset + constants_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let glyph_info = if in_table(glyph_info_offset) {
+            Some(MathGlyphInfo::new(
+                reader,
+                offset + glyph_info_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let variants = if in_table(variants_offset) {
+            Some(MathVariants::new(reader, offset + variants_offset as u64)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            major_version,
+            minor_version,
+            constants,
+            glyph_info,
+            variants,
+        })
+    }
+
+    pub(crate) fn italics_correction(&self, glyph_id: u16) -> Option<i16> {
+        self.glyph_info
+            .as_ref()?
+            .italics_correction
+            .as_ref()?
+            .get(glyph_id)
+    }
+
+    pub(crate) fn top_accent_attachment(&self, glyph_id: u16) -> Option<i16> {
+        self.glyph_info
+            .as_ref()?
+            .top_accent_attachment
+            .as_ref()?
+            .get(glyph_id)
+    }
+
+    pub(crate) fn is_extended_shape(&self, glyph_id: u16) -> bool {
+        self.glyph_info
+            .as_ref()
+            .and_then(|info| info.extended_shape_coverage.as_ref())
+            .and_then(|coverage| coverage.contains(glyph_id as usize))
+            .is_some()
+    }
+
+    /// Returns the kern for `corner` (0 top-right, 1 top-left, 2 bottom-right, 3 bottom-left).
+    pub(crate) fn kern(&self, glyph_id: u16, corner: usize, height: i16) -> Option<i16> {
+        let kern_info = self.glyph_info.as_ref()?.kern_info.as_ref()?;
+        let index = kern_info.coverage.contains(glyph_id as usize)?;
+        kern_info
+            .records
+            .get(index)?
+            .get(corner)?
+            .as_ref()?
+            .kern_at(height)
+    }
+
+    pub(crate) fn construction(
+        &self,
+        glyph_id: u16,
+        vertical: bool,
+    ) -> Option<&MathGlyphConstruction> {
+        let variants = self.variants.as_ref()?;
+        let (coverage, constructions) = if vertical {
+            (
+                variants.vertical_coverage.as_ref()?,
+                &variants.vertical_constructions,
+            )
+        } else {
+            (
+                variants.horizontal_coverage.as_ref()?,
+                &variants.horizontal_constructions,
+            )
+        };
+        constructions.get(coverage.contains(glyph_id as usize)?)
+    }
+
+    pub(crate) fn min_connector_overlap(&self) -> u16 {
+        self.variants
+            .as_ref()
+            .map(|variants| variants.min_connector_overlap)
+            .unwrap_or(0)
+    }
+}
+
+fn read_math_value_record<R: BinaryReader>(reader: &mut R) -> Result<i16, std::io::Error> {
+    let value = reader.read_i16_be()?;
+    let _device_offset = reader.read_u16_be()?;
+    Ok(value)
+}
+
+impl MathConstantsTable {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let script_percent_scale_down = reader.read_i16_be()?;
+        let script_script_percent_scale_down = reader.read_i16_be()?;
+        let delimited_sub_formula_min_height = reader.read_u16_be()?;
+        let display_operator_min_height = reader.read_u16_be()?;
+        let mut values = [0i16; MATH_VALUE_RECORD_COUNT];
+        for value in values.iter_mut() {
+            *value = read_math_value_record(reader)?;
+        }
+        let radical_degree_bottom_raise_percent = reader.read_i16_be()?;
+        Ok(Self {
+            script_percent_scale_down,
+            script_script_percent_scale_down,
+            delimited_sub_formula_min_height,
+            display_operator_min_height,
+            values,
+            radical_degree_bottom_raise_percent,
+        })
+    }
+}
+
+impl MathGlyphInfo {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let italics_correction_offset = reader.read_u16_be()?;
+        let top_accent_attachment_offset = reader.read_u16_be()?;
+        let extended_shape_coverage_offset = reader.read_u16_be()?;
+        let kern_info_offset = reader.read_u16_be()?;
+
+        let italics_correction = if italics_correction_offset != 0 {
+            Some(MathValueTable::new(
+                reader,
+                offset + italics_correction_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let top_accent_attachment = if top_accent_attachment_offset != 0 {
+            Some(MathValueTable::new(
+                reader,
+                offset + top_accent_attachment_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let extended_shape_coverage = if extended_shape_coverage_offset != 0 {
+            Some(Coverage::new(
+                reader,
+                offset + extended_shape_coverage_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let kern_info = if kern_info_offset != 0 {
+            Some(MathKernInfo::new(reader, offset + kern_info_offset as u64)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            italics_correction,
+            top_accent_attachment,
+            extended_shape_coverage,
+            kern_info,
+        })
+    }
+}
+
+impl MathValueTable {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let coverage_offset = reader.read_u16_be()?;
+        let count = reader.read_u16_be()?;
+        let mut values = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            values.push(read_math_value_record(reader)?);
+        }
+        let coverage = Coverage::new(reader, offset + coverage_offset as u64)?;
+        Ok(Self { coverage, values })
+    }
+
+    fn get(&self, glyph_id: u16) -> Option<i16> {
+        let index = self.coverage.contains(glyph_id as usize)?;
+        self.values.get(index).copied()
+    }
+}
+
+impl MathKernInfo {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let coverage_offset = reader.read_u16_be()?;
+        let count = reader.read_u16_be()?;
+        let mut offsets = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let mut corners = [0u16; 4];
+            for corner in corners.iter_mut() {
+                *corner = reader.read_u16_be()?;
+            }
+            offsets.push(corners);
+        }
+        let coverage = Coverage::new(reader, offset + coverage_offset as u64)?;
+        let mut records = Vec::with_capacity(offsets.len());
+        for corners in offsets {
+            let mut record: [Option<MathKern>; 4] = [None, None, None, None];
+            for (slot, kern_offset) in record.iter_mut().zip(corners) {
+                if kern_offset != 0 {
+                    *slot = Some(MathKern::new(reader, offset + kern_offset as u64)?);
+                }
+            }
+            records.push(record);
+        }
+        Ok(Self { coverage, records })
+    }
+}
+
+impl MathKern {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let height_count = reader.read_u16_be()?;
+        let mut correction_heights = Vec::with_capacity(height_count as usize);
+        for _ in 0..height_count {
+            correction_heights.push(read_math_value_record(reader)?);
+        }
+        let mut kern_values = Vec::with_capacity(height_count as usize + 1);
+        for _ in 0..=height_count {
+            kern_values.push(read_math_value_record(reader)?);
+        }
+        Ok(Self {
+            correction_heights,
+            kern_values,
+        })
+    }
+
+    /// Picks the kern value of the height band that contains `height`.
+    pub(crate) fn kern_at(&self, height: i16) -> Option<i16> {
+        let index = self
+            .correction_heights
+            .iter()
+            .position(|correction_height| height < *correction_height)
+            .unwrap_or(self.correction_heights.len());
+        self.kern_values.get(index).copied()
+    }
+}
+
+impl MathVariants {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let min_connector_overlap = reader.read_u16_be()?;
+        let vertical_coverage_offset = reader.read_u16_be()?;
+        let horizontal_coverage_offset = reader.read_u16_be()?;
+        let vertical_count = reader.read_u16_be()?;
+        let horizontal_count = reader.read_u16_be()?;
+        let mut vertical_offsets = Vec::with_capacity(vertical_count as usize);
+        for _ in 0..vertical_count {
+            vertical_offsets.push(reader.read_u16_be()?);
+        }
+        let mut horizontal_offsets = Vec::with_capacity(horizontal_count as usize);
+        for _ in 0..horizontal_count {
+            horizontal_offsets.push(reader.read_u16_be()?);
+        }
+
+        let vertical_coverage = if vertical_coverage_offset != 0 {
+            Some(Coverage::new(
+                reader,
+                offset + vertical_coverage_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let horizontal_coverage = if horizontal_coverage_offset != 0 {
+            Some(Coverage::new(
+                reader,
+                offset + horizontal_coverage_offset as u64,
+            )?)
+        } else {
+            None
+        };
+        let mut vertical_constructions = Vec::with_capacity(vertical_offsets.len());
+        for construction_offset in vertical_offsets {
+            vertical_constructions.push(MathGlyphConstruction::new(
+                reader,
+                offset + construction_offset as u64,
+            )?);
+        }
+        let mut horizontal_constructions = Vec::with_capacity(horizontal_offsets.len());
+        for construction_offset in horizontal_offsets {
+            horizontal_constructions.push(MathGlyphConstruction::new(
+                reader,
+                offset + construction_offset as u64,
+            )?);
+        }
+
+        Ok(Self {
+            min_connector_overlap,
+            vertical_coverage,
+            horizontal_coverage,
+            vertical_constructions,
+            horizontal_constructions,
+        })
+    }
+}
+
+impl MathGlyphConstruction {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let assembly_offset = reader.read_u16_be()?;
+        let variant_count = reader.read_u16_be()?;
+        let mut variants = Vec::with_capacity(variant_count as usize);
+        for _ in 0..variant_count {
+            let variant_glyph = reader.read_u16_be()?;
+            let advance_measurement = reader.read_u16_be()?;
+            variants.push(MathGlyphVariantRecord {
+                variant_glyph,
+                advance_measurement,
+            });
+        }
+        let assembly = if assembly_offset != 0 {
+            Some(GlyphAssembly::new(reader, offset + assembly_offset as u64)?)
+        } else {
+            None
+        };
+        Ok(Self { assembly, variants })
+    }
+}
+
+impl GlyphAssembly {
+    fn new<R: BinaryReader>(reader: &mut R, offset: u64) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let italics_correction = read_math_value_record(reader)?;
+        let part_count = reader.read_u16_be()?;
+        let mut parts = Vec::with_capacity(part_count as usize);
+        for _ in 0..part_count {
+            parts.push(GlyphPart {
+                glyph_id: reader.read_u16_be()?,
+                start_connector_length: reader.read_u16_be()?,
+                end_connector_length: reader.read_u16_be()?,
+                full_advance: reader.read_u16_be()?,
+                part_flags: reader.read_u16_be()?,
+            });
+        }
+        Ok(Self {
+            italics_correction,
+            parts,
+        })
+    }
+
+    /// Lays the parts out to cover at least `target` design units.
+    ///
+    /// Extenders are repeated until the assembly is long enough with the minimum
+    /// overlap, then the overlaps grow evenly so the result lands close to `target`.
+    pub(crate) fn layout(&self, target: f32, min_connector_overlap: u16) -> Vec<AssembledPart> {
+        const MAX_EXTENDER_REPEATS: usize = 1024;
+
+        if self.parts.is_empty() {
+            return Vec::new();
+        }
+        let has_extender = self.parts.iter().any(GlyphPart::is_extender);
+        let min_overlap = min_connector_overlap as f32;
+        let mut sequence = Vec::new();
+        for repeats in 0..=MAX_EXTENDER_REPEATS {
+            sequence = self.sequence(repeats);
+            if !has_extender || Self::length(&sequence, min_overlap) >= target {
+                break;
+            }
+        }
+
+        let joints = sequence.len().saturating_sub(1);
+        let max_extra: Vec<f32> = sequence
+            .windows(2)
+            .map(|pair| {
+                let connector = pair[0]
+                    .end_connector_length
+                    .min(pair[1].start_connector_length);
+                (connector as f32 - min_overlap).max(0.0)
+            })
+            .collect();
+        let mut excess = (Self::length(&sequence, min_overlap) - target).max(0.0);
+        let mut extra = vec![0.0f32; joints];
+        // Spread the excess evenly, handing any joint's leftover to the others.
+        let mut open: Vec<usize> = (0..joints).collect();
+        while excess > f32::EPSILON && !open.is_empty() {
+            let share = excess / open.len() as f32;
+            let mut next_open = Vec::new();
+            for &joint in &open {
+                let room = max_extra[joint] - extra[joint];
+                let used = room.min(share);
+                extra[joint] += used;
+                excess -= used;
+                if room > share {
+                    next_open.push(joint);
+                }
+            }
+            if next_open.len() == open.len() {
+                break;
+            }
+            open = next_open;
+        }
+
+        let mut parts = Vec::with_capacity(sequence.len());
+        let mut offset = 0.0f32;
+        for (index, part) in sequence.iter().enumerate() {
+            let overlap = if index + 1 < sequence.len() {
+                min_overlap + extra[index]
+            } else {
+                0.0
+            };
+            let advance = part.full_advance as f32 - overlap;
+            parts.push(AssembledPart {
+                glyph_id: part.glyph_id,
+                offset,
+                advance,
+            });
+            offset += advance;
+        }
+        parts
+    }
+
+    fn sequence(&self, repeats: usize) -> Vec<GlyphPart> {
+        let mut sequence = Vec::new();
+        for part in &self.parts {
+            if part.is_extender() {
+                sequence.extend(std::iter::repeat_n(*part, repeats));
+            } else {
+                sequence.push(*part);
+            }
+        }
+        sequence
+    }
+
+    fn length(sequence: &[GlyphPart], overlap: f32) -> f32 {
+        let advances: f32 = sequence.iter().map(|part| part.full_advance as f32).sum();
+        advances - overlap * sequence.len().saturating_sub(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bin_rs::reader::BytesReader;
+
+    fn part(glyph_id: u16, connector: u16, advance: u16, extender: bool) -> GlyphPart {
+        GlyphPart {
+            glyph_id,
+            start_connector_length: connector,
+            end_connector_length: connector,
+            full_advance: advance,
+            part_flags: extender as u16,
+        }
+    }
+
+    fn bracket() -> GlyphAssembly {
+        GlyphAssembly {
+            italics_correction: 0,
+            parts: vec![
+                part(10, 100, 500, false),
+                part(11, 100, 500, true),
+                part(12, 100, 500, false),
+            ],
+        }
+    }
+
+    #[test]
+    fn assembly_repeats_extenders_until_target_is_covered() {
+        let parts = bracket().layout(1800.0, 50);
+        let glyphs: Vec<u16> = parts.iter().map(|part| part.glyph_id).collect();
+        assert_eq!(glyphs, vec![10, 11, 11, 12]);
+        let total: f32 = parts.iter().map(|part| part.advance).sum();
+        assert!((total - 1800.0).abs() < 0.01, "{total}");
+        assert_eq!(parts[0].offset, 0.0);
+        assert_eq!(parts[1].offset, parts[0].advance);
+    }
+
+    #[test]
+    fn assembly_keeps_connectors_overlapping_when_target_is_short() {
+        let parts = bracket().layout(100.0, 50);
+        let glyphs: Vec<u16> = parts.iter().map(|part| part.glyph_id).collect();
+        assert_eq!(glyphs, vec![10, 12]);
+        // The only joint can overlap by at most the 100 unit connector.
+        assert_eq!(parts[0].advance, 400.0);
+        assert_eq!(parts[1].advance, 500.0);
+    }
+
+    #[test]
+    fn math_kern_uses_height_bands() {
+        let kern = MathKern {
+            correction_heights: vec![100, 300],
+            kern_values: vec![-10, -20, -30],
+        };
+        assert_eq!(kern.kern_at(0), Some(-10));
+        assert_eq!(kern.kern_at(100), Some(-20));
+        assert_eq!(kern.kern_at(500), Some(-30));
+    }
+
+    fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+        buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    #[test]
+    fn math_reads_constants_and_italics_correction() {
+        let mut buffer = Vec::new();
+        // header: constants at 10, glyph info at 224, no variants
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, 10);
+        push_u16(&mut buffer, 224);
+        push_u16(&mut buffer, 0);
+        // constants: 4 header fields, 51 value records, radical raise percent
+        push_u16(&mut buffer, 70);
+        push_u16(&mut buffer, 55);
+        push_u16(&mut buffer, 1300);
+        push_u16(&mut buffer, 1500);
+        for index in 0..MATH_VALUE_RECORD_COUNT {
+            push_u16(&mut buffer, index as u16 * 10);
+            push_u16(&mut buffer, 0);
+        }
+        push_u16(&mut buffer, 60);
+        assert_eq!(buffer.len(), 224);
+        // glyph info: italics correction at +8
+        push_u16(&mut buffer, 8);
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, 0);
+        push_u16(&mut buffer, 0);
+        // italics correction table: coverage at +8, one record
+        push_u16(&mut buffer, 8);
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 42);
+        push_u16(&mut buffer, 0);
+        // coverage format 1 with glyph 7
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 7);
+
+        let mut reader = BytesReader::new(&buffer);
+        let math = MATH::new(&mut reader, 0, buffer.len()).unwrap();
+        let constants = math.constants.as_ref().unwrap();
+        assert_eq!(constants.script_percent_scale_down, 70);
+        assert_eq!(constants.display_operator_min_height, 1500);
+        assert_eq!(constants.values[1], 10);
+        assert_eq!(constants.values[50], 500);
+        assert_eq!(constants.radical_degree_bottom_raise_percent, 60);
+        assert_eq!(math.italics_correction(7), Some(42));
+        assert_eq!(math.italics_correction(8), None);
+        assert!(math.construction(7, true).is_none());
+    }
+}
@@ -1,4 +1,6 @@
 // あまりにコストが重いのでSVGとsbixを先にやる……。
+pub mod base;
 pub mod gdef;
 pub mod gpos;
 pub mod gsub;
+pub mod math;
@@ -1,5 +1,7 @@
-#![allow(dead_code)]
+use bin_rs::reader::BinaryReader;
+use std::io::SeekFrom;
 
+/// Condition format 1: an axis range in normalized F2DOT14 coordinates.
 #[derive(Debug, Clone)]
 pub(crate) struct ConditionTable {
     pub(crate) format: u16,
@@ -8,8 +10,73 @@ pub(crate) struct ConditionTable {
     pub(crate) filter_range_max_value: f32,
 }
 
+impl ConditionTable {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u64,
+    ) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let format = reader.read_u16_be()?;
+        if format != 1 {
+            // Unknown formats are kept so the set they belong to never matches.
+            return Ok(Self {
+                format,
+                axis_index: 0,
+                filter_range_min_value: 0.0,
+                filter_range_max_value: 0.0,
+            });
+        }
+        let axis_index = reader.read_u16_be()?;
+        let filter_range_min_value = reader.read_i16_be()? as f32 / 16384.0;
+        let filter_range_max_value = reader.read_i16_be()? as f32 / 16384.0;
+        Ok(Self {
+            format,
+            axis_index,
+            filter_range_min_value,
+            filter_range_max_value,
+        })
+    }
+
+    /// Axes missing from `coordinates` sit at their default, 0.
+    pub(crate) fn matches(&self, coordinates: &[f32]) -> bool {
+        if self.format != 1 {
+            return false;
+        }
+        let value = coordinates
+            .get(self.axis_index as usize)
+            .copied()
+            .unwrap_or(0.0);
+        self.filter_range_min_value <= value && value <= self.filter_range_max_value
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct ConditionSet {
-    pub(crate) condition_count: u16,
-    pub(crate) conditions: Box<Vec<ConditionTable>>,
+    pub(crate) conditions: Vec<ConditionTable>,
+}
+
+impl ConditionSet {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u64,
+    ) -> Result<Self, std::io::Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let condition_count = reader.read_u16_be()?;
+        let mut condition_offsets = Vec::with_capacity(condition_count as usize);
+        for _ in 0..condition_count {
+            condition_offsets.push(reader.read_u32_be()?);
+        }
+        let conditions = condition_offsets
+            .into_iter()
+            .map(|condition_offset| ConditionTable::new(reader, offset + condition_offset as u64))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { conditions })
+    }
+
+    /// An empty set matches every instance.
+    pub(crate) fn matches(&self, coordinates: &[f32]) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(coordinates))
+    }
 }
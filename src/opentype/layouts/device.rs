@@ -2,6 +2,7 @@
 
 use std::io::SeekFrom;
 
+use crate::opentype::requires::var_store::ItemVariationStore;
 use bin_rs::reader::BinaryReader;
 
 #[derive(Debug, Clone)]
@@ -13,6 +14,18 @@ pub(crate) struct DeviceTable {
     pub(crate) variation_index: Option<VariationIndex>,
 }
 
+/// The instance and size that Device and VariationIndex tables resolve for.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DeviceContext<'a> {
+    /// Normalized variation coordinates; empty for the default instance.
+    pub(crate) coordinates: &'a [f32],
+    /// GDEF ItemVariationStore that VariationIndex tables point into.
+    pub(crate) variation_store: Option<&'a ItemVariationStore>,
+    /// Pixels per em for hinting deltas, or `None` when laying out in font units.
+    pub(crate) ppem: Option<u16>,
+    pub(crate) units_per_em: u16,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct VariationIndex {
     pub(crate) delta_set_outer_index: u16,
@@ -25,9 +38,9 @@ impl DeviceTable {
         offset: u64,
     ) -> Result<Self, std::io::Error> {
         reader.seek(SeekFrom::Start(offset))?;
-        let start_size = reader.read_u16()?;
-        let end_size = reader.read_u16()?;
-        let delta_format = reader.read_u16()?;
+        let start_size = reader.read_u16_be()?;
+        let end_size = reader.read_u16_be()?;
+        let delta_format = reader.read_u16_be()?;
         let (delta_value, variation_index) = match delta_format {
             1 => {
                 // LOCAL_2_BIT_DELTAS
@@ -35,7 +48,7 @@ impl DeviceTable {
                 let length = ((value_count * 2) + 15) / 16;
                 let mut delta_value = Vec::new();
                 for _ in 0..length {
-                    delta_value.push(reader.read_u16()?);
+                    delta_value.push(reader.read_u16_be()?);
                 }
                 (delta_value, None)
             }
@@ -45,7 +58,7 @@ impl DeviceTable {
                 let length = ((value_count * 4) + 15) / 16;
                 let mut delta_value = Vec::new();
                 for _ in 0..length {
-                    delta_value.push(reader.read_u16()?);
+                    delta_value.push(reader.read_u16_be()?);
                 }
                 (delta_value, None)
             }
@@ -55,7 +68,7 @@ impl DeviceTable {
                 let length = (value_count + 1) / 2;
                 let mut delta_value = Vec::new();
                 for _ in 0..length {
-                    delta_value.push(reader.read_u16()?);
+                    delta_value.push(reader.read_u16_be()?);
                 }
                 (delta_value, None)
             }
@@ -87,4 +100,98 @@ impl DeviceTable {
     pub(crate) fn is_variation_index(&self) -> bool {
         self.variation_index.is_some()
     }
+
+    /// Adjustment in font units: the variation delta at `context.coordinates`,
+    /// or the hinting delta at `context.ppem` converted from pixels.
+    pub(crate) fn delta(&self, context: &DeviceContext<'_>) -> f32 {
+        if let Some(index) = self.variation_index {
+            if context.coordinates.iter().all(|value| *value == 0.0) {
+                return 0.0;
+            }
+            return context
+                .variation_store
+                .and_then(|store| {
+                    store.parse_delta(
+                        index.delta_set_outer_index,
+                        index.delta_set_inner_index,
+                        context.coordinates,
+                    )
+                })
+                .unwrap_or(0.0);
+        }
+        let Some(ppem) = context.ppem.filter(|ppem| *ppem > 0) else {
+            return 0.0;
+        };
+        match self.pixel_delta(ppem) {
+            0 => 0.0,
+            pixels => pixels as f32 * context.units_per_em as f32 / ppem as f32,
+        }
+    }
+
+    /// Packed hinting delta for `ppem`, in pixels.
+    fn pixel_delta(&self, ppem: u16) -> i32 {
+        let bits = match self.delta_format {
+            1 => 2,
+            2 => 4,
+            3 => 8,
+            _ => return 0,
+        };
+        if ppem < self.start_size || ppem > self.end_size {
+            return 0;
+        }
+        let index = (ppem - self.start_size) as usize;
+        let per_word = 16 / bits;
+        let Some(word) = self.delta_value.get(index / per_word) else {
+            return 0;
+        };
+        let shift = 16 - bits * (index % per_word + 1);
+        let raw = ((*word >> shift) as u32 & ((1 << bits) - 1)) as i32;
+        // Sign-extend the `bits`-wide value.
+        if raw >= 1 << (bits - 1) {
+            raw - (1 << bits)
+        } else {
+            raw
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hinting_device(delta_format: u16, delta_value: Vec<u16>) -> DeviceTable {
+        DeviceTable {
+            start_size: 10,
+            end_size: 13,
+            delta_format,
+            delta_value,
+            variation_index: None,
+        }
+    }
+
+    #[test]
+    fn hinting_deltas_unpack_signed_values_per_ppem() {
+        // 2-bit values 1, -1, 0, -2 for ppem 10..=13.
+        let device = hinting_device(1, vec![0b01_11_00_10 << 8]);
+        assert_eq!(
+            (10..=13)
+                .map(|ppem| device.pixel_delta(ppem))
+                .collect::<Vec<_>>(),
+            vec![1, -1, 0, -2]
+        );
+        assert_eq!(device.pixel_delta(14), 0);
+
+        // 8-bit values 3, -4 for ppem 10 and 11.
+        let device = hinting_device(3, vec![0x03FC, 0]);
+        assert_eq!(device.pixel_delta(10), 3);
+        assert_eq!(device.pixel_delta(11), -4);
+
+        let context = DeviceContext {
+            ppem: Some(10),
+            units_per_em: 1000,
+            ..DeviceContext::default()
+        };
+        assert_eq!(device.delta(&context), 300.0);
+        assert_eq!(device.delta(&DeviceContext::default()), 0.0);
+    }
 }
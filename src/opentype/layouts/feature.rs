@@ -115,6 +115,23 @@ impl FeatureList {
         })
     }
 
+    /// Feature `index`, replaced by its alternate when `substitution` has one.
+    pub(crate) fn feature<'a>(
+        &'a self,
+        index: u16,
+        substitution: Option<&'a FeatureTableSubstitution>,
+    ) -> Option<&'a Feature> {
+        substitution
+            .and_then(|substitution| {
+                substitution
+                    .substitutions
+                    .iter()
+                    .find(|(feature_index, _)| *feature_index == index)
+            })
+            .map(|(_, feature)| feature)
+            .or_else(|| self.features.get(index as usize))
+    }
+
     pub(crate) fn to_string(&self) -> String {
         let mut string = format!("FeatureCount: {}\n", self.feature_count);
         for feature in self.features.iter() {
@@ -124,123 +141,167 @@ impl FeatureList {
     }
 }
 
+/// GSUB / GPOS 1.1 FeatureVariations: alternate feature tables chosen by
+/// condition sets over the normalized variation coordinates.
 #[derive(Debug, Clone)]
-pub(crate) struct FeatureVariation {
+pub(crate) struct FeatureVariationList {
     pub(crate) major_version: u16,
     pub(crate) minor_version: u16,
-    pub(crate) feature_variations: Box<Vec<FeatureVariationRecord>>,
+    pub(crate) records: Vec<FeatureVariationRecord>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct FeatureVariationRecord {
-    pub(crate) condition_set_offset: u16,
-    pub(crate) feature_table_substitution_offset: u16,
+    pub(crate) condition_set: ConditionSet,
+    pub(crate) substitution: FeatureTableSubstitution,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct FeatureVariations {
+pub(crate) struct FeatureTableSubstitution {
     pub(crate) major_version: u16,
     pub(crate) minor_version: u16,
-    pub(crate) condition_set_count: u16,
-    pub(crate) condition_sets: Box<Vec<ConditionSet>>,
-    pub(crate) feature_table_substitutions: Box<Vec<FeatureTableSubstitution>>,
+    /// Feature index and the alternate feature table that replaces it.
+    pub(crate) substitutions: Vec<(u16, Feature)>,
 }
-impl FeatureVariations {
+
+impl FeatureVariationList {
     pub(crate) fn new<R: BinaryReader>(
         reader: &mut R,
         offset: u64,
-        _length: u32,
+        table_end: u64,
+        features: &FeatureList,
     ) -> Result<Self, std::io::Error> {
+        let out_of_range = |position: u64| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "feature variations offset {} exceeds layout table end {}",
+                    position, table_end
+                ),
+            )
+        };
+        if offset + 8 > table_end {
+            return Err(out_of_range(offset));
+        }
         reader.seek(SeekFrom::Start(offset))?;
         let major_version = reader.read_u16_be()?;
         let minor_version = reader.read_u16_be()?;
-        let feature_table_substitution_count = reader.read_u16_be()?;
-        let mut feature_table_substitutions = Vec::new();
-        for _ in 0..feature_table_substitution_count {
-            let feature_table_substitution_offset = reader.read_u16_be()?;
-            feature_table_substitutions.push(FeatureTableSubstitution {
-                feature_table_substitution: feature_table_substitution_offset,
-            });
+        let record_count = reader.read_u32_be()?;
+        if offset + 8 + record_count as u64 * 8 > table_end {
+            return Err(out_of_range(offset + 8 + record_count as u64 * 8));
         }
-        let condition_set_count = reader.read_u16_be()?;
-        let mut condition_sets = Vec::new();
-        for _ in 0..condition_set_count {
-            let condition_count = reader.read_u16_be()?;
-            let mut conditions = Vec::new();
-            for _ in 0..condition_count {
-                let format = reader.read_u16_be()?;
-                let axis_index = reader.read_u16_be()?;
-                let filter_range_min_value = reader.read_f32_be()?;
-                let filter_range_max_value = reader.read_f32_be()?;
-                conditions.push(ConditionTable {
-                    format,
-                    axis_index,
-                    filter_range_min_value,
-                    filter_range_max_value,
-                });
-            }
-            condition_sets.push(ConditionSet {
-                condition_count,
-                conditions: Box::new(conditions),
+        let mut record_offsets = Vec::with_capacity(record_count as usize);
+        for _ in 0..record_count {
+            let condition_set_offset = reader.read_u32_be()?;
+            let substitution_offset = reader.read_u32_be()?;
+            record_offsets.push((condition_set_offset, substitution_offset));
+        }
+
+        let mut records = Vec::with_capacity(record_offsets.len());
+        for (condition_set_offset, substitution_offset) in record_offsets {
+            let condition_set = if condition_set_offset == 0 {
+                ConditionSet {
+                    conditions: Vec::new(),
+                }
+            } else {
+                let start = offset + condition_set_offset as u64;
+                if start + 2 > table_end {
+                    return Err(out_of_range(start));
+                }
+                ConditionSet::new(reader, start)?
+            };
+            let substitution = if substitution_offset == 0 {
+                FeatureTableSubstitution {
+                    major_version: 1,
+                    minor_version: 0,
+                    substitutions: Vec::new(),
+                }
+            } else {
+                let start = offset + substitution_offset as u64;
+                if start + 6 > table_end {
+                    return Err(out_of_range(start));
+                }
+                FeatureTableSubstitution::new(reader, start, table_end, features)?
+            };
+            records.push(FeatureVariationRecord {
+                condition_set,
+                substitution,
             });
         }
+
         Ok(Self {
             major_version,
             minor_version,
-            condition_set_count,
-            condition_sets: Box::new(condition_sets),
-            feature_table_substitutions: Box::new(feature_table_substitutions),
+            records,
         })
     }
-}
-
-#[derive(Debug, Clone)]
-pub(crate) struct FeatureTableSubstitution {
-    pub(crate) feature_table_substitution: u16,
-}
 
-#[derive(Debug, Clone)]
-pub(crate) struct FeatureVariationRecordList {
-    pub(crate) feature_variation_record_count: u16,
-    pub(crate) feature_variation_records: Box<Vec<FeatureVariationRecord>>,
+    /// The substitution of the first record whose conditions hold at
+    /// `coordinates`.
+    pub(crate) fn substitution(&self, coordinates: &[f32]) -> Option<&FeatureTableSubstitution> {
+        self.records
+            .iter()
+            .find(|record| record.condition_set.matches(coordinates))
+            .map(|record| &record.substitution)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct FeatureVariationList {
-    pub(crate) feature_variation_count: u16,
-    pub(crate) feature_variations: Vec<FeatureVariation>,
-}
-impl FeatureVariationList {
-    pub(crate) fn new<R: BinaryReader>(
+impl FeatureTableSubstitution {
+    fn new<R: BinaryReader>(
         reader: &mut R,
         offset: u64,
-        _length: u32,
+        table_end: u64,
+        features: &FeatureList,
     ) -> Result<Self, std::io::Error> {
         reader.seek(SeekFrom::Start(offset))?;
-        let feature_variation_count = reader.read_u16_be()?;
-        let mut feature_variations = Vec::new();
-        for _ in 0..feature_variation_count {
-            let major_version = reader.read_u16_be()?;
-            let minor_version = reader.read_u16_be()?;
-            let feature_variation_record_count = reader.read_u16_be()?;
-            let mut feature_variation_records = Vec::new();
-            for _ in 0..feature_variation_record_count {
-                let condition_set_offset = reader.read_u16_be()?;
-                let feature_table_substitution_offset = reader.read_u16_be()?;
-                feature_variation_records.push(FeatureVariationRecord {
-                    condition_set_offset,
-                    feature_table_substitution_offset,
-                });
+        let major_version = reader.read_u16_be()?;
+        let minor_version = reader.read_u16_be()?;
+        let substitution_count = reader.read_u16_be()?;
+        let mut records = Vec::with_capacity(substitution_count as usize);
+        for _ in 0..substitution_count {
+            let feature_index = reader.read_u16_be()?;
+            let alternate_offset = reader.read_u32_be()?;
+            records.push((feature_index, alternate_offset));
+        }
+
+        let mut substitutions = Vec::with_capacity(records.len());
+        for (feature_index, alternate_offset) in records {
+            let Some(feature) = features.features.get(feature_index as usize) else {
+                continue;
+            };
+            let start = offset + alternate_offset as u64;
+            if start + 4 > table_end {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "alternate feature table offset {} exceeds layout table end {}",
+                        start, table_end
+                    ),
+                ));
             }
-            feature_variations.push(FeatureVariation {
-                major_version,
-                minor_version,
-                feature_variations: Box::new(feature_variation_records),
-            });
+            reader.seek(SeekFrom::Start(start))?;
+            let _feature_params_offset = reader.read_u16_be()?;
+            let lookup_count = reader.read_u16_be()?;
+            let mut lookup_list_indices = Vec::with_capacity(lookup_count as usize);
+            for _ in 0..lookup_count {
+                lookup_list_indices.push(reader.read_u16_be()?);
+            }
+            // Feature parameters stay with the default feature table.
+            substitutions.push((
+                feature_index,
+                Feature {
+                    feature_tag: feature.feature_tag,
+                    feature_offset: feature.feature_offset,
+                    feature_params: feature.feature_params.clone(),
+                    lookup_list_indices,
+                },
+            ));
         }
+
         Ok(Self {
-            feature_variation_count,
-            feature_variations,
+            major_version,
+            minor_version,
+            substitutions,
         })
     }
 }
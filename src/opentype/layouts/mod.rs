@@ -18,8 +18,7 @@ pub(crate) use language::LanguageSystem;
 pub(crate) use language::LanguageSystemRecord;
 
 pub(crate) use condition::ConditionSet;
-pub(crate) use condition::ConditionTable;
 
 pub(crate) use classdef::ClassDef;
 pub(crate) use classdef::ClassRangeRecord;
-pub(crate) use device::DeviceTable;
+pub(crate) use device::{DeviceContext, DeviceTable};
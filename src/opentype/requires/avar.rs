@@ -1,9 +1,21 @@
 use bin_rs::reader::BinaryReader;
 use std::io::{Error, ErrorKind, SeekFrom};
 
+use super::delta_set::DeltaSetIndexMap;
+use super::var_store::ItemVariationStore;
+
 #[derive(Debug, Clone)]
 pub(crate) struct AVAR {
     segment_maps: Vec<Vec<AxisValueMap>>,
+    /// avar 2.0 cross-axis mapping, applied after the segment maps.
+    axis_variations: Option<AxisVariations>,
+}
+
+#[derive(Debug, Clone)]
+struct AxisVariations {
+    data: Vec<u8>,
+    axis_index_map_offset: Option<u32>,
+    variation_store: ItemVariationStore,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -26,7 +38,7 @@ impl AVAR {
     pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, Error> {
         let mut cursor = 0usize;
         let version = read_u32(data, &mut cursor)?;
-        if version != 0x0001_0000 {
+        if version != 0x0001_0000 && version != 0x0002_0000 {
             return Err(Error::new(
                 ErrorKind::InvalidData,
                 format!("unsupported avar version: {version:#010x}"),
@@ -48,10 +60,45 @@ impl AVAR {
             segment_maps.push(map);
         }
 
-        Ok(Self { segment_maps })
+        let axis_variations = if version == 0x0002_0000 {
+            let axis_index_map_offset = read_u32(data, &mut cursor)?;
+            let variation_store_offset = read_u32(data, &mut cursor)? as usize;
+            if variation_store_offset == 0 {
+                None
+            } else {
+                let variation_store =
+                    ItemVariationStore::parse(data.get(variation_store_offset..).ok_or_else(
+                        || Error::new(ErrorKind::UnexpectedEof, "invalid avar store offset"),
+                    )?)?;
+                Some(AxisVariations {
+                    data: data.to_vec(),
+                    axis_index_map_offset: (axis_index_map_offset != 0)
+                        .then_some(axis_index_map_offset),
+                    variation_store,
+                })
+            }
+        } else {
+            None
+        };
+
+        Ok(Self {
+            segment_maps,
+            axis_variations,
+        })
+    }
+
+    /// Maps normalized coordinates through the segment maps and, for avar
+    /// 2.0, the cross-axis deltas evaluated at the segment-mapped instance.
+    pub(crate) fn map_coordinates(&self, coordinates: &mut [f32]) {
+        for index in 0..coordinates.len() {
+            self.map_coordinate(coordinates, index);
+        }
+        if let Some(axis_variations) = &self.axis_variations {
+            axis_variations.apply(coordinates);
+        }
     }
 
-    pub(crate) fn map_coordinate(&self, coordinates: &mut [f32], coordinate_index: usize) {
+    fn map_coordinate(&self, coordinates: &mut [f32], coordinate_index: usize) {
         if coordinates.len() != self.segment_maps.len() {
             return;
         }
@@ -67,6 +114,38 @@ impl AVAR {
     }
 }
 
+impl AxisVariations {
+    fn apply(&self, coordinates: &mut [f32]) {
+        let mapped = coordinates.to_vec();
+        for (index, value) in coordinates.iter_mut().enumerate() {
+            let (outer_index, inner_index) = match self.axis_index_map_offset {
+                Some(offset) => {
+                    let Some((outer_index, inner_index)) = self
+                        .data
+                        .get(offset as usize..)
+                        .and_then(|data| DeltaSetIndexMap::new(data).map(index as u32))
+                    else {
+                        continue;
+                    };
+                    (outer_index, inner_index)
+                }
+                None => (0, index as u16),
+            };
+            // 0xFFFF/0xFFFF marks an axis without variations.
+            if (outer_index, inner_index) == (0xFFFF, 0xFFFF) {
+                continue;
+            }
+            let Some(delta) = self
+                .variation_store
+                .parse_delta(outer_index, inner_index, &mapped)
+            else {
+                continue;
+            };
+            *value = ((*value * 16384.0 + delta).round() / 16384.0).clamp(-1.0, 1.0);
+        }
+    }
+}
+
 fn map_value(map: &[AxisValueMap], value: f32) -> Option<f32> {
     if map.is_empty() {
         return Some(value);
@@ -136,3 +215,49 @@ fn read_bytes<const N: usize>(data: &[u8], cursor: &mut usize) -> Result<[u8; N]
 fn f2dot14_to_f32(value: i16) -> f32 {
     value as f32 / 16384.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avar2_moves_one_axis_from_another() {
+        let data = [
+            0x00, 0x02, 0x00, 0x00, // version 2.0
+            0x00, 0x00, // reserved
+            0x00, 0x02, // axis count
+            0x00, 0x00, // axis 0: no segment map
+            0x00, 0x00, // axis 1: no segment map
+            0x00, 0x00, 0x00, 0x00, // axisIndexMapOffset
+            0x00, 0x00, 0x00, 0x14, // varStoreOffset
+            // ItemVariationStore
+            0x00, 0x01, // format
+            0x00, 0x00, 0x00, 0x0C, // region list offset
+            0x00, 0x01, // data count
+            0x00, 0x00, 0x00, 0x1C, // data offset
+            0x00, 0x02, // axis count
+            0x00, 0x01, // region count
+            0x00, 0x00, 0x40, 0x00, 0x40, 0x00, // axis 0: 0..1, peak 1
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // axis 1: ignored
+            0x00, 0x02, // item count
+            0x00, 0x01, // word delta count
+            0x00, 0x01, // region index count
+            0x00, 0x00, // region index 0
+            0x00, 0x00, // axis 0 delta
+            0x10, 0x00, // axis 1 delta +0.25
+        ];
+        let avar = AVAR::from_bytes(&data).expect("parse avar2");
+
+        let mut coordinates = [1.0, 0.0];
+        avar.map_coordinates(&mut coordinates);
+        assert_eq!(coordinates, [1.0, 0.25]);
+
+        let mut coordinates = [0.5, 0.9];
+        avar.map_coordinates(&mut coordinates);
+        assert_eq!(coordinates, [0.5, 1.0]);
+
+        let mut coordinates = [-1.0, -0.5];
+        avar.map_coordinates(&mut coordinates);
+        assert_eq!(coordinates, [-1.0, -0.5]);
+    }
+}
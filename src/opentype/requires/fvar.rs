@@ -4,6 +4,7 @@ use std::io::{Error, ErrorKind, SeekFrom};
 #[derive(Debug, Clone)]
 pub(crate) struct FVAR {
     pub(crate) axes: Vec<VariationAxisRecord>,
+    pub(crate) instances: Vec<InstanceRecord>,
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +17,14 @@ pub(crate) struct VariationAxisRecord {
     pub(crate) hidden: bool,
 }
 
+#[derive(Debug, Clone)]
+pub(crate) struct InstanceRecord {
+    pub(crate) subfamily_name_id: u16,
+    /// User-space values in axis order.
+    pub(crate) coordinates: Vec<f32>,
+    pub(crate) postscript_name_id: Option<u16>,
+}
+
 impl FVAR {
     pub(crate) fn new<R: BinaryReader>(
         reader: &mut R,
@@ -41,8 +50,8 @@ impl FVAR {
         let _reserved = read_u16(data, &mut cursor)?;
         let axis_count = read_u16(data, &mut cursor)? as usize;
         let axis_size = read_u16(data, &mut cursor)? as usize;
-        let _instance_count = read_u16(data, &mut cursor)?;
-        let _instance_size = read_u16(data, &mut cursor)?;
+        let instance_count = read_u16(data, &mut cursor)? as usize;
+        let instance_size = read_u16(data, &mut cursor)? as usize;
 
         if axis_size < 20 {
             return Err(Error::new(
@@ -76,7 +85,55 @@ impl FVAR {
             });
         }
 
-        Ok(Self { axes })
+        // Instances follow the axes; the optional PostScript name id is only
+        // present when each record has room for it.
+        let coordinates_size = axis_count * 4 + 4;
+        let has_postscript_name = instance_size >= coordinates_size + 2;
+        // A truncated instance array keeps the axes and the instances read so far.
+        let mut instances = Vec::with_capacity(instance_count);
+        let mut instance_start = axis_cursor;
+        for _ in 0..instance_count {
+            if instance_size < coordinates_size {
+                break;
+            }
+            let Ok(instance) =
+                InstanceRecord::read(data, instance_start, axis_count, has_postscript_name)
+            else {
+                break;
+            };
+            instances.push(instance);
+            instance_start = instance_start.checked_add(instance_size).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidData, "fvar instance cursor overflow")
+            })?;
+        }
+
+        Ok(Self { axes, instances })
+    }
+}
+
+impl InstanceRecord {
+    fn read(
+        data: &[u8],
+        start: usize,
+        axis_count: usize,
+        has_postscript_name: bool,
+    ) -> Result<Self, Error> {
+        let mut cursor = start;
+        let subfamily_name_id = read_u16(data, &mut cursor)?;
+        let _flags = read_u16(data, &mut cursor)?;
+        let coordinates = (0..axis_count)
+            .map(|_| read_i32(data, &mut cursor).map(fixed_to_f32))
+            .collect::<Result<Vec<_>, _>>()?;
+        let postscript_name_id = if has_postscript_name {
+            Some(read_u16(data, &mut cursor)?).filter(|id| *id != 0xFFFF)
+        } else {
+            None
+        };
+        Ok(Self {
+            subfamily_name_id,
+            coordinates,
+            postscript_name_id,
+        })
     }
 }
 
@@ -134,3 +191,58 @@ fn read_bytes<const N: usize>(data: &[u8], cursor: &mut usize) -> Result<[u8; N]
 fn fixed_to_f32(value: i32) -> f32 {
     value as f32 / 65536.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fvar_bytes(instance_size: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        data.extend_from_slice(&16u16.to_be_bytes()); // axesArrayOffset
+        data.extend_from_slice(&2u16.to_be_bytes()); // reserved
+        data.extend_from_slice(&1u16.to_be_bytes()); // axisCount
+        data.extend_from_slice(&20u16.to_be_bytes()); // axisSize
+        data.extend_from_slice(&2u16.to_be_bytes()); // instanceCount
+        data.extend_from_slice(&instance_size.to_be_bytes());
+        data.extend_from_slice(b"wght");
+        for value in [100i32, 400, 900] {
+            data.extend_from_slice(&(value << 16).to_be_bytes());
+        }
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&256u16.to_be_bytes());
+        for (name_id, weight, postscript) in [(258u16, 400i32, 0xFFFFu16), (259, 700, 300)] {
+            data.extend_from_slice(&name_id.to_be_bytes());
+            data.extend_from_slice(&0u16.to_be_bytes());
+            data.extend_from_slice(&(weight << 16).to_be_bytes());
+            if instance_size == 10 {
+                data.extend_from_slice(&postscript.to_be_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn parses_named_instances_with_and_without_postscript_names() {
+        let fvar = FVAR::from_bytes(&fvar_bytes(10)).expect("fvar");
+        assert_eq!(fvar.instances.len(), 2);
+        assert_eq!(fvar.instances[0].subfamily_name_id, 258);
+        assert_eq!(fvar.instances[0].postscript_name_id, None);
+        assert_eq!(fvar.instances[1].coordinates, vec![700.0]);
+        assert_eq!(fvar.instances[1].postscript_name_id, Some(300));
+
+        let fvar = FVAR::from_bytes(&fvar_bytes(8)).expect("fvar without PostScript names");
+        assert_eq!(fvar.instances[1].subfamily_name_id, 259);
+        assert_eq!(fvar.instances[1].postscript_name_id, None);
+    }
+
+    #[test]
+    fn truncated_instances_keep_the_axes_and_earlier_instances() {
+        let mut data = fvar_bytes(10);
+        data.truncate(data.len() - 4);
+        let fvar = FVAR::from_bytes(&data).expect("truncated fvar");
+        assert_eq!(fvar.axes.len(), 1);
+        assert_eq!(fvar.instances.len(), 1);
+        assert_eq!(fvar.instances[0].subfamily_name_id, 258);
+    }
+}
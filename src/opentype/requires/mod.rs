@@ -12,6 +12,7 @@ pub(crate) mod mvar;
 pub(crate) mod name;
 pub(crate) mod os2;
 pub(crate) mod post;
+pub(crate) mod stat;
 pub(crate) mod var_store;
 pub(crate) mod vhea;
 pub(crate) mod vmtx;
@@ -180,6 +180,44 @@ impl OS2 {
     pub(crate) fn selection_flags(&self) -> u16 {
         self.fs_selection
     }
+
+    pub(crate) fn strikeout_size(&self) -> i16 {
+        self.y_strikeout_size
+    }
+
+    pub(crate) fn strikeout_position(&self) -> i16 {
+        self.y_strikeout_position
+    }
+
+    /// Returns `(x_size, y_size, x_offset, y_offset)` for superscripts.
+    pub(crate) fn superscript_metrics(&self) -> (i16, i16, i16, i16) {
+        (
+            self.y_superscript_x_size,
+            self.y_superscript_y_size,
+            self.y_superscript_x_offset,
+            self.y_superscript_y_offset,
+        )
+    }
+
+    /// Returns `(x_size, y_size, x_offset, y_offset)` for subscripts.
+    pub(crate) fn subscript_metrics(&self) -> (i16, i16, i16, i16) {
+        (
+            self.y_subscript_x_size,
+            self.y_subscript_y_size,
+            self.y_subscript_x_offset,
+            self.y_subscript_y_offset,
+        )
+    }
+
+    /// `sxHeight`, or 0 before OS/2 version 2.
+    pub(crate) fn x_height(&self) -> i16 {
+        self.sx_height
+    }
+
+    /// `sCapHeight`, or 0 before OS/2 version 2.
+    pub(crate) fn cap_height(&self) -> i16 {
+        self.s_cap_height
+    }
 }
 
 fn get_os2<R: BinaryReader>(file: &mut R, offest: u32, _length: u32) -> Result<OS2, Error> {
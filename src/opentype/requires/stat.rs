@@ -0,0 +1,460 @@
+use bin_rs::reader::BinaryReader;
+use std::io::{Error, ErrorKind, SeekFrom};
+
+/// Axis value applies to an older sibling font, not to this one.
+pub(crate) const OLDER_SIBLING_FONT_ATTRIBUTE: u16 = 0x0001;
+/// Axis value name may be left out of composed style names.
+pub(crate) const ELIDABLE_AXIS_VALUE_NAME: u16 = 0x0002;
+
+#[derive(Debug, Clone)]
+pub(crate) struct STAT {
+    pub(crate) axes: Vec<DesignAxisRecord>,
+    pub(crate) values: Vec<AxisValue>,
+    pub(crate) elided_fallback_name_id: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DesignAxisRecord {
+    pub(crate) tag: u32,
+    pub(crate) name_id: u16,
+    pub(crate) ordering: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AxisValue {
+    pub(crate) flags: u16,
+    pub(crate) name_id: u16,
+    pub(crate) kind: AxisValueKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum AxisValueKind {
+    /// Format 1: one value on one axis.
+    Single { axis_index: u16, value: f32 },
+    /// Format 2: a nominal value and the range it names.
+    Range {
+        axis_index: u16,
+        nominal: f32,
+        min: f32,
+        max: f32,
+    },
+    /// Format 3: one value plus its style-linked counterpart, e.g. Regular → Bold.
+    Linked {
+        axis_index: u16,
+        value: f32,
+        linked_value: f32,
+    },
+    /// Format 4: a combination of values on several axes.
+    Combined(Vec<(u16, f32)>),
+}
+
+impl AxisValue {
+    pub(crate) fn is_elidable(&self) -> bool {
+        self.flags & ELIDABLE_AXIS_VALUE_NAME != 0
+    }
+
+    pub(crate) fn is_older_sibling(&self) -> bool {
+        self.flags & OLDER_SIBLING_FONT_ATTRIBUTE != 0
+    }
+
+    /// `(axis index, value)` pairs; ranges report their nominal value.
+    pub(crate) fn coordinates(&self) -> Vec<(u16, f32)> {
+        match &self.kind {
+            AxisValueKind::Single { axis_index, value }
+            | AxisValueKind::Linked {
+                axis_index, value, ..
+            } => vec![(*axis_index, *value)],
+            AxisValueKind::Range {
+                axis_index,
+                nominal,
+                ..
+            } => vec![(*axis_index, *nominal)],
+            AxisValueKind::Combined(records) => records.clone(),
+        }
+    }
+
+    fn matches(&self, coordinates: &[Option<f32>]) -> bool {
+        let at = |axis_index: u16| coordinates.get(axis_index as usize).copied().flatten();
+        match &self.kind {
+            AxisValueKind::Single { axis_index, value }
+            | AxisValueKind::Linked {
+                axis_index, value, ..
+            } => at(*axis_index).is_some_and(|current| same_value(current, *value)),
+            AxisValueKind::Range {
+                axis_index,
+                min,
+                max,
+                ..
+            } => at(*axis_index).is_some_and(|current| *min <= current && current <= *max),
+            AxisValueKind::Combined(records) => records.iter().all(|(axis_index, value)| {
+                at(*axis_index).is_some_and(|current| same_value(current, *value))
+            }),
+        }
+    }
+}
+
+impl STAT {
+    pub(crate) fn new<R: BinaryReader>(
+        reader: &mut R,
+        offset: u32,
+        length: u32,
+    ) -> Result<Self, Error> {
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        let data = reader.read_bytes_as_vec(length as usize)?;
+        Self::from_bytes(&data)
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut cursor = 0usize;
+        let major_version = read_u16(data, &mut cursor)?;
+        let minor_version = read_u16(data, &mut cursor)?;
+        if major_version != 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported STAT version: {major_version}.{minor_version}"),
+            ));
+        }
+        let design_axis_size = read_u16(data, &mut cursor)? as usize;
+        let design_axis_count = read_u16(data, &mut cursor)? as usize;
+        let design_axes_offset = read_u32(data, &mut cursor)? as usize;
+        let axis_value_count = read_u16(data, &mut cursor)? as usize;
+        let axis_value_offsets_offset = read_u32(data, &mut cursor)? as usize;
+        // Version 1.0 has no elided fallback name; 2 means "Regular".
+        let elided_fallback_name_id = if minor_version >= 1 {
+            Some(read_u16(data, &mut cursor)?)
+        } else {
+            None
+        };
+
+        if design_axis_count > 0 && design_axis_size < 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "STAT design axis record was smaller than 8 bytes",
+            ));
+        }
+
+        let mut axes = Vec::with_capacity(design_axis_count);
+        for index in 0..design_axis_count {
+            let mut axis_cursor = design_axes_offset + index * design_axis_size;
+            axes.push(DesignAxisRecord {
+                tag: read_u32(data, &mut axis_cursor)?,
+                name_id: read_u16(data, &mut axis_cursor)?,
+                ordering: read_u16(data, &mut axis_cursor)?,
+            });
+        }
+
+        let mut values = Vec::with_capacity(axis_value_count);
+        for index in 0..axis_value_count {
+            let mut offset_cursor = axis_value_offsets_offset + index * 2;
+            let offset = read_u16(data, &mut offset_cursor)? as usize;
+            if let Some(value) = read_axis_value(data, axis_value_offsets_offset + offset)? {
+                values.push(value);
+            }
+        }
+
+        Ok(Self {
+            axes,
+            values,
+            elided_fallback_name_id,
+        })
+    }
+
+    /// Axis values naming `coordinates` (user space, indexed like `axes`), in
+    /// design-axis ordering. Elidable names are skipped; an empty result means
+    /// the elided fallback name applies.
+    pub(crate) fn style_values(&self, coordinates: &[Option<f32>]) -> Vec<&AxisValue> {
+        let mut covered = vec![false; self.axes.len()];
+        let mut picked: Vec<(u16, &AxisValue)> = Vec::new();
+
+        // A format 4 combination that matches exactly wins over single values.
+        for value in &self.values {
+            let AxisValueKind::Combined(records) = &value.kind else {
+                continue;
+            };
+            if value.is_older_sibling()
+                || !value.matches(coordinates)
+                || records
+                    .iter()
+                    .any(|(axis_index, _)| covered.get(*axis_index as usize) != Some(&false))
+            {
+                continue;
+            }
+            for (axis_index, _) in records {
+                covered[*axis_index as usize] = true;
+            }
+            let ordering = records
+                .iter()
+                .map(|(axis_index, _)| self.axes[*axis_index as usize].ordering)
+                .min()
+                .unwrap_or(u16::MAX);
+            picked.push((ordering, value));
+        }
+
+        for (axis_index, axis) in self.axes.iter().enumerate() {
+            if covered[axis_index] {
+                continue;
+            }
+            let candidates = self.values.iter().filter(|value| {
+                !value.is_older_sibling()
+                    && !matches!(value.kind, AxisValueKind::Combined(_))
+                    && value.coordinates()[0].0 as usize == axis_index
+                    && value.matches(coordinates)
+            });
+            // Prefer an exact value over a range that merely contains it.
+            let current = coordinates.get(axis_index).copied().flatten();
+            let value = candidates
+                .clone()
+                .find(|value| {
+                    current.is_some_and(|current| same_value(value.coordinates()[0].1, current))
+                })
+                .or_else(|| candidates.clone().next());
+            if let Some(value) = value {
+                picked.push((axis.ordering, value));
+            }
+        }
+
+        picked.sort_by_key(|(ordering, _)| *ordering);
+        picked
+            .into_iter()
+            .map(|(_, value)| value)
+            .filter(|value| !value.is_elidable())
+            .collect()
+    }
+
+    /// The value on `axis_index` when the table names exactly one for this
+    /// font, as static fonts in a family do.
+    pub(crate) fn own_value(&self, axis_index: usize) -> Option<f32> {
+        let mut values = self
+            .values
+            .iter()
+            .filter(|value| !value.is_older_sibling())
+            .flat_map(|value| value.coordinates())
+            .filter(|(index, _)| *index as usize == axis_index)
+            .map(|(_, value)| value);
+        let first = values.next()?;
+        values
+            .all(|value| same_value(value, first))
+            .then_some(first)
+    }
+
+    pub(crate) fn axis_index(&self, tag: u32) -> Option<usize> {
+        self.axes.iter().position(|axis| axis.tag == tag)
+    }
+}
+
+fn read_axis_value(data: &[u8], offset: usize) -> Result<Option<AxisValue>, Error> {
+    let mut cursor = offset;
+    let format = read_u16(data, &mut cursor)?;
+    let value = match format {
+        1..=3 => {
+            let axis_index = read_u16(data, &mut cursor)?;
+            let flags = read_u16(data, &mut cursor)?;
+            let name_id = read_u16(data, &mut cursor)?;
+            let value = read_fixed(data, &mut cursor)?;
+            let kind = match format {
+                1 => AxisValueKind::Single { axis_index, value },
+                2 => AxisValueKind::Range {
+                    axis_index,
+                    nominal: value,
+                    min: read_fixed(data, &mut cursor)?,
+                    max: read_fixed(data, &mut cursor)?,
+                },
+                _ => AxisValueKind::Linked {
+                    axis_index,
+                    value,
+                    linked_value: read_fixed(data, &mut cursor)?,
+                },
+            };
+            AxisValue {
+                flags,
+                name_id,
+                kind,
+            }
+        }
+        4 => {
+            let axis_count = read_u16(data, &mut cursor)?;
+            let flags = read_u16(data, &mut cursor)?;
+            let name_id = read_u16(data, &mut cursor)?;
+            let records = (0..axis_count)
+                .map(|_| Ok((read_u16(data, &mut cursor)?, read_fixed(data, &mut cursor)?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            AxisValue {
+                flags,
+                name_id,
+                kind: AxisValueKind::Combined(records),
+            }
+        }
+        // Unknown formats are skipped so newer tables still load.
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+fn same_value(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.001
+}
+
+fn read_u16(data: &[u8], cursor: &mut usize) -> Result<u16, Error> {
+    let bytes = read_bytes::<2>(data, cursor)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32(data: &[u8], cursor: &mut usize) -> Result<u32, Error> {
+    let bytes = read_bytes::<4>(data, cursor)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_fixed(data: &[u8], cursor: &mut usize) -> Result<f32, Error> {
+    let bytes = read_bytes::<4>(data, cursor)?;
+    Ok(i32::from_be_bytes(bytes) as f32 / 65536.0)
+}
+
+fn read_bytes<const N: usize>(data: &[u8], cursor: &mut usize) -> Result<[u8; N], Error> {
+    let end = cursor
+        .checked_add(N)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "STAT offset overflow"))?;
+    let slice = data
+        .get(*cursor..end)
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "unexpected end of STAT data"))?;
+    let mut bytes = [0u8; N];
+    bytes.copy_from_slice(slice);
+    *cursor = end;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: f32) -> [u8; 4] {
+        ((value * 65536.0) as i32).to_be_bytes()
+    }
+
+    /// wght (ordering 1), wdth (ordering 0) and ital (ordering 2) with values
+    /// in formats 1 to 4.
+    fn stat_bytes() -> Vec<u8> {
+        let axes: [(&[u8; 4], u16, u16); 3] =
+            [(b"wght", 256, 1), (b"wdth", 257, 0), (b"ital", 258, 2)];
+        let mut tables: Vec<Vec<u8>> = Vec::new();
+        let single = |format: u16, axis: u16, flags: u16, name: u16, value: f32, extra: &[f32]| {
+            let mut table = Vec::new();
+            for field in [format, axis, flags, name] {
+                table.extend_from_slice(&field.to_be_bytes());
+            }
+            table.extend_from_slice(&fixed(value));
+            for value in extra {
+                table.extend_from_slice(&fixed(*value));
+            }
+            table
+        };
+        tables.push(single(3, 0, ELIDABLE_AXIS_VALUE_NAME, 300, 400.0, &[700.0]));
+        tables.push(single(1, 0, 0, 301, 600.0, &[]));
+        tables.push(single(2, 1, 0, 302, 75.0, &[62.5, 87.5]));
+        tables.push(single(1, 1, ELIDABLE_AXIS_VALUE_NAME, 303, 100.0, &[]));
+        tables.push(single(1, 2, ELIDABLE_AXIS_VALUE_NAME, 304, 0.0, &[]));
+        tables.push(single(1, 2, 0, 305, 1.0, &[]));
+        let mut combined = Vec::new();
+        for field in [4u16, 2, 0, 306] {
+            combined.extend_from_slice(&field.to_be_bytes());
+        }
+        for (axis, value) in [(0u16, 900.0f32), (1, 75.0)] {
+            combined.extend_from_slice(&axis.to_be_bytes());
+            combined.extend_from_slice(&fixed(value));
+        }
+        tables.push(combined);
+        tables.push(vec![0, 9, 0, 0]);
+
+        let header_size = 20u32;
+        let axes_size = axes.len() as u32 * 8;
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&8u16.to_be_bytes());
+        data.extend_from_slice(&(axes.len() as u16).to_be_bytes());
+        data.extend_from_slice(&header_size.to_be_bytes());
+        data.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+        data.extend_from_slice(&(header_size + axes_size).to_be_bytes());
+        data.extend_from_slice(&2u16.to_be_bytes());
+        for (tag, name_id, ordering) in axes {
+            data.extend_from_slice(tag);
+            data.extend_from_slice(&name_id.to_be_bytes());
+            data.extend_from_slice(&ordering.to_be_bytes());
+        }
+        let mut offset = tables.len() as u16 * 2;
+        for table in &tables {
+            data.extend_from_slice(&offset.to_be_bytes());
+            offset += table.len() as u16;
+        }
+        for table in tables {
+            data.extend_from_slice(&table);
+        }
+        data
+    }
+
+    fn name_ids(stat: &STAT, coordinates: &[Option<f32>]) -> Vec<u16> {
+        stat.style_values(coordinates)
+            .into_iter()
+            .map(|value| value.name_id)
+            .collect()
+    }
+
+    #[test]
+    fn parses_axes_and_axis_value_formats() {
+        let stat = STAT::from_bytes(&stat_bytes()).expect("STAT");
+        assert_eq!(stat.axes.len(), 3);
+        assert_eq!(stat.axes[1].tag, u32::from_be_bytes(*b"wdth"));
+        assert_eq!(stat.elided_fallback_name_id, Some(2));
+        assert_eq!(stat.values.len(), 7, "unknown formats are skipped");
+        assert_eq!(
+            stat.values[0].kind,
+            AxisValueKind::Linked {
+                axis_index: 0,
+                value: 400.0,
+                linked_value: 700.0
+            }
+        );
+        assert_eq!(
+            stat.values[6].kind,
+            AxisValueKind::Combined(vec![(0, 900.0), (1, 75.0)])
+        );
+    }
+
+    #[test]
+    fn composes_style_names_in_axis_ordering_and_elides() {
+        let stat = STAT::from_bytes(&stat_bytes()).expect("STAT");
+        // Condensed SemiBold Italic: wdth sorts first.
+        assert_eq!(
+            name_ids(&stat, &[Some(600.0), Some(70.0), Some(1.0)]),
+            vec![302, 301, 305]
+        );
+        // Regular, Normal width and upright are all elidable.
+        assert!(name_ids(&stat, &[Some(400.0), Some(100.0), Some(0.0)]).is_empty());
+        // The format 4 combination replaces the wght and wdth names.
+        assert_eq!(
+            name_ids(&stat, &[Some(900.0), Some(75.0), Some(0.0)]),
+            vec![306]
+        );
+    }
+
+    #[test]
+    fn own_value_needs_a_single_value_for_the_axis() {
+        let stat = STAT::from_bytes(&stat_bytes()).expect("STAT");
+        assert_eq!(stat.own_value(1), None, "wdth names 75 and 100");
+
+        let value = |flags: u16, value: f32| AxisValue {
+            flags,
+            name_id: 300,
+            kind: AxisValueKind::Single {
+                axis_index: 0,
+                value,
+            },
+        };
+        let stat = STAT {
+            axes: stat.axes,
+            values: vec![value(OLDER_SIBLING_FONT_ATTRIBUTE, 400.0), value(0, 600.0)],
+            elided_fallback_name_id: Some(2),
+        };
+        assert_eq!(stat.own_value(0), Some(600.0));
+        assert_eq!(stat.own_value(2), None);
+    }
+}
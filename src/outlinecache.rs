@@ -0,0 +1,145 @@
+//! Outline cache: resolved glyph outlines and metrics per glyph and variation instance.
+
+use crate::commands::Command;
+use crate::fontreader::OpenTypeGlyph;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Glyph id, orientation and the exact normalized coordinates.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct OutlineKey {
+    glyph_id: usize,
+    is_vertical: bool,
+    coordinates: Vec<u32>,
+}
+
+impl OutlineKey {
+    pub(crate) fn new(glyph_id: usize, is_vertical: bool, coordinates: &[f32]) -> Self {
+        Self {
+            glyph_id,
+            is_vertical,
+            coordinates: coordinates.iter().map(|value| value.to_bits()).collect(),
+        }
+    }
+}
+
+/// A glyph with gvar deltas and varied metrics applied, plus its outline in
+/// font units, built the first time a layer needs it.
+#[derive(Debug)]
+pub(crate) struct CachedOutline {
+    pub(crate) glyph: OpenTypeGlyph,
+    outline: OnceLock<Option<Vec<Command>>>,
+}
+
+impl CachedOutline {
+    pub(crate) fn new(glyph: OpenTypeGlyph) -> Self {
+        Self {
+            glyph,
+            outline: OnceLock::new(),
+        }
+    }
+
+    /// Unscaled outline commands; `None` when the glyph has no plain outline.
+    pub(crate) fn outline(
+        &self,
+        build: impl FnOnce() -> Option<Vec<Command>>,
+    ) -> Option<&[Command]> {
+        self.outline.get_or_init(build).as_deref()
+    }
+}
+
+/// Per-face bounded cache of [`CachedOutline`]s.
+pub(crate) struct OutlineCache {
+    capacity: usize,
+    outlines: Mutex<HashMap<OutlineKey, Arc<CachedOutline>>>,
+}
+
+impl OutlineCache {
+    pub(crate) const DEFAULT_CAPACITY: usize = 512;
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            outlines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the cached outline for `key`, resolving it with `resolve` on a
+    /// miss. A zero capacity disables caching; a full cache is emptied first.
+    pub(crate) fn get_or_insert(
+        &self,
+        key: OutlineKey,
+        resolve: impl FnOnce() -> CachedOutline,
+    ) -> Arc<CachedOutline> {
+        if self.capacity == 0 {
+            return Arc::new(resolve());
+        }
+        if let Some(outline) = self.lock().get(&key) {
+            return Arc::clone(outline);
+        }
+        // Resolve without holding the lock so other threads are not blocked on
+        // glyf / CFF parsing.
+        let outline = Arc::new(resolve());
+        let mut outlines = self.lock();
+        if outlines.len() >= self.capacity {
+            outlines.clear();
+        }
+        Arc::clone(outlines.entry(key).or_insert(outline))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<OutlineKey, Arc<CachedOutline>>> {
+        self.outlines
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+    }
+}
+
+impl Default for OutlineCache {
+    fn default() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl Clone for OutlineCache {
+    fn clone(&self) -> Self {
+        Self {
+            capacity: self.capacity,
+            outlines: Mutex::new(self.lock().clone()),
+        }
+    }
+}
+
+impl std::fmt::Debug for OutlineCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutlineCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_distinguishes_orientation_and_exact_coordinates() {
+        let a = OutlineKey::new(3, false, &[0.5]);
+        assert_eq!(a, OutlineKey::new(3, false, &[0.5]));
+        assert_ne!(a, OutlineKey::new(3, true, &[0.5]));
+        assert_ne!(a, OutlineKey::new(3, false, &[0.50001]));
+        assert_ne!(a, OutlineKey::new(4, false, &[0.5]));
+    }
+}
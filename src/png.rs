@@ -0,0 +1,488 @@
+//! Minimal PNG codec for raster glyph layers.
+//!
+//! Decoding covers every color type and bit depth, palettes, `tRNS` and
+//! Adam7 interlacing; encoding writes 8-bit RGBA for SVG export.
+
+use std::io::{Error, ErrorKind};
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// Glyph bitmaps larger than this are rejected rather than decoded.
+const MAX_PIXELS: u64 = 1 << 26;
+
+/// Adam7 passes as `(x0, y0, dx, dy)`.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Bytes in one filtered scanline of `width` pixels, without the filter byte.
+    fn row_bytes(&self, width: usize) -> usize {
+        (width * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// Distance to the corresponding byte of the previous pixel when unfiltering.
+    fn filter_stride(&self) -> usize {
+        (self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+}
+
+/// Transparency from `PLTE` / `tRNS`.
+#[derive(Default)]
+struct Transparency {
+    palette: Vec<[u8; 4]>,
+    gray: Option<u16>,
+    rgb: Option<[u16; 3]>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// Decodes a PNG into `(width, height, RGBA8 pixels)` (straight alpha).
+pub(crate) fn decode(data: &[u8]) -> Result<(u32, u32, Vec<u8>), Error> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("not a PNG image"));
+    }
+    let mut header = None;
+    let mut transparency = Transparency::default();
+    let mut compressed = Vec::new();
+    let mut offset = SIGNATURE.len();
+    while offset + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let tag = &data[offset + 4..offset + 8];
+        let body = data
+            .get(offset + 8..(offset + 8).saturating_add(length))
+            .ok_or_else(|| invalid("truncated PNG chunk"))?;
+        match tag {
+            b"IHDR" => header = Some(read_header(body)?),
+            b"PLTE" => {
+                transparency.palette = body
+                    .chunks_exact(3)
+                    .map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect();
+            }
+            b"tRNS" => read_transparency(body, header.as_ref(), &mut transparency)?,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Chunk data is followed by a CRC, which is not verified.
+        offset += 12 + length;
+    }
+    let header = header.ok_or_else(|| invalid("PNG has no IHDR chunk"))?;
+    if header.color_type == 3 && transparency.palette.is_empty() {
+        return Err(invalid("indexed PNG has no PLTE chunk"));
+    }
+
+    let passes: Vec<(usize, usize, usize, usize)> = if header.interlaced {
+        ADAM7.to_vec()
+    } else {
+        vec![(0, 0, 1, 1)]
+    };
+    let expected = passes
+        .iter()
+        .map(|&(x0, y0, dx, dy)| {
+            let width = header.width.saturating_sub(x0).div_ceil(dx);
+            let height = header.height.saturating_sub(y0).div_ceil(dy);
+            if width == 0 {
+                0
+            } else {
+                (header.row_bytes(width) + 1) * height
+            }
+        })
+        .sum::<usize>();
+    let raw = decompress_to_vec_zlib_with_limit(&compressed, expected)
+        .map_err(|_| invalid("failed to decompress PNG image data"))?;
+    if raw.len() < expected {
+        return Err(invalid("PNG image data is truncated"));
+    }
+
+    let mut rgba = vec![0u8; header.width * header.height * 4];
+    let mut cursor = 0;
+    for (x0, y0, dx, dy) in passes {
+        let width = header.width.saturating_sub(x0).div_ceil(dx);
+        let height = header.height.saturating_sub(y0).div_ceil(dy);
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let row_bytes = header.row_bytes(width);
+        let mut previous = vec![0u8; row_bytes];
+        for row in 0..height {
+            let filter = raw[cursor];
+            let mut current = raw[cursor + 1..cursor + 1 + row_bytes].to_vec();
+            cursor += row_bytes + 1;
+            unfilter(filter, &mut current, &previous, header.filter_stride())?;
+            let y = y0 + row * dy;
+            for column in 0..width {
+                let x = x0 + column * dx;
+                let pixel = read_pixel(&header, &transparency, &current, column);
+                let at = (y * header.width + x) * 4;
+                rgba[at..at + 4].copy_from_slice(&pixel);
+            }
+            previous = current;
+        }
+    }
+    Ok((header.width as u32, header.height as u32, rgba))
+}
+
+fn read_header(body: &[u8]) -> Result<Header, Error> {
+    if body.len() < 13 {
+        return Err(invalid("truncated PNG IHDR chunk"));
+    }
+    let width = u32::from_be_bytes(body[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(body[4..8].try_into().unwrap());
+    let bit_depth = body[8];
+    let color_type = body[9];
+    let valid = match color_type {
+        0 => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+        2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !valid || body[10] != 0 || body[11] != 0 || body[12] > 1 {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("unsupported PNG format: color type {color_type}, bit depth {bit_depth}"),
+        ));
+    }
+    if width == 0 || height == 0 || width as u64 * height as u64 > MAX_PIXELS {
+        return Err(invalid("PNG dimensions are out of range"));
+    }
+    Ok(Header {
+        width: width as usize,
+        height: height as usize,
+        bit_depth,
+        color_type,
+        interlaced: body[12] == 1,
+    })
+}
+
+fn read_transparency(
+    body: &[u8],
+    header: Option<&Header>,
+    transparency: &mut Transparency,
+) -> Result<(), Error> {
+    let header = header.ok_or_else(|| invalid("PNG tRNS chunk before IHDR"))?;
+    let sample = |index: usize| -> Option<u16> {
+        Some(u16::from_be_bytes([
+            *body.get(index * 2)?,
+            *body.get(index * 2 + 1)?,
+        ]))
+    };
+    match header.color_type {
+        0 => transparency.gray = sample(0),
+        2 => {
+            transparency.rgb = match (sample(0), sample(1), sample(2)) {
+                (Some(red), Some(green), Some(blue)) => Some([red, green, blue]),
+                _ => None,
+            }
+        }
+        3 => {
+            for (entry, alpha) in transparency.palette.iter_mut().zip(body) {
+                entry[3] = *alpha;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn unfilter(filter: u8, current: &mut [u8], previous: &[u8], stride: usize) -> Result<(), Error> {
+    match filter {
+        0 => {}
+        1 => {
+            for index in stride..current.len() {
+                current[index] = current[index].wrapping_add(current[index - stride]);
+            }
+        }
+        2 => {
+            for (byte, up) in current.iter_mut().zip(previous) {
+                *byte = byte.wrapping_add(*up);
+            }
+        }
+        3 => {
+            for index in 0..current.len() {
+                let left = if index >= stride {
+                    current[index - stride] as u16
+                } else {
+                    0
+                };
+                let average = ((left + previous[index] as u16) / 2) as u8;
+                current[index] = current[index].wrapping_add(average);
+            }
+        }
+        4 => {
+            for index in 0..current.len() {
+                let (left, upper_left) = if index >= stride {
+                    (current[index - stride], previous[index - stride])
+                } else {
+                    (0, 0)
+                };
+                current[index] =
+                    current[index].wrapping_add(paeth(left, previous[index], upper_left));
+            }
+        }
+        _ => return Err(invalid("unknown PNG filter type")),
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - upper_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_upper_left = (estimate - upper_left as i16).abs();
+    if distance_left <= distance_up && distance_left <= distance_upper_left {
+        left
+    } else if distance_up <= distance_upper_left {
+        up
+    } else {
+        upper_left
+    }
+}
+
+/// Sample `channel` of pixel `column` at the image bit depth.
+fn sample(header: &Header, row: &[u8], column: usize, channel: usize) -> u16 {
+    let index = column * header.channels() + channel;
+    match header.bit_depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        depth => {
+            let bit = index * depth as usize;
+            let mask = (1u16 << depth) - 1;
+            (row[bit / 8] as u16 >> (8 - depth as usize - bit % 8)) & mask
+        }
+    }
+}
+
+fn read_pixel(header: &Header, transparency: &Transparency, row: &[u8], column: usize) -> [u8; 4] {
+    let to_8bit = |value: u16| -> u8 {
+        match header.bit_depth {
+            16 => (value >> 8) as u8,
+            depth => (value as u32 * 255 / ((1u32 << depth) - 1)) as u8,
+        }
+    };
+    let sample = |channel| sample(header, row, column, channel);
+    match header.color_type {
+        0 => {
+            let gray = sample(0);
+            let alpha = if transparency.gray == Some(gray) {
+                0
+            } else {
+                255
+            };
+            let gray = to_8bit(gray);
+            [gray, gray, gray, alpha]
+        }
+        2 => {
+            let rgb = [sample(0), sample(1), sample(2)];
+            let alpha = if transparency.rgb == Some(rgb) {
+                0
+            } else {
+                255
+            };
+            [to_8bit(rgb[0]), to_8bit(rgb[1]), to_8bit(rgb[2]), alpha]
+        }
+        3 => transparency
+            .palette
+            .get(sample(0) as usize)
+            .copied()
+            .unwrap_or([0, 0, 0, 0]),
+        4 => {
+            let gray = to_8bit(sample(0));
+            [gray, gray, gray, to_8bit(sample(1))]
+        }
+        _ => [
+            to_8bit(sample(0)),
+            to_8bit(sample(1)),
+            to_8bit(sample(2)),
+            to_8bit(sample(3)),
+        ],
+    }
+}
+
+/// Encodes straight-alpha RGBA8 pixels as a PNG.
+pub(crate) fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, Error> {
+    let row_bytes = width as usize * 4;
+    if width == 0 || height == 0 || rgba.len() != row_bytes * height as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "RGBA buffer does not match the raster size",
+        ));
+    }
+    let mut raw = Vec::with_capacity((row_bytes + 1) * height as usize);
+    for row in rgba.chunks_exact(row_bytes) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &compress_to_vec_zlib(&raw, 6));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, tag: &[u8; 4], body: &[u8]) {
+    png.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(tag);
+    png.extend_from_slice(body);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PNG from unfiltered scanlines (filter bytes included).
+    fn png(header: [u8; 13], extra: &[(&[u8; 4], Vec<u8>)], raw: &[u8]) -> Vec<u8> {
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        for (tag, body) in extra {
+            write_chunk(&mut png, tag, body);
+        }
+        write_chunk(&mut png, b"IDAT", &compress_to_vec_zlib(raw, 6));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8) -> [u8; 13] {
+        let mut header = [0; 13];
+        header[0..4].copy_from_slice(&width.to_be_bytes());
+        header[4..8].copy_from_slice(&height.to_be_bytes());
+        header[8] = bit_depth;
+        header[9] = color_type;
+        header[12] = interlace;
+        header
+    }
+
+    #[test]
+    fn png_round_trips_rgba_through_encoder() {
+        let rgba: Vec<u8> = (0..3 * 2 * 4).map(|value| value as u8 * 10).collect();
+        let encoded = encode_rgba(3, 2, &rgba).unwrap();
+        assert_eq!(
+            crate::util::sniff_encoded_image_dimensions(&encoded),
+            Some(("image/png", 3, 2))
+        );
+        assert_eq!(decode(&encoded).unwrap(), (3, 2, rgba));
+        // CRC of "IEND" from the PNG specification examples.
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn png_decodes_palette_with_trns_and_low_bit_gray() {
+        let palette = vec![255, 0, 0, 0, 0, 255];
+        let image = png(
+            ihdr(3, 1, 2, 3, 0),
+            &[(b"PLTE", palette), (b"tRNS", vec![128])],
+            // Indices 0, 1, 0 packed two bits each.
+            &[0, 0b0001_0000],
+        );
+        let (_, _, rgba) = decode(&image).unwrap();
+        assert_eq!(rgba, vec![255, 0, 0, 128, 0, 0, 255, 255, 255, 0, 0, 128]);
+
+        // 1-bit gray with tRNS marking black transparent; Sub filter on row 2.
+        let image = png(
+            ihdr(2, 2, 1, 0, 0),
+            &[(b"tRNS", vec![0, 0])],
+            &[0, 0b1000_0000, 1, 0b0100_0000],
+        );
+        let (_, _, rgba) = decode(&image).unwrap();
+        assert_eq!(
+            rgba,
+            vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn png_decodes_sixteen_bit_filters_and_adam7() {
+        // 16-bit gray + alpha, Up and Paeth filters.
+        let image = png(
+            ihdr(1, 3, 16, 4, 0),
+            &[],
+            &[
+                0, 0x80, 0x00, 0xff, 0xff, 2, 0x01, 0x00, 0x00, 0x00, 4, 0x01, 0x00, 0x00, 0x00,
+            ],
+        );
+        let (_, _, rgba) = decode(&image).unwrap();
+        assert_eq!(
+            rgba,
+            vec![128, 128, 128, 255, 129, 129, 129, 255, 130, 130, 130, 255]
+        );
+
+        // 3x3 RGB interlaced: pass 1 has (0,0), pass 4 (2,0), pass 5 the even
+        // columns of row 2, pass 6 column 1 of rows 0 and 2, pass 7 row 1.
+        let pixel = |x: u8, y: u8| [x * 80, y * 80, 7];
+        let mut raw = Vec::new();
+        let mut row = |pixels: &[(u8, u8)]| {
+            raw.push(0);
+            for &(x, y) in pixels {
+                raw.extend_from_slice(&pixel(x, y));
+            }
+        };
+        row(&[(0, 0)]);
+        row(&[(2, 0)]);
+        row(&[(0, 2), (2, 2)]);
+        row(&[(1, 0)]);
+        row(&[(1, 2)]);
+        row(&[(0, 1), (1, 1), (2, 1)]);
+        let image = png(ihdr(3, 3, 8, 2, 1), &[], &raw);
+        let (_, _, rgba) = decode(&image).unwrap();
+        for y in 0..3u8 {
+            for x in 0..3u8 {
+                let at = (y as usize * 3 + x as usize) * 4;
+                let [red, green, blue] = pixel(x, y);
+                assert_eq!(rgba[at..at + 4], [red, green, blue, 255], "pixel {x},{y}");
+            }
+        }
+    }
+}
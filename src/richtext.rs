@@ -0,0 +1,543 @@
+//! Attributed text: styled span layout and the Unity-style rich text markup parser.
+
+use crate::commands::{
+    FontOptions, FontRef, FontStyle, FontWeight, GlyphLayer, GlyphPaint, GlyphRun, PositionedGlyph,
+    RichTextSpan, TextDirection,
+};
+use crate::decoration::DecoratedLine;
+use crate::fontreader::ClusterSize;
+use std::io::Error;
+use std::ops::Range;
+
+/// Span attributes over a char range of the piece that shapes them.
+struct PieceStyle<'a> {
+    chars: Range<usize>,
+    options: FontOptions<'a>,
+    color: Option<u32>,
+}
+
+/// Neighbouring span text on one line that shapes alike, shaped as one run
+/// so ligatures and kerning cross the span boundaries.
+struct LinePiece<'a> {
+    text: String,
+    options: FontOptions<'a>,
+    styles: Vec<PieceStyle<'a>>,
+}
+
+#[derive(Default)]
+struct Line<'a> {
+    pieces: Vec<LinePiece<'a>>,
+    line_height: f32,
+}
+
+/// Shapes the spans line by line and applies color, size, and decorations
+/// per span by cluster.
+///
+/// The paragraph direction comes from the first span. Spans without a font use `default_font`.
+pub(crate) fn layout_rich_text<'a>(
+    spans: &[RichTextSpan<'a>],
+    default_font: Option<FontRef<'a>>,
+) -> Result<GlyphRun, Error> {
+    let Some(first) = spans.first() else {
+        return Ok(GlyphRun::default());
+    };
+    let text_direction = first.options.text_direction;
+
+    let mut lines = vec![Line::default()];
+    for span in spans {
+        let mut options = span.options.clone().with_text_direction(text_direction);
+        if options.font.is_none() {
+            options.font = default_font.clone();
+        }
+        let line_height = options.line_height.unwrap_or(options.font_size);
+
+        for (index, part) in span.text.split('\n').enumerate() {
+            if index > 0 {
+                lines.push(Line::default());
+            }
+            let line = lines.last_mut().expect("at least one line");
+            line.line_height = line.line_height.max(line_height);
+            if part.is_empty() {
+                continue;
+            }
+            let piece = match line.pieces.last_mut() {
+                Some(piece) if shapes_alike(&piece.options, &options) => piece,
+                _ => {
+                    let mut shaping = options.clone();
+                    shaping.decorations.clear();
+                    line.pieces.push(LinePiece {
+                        text: String::new(),
+                        options: shaping,
+                        styles: Vec::new(),
+                    });
+                    line.pieces.last_mut().expect("just pushed")
+                }
+            };
+            let start = piece.text.chars().count();
+            piece.text.push_str(part);
+            piece.styles.push(PieceStyle {
+                chars: start..start + part.chars().count(),
+                options: options.clone(),
+                color: span.color,
+            });
+        }
+    }
+
+    let mut glyphs = Vec::new();
+    let mut decorations = Vec::new();
+    let mut line_offset = 0.0f32;
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            line_offset += line.line_height;
+        }
+        let mut pen = 0.0f32;
+        for piece in &line.pieces {
+            let (run, clusters) = shape_piece(piece)?;
+            let piece_start = glyphs.len();
+            for glyph in &run.glyphs {
+                let (x, y) = match text_direction {
+                    TextDirection::LeftToRight | TextDirection::RightToLeft => {
+                        (glyph.x + pen, glyph.y + line_offset)
+                    }
+                    TextDirection::TopToBottom => (glyph.x - line_offset, glyph.y + pen),
+                };
+                glyphs.push(PositionedGlyph::new(glyph.glyph.clone(), x, y));
+            }
+
+            for style in &piece.styles {
+                let style_glyphs = clusters
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, cluster)| cluster.is_some_and(|c| style.chars.contains(&c)))
+                    .map(|(glyph_index, _)| piece_start + glyph_index)
+                    .collect::<Vec<_>>();
+                if let Some(color) = style.color {
+                    for &glyph_index in &style_glyphs {
+                        apply_fill_color(&mut glyphs[glyph_index], color);
+                    }
+                }
+                if style.options.decorations.is_empty() || style_glyphs.is_empty() {
+                    continue;
+                }
+                let cross_origin = if text_direction.is_vertical() {
+                    -line_offset
+                } else {
+                    line_offset
+                };
+                let line = decorated_span(&glyphs, &style_glyphs, cross_origin, text_direction);
+                let metrics = style
+                    .options
+                    .resolve_font()?
+                    .scaled_decoration_metrics(&style.options)?;
+                for mut decoration in crate::decoration::build_decoration_glyphs(
+                    &glyphs,
+                    &[line],
+                    &style.options.decorations,
+                    &metrics,
+                    text_direction.is_vertical(),
+                ) {
+                    if let Some(color) = style.color {
+                        apply_fill_color(&mut decoration, color);
+                    }
+                    decorations.push(decoration);
+                }
+            }
+
+            let extent = run_extent(&run, text_direction);
+            if text_direction.is_right_to_left() {
+                pen -= extent;
+            } else {
+                pen += extent;
+            }
+        }
+    }
+    glyphs.extend(decorations);
+
+    Ok(GlyphRun::new(glyphs))
+}
+
+/// Shapes a piece once, sizing each span's clusters at its own font size.
+fn shape_piece(piece: &LinePiece<'_>) -> Result<(GlyphRun, Vec<Option<usize>>), Error> {
+    let mut options = piece.options.clone();
+    // The foreground entry of color glyphs is resolved while shaping, so it
+    // can only follow the span color when the whole piece shares one.
+    if options.foreground_color.is_none() {
+        let color = piece.styles[0].color;
+        if piece.styles.iter().all(|style| style.color == color) {
+            options.foreground_color = color;
+        }
+    }
+    let sizes = piece
+        .styles
+        .iter()
+        .filter(|style| style.options.font_size != options.font_size)
+        .map(|style| ClusterSize {
+            chars: style.chars.clone(),
+            font_size: style.options.font_size,
+        })
+        .collect::<Vec<_>>();
+    crate::commands::text2clustered_run(&piece.text, options, &sizes)
+}
+
+/// Whether two spans can share a shaping run: everything but color, size,
+/// line height, and decorations must match, and sizes must not pick
+/// different optical-size instances.
+fn shapes_alike(a: &FontOptions<'_>, b: &FontOptions<'_>) -> bool {
+    let same_font = match (&a.font, &b.font) {
+        (None, None) => true,
+        (Some(FontRef::Loaded(a)), Some(FontRef::Loaded(b))) => std::ptr::eq(*a, *b),
+        #[cfg(feature = "raw")]
+        (Some(FontRef::Parsed(a)), Some(FontRef::Parsed(b))) => std::ptr::eq(*a, *b),
+        (Some(FontRef::Family(a)), Some(FontRef::Family(b))) => std::ptr::eq(*a, *b),
+        _ => false,
+    };
+    let alike = same_font
+        && a.font_family == b.font_family
+        && a.font_name == b.font_name
+        && a.locale == b.locale
+        && a.text_direction == b.text_direction
+        && a.font_stretch == b.font_stretch
+        && a.font_style == b.font_style
+        && a.font_variant == b.font_variant
+        && a.font_variant_caps == b.font_variant_caps
+        && a.font_variant_position == b.font_variant_position
+        && a.font_weight == b.font_weight
+        && a.font_optical_sizing == b.font_optical_sizing
+        && a.palette == b.palette
+        && a.palette_type == b.palette_type
+        && a.palette_overrides == b.palette_overrides
+        && a.foreground_color == b.foreground_color
+        && a.embedded_bitmaps == b.embedded_bitmaps
+        && a.variations == b.variations
+        && a.features == b.features
+        && a.dominant_baseline == b.dominant_baseline;
+    if !alike || a.font_size == b.font_size {
+        return alike;
+    }
+    match (a.resolve_font(), b.resolve_font()) {
+        (Ok(font_a), Ok(font_b)) => {
+            font_a.normalized_variation_coords(a) == font_b.normalized_variation_coords(b)
+        }
+        _ => false,
+    }
+}
+
+/// The line a span's decorations follow: its glyphs and their inline extent.
+fn decorated_span(
+    glyphs: &[PositionedGlyph],
+    span_glyphs: &[usize],
+    cross_origin: f32,
+    text_direction: TextDirection,
+) -> DecoratedLine {
+    let mut inline_start = f32::INFINITY;
+    let mut inline_end = f32::NEG_INFINITY;
+    for &index in span_glyphs {
+        let glyph = &glyphs[index];
+        let metrics = &glyph.glyph.metrics;
+        let (start, advance) = if text_direction.is_vertical() {
+            (glyph.y, metrics.advance_y)
+        } else {
+            (glyph.x, metrics.advance_x)
+        };
+        inline_start = inline_start.min(start);
+        inline_end = inline_end.max(start + advance);
+    }
+    DecoratedLine {
+        glyphs: span_glyphs[0]..span_glyphs[span_glyphs.len() - 1] + 1,
+        cross_origin,
+        inline_start,
+        inline_end,
+    }
+}
+
+/// Inline extent of a run that starts at the origin.
+fn run_extent(run: &GlyphRun, text_direction: TextDirection) -> f32 {
+    let mut extent = 0.0f32;
+    for glyph in &run.glyphs {
+        let metrics = &glyph.glyph.metrics;
+        extent = extent.max(match text_direction {
+            TextDirection::LeftToRight => glyph.x + metrics.advance_x,
+            TextDirection::RightToLeft => -glyph.x,
+            TextDirection::TopToBottom => glyph.y + metrics.advance_y,
+        });
+    }
+    extent
+}
+
+fn apply_fill_color(glyph: &mut PositionedGlyph, color: u32) {
+    for layer in &mut glyph.glyph.layers {
+        if let GlyphLayer::Path(path) = layer {
+            if path.paint == GlyphPaint::CurrentColor {
+                path.paint = GlyphPaint::Solid(color);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MarkupStyle<'a> {
+    options: FontOptions<'a>,
+    color: Option<u32>,
+}
+
+/// Parses Unity rich text plus the `<font>`, `<lang>`, and `<noparse>` extensions.
+pub(crate) fn parse_markup<'a>(
+    markup: &'a str,
+    options: &FontOptions<'a>,
+) -> Vec<RichTextSpan<'a>> {
+    let mut spans = Vec::new();
+    let mut style = MarkupStyle {
+        options: options.clone(),
+        color: None,
+    };
+    let mut stack: Vec<(&'a str, MarkupStyle<'a>)> = Vec::new();
+    let mut text = String::new();
+    let mut rest = markup;
+
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        let tail = &rest[open..];
+        let Some(close) = tail.find('>') else {
+            text.push_str(tail);
+            rest = "";
+            break;
+        };
+        let tag = &tail[1..close];
+        let after = &tail[close + 1..];
+
+        if tag.eq_ignore_ascii_case("noparse") {
+            let end = find_ignore_ascii_case(after, "</noparse>");
+            let literal_end = end.unwrap_or(after.len());
+            text.push_str(&after[..literal_end]);
+            rest = end.map_or("", |end| &after[end + "</noparse>".len()..]);
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if let Some(index) = stack
+                .iter()
+                .rposition(|(open_name, _)| open_name.eq_ignore_ascii_case(name))
+            {
+                flush_span(&mut spans, &mut text, &style);
+                style = stack[index].1.clone();
+                stack.truncate(index);
+                rest = after;
+                continue;
+            }
+        } else if let Some((name, next_style)) = apply_tag(tag, &style) {
+            flush_span(&mut spans, &mut text, &style);
+            stack.push((name, style));
+            style = next_style;
+            rest = after;
+            continue;
+        }
+
+        text.push_str(&tail[..close + 1]);
+        rest = after;
+    }
+    text.push_str(rest);
+    flush_span(&mut spans, &mut text, &style);
+    spans
+}
+
+fn flush_span<'a>(spans: &mut Vec<RichTextSpan<'a>>, text: &mut String, style: &MarkupStyle<'a>) {
+    if text.is_empty() {
+        return;
+    }
+    spans.push(RichTextSpan {
+        text: std::mem::take(text),
+        options: style.options.clone(),
+        color: style.color,
+    });
+}
+
+/// Returns the tag name and the style inside it, or `None` for unknown or malformed tags.
+fn apply_tag<'a>(tag: &'a str, style: &MarkupStyle<'a>) -> Option<(&'a str, MarkupStyle<'a>)> {
+    let (name, value) = match tag.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(unquote(value.trim()))),
+        None => (tag.trim(), None),
+    };
+    let mut next = style.clone();
+    match (name.to_ascii_lowercase().as_str(), value) {
+        ("b", None) => next.options.font_weight = FontWeight::BOLD,
+        ("i", None) => next.options.font_style = FontStyle::Italic,
+        ("u", None) => next.options = next.options.with_underline(),
+        ("s", None) => next.options = next.options.with_line_through(),
+        ("color", Some(value)) => next.color = Some(parse_color(value)?),
+        ("size", Some(value)) => {
+            next.options.font_size = parse_size(value, style.options.font_size)?;
+        }
+        ("font", Some(value)) if !value.is_empty() => next.options.font_name = Some(value),
+        ("lang", Some(value)) if !value.is_empty() => next.options.locale = Some(value),
+        _ => return None,
+    }
+    Some((name, next))
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Parses `#RGB`, `#RGBA`, `#RRGGBB`, `#RRGGBBAA`, or a Unity color name into `0xAARRGGBB`.
+fn parse_color(value: &str) -> Option<u32> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        let expand = |digit: &str| u32::from_str_radix(digit, 16).ok().map(|v| v * 0x11);
+        let (rgb, alpha) = match hex.len() {
+            3 | 4 => {
+                let mut channels = [0u32; 4];
+                channels[3] = 0xff;
+                for (index, channel) in channels.iter_mut().enumerate().take(hex.len()) {
+                    *channel = expand(&hex[index..index + 1])?;
+                }
+                (
+                    (channels[0] << 16) | (channels[1] << 8) | channels[2],
+                    channels[3],
+                )
+            }
+            6 => (u32::from_str_radix(hex, 16).ok()?, 0xff),
+            8 => {
+                let rgba = u32::from_str_radix(hex, 16).ok()?;
+                (rgba >> 8, rgba & 0xff)
+            }
+            _ => return None,
+        };
+        return Some((alpha << 24) | rgb);
+    }
+
+    let rgb = match value.to_ascii_lowercase().as_str() {
+        "aqua" | "cyan" => 0x00ffff,
+        "black" => 0x000000,
+        "blue" => 0x0000ff,
+        "brown" => 0xa52a2a,
+        "darkblue" => 0x0000a0,
+        "fuchsia" | "magenta" => 0xff00ff,
+        "green" => 0x008000,
+        "grey" | "gray" => 0x808080,
+        "lightblue" => 0xadd8e6,
+        "lime" => 0x00ff00,
+        "maroon" => 0x800000,
+        "navy" => 0x000080,
+        "olive" => 0x808000,
+        "orange" => 0xffa500,
+        "purple" => 0x800080,
+        "red" => 0xff0000,
+        "silver" => 0xc0c0c0,
+        "teal" => 0x008080,
+        "white" => 0xffffff,
+        "yellow" => 0xffff00,
+        _ => return None,
+    };
+    Some(0xff00_0000 | rgb)
+}
+
+/// Parses `24`, `24px`, `150%`, `1.5em`, `+4`, or `-4` relative to `current`.
+fn parse_size(value: &str, current: f32) -> Option<f32> {
+    let size = if let Some(percent) = value.strip_suffix('%') {
+        current * percent.trim().parse::<f32>().ok()? / 100.0
+    } else if let Some(em) = value.strip_suffix("em") {
+        current * em.trim().parse::<f32>().ok()?
+    } else {
+        let number = value.strip_suffix("px").unwrap_or(value).trim();
+        let parsed = number.parse::<f32>().ok()?;
+        if number.starts_with('+') || number.starts_with('-') {
+            current + parsed
+        } else {
+            parsed
+        }
+    };
+    (size.is_finite() && size > 0.0).then_some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_options(family: &crate::FontFamily) -> FontOptions<'_> {
+        FontOptions::from_family(family).with_font_size(20.0)
+    }
+
+    #[test]
+    fn markup_splits_spans_on_style_tags() {
+        let family = crate::FontFamily::new("Test");
+        let options = base_options(&family);
+        let spans = parse_markup("a<b>b<i>c</i></b><color=red>d</color><u>e</u>", &options);
+
+        assert_eq!(spans.len(), 5);
+        assert_eq!(spans[0].text, "a");
+        assert_eq!(spans[1].options.font_weight, FontWeight::BOLD);
+        assert_eq!(spans[2].options.font_weight, FontWeight::BOLD);
+        assert_eq!(spans[2].options.font_style, FontStyle::Italic);
+        assert_eq!(spans[3].options.font_weight, FontWeight::NORMAL);
+        assert_eq!(spans[3].color, Some(0xffff_0000));
+        assert_eq!(spans[4].options.decorations.len(), 1);
+    }
+
+    #[test]
+    fn markup_resolves_sizes_and_attributes() {
+        let family = crate::FontFamily::new("Test");
+        let options = base_options(&family);
+        let spans = parse_markup(
+            "<size=150%>a<size=+2>b</size></size><font=\"Noto Sans\"><lang=ja-JP>c</lang></font>",
+            &options,
+        );
+
+        assert_eq!(spans[0].options.font_size, 30.0);
+        assert_eq!(spans[1].options.font_size, 32.0);
+        assert_eq!(spans[2].options.font_size, 20.0);
+        assert_eq!(spans[2].options.font_name, Some("Noto Sans"));
+        assert_eq!(spans[2].options.locale, Some("ja-JP"));
+    }
+
+    #[test]
+    fn markup_keeps_unknown_and_noparse_tags_literal() {
+        let family = crate::FontFamily::new("Test");
+        let options = base_options(&family);
+        let spans = parse_markup("<x>1</b><noparse><b></noparse>2<size=big>", &options);
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "<x>1</b><b>2<size=big>");
+    }
+
+    #[test]
+    fn decorations_do_not_split_shaping_but_weight_does() {
+        let family = crate::FontFamily::new("Test");
+        let options = base_options(&family);
+
+        assert!(shapes_alike(&options, &options.clone().with_underline()));
+        assert!(shapes_alike(
+            &options,
+            &options.clone().with_line_height(40.0)
+        ));
+        let mut bold = options.clone();
+        bold.font_weight = FontWeight::BOLD;
+        assert!(!shapes_alike(&options, &bold));
+    }
+
+    #[test]
+    fn colors_accept_short_long_and_named_forms() {
+        assert_eq!(parse_color("#f00"), Some(0xffff_0000));
+        assert_eq!(parse_color("#00ff0080"), Some(0x8000_ff00));
+        assert_eq!(parse_color("#1234"), Some(0x4411_2233));
+        assert_eq!(parse_color("Navy"), Some(0xff00_0080));
+        assert_eq!(parse_color("#12345"), None);
+    }
+}
@@ -0,0 +1,405 @@
+//! Ruby (furigana) layout on top of the regular shaping pipeline.
+//!
+//! Base text and annotations are shaped separately, then merged into one [`GlyphRun`].
+//! Spacing follows the JIS X 4051 defaults: a short annotation is spread 1:2:1 over its
+//! base, and a long annotation may overhang adjacent kana by up to one ruby character
+//! before the base itself is spread out.
+
+use crate::commands::{FontOptions, GlyphRun, PositionedGlyph, RubyKind, RubyText};
+use std::io::{Error, ErrorKind};
+
+/// Ruby font size relative to the base font size.
+pub(crate) const DEFAULT_RUBY_SCALE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RubyAlign {
+    Center,
+    Distribute,
+}
+
+struct ShapedText {
+    run: GlyphRun,
+    extent: f32,
+    spacing_glyphs: usize,
+}
+
+struct RubyUnit {
+    base_text: String,
+    base: ShapedText,
+    ruby: Option<ShapedText>,
+    align: RubyAlign,
+}
+
+/// Inline placement of one base/ruby pair, relative to the pen position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct UnitPlacement {
+    base_lead: f32,
+    base_gap: f32,
+    ruby_lead: f32,
+    ruby_gap: f32,
+    advance: f32,
+}
+
+/// Lays out ruby spans with `shape` and returns a single run in `options` coordinates.
+pub(crate) fn layout_ruby<'a, F>(
+    spans: &[RubyText],
+    options: &FontOptions<'a>,
+    ruby_scale: f32,
+    mut shape: F,
+) -> Result<GlyphRun, Error>
+where
+    F: FnMut(&str, &FontOptions<'a>) -> Result<GlyphRun, Error>,
+{
+    if options.text_direction.is_right_to_left() {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "ruby layout supports left-to-right and top-to-bottom text only",
+        ));
+    }
+    if !ruby_scale.is_finite() || ruby_scale <= 0.0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "ruby_scale must be a positive finite value",
+        ));
+    }
+
+    let vertical = options.text_direction.is_vertical();
+    let mut ruby_options = options
+        .clone()
+        .with_font_size(options.font_size * ruby_scale)
+        .with_feature("ruby", 1);
+    ruby_options.line_height = None;
+    ruby_options.decorations.clear();
+
+    let mut units = Vec::new();
+    for span in spans {
+        resolve_span_units(
+            span,
+            options,
+            &ruby_options,
+            vertical,
+            &mut shape,
+            &mut units,
+        )?;
+    }
+
+    let ruby_size = ruby_options.font_size;
+    let mut base_ascent = 0.0f32;
+    let mut column_width = 0.0f32;
+    let mut ruby_descent = 0.0f32;
+    for unit in &units {
+        for glyph in &unit.base.run.glyphs {
+            if let Some(font) = glyph.glyph.font {
+                base_ascent = base_ascent.max(font.ascent);
+                column_width = column_width.max(font.ascent + font.descent);
+            }
+        }
+        if let Some(ruby) = &unit.ruby {
+            for glyph in &ruby.run.glyphs {
+                if let Some(font) = glyph.glyph.font {
+                    ruby_descent = ruby_descent.max(font.descent);
+                }
+            }
+        }
+    }
+    if base_ascent <= 0.0 {
+        base_ascent = options.font_size;
+    }
+    if column_width <= 0.0 {
+        column_width = options.font_size;
+    }
+
+    let mut glyphs = Vec::new();
+    let mut pen = 0.0f32;
+    for (index, unit) in units.iter().enumerate() {
+        let Some(ruby) = &unit.ruby else {
+            push_shifted(&mut glyphs, &unit.base.run, vertical, pen, 0.0, 0.0, 0.0);
+            pen += unit.base.extent;
+            continue;
+        };
+
+        let overhang_before = index
+            .checked_sub(1)
+            .and_then(|previous| units.get(previous))
+            .filter(|previous| previous.ruby.is_none())
+            .and_then(|previous| previous.base_text.chars().last())
+            .filter(|ch| is_kana(*ch))
+            .map_or(0.0, |_| ruby_size);
+        let overhang_after = units
+            .get(index + 1)
+            .filter(|next| next.ruby.is_none())
+            .and_then(|next| next.base_text.chars().next())
+            .filter(|ch| is_kana(*ch))
+            .map_or(0.0, |_| ruby_size);
+        let placement = place_unit(
+            unit.base.extent,
+            ruby.extent,
+            unit.base.spacing_glyphs,
+            ruby.spacing_glyphs,
+            unit.align,
+            overhang_before,
+            overhang_after,
+        );
+
+        push_shifted(
+            &mut glyphs,
+            &unit.base.run,
+            vertical,
+            pen + placement.base_lead,
+            placement.base_gap,
+            0.0,
+            0.0,
+        );
+        let (block_x, block_y) = if vertical {
+            (column_width, 0.0)
+        } else {
+            (0.0, -(base_ascent + ruby_descent))
+        };
+        push_shifted(
+            &mut glyphs,
+            &ruby.run,
+            vertical,
+            pen + placement.ruby_lead,
+            placement.ruby_gap,
+            block_x,
+            block_y,
+        );
+        pen += placement.advance;
+    }
+
+    Ok(GlyphRun::new(glyphs))
+}
+
+fn resolve_span_units<'a, F>(
+    span: &RubyText,
+    options: &FontOptions<'a>,
+    ruby_options: &FontOptions<'a>,
+    vertical: bool,
+    shape: &mut F,
+    units: &mut Vec<RubyUnit>,
+) -> Result<(), Error>
+where
+    F: FnMut(&str, &FontOptions<'a>) -> Result<GlyphRun, Error>,
+{
+    let annotations: Vec<&str> = span
+        .annotations
+        .iter()
+        .map(String::as_str)
+        .filter(|ruby| !ruby.is_empty())
+        .collect();
+    if annotations.is_empty() {
+        units.push(RubyUnit {
+            base_text: span.base.clone(),
+            base: shape_text(&span.base, options, vertical, shape)?,
+            ruby: None,
+            align: RubyAlign::Center,
+        });
+        return Ok(());
+    }
+
+    let base_chars: Vec<char> = span.base.chars().collect();
+    let per_character = matches!(span.kind, RubyKind::Mono | RubyKind::Jukugo)
+        && span.annotations.len() == base_chars.len();
+    if per_character {
+        let mut per_char_units = Vec::with_capacity(base_chars.len());
+        for (ch, ruby) in base_chars.iter().zip(&span.annotations) {
+            let base_text = ch.to_string();
+            let base = shape_text(&base_text, options, vertical, shape)?;
+            let ruby = if ruby.is_empty() {
+                None
+            } else {
+                Some(shape_text(ruby, ruby_options, vertical, shape)?)
+            };
+            per_char_units.push(RubyUnit {
+                base_text,
+                base,
+                ruby,
+                align: RubyAlign::Center,
+            });
+        }
+
+        let fits = per_char_units.iter().all(|unit| {
+            unit.ruby
+                .as_ref()
+                .is_none_or(|ruby| ruby.extent <= unit.base.extent)
+        });
+        if span.kind == RubyKind::Mono || fits {
+            units.extend(per_char_units);
+            return Ok(());
+        }
+    }
+
+    let ruby_text = annotations.concat();
+    units.push(RubyUnit {
+        base_text: span.base.clone(),
+        base: shape_text(&span.base, options, vertical, shape)?,
+        ruby: Some(shape_text(&ruby_text, ruby_options, vertical, shape)?),
+        align: RubyAlign::Distribute,
+    });
+    Ok(())
+}
+
+fn shape_text<'a, F>(
+    text: &str,
+    options: &FontOptions<'a>,
+    vertical: bool,
+    shape: &mut F,
+) -> Result<ShapedText, Error>
+where
+    F: FnMut(&str, &FontOptions<'a>) -> Result<GlyphRun, Error>,
+{
+    let run = shape(text, options)?;
+    let mut extent = 0.0f32;
+    let mut spacing_glyphs = 0;
+    for glyph in &run.glyphs {
+        let (position, advance) = inline_position_and_advance(glyph, vertical);
+        if advance > 0.0 {
+            spacing_glyphs += 1;
+        }
+        extent = extent.max(position + advance);
+    }
+    Ok(ShapedText {
+        run,
+        extent,
+        spacing_glyphs,
+    })
+}
+
+fn inline_position_and_advance(glyph: &PositionedGlyph, vertical: bool) -> (f32, f32) {
+    if vertical {
+        (glyph.y, glyph.glyph.metrics.advance_y)
+    } else {
+        (glyph.x, glyph.glyph.metrics.advance_x)
+    }
+}
+
+/// Copies `run` into `glyphs`, spreading spacing glyphs apart by `gap` after `lead`.
+fn push_shifted(
+    glyphs: &mut Vec<PositionedGlyph>,
+    run: &GlyphRun,
+    vertical: bool,
+    lead: f32,
+    gap: f32,
+    block_x: f32,
+    block_y: f32,
+) {
+    let mut shift = lead;
+    let mut spacing_index = 0usize;
+    for glyph in &run.glyphs {
+        let (_, advance) = inline_position_and_advance(glyph, vertical);
+        if advance > 0.0 {
+            shift = lead + gap * spacing_index as f32;
+            spacing_index += 1;
+        }
+        let (x, y) = if vertical {
+            (glyph.x + block_x, glyph.y + shift + block_y)
+        } else {
+            (glyph.x + shift + block_x, glyph.y + block_y)
+        };
+        glyphs.push(PositionedGlyph::new(glyph.glyph.clone(), x, y));
+    }
+}
+
+fn place_unit(
+    base_extent: f32,
+    ruby_extent: f32,
+    base_glyphs: usize,
+    ruby_glyphs: usize,
+    align: RubyAlign,
+    overhang_before: f32,
+    overhang_after: f32,
+) -> UnitPlacement {
+    if ruby_extent <= base_extent {
+        let (ruby_lead, ruby_gap) = distribute(base_extent - ruby_extent, ruby_glyphs, align);
+        return UnitPlacement {
+            base_lead: 0.0,
+            base_gap: 0.0,
+            ruby_lead,
+            ruby_gap,
+            advance: base_extent,
+        };
+    }
+
+    let excess = ruby_extent - base_extent;
+    let before = overhang_before.min(excess / 2.0);
+    let after = overhang_after.min(excess / 2.0);
+    let remaining = excess - before - after;
+    let (base_lead, base_gap) = distribute(remaining, base_glyphs, align);
+    UnitPlacement {
+        base_lead,
+        base_gap,
+        ruby_lead: -before,
+        ruby_gap: 0.0,
+        advance: base_extent + remaining,
+    }
+}
+
+/// Splits `space` into a leading offset and an inter-glyph gap (1:2:1 when distributing).
+fn distribute(space: f32, glyphs: usize, align: RubyAlign) -> (f32, f32) {
+    if space <= 0.0 {
+        return (0.0, 0.0);
+    }
+    match align {
+        RubyAlign::Distribute if glyphs > 1 => {
+            let gap = space / glyphs as f32;
+            (gap / 2.0, gap)
+        }
+        _ => (space / 2.0, 0.0),
+    }
+}
+
+fn is_kana(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{3041}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_group_ruby_is_spread_one_two_one() {
+        let placement = place_unit(40.0, 20.0, 2, 2, RubyAlign::Distribute, 0.0, 0.0);
+
+        assert_eq!(placement.ruby_lead, 5.0);
+        assert_eq!(placement.ruby_gap, 10.0);
+        assert_eq!(placement.advance, 40.0);
+    }
+
+    #[test]
+    fn short_mono_ruby_is_centered() {
+        let placement = place_unit(20.0, 10.0, 1, 1, RubyAlign::Center, 0.0, 0.0);
+
+        assert_eq!(placement.ruby_lead, 5.0);
+        assert_eq!(placement.ruby_gap, 0.0);
+        assert_eq!(placement.advance, 20.0);
+    }
+
+    #[test]
+    fn long_ruby_overhangs_kana_before_spreading_base() {
+        let placement = place_unit(20.0, 40.0, 1, 4, RubyAlign::Center, 10.0, 0.0);
+
+        assert_eq!(placement.ruby_lead, -10.0);
+        assert_eq!(placement.base_lead, 5.0);
+        assert_eq!(placement.advance, 30.0);
+    }
+
+    #[test]
+    fn long_group_ruby_spreads_base_glyphs() {
+        let placement = place_unit(40.0, 60.0, 2, 6, RubyAlign::Distribute, 0.0, 0.0);
+
+        assert_eq!(placement.base_lead, 5.0);
+        assert_eq!(placement.base_gap, 10.0);
+        assert_eq!(placement.advance, 60.0);
+    }
+
+    #[test]
+    fn kana_detection_excludes_kanji() {
+        assert!(is_kana('の'));
+        assert!(is_kana('ア'));
+        assert!(!is_kana('漢'));
+        assert!(!is_kana('a'));
+    }
+}
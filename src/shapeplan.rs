@@ -0,0 +1,443 @@
+//! Shape plans: GSUB and GPOS lookups resolved once per shaping configuration.
+
+use crate::commands::{FontOptions, TextDirection};
+use crate::fontreader::Font;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// GSUB stages run by the shaper, in the order `text2glyph_run` applies them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GsubStage {
+    RequiredVariation,
+    Vertical,
+    Locale,
+    Ccmp,
+    Numerator,
+    Denominator,
+    Fraction,
+    Features,
+    Isolated,
+    Initial,
+    Medial,
+    Final,
+    RtlContextual,
+    Ligature,
+    RequiredLigature,
+    SmallCaps,
+    CapitalsToSmallCaps,
+    Superscript,
+    Subscript,
+}
+
+impl GsubStage {
+    const ALL: [Self; 19] = [
+        Self::RequiredVariation,
+        Self::Vertical,
+        Self::Locale,
+        Self::Ccmp,
+        Self::Numerator,
+        Self::Denominator,
+        Self::Fraction,
+        Self::Features,
+        Self::Isolated,
+        Self::Initial,
+        Self::Medial,
+        Self::Final,
+        Self::RtlContextual,
+        Self::Ligature,
+        Self::RequiredLigature,
+        Self::SmallCaps,
+        Self::CapitalsToSmallCaps,
+        Self::Superscript,
+        Self::Subscript,
+    ];
+
+    /// Feature tags of this stage minus the `disabled` ones. `Features` is the
+    /// requested set minus `frac`, which runs as its own numerator /
+    /// denominator / fraction stages.
+    #[cfg_attr(not(feature = "layout"), allow(dead_code))]
+    fn feature_tags(self, requested: &[[u8; 4]], disabled: &[[u8; 4]]) -> Vec<[u8; 4]> {
+        let tags: &[[u8; 4]] = match self {
+            Self::RequiredVariation => &[*b"rvrn"],
+            Self::Vertical => &[*b"vert", *b"vrt2", *b"vrtr"],
+            Self::Locale => &[*b"locl"],
+            Self::Ccmp => &[*b"ccmp"],
+            Self::Numerator => &[*b"numr"],
+            Self::Denominator => &[*b"dnom"],
+            Self::Fraction => &[*b"frac"],
+            Self::Features => {
+                return requested
+                    .iter()
+                    .copied()
+                    .filter(|tag| tag != b"frac")
+                    .collect()
+            }
+            Self::Isolated => &[*b"isol"],
+            Self::Initial => &[*b"init"],
+            Self::Medial => &[*b"medi"],
+            Self::Final => &[*b"fina"],
+            Self::RtlContextual => &[*b"rlig", *b"rclt", *b"calt", *b"clig"],
+            Self::Ligature => &[*b"liga"],
+            Self::RequiredLigature => &[*b"rlig"],
+            Self::SmallCaps => &[*b"smcp"],
+            Self::CapitalsToSmallCaps => &[*b"c2sc"],
+            Self::Superscript => &[*b"sups"],
+            Self::Subscript => &[*b"subs"],
+        };
+        tags.iter()
+            .copied()
+            .filter(|tag| !disabled.contains(tag))
+            .collect()
+    }
+
+    /// `rvrn`, vertical forms, `ccmp` and `liga` are looked up without a
+    /// language system.
+    #[cfg_attr(not(feature = "layout"), allow(dead_code))]
+    fn uses_locale(self) -> bool {
+        !matches!(
+            self,
+            Self::RequiredVariation | Self::Vertical | Self::Ccmp | Self::Ligature
+        )
+    }
+}
+
+/// GPOS stages run by the shaper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GposStage {
+    Kerning,
+    Mark,
+    MarkToMark,
+}
+
+impl GposStage {
+    const ALL: [Self; 3] = [Self::Kerning, Self::Mark, Self::MarkToMark];
+
+    #[cfg_attr(not(feature = "layout"), allow(dead_code))]
+    fn feature_tag(self, direction: TextDirection) -> [u8; 4] {
+        match self {
+            Self::Kerning if direction.is_vertical() => *b"vkrn",
+            Self::Kerning => *b"kern",
+            Self::Mark => *b"mark",
+            Self::MarkToMark => *b"mkmk",
+        }
+    }
+}
+
+/// Everything lookup resolution depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ShapePlanKey {
+    has_locale: bool,
+    script_tags: Vec<u32>,
+    language_tags: Vec<u32>,
+    direction: TextDirection,
+    feature_tags: Vec<[u8; 4]>,
+    /// Features turned off with a zero value; dropped from every stage.
+    disabled_tags: Vec<[u8; 4]>,
+    /// Normalized coordinates in F2DOT14 units.
+    coordinates: Vec<i32>,
+}
+
+impl ShapePlanKey {
+    fn new(
+        locale: Option<&str>,
+        direction: TextDirection,
+        feature_tags: &[[u8; 4]],
+        disabled_tags: &[[u8; 4]],
+        coordinates: &[f32],
+    ) -> Self {
+        #[cfg(feature = "layout")]
+        let (script_tags, language_tags) = locale
+            .map(|locale| {
+                (
+                    crate::opentype::extentions::gsub::GSUB::locale_to_script_tags(locale),
+                    crate::opentype::extentions::gsub::GSUB::locale_to_language_system_tags(locale),
+                )
+            })
+            .unwrap_or_default();
+        #[cfg(not(feature = "layout"))]
+        let (script_tags, language_tags) = (Vec::new(), Vec::new());
+
+        Self {
+            has_locale: locale.is_some(),
+            script_tags,
+            language_tags,
+            direction,
+            feature_tags: feature_tags.to_vec(),
+            disabled_tags: disabled_tags.to_vec(),
+            coordinates: coordinates
+                .iter()
+                .map(|value| (value * 16384.0).round() as i32)
+                .collect(),
+        }
+    }
+}
+
+/// GSUB and GPOS lookup indices resolved once for a script, language,
+/// direction, feature set and variation instance.
+///
+/// Get one from [`crate::FontFace::shape_plan`]; faces cache their plans, so
+/// shaping many short strings with the same options skips the ScriptList /
+/// FeatureList walk after the first call.
+#[derive(Debug, Clone)]
+pub struct ShapePlan {
+    key: ShapePlanKey,
+    /// Normalized coordinates the plan was resolved for.
+    coordinates: Vec<f32>,
+    gsub: Vec<Vec<u16>>,
+    gpos: Vec<Vec<u16>>,
+}
+
+impl ShapePlan {
+    /// Resolves a plan for `font` from the locale, direction, GSUB features and
+    /// variation settings in `options`.
+    pub(crate) fn new(font: &Font, options: &FontOptions<'_>) -> Self {
+        Self::resolve(
+            font,
+            options.locale,
+            options.text_direction,
+            &options.gsub_feature_tags(),
+            &options.disabled_feature_tags(),
+            &font.normalized_variation_coords(options),
+        )
+    }
+
+    pub(crate) fn resolve(
+        font: &Font,
+        locale: Option<&str>,
+        direction: TextDirection,
+        feature_tags: &[[u8; 4]],
+        disabled_tags: &[[u8; 4]],
+        coordinates: &[f32],
+    ) -> Self {
+        Self::with_key(
+            font,
+            locale,
+            ShapePlanKey::new(locale, direction, feature_tags, disabled_tags, coordinates),
+        )
+    }
+
+    fn with_key(font: &Font, locale: Option<&str>, key: ShapePlanKey) -> Self {
+        let coordinates = key
+            .coordinates
+            .iter()
+            .map(|value| *value as f32 / 16384.0)
+            .collect::<Vec<_>>();
+        #[cfg(feature = "layout")]
+        let (gsub, gpos) = {
+            let gsub = GsubStage::ALL
+                .iter()
+                .map(|stage| {
+                    let Some(gsub) = font.current_gsub() else {
+                        return Vec::new();
+                    };
+                    // `locl` only applies when the caller asked for a locale.
+                    if *stage == GsubStage::Locale && locale.is_none() {
+                        return Vec::new();
+                    }
+                    let locale = if stage.uses_locale() { locale } else { None };
+                    gsub.feature_lookup_indices_at(
+                        locale,
+                        &stage.feature_tags(&key.feature_tags, &key.disabled_tags),
+                        &coordinates,
+                    )
+                })
+                .collect();
+            let gpos = GposStage::ALL
+                .iter()
+                .map(|stage| {
+                    let tag = stage.feature_tag(key.direction);
+                    if key.disabled_tags.contains(&tag) {
+                        return Vec::new();
+                    }
+                    font.current_gpos()
+                        .map(|gpos| gpos.feature_lookup_indices_at(locale, &[tag], &coordinates))
+                        .unwrap_or_default()
+                })
+                .collect();
+            (gsub, gpos)
+        };
+        #[cfg(not(feature = "layout"))]
+        let (gsub, gpos) = {
+            let _ = (font, locale);
+            (
+                vec![Vec::new(); GsubStage::ALL.len()],
+                vec![Vec::new(); GposStage::ALL.len()],
+            )
+        };
+
+        Self {
+            key,
+            coordinates,
+            gsub,
+            gpos,
+        }
+    }
+
+    /// Text direction the plan was resolved for.
+    pub fn direction(&self) -> TextDirection {
+        self.key.direction
+    }
+
+    /// GSUB features requested through the options, in request order.
+    pub fn feature_tags(&self) -> &[[u8; 4]] {
+        &self.key.feature_tags
+    }
+
+    /// Total number of resolved lookups across all stages.
+    pub fn lookup_count(&self) -> usize {
+        self.gsub.iter().chain(self.gpos.iter()).map(Vec::len).sum()
+    }
+
+    /// Normalized variation coordinates, empty for the default instance.
+    pub(crate) fn coordinates(&self) -> &[f32] {
+        &self.coordinates
+    }
+
+    pub(crate) fn gsub_lookups(&self, stage: GsubStage) -> &[u16] {
+        &self.gsub[stage as usize]
+    }
+
+    pub(crate) fn gpos_lookups(&self, stage: GposStage) -> &[u16] {
+        &self.gpos[stage as usize]
+    }
+
+    /// Whether the `feature_tags` stage was requested, independent of font support.
+    pub(crate) fn requests_feature(&self, tag: [u8; 4]) -> bool {
+        self.key.feature_tags.contains(&tag)
+    }
+}
+
+/// Per-face cache of shape plans.
+#[derive(Default)]
+pub(crate) struct ShapePlanCache {
+    plans: Mutex<HashMap<ShapePlanKey, Arc<ShapePlan>>>,
+}
+
+impl ShapePlanCache {
+    /// Plans beyond this many are dropped rather than grown without bound.
+    const CAPACITY: usize = 64;
+
+    pub(crate) fn get_or_build(&self, font: &Font, options: &FontOptions<'_>) -> Arc<ShapePlan> {
+        let key = ShapePlanKey::new(
+            options.locale,
+            options.text_direction,
+            &options.gsub_feature_tags(),
+            &options.disabled_feature_tags(),
+            &font.normalized_variation_coords(options),
+        );
+        let mut plans = self
+            .plans
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner());
+        if let Some(plan) = plans.get(&key) {
+            return Arc::clone(plan);
+        }
+        if plans.len() >= Self::CAPACITY {
+            plans.clear();
+        }
+        let plan = Arc::new(ShapePlan::with_key(font, options.locale, key.clone()));
+        plans.insert(key, Arc::clone(&plan));
+        plan
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.plans
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .len()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.plans
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .clear();
+    }
+}
+
+impl Clone for ShapePlanCache {
+    fn clone(&self) -> Self {
+        let plans = self
+            .plans
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner())
+            .clone();
+        Self {
+            plans: Mutex::new(plans),
+        }
+    }
+}
+
+impl std::fmt::Debug for ShapePlanCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShapePlanCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_normalizes_locale_to_tags_and_coords_to_f2dot14() {
+        let a = ShapePlanKey::new(Some("ja-JP"), TextDirection::LeftToRight, &[], &[], &[0.5]);
+        let b = ShapePlanKey::new(
+            Some("ja_jp"),
+            TextDirection::LeftToRight,
+            &[],
+            &[],
+            &[0.50001],
+        );
+        assert_eq!(a, b);
+        assert_eq!(a.coordinates, vec![8192]);
+
+        let none = ShapePlanKey::new(None, TextDirection::LeftToRight, &[], &[], &[0.5]);
+        assert_ne!(a, none);
+        let vertical =
+            ShapePlanKey::new(Some("ja-JP"), TextDirection::TopToBottom, &[], &[], &[0.5]);
+        assert_ne!(a, vertical);
+        let no_liga = ShapePlanKey::new(
+            Some("ja-JP"),
+            TextDirection::LeftToRight,
+            &[],
+            &[*b"liga"],
+            &[0.5],
+        );
+        assert_ne!(a, no_liga);
+    }
+
+    #[test]
+    fn features_stage_leaves_fraction_to_its_own_stages() {
+        let requested = [*b"smcp", *b"frac", *b"tnum"];
+        assert_eq!(
+            GsubStage::Features.feature_tags(&requested, &[]),
+            vec![*b"smcp", *b"tnum"]
+        );
+        assert_eq!(
+            GsubStage::Fraction.feature_tags(&requested, &[]),
+            vec![*b"frac"]
+        );
+        assert!(!GsubStage::Ligature.uses_locale());
+        assert_eq!(
+            GsubStage::RequiredVariation.feature_tags(&requested, &[]),
+            vec![*b"rvrn"]
+        );
+        assert_eq!(
+            GposStage::Kerning.feature_tag(TextDirection::TopToBottom),
+            *b"vkrn"
+        );
+    }
+
+    #[test]
+    fn disabled_features_drop_out_of_fixed_stages() {
+        let disabled = [*b"liga", *b"calt"];
+        assert!(GsubStage::Ligature.feature_tags(&[], &disabled).is_empty());
+        assert_eq!(
+            GsubStage::RtlContextual.feature_tags(&[], &disabled),
+            vec![*b"rlig", *b"rclt", *b"clig"]
+        );
+        assert_eq!(GsubStage::Ccmp.feature_tags(&[], &disabled), vec![*b"ccmp"]);
+    }
+}
@@ -1,13 +1,28 @@
 use crate::commands::{
-    Command, FillRule, GlyphGradientSpread, GlyphGradientStop, GlyphGradientUnits,
-    GlyphLinearGradient, GlyphPaint, GlyphRadialGradient, PathGlyphLayer,
+    Command, FillRule, GlyphFilter, GlyphGradientSpread, GlyphGradientStop, GlyphGradientUnits,
+    GlyphLinearGradient, GlyphMask, GlyphMaskMode, GlyphPaint, GlyphRadialGradient, PathGlyphLayer,
 };
 use std::collections::HashMap;
 
+/// `mask` / `pattern` content may reference further masks and patterns this deep.
+const MAX_REFERENCE_DEPTH: usize = 4;
+/// Patterns needing more tiles than this are dropped; the budget shrinks 32
+/// times per nesting level so nested patterns stay bounded.
+const MAX_PATTERN_TILES: i64 = 1024;
+/// Filter primitives that `resolve_filter` understands.
+const SUPPORTED_FILTER_PRIMITIVES: [&str; 6] = [
+    "fegaussianblur",
+    "feoffset",
+    "feflood",
+    "fecomposite",
+    "femerge",
+    "femergenode",
+];
+
 #[derive(Debug, Clone)]
 enum SvgNode {
     Element(SvgElement),
-    Text,
+    Text(String),
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +41,17 @@ struct RenderState {
     stroke_width: f32,
     clip_path: Option<ClipPathSpec>,
     mask_path: Option<ClipPathSpec>,
+    /// Id of the `pattern` painting the fill; `fill` is `None` meanwhile.
+    fill_pattern: Option<String>,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    mask: Option<MaskSpec>,
+    /// Bounding box of the group the `mask` is set on; shapes use their own otherwise.
+    mask_bounds: Option<(f32, f32, f32, f32)>,
+    /// Filter outputs, each a chain of primitives applied to a copy of the layer.
+    filters: Option<Vec<Vec<GlyphFilter>>>,
+    /// Nesting of `mask` / `pattern` content.
+    depth: usize,
 }
 
 impl Default for RenderState {
@@ -38,10 +64,32 @@ impl Default for RenderState {
             stroke_width: 1.0,
             clip_path: None,
             mask_path: None,
+            fill_pattern: None,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            mask: None,
+            mask_bounds: None,
+            filters: None,
+            depth: 0,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+struct MaskSpec {
+    element: SvgElement,
+    mode: GlyphMaskMode,
+    units: ClipPathUnits,
+}
+
+/// Result of a filter primitive: layer copies, or a flood color waiting to be
+/// composited into one.
+#[derive(Debug, Clone)]
+enum FilterValue {
+    Chains(Vec<Vec<GlyphFilter>>),
+    Flood(u32),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ClipPathUnits {
     UserSpaceOnUse,
@@ -132,45 +180,61 @@ impl Transform2D {
             self.b * x + self.d * y + self.f,
         )
     }
+
+    fn invert(self) -> Option<Self> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let a = self.d / determinant;
+        let b = -self.b / determinant;
+        let c = -self.c / determinant;
+        let d = self.a / determinant;
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
 }
 
+/// Empty when the document cannot be drawn as path layers, so the caller keeps the SVG.
 pub(crate) fn svg_to_path_layers(
     document: &str,
     scale_x: f32,
     scale_y: f32,
 ) -> Vec<PathGlyphLayer> {
-    let Ok(root) = parse_svg_document(document) else {
+    let Ok(mut root) = parse_svg_document(document) else {
         return Vec::new();
     };
+    apply_style_sheets(&mut root);
 
     let mut defs = HashMap::new();
     collect_definitions(&root, &mut defs);
 
     let mut layers = Vec::new();
     let state = RenderState::default();
-    flatten_node(
+    if !flatten_node(
         &root,
         &defs,
         &state,
         scale_x,
         scale_y,
         &mut layers,
-    );
+    ) {
+        return Vec::new();
+    }
     layers
 }
 
 pub(crate) fn svg_requires_svg_fallback(document: &str) -> bool {
     let lowered = document.to_ascii_lowercase();
-    lowered.contains("<pattern")
-        || lowered.contains("<mask")
-        || lowered.contains("<filter")
-        || lowered.contains("patternunits=")
-        || lowered.contains("maskunits=")
-        || lowered.contains("maskcontentunits=")
-        || lowered.contains(" filter=")
-        || lowered.contains("filter=\"")
-        || lowered.contains("mask=\"")
-        || lowered.contains("mask:")
+    lowered.match_indices("<fe").any(|(start, _)| {
+        tag_name(&lowered[start..]).is_none_or(|name| !SUPPORTED_FILTER_PRIMITIVES.contains(&name))
+    })
 }
 
 fn parse_svg_document(document: &str) -> Result<SvgNode, ()> {
@@ -189,7 +253,7 @@ fn parse_svg_document(document: &str) -> Result<SvgNode, ()> {
                     .last_mut()
                     .expect("document root")
                     .children
-                    .push(SvgNode::Text);
+                    .push(SvgNode::Text(trailing.to_string()));
             }
             break;
         };
@@ -200,7 +264,22 @@ fn parse_svg_document(document: &str) -> Result<SvgNode, ()> {
                 .last_mut()
                 .expect("document root")
                 .children
-                .push(SvgNode::Text);
+                .push(SvgNode::Text(text.to_string()));
+        }
+
+        if document[start..].starts_with("<![CDATA[") {
+            let Some(relative_end) = document[start..].find("]]>") else {
+                return Err(());
+            };
+            stack
+                .last_mut()
+                .expect("document root")
+                .children
+                .push(SvgNode::Text(
+                    document[start + 9..start + relative_end].to_string(),
+                ));
+            cursor = start + relative_end + 3;
+            continue;
         }
 
         let Some(relative_end) = document[start..].find('>') else {
@@ -263,6 +342,191 @@ fn parse_svg_document(document: &str) -> Result<SvgNode, ()> {
     Ok(SvgNode::Element(stack.pop().ok_or(())?))
 }
 
+/// Compound CSS selector such as `path.skin#nose`; combinators are not supported.
+#[derive(Debug, Clone, Default)]
+struct SimpleSelector {
+    element: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+impl SimpleSelector {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty()
+            || text.contains(|ch: char| {
+                ch.is_whitespace() || matches!(ch, '>' | '+' | '~' | ':' | '[')
+            })
+        {
+            return None;
+        }
+        let mut selector = Self::default();
+        let end = text.find(['.', '#']).unwrap_or(text.len());
+        if !text[..end].is_empty() && &text[..end] != "*" {
+            selector.element = Some(text[..end].to_ascii_lowercase());
+        }
+        let mut rest = &text[end..];
+        while let Some(kind) = rest.chars().next() {
+            let end = rest[1..].find(['.', '#']).map_or(rest.len(), |end| end + 1);
+            let name = &rest[1..end];
+            if name.is_empty() {
+                return None;
+            }
+            if kind == '.' {
+                selector.classes.push(name.to_string());
+            } else {
+                selector.id = Some(name.to_string());
+            }
+            rest = &rest[end..];
+        }
+        Some(selector)
+    }
+
+    fn specificity(&self) -> (usize, usize, usize) {
+        (
+            self.id.is_some() as usize,
+            self.classes.len(),
+            self.element.is_some() as usize,
+        )
+    }
+
+    fn matches(&self, element: &SvgElement) -> bool {
+        if self
+            .element
+            .as_ref()
+            .is_some_and(|name| *name != element.name)
+        {
+            return false;
+        }
+        if self.id.is_some() && self.id.as_ref() != element.attrs.get("id") {
+            return false;
+        }
+        let classes = element.attrs.get("class").map(String::as_str).unwrap_or("");
+        self.classes.iter().all(|class| {
+            classes
+                .split_whitespace()
+                .any(|candidate| candidate == class)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StyleRule {
+    selector: SimpleSelector,
+    declarations: HashMap<String, String>,
+}
+
+/// Folds `<style>` sheets into each element's `style` attribute, below its own
+/// inline declarations and above presentation attributes.
+fn apply_style_sheets(root: &mut SvgNode) {
+    let mut sheet = String::new();
+    collect_style_text(root, &mut sheet);
+    let rules = parse_style_sheet(&sheet);
+    if !rules.is_empty() {
+        apply_style_rules(root, &rules);
+    }
+}
+
+fn collect_style_text(node: &SvgNode, out: &mut String) {
+    let SvgNode::Element(element) = node else {
+        return;
+    };
+    for child in &element.children {
+        match child {
+            SvgNode::Text(text) if element.name == "style" => {
+                out.push_str(text);
+                out.push('\n');
+            }
+            _ => collect_style_text(child, out),
+        }
+    }
+}
+
+fn parse_style_sheet(sheet: &str) -> Vec<StyleRule> {
+    let mut source = String::with_capacity(sheet.len());
+    let mut rest = sheet;
+    while let Some(start) = rest.find("/*") {
+        source.push_str(&rest[..start]);
+        rest = rest[start + 2..]
+            .find("*/")
+            .map_or("", |end| &rest[start + 2 + end + 2..]);
+    }
+    source.push_str(rest);
+
+    let mut rules = Vec::new();
+    let mut rest = source.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        // At-rules may nest blocks; skip to the matching brace.
+        let mut depth = 0usize;
+        let mut close = None;
+        for (index, ch) in rest[open..].char_indices() {
+            match ch {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + index);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(close) = close else {
+            break;
+        };
+        if !prelude.starts_with('@') {
+            let declarations: HashMap<String, String> = parse_style(&rest[open + 1..close])
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = value.trim_end_matches("!important").trim_end().to_string();
+                    (key, value)
+                })
+                .collect();
+            for selector in prelude.split(',').filter_map(SimpleSelector::parse) {
+                rules.push(StyleRule {
+                    selector,
+                    declarations: declarations.clone(),
+                });
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    rules
+}
+
+fn apply_style_rules(node: &mut SvgNode, rules: &[StyleRule]) {
+    let SvgNode::Element(element) = node else {
+        return;
+    };
+    let mut matched: Vec<(usize, &StyleRule)> = rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| rule.selector.matches(element))
+        .collect();
+    if !matched.is_empty() {
+        // Later and more specific rules win.
+        matched.sort_by_key(|(index, rule)| (rule.selector.specificity(), *index));
+        let mut style = HashMap::new();
+        for (_, rule) in matched {
+            style.extend(rule.declarations.clone());
+        }
+        if let Some(inline) = element.attrs.get("style") {
+            style.extend(parse_style(inline));
+        }
+        let style = style
+            .into_iter()
+            .map(|(key, value)| format!("{key}:{value}"))
+            .collect::<Vec<_>>()
+            .join(";");
+        element.attrs.insert("style".to_string(), style);
+    }
+    for child in &mut element.children {
+        apply_style_rules(child, rules);
+    }
+}
+
 fn collect_definitions(node: &SvgNode, defs: &mut HashMap<String, SvgElement>) {
     let SvgNode::Element(element) = node else {
         return;
@@ -270,6 +534,14 @@ fn collect_definitions(node: &SvgNode, defs: &mut HashMap<String, SvgElement>) {
 
     if element.name == "defs" {
         collect_id_elements(&element.children, defs);
+    } else if matches!(
+        element.name.as_str(),
+        "mask" | "pattern" | "filter" | "clippath" | "lineargradient" | "radialgradient"
+    ) {
+        // Paint servers and effects are referenced by id wherever they appear.
+        if let Some(id) = element.attrs.get("id") {
+            defs.entry(id.clone()).or_insert_with(|| element.clone());
+        }
     }
 
     for child in &element.children {
@@ -289,6 +561,8 @@ fn collect_id_elements(children: &[SvgNode], defs: &mut HashMap<String, SvgEleme
     }
 }
 
+/// Returns `false` when the node cannot be drawn as path layers, as for a group
+/// whose `opacity` would have to be composited over several layers.
 fn flatten_node(
     node: &SvgNode,
     defs: &HashMap<String, SvgElement>,
@@ -296,20 +570,20 @@ fn flatten_node(
     scale_x: f32,
     scale_y: f32,
     out: &mut Vec<PathGlyphLayer>,
-) {
+) -> bool {
     let SvgNode::Element(element) = node else {
-        return;
+        return true;
     };
 
     if element.name == "#document" {
-        for child in &element.children {
-            flatten_node(child, defs, state, scale_x, scale_y, out);
-        }
-        return;
+        return element
+            .children
+            .iter()
+            .all(|child| flatten_node(child, defs, state, scale_x, scale_y, out));
     }
 
     if element.name == "defs" {
-        return;
+        return true;
     }
 
     let style = element.attrs.get("style").map(|value| parse_style(value));
@@ -358,35 +632,101 @@ fn flatten_node(
         scale_x,
         scale_y,
     );
+    let fill_pattern = match presentation_value(style.as_ref(), element, "fill") {
+        Some(value) => parse_url_reference(value.trim())
+            .filter(|reference| {
+                state.depth < MAX_REFERENCE_DEPTH
+                    && defs
+                        .get(*reference)
+                        .is_some_and(|referenced| referenced.name == "pattern")
+            })
+            .map(str::to_string),
+        None => state.fill_pattern.clone(),
+    };
+    let opacity_value = |key: &str, inherited: f32| {
+        presentation_value(style.as_ref(), element, key)
+            .and_then(|value| parse_number_or_percent(value))
+            .map(|value| value.clamp(0.0, 1.0))
+            .unwrap_or(inherited)
+    };
+    let transform = state.transform.multiply(parse_transform(
+        style
+            .as_ref()
+            .and_then(|style| style.get("transform"))
+            .or_else(|| element.attrs.get("transform")),
+        scale_x,
+        scale_y,
+    ));
+    let local_filters = presentation_value(style.as_ref(), element, "filter")
+        .and_then(|value| parse_url_reference(value.trim()))
+        .and_then(|reference| defs.get(reference))
+        .filter(|referenced| referenced.name == "filter")
+        .and_then(|referenced| resolve_filter(referenced, transform, scale_x, scale_y));
+    let local_mask = presentation_value(style.as_ref(), element, "mask");
+    let is_group = matches!(element.name.as_str(), "svg" | "g" | "symbol" | "use");
+    let mask_bounds = match local_mask {
+        // objectBoundingBox masks on a group are sized to the whole group.
+        Some(_) if is_group => {
+            let mut commands = Vec::new();
+            collect_clip_commands(node, defs, state.transform, scale_x, scale_y, &mut commands);
+            command_bounds(&commands)
+        }
+        Some(_) => None,
+        None => state.mask_bounds,
+    };
     let next_state = RenderState {
-        transform: state.transform.multiply(parse_transform(
-            style
-                .as_ref()
-                .and_then(|style| style.get("transform"))
-                .or_else(|| element.attrs.get("transform")),
-            scale_x,
-            scale_y,
-        )),
+        transform,
         fill: local_fill,
         fill_rule: local_fill_rule,
         stroke: local_stroke,
         stroke_width: local_stroke_width,
         clip_path: local_clip_path,
         mask_path: local_mask_path,
+        fill_pattern,
+        fill_opacity: opacity_value("fill-opacity", state.fill_opacity),
+        stroke_opacity: opacity_value("stroke-opacity", state.stroke_opacity),
+        mask: resolve_mask(local_mask, state, defs),
+        mask_bounds,
+        filters: compose_filters(local_filters, state.filters.as_ref()),
+        depth: state.depth,
     };
 
-    match element.name.as_str() {
-        "svg" | "g" | "symbol" => {
-            for child in &element.children {
-                flatten_node(child, defs, &next_state, scale_x, scale_y, out);
+    let opacity = opacity_value("opacity", 1.0);
+    let mut layers = Vec::new();
+    let flattened = match element.name.as_str() {
+        "svg" | "g" | "symbol" => element
+            .children
+            .iter()
+            .all(|child| flatten_node(child, defs, &next_state, scale_x, scale_y, &mut layers)),
+        "use" => flatten_use(element, defs, next_state, scale_x, scale_y, &mut layers),
+        "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
+            match element_to_path_layers(element, defs, &next_state, scale_x, scale_y) {
+                Some(shape_layers) => {
+                    layers = shape_layers;
+                    true
+                }
+                None => false,
             }
         }
-        "use" => flatten_use(element, defs, next_state, scale_x, scale_y, out),
-        "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
-            out.extend(element_to_path_layers(element, &next_state, scale_x, scale_y));
+        _ => true,
+    };
+    if !flattened {
+        return false;
+    }
+    // `opacity` applies to the element composited as a whole, which only a
+    // single layer (not a fill under its stroke) can carry.
+    if opacity <= 0.0 {
+        return true;
+    }
+    if opacity < 1.0 {
+        match layers.as_mut_slice() {
+            [] => {}
+            [layer] => layer.opacity *= opacity,
+            _ => return false,
         }
-        _ => {}
     }
+    out.extend(layers);
+    true
 }
 
 fn flatten_use(
@@ -396,17 +736,17 @@ fn flatten_use(
     scale_x: f32,
     scale_y: f32,
     out: &mut Vec<PathGlyphLayer>,
-) {
+) -> bool {
     let Some(reference) = element
         .attrs
         .get("href")
         .or_else(|| element.attrs.get("xlink:href"))
         .and_then(|value| value.strip_prefix('#'))
     else {
-        return;
+        return true;
     };
     let Some(referenced) = defs.get(reference) else {
-        return;
+        return true;
     };
     let mut referenced = referenced.clone();
     for key in ["fill", "fill-rule", "stroke", "stroke-width"] {
@@ -420,12 +760,7 @@ fn flatten_use(
             parse_attr_f32(&element.attrs, "x").unwrap_or(0.0) * scale_x,
             parse_attr_f32(&element.attrs, "y").unwrap_or(0.0) * scale_y,
         )),
-        fill: state.fill,
-        fill_rule: state.fill_rule,
-        stroke: state.stroke,
-        stroke_width: state.stroke_width,
-        clip_path: state.clip_path,
-        mask_path: state.mask_path,
+        ..state
     };
     flatten_node(
         &SvgNode::Element(referenced),
@@ -434,15 +769,16 @@ fn flatten_use(
         scale_x,
         scale_y,
         out,
-    );
+    )
 }
 
 fn element_to_path_layers(
     element: &SvgElement,
+    defs: &HashMap<String, SvgElement>,
     state: &RenderState,
     scale_x: f32,
     scale_y: f32,
-) -> Vec<PathGlyphLayer> {
+) -> Option<Vec<PathGlyphLayer>> {
     let commands = match element.name.as_str() {
         "path" => element
             .attrs
@@ -454,16 +790,35 @@ fn element_to_path_layers(
         "line" => line_to_commands(&element.attrs, scale_x, scale_y),
         "polyline" => poly_points_to_commands(&element.attrs, false, scale_x, scale_y),
         "polygon" => poly_points_to_commands(&element.attrs, true, scale_x, scale_y),
-        _ => return Vec::new(),
+        _ => return Some(Vec::new()),
     };
     let Some(commands) = commands else {
-        return Vec::new();
+        return Some(Vec::new());
     };
     if commands.is_empty() {
-        return Vec::new();
+        return Some(Vec::new());
     }
     let transformed = transform_commands(&commands, state.transform);
     let bounds = command_bounds(&transformed);
+    let mask_bounds = state.mask_bounds.or(bounds);
+    let clip_commands = resolve_combined_clip_commands(
+        state.clip_path.as_ref(),
+        state.mask_path.as_ref(),
+        bounds,
+        mask_bounds,
+        state.transform,
+    );
+    let mask = match &state.mask {
+        Some(mask) => Some(resolve_mask_layers(
+            mask,
+            defs,
+            state,
+            mask_bounds,
+            scale_x,
+            scale_y,
+        )?),
+        None => None,
+    };
     let mut layers = Vec::new();
     let supports_fill = !matches!(element.name.as_str(), "line");
 
@@ -473,14 +828,35 @@ fn element_to_path_layers(
                 transformed.clone(),
                 resolve_gradient_paint(paint, bounds, state.transform),
             );
-            layer.clip_commands = resolve_combined_clip_commands(
-                state.clip_path.as_ref(),
-                state.mask_path.as_ref(),
-                bounds,
-                state.transform,
-            );
+            layer.clip_commands = clip_commands.clone();
             layer.fill_rule = state.fill_rule;
+            layer.opacity = state.fill_opacity;
+            layer.mask = mask.clone();
             layers.push(layer);
+        } else if let Some(pattern) = state.fill_pattern.as_ref().and_then(|id| defs.get(id)) {
+            // Tiles are cut to the shape by an alpha mask holding the shape itself.
+            let mut shape =
+                PathGlyphLayer::new(transformed.clone(), GlyphPaint::Solid(0xff00_0000));
+            shape.clip_commands = clip_commands.clone();
+            shape.fill_rule = state.fill_rule;
+            shape.mask = mask.clone();
+            let shape_mask = GlyphMask {
+                mode: GlyphMaskMode::Alpha,
+                layers: vec![shape],
+            };
+            for mut tile in pattern_tile_layers(pattern, defs, state, &commands, scale_x, scale_y)?
+            {
+                tile.opacity *= state.fill_opacity;
+                match tile.mask.as_mut() {
+                    None => tile.mask = Some(shape_mask.clone()),
+                    Some(inner) => {
+                        for layer in inner.layers.iter_mut().filter(|layer| layer.mask.is_none()) {
+                            layer.mask = Some(shape_mask.clone());
+                        }
+                    }
+                }
+                layers.push(tile);
+            }
         }
     }
 
@@ -491,17 +867,462 @@ fn element_to_path_layers(
                 resolve_gradient_paint(paint, bounds, state.transform),
                 state.stroke_width,
             );
-            layer.clip_commands = resolve_combined_clip_commands(
-                state.clip_path.as_ref(),
-                state.mask_path.as_ref(),
-                bounds,
-                state.transform,
-            );
+            layer.clip_commands = clip_commands;
+            layer.opacity = state.stroke_opacity;
+            layer.mask = mask;
             layers.push(layer);
         }
     }
 
-    layers
+    let Some(chains) = &state.filters else {
+        return Some(layers);
+    };
+    let layers = layers
+        .into_iter()
+        .flat_map(|layer| {
+            chains.iter().map(move |chain| {
+                let mut layer = layer.clone();
+                layer.filters.extend(chain.iter().copied());
+                layer
+            })
+        })
+        .collect();
+    Some(layers)
+}
+
+/// Layers of every `pattern` tile overlapping `commands`, the untransformed shape.
+fn pattern_tile_layers(
+    pattern: &SvgElement,
+    defs: &HashMap<String, SvgElement>,
+    state: &RenderState,
+    commands: &[Command],
+    scale_x: f32,
+    scale_y: f32,
+) -> Option<Vec<PathGlyphLayer>> {
+    let Some((min_x, min_y, max_x, max_y)) = command_bounds(commands) else {
+        return Some(Vec::new());
+    };
+    let (bbox_width, bbox_height) = (max_x - min_x, max_y - min_y);
+    let attribute = |key: &str| pattern_attribute(pattern, defs, key);
+    let number = |key: &str| {
+        attribute(key)
+            .and_then(|value| parse_number_or_percent(value))
+            .unwrap_or(0.0)
+    };
+    let object_bounding_box = attribute("patternunits")
+        .is_none_or(|value| !value.trim().eq_ignore_ascii_case("userspaceonuse"));
+    let (tile_x, tile_y, tile_width, tile_height) = if object_bounding_box {
+        (
+            min_x + number("x") * bbox_width,
+            min_y + number("y") * bbox_height,
+            number("width") * bbox_width,
+            number("height") * bbox_height,
+        )
+    } else {
+        (
+            number("x") * scale_x,
+            number("y") * scale_y,
+            number("width") * scale_x,
+            number("height") * scale_y,
+        )
+    };
+    if tile_width.abs() < f32::EPSILON || tile_height.abs() < f32::EPSILON {
+        return Some(Vec::new());
+    }
+
+    let view_box = attribute("viewbox")
+        .map(|value| parse_numbers(value))
+        .filter(|values| values.len() == 4 && values[2] > 0.0 && values[3] > 0.0);
+    let (content, content_scale_x, content_scale_y) = if let Some(view_box) = view_box {
+        // preserveAspectRatio="xMidYMid meet"
+        let scale = (tile_width / view_box[2])
+            .abs()
+            .min((tile_height / view_box[3]).abs());
+        let (scale_x, scale_y) = (scale * tile_width.signum(), scale * tile_height.signum());
+        let content = Transform2D::translate(
+            (tile_width - view_box[2] * scale_x) / 2.0,
+            (tile_height - view_box[3] * scale_y) / 2.0,
+        )
+        .multiply(Transform2D::scale(scale_x, scale_y))
+        .multiply(Transform2D::translate(-view_box[0], -view_box[1]));
+        (content, 1.0, 1.0)
+    } else if attribute("patterncontentunits")
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("objectboundingbox"))
+    {
+        (Transform2D::scale(bbox_width, bbox_height), 1.0, 1.0)
+    } else {
+        (Transform2D::IDENTITY, scale_x, scale_y)
+    };
+
+    let pattern_transform = parse_transform(attribute("patterntransform"), scale_x, scale_y);
+    let Some(inverse) = pattern_transform.invert() else {
+        return Some(Vec::new());
+    };
+    let mut range = None;
+    for (x, y) in [
+        (min_x, min_y),
+        (max_x, min_y),
+        (min_x, max_y),
+        (max_x, max_y),
+    ] {
+        let (x, y) = inverse.apply(x, y);
+        extend_command_bounds(
+            &mut range,
+            (x - tile_x) / tile_width,
+            (y - tile_y) / tile_height,
+        );
+    }
+    let Some((min_column, min_row, max_column, max_row)) = range else {
+        return Some(Vec::new());
+    };
+    let first_column = min_column.floor() as i64;
+    let last_column = (max_column.ceil() as i64 - 1).max(first_column);
+    let first_row = min_row.floor() as i64;
+    let last_row = (max_row.ceil() as i64 - 1).max(first_row);
+    let tiles = (last_column - first_column + 1).saturating_mul(last_row - first_row + 1);
+    if tiles > MAX_PATTERN_TILES >> (5 * state.depth).min(63) {
+        return Some(Vec::new());
+    }
+
+    let content_element = pattern_content(pattern, defs);
+    let mut layers = Vec::new();
+    for row in first_row..=last_row {
+        for column in first_column..=last_column {
+            let tile_state = RenderState {
+                transform: state
+                    .transform
+                    .multiply(pattern_transform)
+                    .multiply(Transform2D::translate(
+                        tile_x + column as f32 * tile_width,
+                        tile_y + row as f32 * tile_height,
+                    ))
+                    .multiply(content),
+                depth: state.depth + 1,
+                ..RenderState::default()
+            };
+            for child in &content_element.children {
+                if !flatten_node(
+                    child,
+                    defs,
+                    &tile_state,
+                    content_scale_x,
+                    content_scale_y,
+                    &mut layers,
+                ) {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(layers)
+}
+
+fn pattern_reference<'a>(
+    pattern: &SvgElement,
+    defs: &'a HashMap<String, SvgElement>,
+) -> Option<&'a SvgElement> {
+    pattern
+        .attrs
+        .get("href")
+        .or_else(|| pattern.attrs.get("xlink:href"))
+        .and_then(|value| value.strip_prefix('#'))
+        .and_then(|reference| defs.get(reference))
+        .filter(|referenced| referenced.name == "pattern")
+}
+
+/// Attribute of `pattern` or of the patterns it inherits from through `href`.
+fn pattern_attribute<'a>(
+    pattern: &'a SvgElement,
+    defs: &'a HashMap<String, SvgElement>,
+    key: &str,
+) -> Option<&'a String> {
+    let mut current = pattern;
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        if let Some(value) = current.attrs.get(key) {
+            return Some(value);
+        }
+        current = pattern_reference(current, defs)?;
+    }
+    None
+}
+
+/// The first pattern along the `href` chain that has child elements.
+fn pattern_content<'a>(
+    pattern: &'a SvgElement,
+    defs: &'a HashMap<String, SvgElement>,
+) -> &'a SvgElement {
+    let mut current = pattern;
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        if current
+            .children
+            .iter()
+            .any(|child| matches!(child, SvgNode::Element(_)))
+        {
+            return current;
+        }
+        match pattern_reference(current, defs) {
+            Some(referenced) => current = referenced,
+            None => break,
+        }
+    }
+    current
+}
+
+fn resolve_mask(
+    value: Option<&String>,
+    state: &RenderState,
+    defs: &HashMap<String, SvgElement>,
+) -> Option<MaskSpec> {
+    let Some(value) = value else {
+        return state.mask.clone();
+    };
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("none") {
+        return None;
+    }
+    let element = parse_url_reference(value)
+        .and_then(|reference| defs.get(reference))
+        .filter(|element| element.name == "mask" && state.depth < MAX_REFERENCE_DEPTH);
+    let Some(element) = element else {
+        return state.mask.clone();
+    };
+    let style = element.attrs.get("style").map(|value| parse_style(value));
+    let mode = match presentation_value(style.as_ref(), element, "mask-type") {
+        Some(value) if value.trim().eq_ignore_ascii_case("alpha") => GlyphMaskMode::Alpha,
+        _ => GlyphMaskMode::Luminance,
+    };
+    let units = match element
+        .attrs
+        .get("maskcontentunits")
+        .map(|value| value.trim().to_ascii_lowercase())
+    {
+        Some(value) if value == "objectboundingbox" => ClipPathUnits::ObjectBoundingBox,
+        _ => ClipPathUnits::UserSpaceOnUse,
+    };
+    Some(MaskSpec {
+        element: element.clone(),
+        mode,
+        units,
+    })
+}
+
+/// Flattens the mask content for one element; mask content is black unless painted.
+/// `None` when the content cannot be drawn as path layers.
+fn resolve_mask_layers(
+    mask: &MaskSpec,
+    defs: &HashMap<String, SvgElement>,
+    state: &RenderState,
+    bounds: Option<(f32, f32, f32, f32)>,
+    scale_x: f32,
+    scale_y: f32,
+) -> Option<GlyphMask> {
+    let (transform, content_scale_x, content_scale_y) = match mask.units {
+        ClipPathUnits::ObjectBoundingBox => {
+            let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((0.0, 0.0, 0.0, 0.0));
+            let transform = Transform2D {
+                a: max_x - min_x,
+                b: 0.0,
+                c: 0.0,
+                d: max_y - min_y,
+                e: min_x,
+                f: min_y,
+            };
+            (transform, 1.0, 1.0)
+        }
+        ClipPathUnits::UserSpaceOnUse => (state.transform, scale_x, scale_y),
+    };
+    let content_state = RenderState {
+        transform,
+        fill: Some(GlyphPaint::Solid(0xff00_0000)),
+        depth: state.depth + 1,
+        ..RenderState::default()
+    };
+    let mut layers = Vec::new();
+    for child in &mask.element.children {
+        if !flatten_node(
+            child,
+            defs,
+            &content_state,
+            content_scale_x,
+            content_scale_y,
+            &mut layers,
+        ) {
+            return None;
+        }
+    }
+    Some(GlyphMask {
+        mode: mask.mode,
+        layers,
+    })
+}
+
+/// Evaluates the primitives of a `filter` element into chains of supported
+/// primitives, scaled into glyph space by `transform`.
+fn resolve_filter(
+    element: &SvgElement,
+    transform: Transform2D,
+    scale_x: f32,
+    scale_y: f32,
+) -> Option<Vec<Vec<GlyphFilter>>> {
+    let mut results: HashMap<String, FilterValue> = HashMap::new();
+    let mut previous: Option<FilterValue> = None;
+    for child in &element.children {
+        let SvgNode::Element(primitive) = child else {
+            continue;
+        };
+        let style = primitive.attrs.get("style").map(|value| parse_style(value));
+        let input = |key: &str| filter_input(primitive.attrs.get(key), &results, previous.as_ref());
+        let value = match primitive.name.as_str() {
+            "fegaussianblur" => {
+                let deviation = primitive
+                    .attrs
+                    .get("stddeviation")
+                    .map(|value| parse_numbers(value))
+                    .unwrap_or_default();
+                let deviation_x = deviation.first().copied().unwrap_or(0.0);
+                let deviation_y = deviation.get(1).copied().unwrap_or(deviation_x);
+                append_filter(
+                    input("in"),
+                    GlyphFilter::GaussianBlur {
+                        std_deviation_x: deviation_x
+                            * scale_x.abs()
+                            * transform.a.hypot(transform.b),
+                        std_deviation_y: deviation_y
+                            * scale_y.abs()
+                            * transform.c.hypot(transform.d),
+                    },
+                )
+            }
+            "feoffset" => {
+                let dx = parse_attr_f32(&primitive.attrs, "dx").unwrap_or(0.0) * scale_x;
+                let dy = parse_attr_f32(&primitive.attrs, "dy").unwrap_or(0.0) * scale_y;
+                append_filter(
+                    input("in"),
+                    GlyphFilter::Offset {
+                        dx: transform.a * dx + transform.c * dy,
+                        dy: transform.b * dx + transform.d * dy,
+                    },
+                )
+            }
+            "feflood" => {
+                let color = presentation_value(style.as_ref(), primitive, "flood-color")
+                    .and_then(|value| match parse_basic_paint(value) {
+                        Some(GlyphPaint::Solid(color)) => Some(color),
+                        _ => None,
+                    })
+                    .unwrap_or(0xff00_0000);
+                let opacity = presentation_value(style.as_ref(), primitive, "flood-opacity")
+                    .and_then(|value| parse_number_or_percent(value))
+                    .unwrap_or(1.0)
+                    .clamp(0.0, 1.0);
+                FilterValue::Flood(apply_alpha(color, opacity))
+            }
+            "fecomposite" => {
+                let operator = primitive
+                    .attrs
+                    .get("operator")
+                    .map(|value| value.trim().to_ascii_lowercase())
+                    .unwrap_or_else(|| "over".to_string());
+                match (operator.as_str(), input("in"), input("in2")) {
+                    ("in" | "atop", FilterValue::Flood(color), FilterValue::Chains(chains)) => {
+                        append_filter(FilterValue::Chains(chains), GlyphFilter::Flood { color })
+                    }
+                    ("over", source, destination) => merge_filter_values([destination, source]),
+                    (_, source, _) => source,
+                }
+            }
+            "femerge" => merge_filter_values(primitive.children.iter().filter_map(|node| {
+                let SvgNode::Element(node) = node else {
+                    return None;
+                };
+                (node.name == "femergenode")
+                    .then(|| filter_input(node.attrs.get("in"), &results, previous.as_ref()))
+            })),
+            // Other primitives pass their input through unchanged.
+            _ => input("in"),
+        };
+        if let Some(result) = primitive.attrs.get("result") {
+            results.insert(result.trim().to_string(), value.clone());
+        }
+        previous = Some(value);
+    }
+    match previous {
+        Some(FilterValue::Chains(chains)) if !chains.is_empty() => Some(chains),
+        _ => None,
+    }
+}
+
+fn filter_input(
+    reference: Option<&String>,
+    results: &HashMap<String, FilterValue>,
+    previous: Option<&FilterValue>,
+) -> FilterValue {
+    let source_graphic = || FilterValue::Chains(vec![Vec::new()]);
+    match reference.map(|value| value.trim()) {
+        Some("SourceGraphic") => source_graphic(),
+        Some("SourceAlpha") => {
+            FilterValue::Chains(vec![vec![GlyphFilter::Flood { color: 0xff00_0000 }]])
+        }
+        Some(name) => results.get(name).cloned().unwrap_or_else(source_graphic),
+        None => previous.cloned().unwrap_or_else(source_graphic),
+    }
+}
+
+fn append_filter(value: FilterValue, filter: GlyphFilter) -> FilterValue {
+    match value {
+        FilterValue::Chains(mut chains) => {
+            for chain in &mut chains {
+                chain.push(filter);
+            }
+            FilterValue::Chains(chains)
+        }
+        flood => flood,
+    }
+}
+
+/// Stacks values bottom to top; a bare flood fills the whole filter region and is dropped.
+fn merge_filter_values(values: impl IntoIterator<Item = FilterValue>) -> FilterValue {
+    let mut merged = Vec::new();
+    for value in values {
+        if let FilterValue::Chains(chains) = value {
+            merged.extend(chains);
+        }
+    }
+    FilterValue::Chains(merged)
+}
+
+/// Filters of an element inside a filtered group run before the group's.
+fn compose_filters(
+    local: Option<Vec<Vec<GlyphFilter>>>,
+    inherited: Option<&Vec<Vec<GlyphFilter>>>,
+) -> Option<Vec<Vec<GlyphFilter>>> {
+    let Some(inherited) = inherited else {
+        return local;
+    };
+    let Some(local) = local else {
+        return Some(inherited.clone());
+    };
+    Some(
+        local
+            .iter()
+            .flat_map(|chain| {
+                inherited
+                    .iter()
+                    .map(move |outer| chain.iter().chain(outer).copied().collect())
+            })
+            .collect(),
+    )
+}
+
+/// `style` declaration or, failing that, the presentation attribute `key`.
+fn presentation_value<'a>(
+    style: Option<&'a HashMap<String, String>>,
+    element: &'a SvgElement,
+    key: &str,
+) -> Option<&'a String> {
+    style
+        .and_then(|style| style.get(key))
+        .or_else(|| element.attrs.get(key))
 }
 
 fn resolve_gradient_paint(
@@ -524,6 +1345,7 @@ fn resolve_combined_clip_commands(
     clip_path: Option<&ClipPathSpec>,
     mask_path: Option<&ClipPathSpec>,
     bounds: Option<(f32, f32, f32, f32)>,
+    mask_bounds: Option<(f32, f32, f32, f32)>,
     element_transform: Transform2D,
 ) -> Vec<Command> {
     let mut commands = Vec::new();
@@ -531,7 +1353,7 @@ fn resolve_combined_clip_commands(
         commands.extend(resolve_clip_spec_commands(clip_path, bounds, element_transform));
     }
     if let Some(mask_path) = mask_path {
-        commands.extend(resolve_clip_spec_commands(mask_path, bounds, element_transform));
+        commands.extend(resolve_clip_spec_commands(mask_path, mask_bounds, element_transform));
     }
     commands
 }
@@ -944,6 +1766,7 @@ fn parse_radial_gradient(
         r,
         fx,
         fy,
+        fr: 0.0,
         units,
         transform,
         spread,
@@ -1101,7 +1924,9 @@ fn collect_gradient_stops(element: &SvgElement) -> Vec<GlyphGradientStop> {
         let argb = match color {
             GlyphPaint::Solid(argb) => apply_alpha(argb, opacity),
             GlyphPaint::CurrentColor => apply_alpha(0xff00_0000, opacity),
-            GlyphPaint::LinearGradient(_) | GlyphPaint::RadialGradient(_) => continue,
+            GlyphPaint::LinearGradient(_)
+            | GlyphPaint::RadialGradient(_)
+            | GlyphPaint::SweepGradient(_) => continue,
         };
         stops.push(GlyphGradientStop {
             offset,
@@ -1222,7 +2047,6 @@ fn parse_mask_spec(
     let units = match element
         .attrs
         .get("maskcontentunits")
-        .or_else(|| element.attrs.get("maskunits"))
         .map(|value| value.trim().to_ascii_lowercase())
     {
         Some(value) if value == "objectboundingbox" => ClipPathUnits::ObjectBoundingBox,
@@ -2256,13 +3080,174 @@ mod tests {
     }
 
     #[test]
-    fn svg_requires_svg_fallback_detects_mask_pattern_and_filter() {
-        assert!(svg_requires_svg_fallback("<svg><mask id=\"m\"/></svg>"));
-        assert!(svg_requires_svg_fallback("<svg><pattern id=\"p\"/></svg>"));
-        assert!(svg_requires_svg_fallback("<svg><filter id=\"f\"/></svg>"));
+    fn svg_requires_svg_fallback_detects_unsupported_filter_primitives() {
+        assert!(svg_requires_svg_fallback(
+            "<svg><filter id=\"f\"><feTurbulence baseFrequency=\"0.1\"/></filter></svg>"
+        ));
+        assert!(svg_requires_svg_fallback(
+            "<svg><filter id=\"f\"><feColorMatrix type=\"saturate\"/></filter></svg>"
+        ));
+        assert!(!svg_requires_svg_fallback(
+            "<svg><filter id=\"f\"><feGaussianBlur stdDeviation=\"1\"/><feOffset dx=\"1\"/></filter></svg>"
+        ));
+        assert!(!svg_requires_svg_fallback("<svg><mask id=\"m\"/><pattern id=\"p\"/></svg>"));
         assert!(!svg_requires_svg_fallback("<svg><path d=\"M0 0 L1 1\"/></svg>"));
     }
 
+    #[test]
+    fn svg_to_path_layers_applies_style_sheet_selectors() {
+        let document = concat!(
+            "<svg><style><![CDATA[",
+            "/* specificity: id > class > element */",
+            "rect { fill: #000001 } .a { fill: #000002 } #b { fill: #000003 }",
+            "path, rect.c { fill: #000004 !important; stroke: #000005 }",
+            "@media print { rect { fill: #ffffff } } g > rect { fill: #ffffff }",
+            "]]></style>",
+            "<rect width=\"1\" height=\"1\" fill=\"#ffffff\"/>",
+            "<rect class=\"x a\" width=\"1\" height=\"1\"/>",
+            "<rect id=\"b\" class=\"a\" width=\"1\" height=\"1\"/>",
+            "<rect class=\"a\" width=\"1\" height=\"1\" style=\"fill:#000006\"/>",
+            "<rect class=\"c\" width=\"1\" height=\"1\" stroke-width=\"0\"/>",
+            "</svg>"
+        );
+        let layers = svg_to_path_layers(document, 1.0, 1.0);
+        let fills: Vec<_> = layers
+            .iter()
+            .map(|layer| match layer.paint {
+                GlyphPaint::Solid(color) => color & 0x00ff_ffff,
+                _ => u32::MAX,
+            })
+            .collect();
+        assert_eq!(fills, vec![1, 2, 3, 6, 4]);
+    }
+
+    #[test]
+    fn svg_to_path_layers_tiles_pattern_fill_inside_the_shape() {
+        let document = concat!(
+            "<svg>",
+            "<defs><pattern id=\"p\" patternUnits=\"userSpaceOnUse\" width=\"5\" height=\"5\">",
+            "<circle cx=\"2\" cy=\"2\" r=\"1\" fill=\"#ff0000\"/></pattern></defs>",
+            "<rect x=\"0\" y=\"0\" width=\"10\" height=\"10\" fill=\"url(#p)\" fill-rule=\"evenodd\"/>",
+            "</svg>"
+        );
+        let layers = svg_to_path_layers(document, 1.0, 1.0);
+        assert_eq!(layers.len(), 4);
+        let starts: Vec<_> = layers
+            .iter()
+            .map(|layer| match layer.commands[0] {
+                Command::MoveTo(x, y) => (x.round(), y.round()),
+                _ => (f32::NAN, f32::NAN),
+            })
+            .collect();
+        assert_eq!(starts, vec![(3.0, 2.0), (8.0, 2.0), (3.0, 7.0), (8.0, 7.0)]);
+        let mask = layers[3].mask.as_ref().expect("shape mask");
+        assert_eq!(mask.mode, GlyphMaskMode::Alpha);
+        assert_eq!(mask.layers.len(), 1);
+        assert_eq!(mask.layers[0].fill_rule, FillRule::EvenOdd);
+        assert!(matches!(mask.layers[0].commands[0], Command::MoveTo(0.0, 0.0)));
+    }
+
+    #[test]
+    fn svg_to_path_layers_resolves_luminance_and_alpha_masks() {
+        let document = concat!(
+            "<svg>",
+            "<mask id=\"m\"><rect width=\"4\" height=\"4\" fill=\"#ffffff\"/><circle cx=\"2\" cy=\"2\" r=\"1\"/></mask>",
+            "<mask id=\"a\" style=\"mask-type:alpha\"><rect width=\"4\" height=\"4\" fill=\"#ffffff\" opacity=\"0.5\"/></mask>",
+            "<rect width=\"10\" height=\"10\" fill=\"#123456\" mask=\"url(#m)\"/>",
+            "<rect width=\"10\" height=\"10\" fill=\"#123456\" mask=\"url(#a)\"/>",
+            "</svg>"
+        );
+        let layers = svg_to_path_layers(document, 2.0, 2.0);
+        assert_eq!(layers.len(), 2);
+        let mask = layers[0].mask.as_ref().expect("luminance mask");
+        assert_eq!(mask.mode, GlyphMaskMode::Luminance);
+        assert_eq!(mask.layers.len(), 2);
+        assert!(matches!(mask.layers[0].paint, GlyphPaint::Solid(0xffff_ffff)));
+        // Unpainted mask content is black, hiding what it covers.
+        assert!(matches!(mask.layers[1].paint, GlyphPaint::Solid(0xff00_0000)));
+        assert!(matches!(mask.layers[0].commands[2], Command::Line(8.0, 8.0)));
+        let mask = layers[1].mask.as_ref().expect("alpha mask");
+        assert_eq!(mask.mode, GlyphMaskMode::Alpha);
+        assert_eq!(mask.layers[0].opacity, 0.5);
+    }
+
+    #[test]
+    fn svg_to_path_layers_multiplies_group_and_paint_opacity() {
+        let document = concat!(
+            "<svg><g opacity=\"0.5\" fill-opacity=\"0.5\">",
+            "<rect width=\"1\" height=\"1\" opacity=\"50%\" fill=\"#123456\"/>",
+            "</g></svg>"
+        );
+        let layers = svg_to_path_layers(document, 1.0, 1.0);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].opacity, 0.125);
+
+        let document = concat!(
+            "<svg><rect width=\"1\" height=\"1\" fill=\"#123456\" fill-opacity=\"0.5\"",
+            " stroke=\"#654321\" stroke-opacity=\"0.25\"/></svg>"
+        );
+        let layers = svg_to_path_layers(document, 1.0, 1.0);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].opacity, 0.5);
+        assert_eq!(layers[1].opacity, 0.25);
+    }
+
+    #[test]
+    fn svg_to_path_layers_falls_back_when_opacity_spans_several_layers() {
+        // A fill under its stroke overlaps, so the element needs group compositing.
+        let document = concat!(
+            "<svg><rect width=\"1\" height=\"1\" opacity=\"0.5\" fill=\"#123456\" stroke=\"#654321\"/></svg>"
+        );
+        assert!(svg_to_path_layers(document, 1.0, 1.0).is_empty());
+
+        let document = concat!(
+            "<svg><g opacity=\"0.5\">",
+            "<rect width=\"2\" height=\"2\" fill=\"#123456\"/>",
+            "<rect x=\"1\" width=\"2\" height=\"2\" fill=\"#654321\"/>",
+            "</g></svg>"
+        );
+        assert!(svg_to_path_layers(document, 1.0, 1.0).is_empty());
+
+        let document = concat!(
+            "<svg><mask id=\"m\"><g opacity=\"0.5\">",
+            "<rect width=\"2\" height=\"2\" fill=\"#ffffff\"/><rect width=\"1\" height=\"1\"/>",
+            "</g></mask>",
+            "<rect width=\"2\" height=\"2\" fill=\"#123456\" mask=\"url(#m)\"/></svg>"
+        );
+        assert!(svg_to_path_layers(document, 1.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn svg_to_path_layers_expands_drop_shadow_filter() {
+        let document = concat!(
+            "<svg><filter id=\"shadow\">",
+            "<feGaussianBlur in=\"SourceAlpha\" stdDeviation=\"2\"/>",
+            "<feOffset dx=\"1\" dy=\"2\" result=\"offsetblur\"/>",
+            "<feFlood flood-color=\"#ff0000\" flood-opacity=\"0.5\"/>",
+            "<feComposite in2=\"offsetblur\" operator=\"in\"/>",
+            "<feMerge><feMergeNode/><feMergeNode in=\"SourceGraphic\"/></feMerge>",
+            "</filter>",
+            "<rect width=\"1\" height=\"1\" fill=\"#123456\" filter=\"url(#shadow)\"/>",
+            "</svg>"
+        );
+        let layers = svg_to_path_layers(document, 2.0, 2.0);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(
+            layers[0].filters,
+            vec![
+                GlyphFilter::Flood { color: 0xff00_0000 },
+                GlyphFilter::GaussianBlur {
+                    std_deviation_x: 4.0,
+                    std_deviation_y: 4.0,
+                },
+                GlyphFilter::Offset { dx: 2.0, dy: 4.0 },
+                GlyphFilter::Flood { color: 0x80ff_0000 },
+            ]
+        );
+        assert!(layers[1].filters.is_empty());
+        assert!(matches!(layers[1].paint, GlyphPaint::Solid(0xff12_3456)));
+    }
+
     #[test]
     fn svg_to_path_layers_does_not_fallback_to_current_color_for_pattern_fill() {
         let document = concat!(
@@ -2343,7 +3328,7 @@ mod tests {
     fn svg_to_path_layers_resolves_object_bounding_box_mask_as_clip_commands() {
         let document = concat!(
             "<svg>",
-            "<defs><mask id=\"m\" maskUnits=\"objectBoundingBox\"><rect x=\"0.25\" y=\"0.5\" width=\"0.5\" height=\"0.5\"/></mask></defs>",
+            "<defs><mask id=\"m\" maskContentUnits=\"objectBoundingBox\"><rect x=\"0.25\" y=\"0.5\" width=\"0.5\" height=\"0.5\"/></mask></defs>",
             "<rect x=\"10\" y=\"20\" width=\"8\" height=\"6\" fill=\"#123456\" mask=\"url(#m)\"/>",
             "</svg>"
         );
@@ -2351,4 +3336,37 @@ mod tests {
         assert_eq!(layers.len(), 1);
         assert!(matches!(layers[0].clip_commands[0], Command::MoveTo(12.0, 23.0)));
     }
+
+    #[test]
+    fn svg_to_path_layers_keeps_mask_content_in_user_space_for_object_bounding_box_mask_units() {
+        let document = concat!(
+            "<svg>",
+            "<mask id=\"m\" maskUnits=\"objectBoundingBox\"><rect x=\"2\" y=\"3\" width=\"4\" height=\"5\"/></mask>",
+            "<rect x=\"10\" y=\"20\" width=\"8\" height=\"6\" fill=\"#123456\" mask=\"url(#m)\"/>",
+            "</svg>"
+        );
+        let layers = svg_to_path_layers(document, 1.0, 1.0);
+        assert_eq!(layers.len(), 1);
+        assert!(matches!(layers[0].clip_commands[0], Command::MoveTo(2.0, 3.0)));
+        let mask = layers[0].mask.as_ref().expect("mask");
+        assert!(matches!(mask.layers[0].commands[0], Command::MoveTo(2.0, 3.0)));
+    }
+
+    #[test]
+    fn svg_to_path_layers_sizes_group_masks_to_the_group_bounding_box() {
+        let document = concat!(
+            "<svg>",
+            "<mask id=\"m\" maskContentUnits=\"objectBoundingBox\"><rect x=\"0.5\" width=\"0.5\" height=\"1\" fill=\"#ffffff\"/></mask>",
+            "<g mask=\"url(#m)\" fill=\"#123456\">",
+            "<rect width=\"4\" height=\"4\"/><rect x=\"4\" width=\"4\" height=\"4\"/>",
+            "</g></svg>"
+        );
+        let layers = svg_to_path_layers(document, 1.0, 1.0);
+        assert_eq!(layers.len(), 2);
+        for layer in &layers {
+            assert!(matches!(layer.clip_commands[0], Command::MoveTo(4.0, 0.0)));
+            let mask = layer.mask.as_ref().expect("group mask");
+            assert!(matches!(mask.layers[0].commands[0], Command::MoveTo(4.0, 0.0)));
+        }
+    }
 }
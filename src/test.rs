@@ -222,6 +222,36 @@ mod tests {
         assert_eq!(single2.get_single_glyph_id(31), None);
     }
 
+    #[test]
+    #[cfg(feature = "layout")]
+    fn gsub_feature_variations_swap_rvrn_lookups_by_axis_range() {
+        let gsub = parse_gsub(build_gsub_v11_with_rvrn_variation(vec![
+            lookup_single_record(36, 37),
+            lookup_single_record(36, 99),
+        ]));
+        assert!(gsub.feature_variations.is_some());
+
+        let rvrn = [*b"rvrn"];
+        assert_eq!(gsub.feature_lookup_indices(None, &rvrn), vec![0]);
+        assert_eq!(
+            gsub.feature_lookup_indices_at(None, &rvrn, &[0.25]),
+            vec![0]
+        );
+        assert_eq!(gsub.feature_lookup_indices_at(None, &rvrn, &[0.5]), vec![1]);
+        assert_eq!(gsub.feature_lookup_indices_at(None, &rvrn, &[1.0]), vec![1]);
+        assert_eq!(
+            gsub.feature_lookup_indices_at(Some("en-US"), &rvrn, &[0.75]),
+            vec![1]
+        );
+
+        let mut glyphs = vec![(36usize, 0usize)];
+        gsub.apply_lookups(
+            &mut glyphs,
+            &gsub.feature_lookup_indices_at(None, &rvrn, &[1.0]),
+        );
+        assert_eq!(glyphs, vec![(99, 0)]);
+    }
+
     #[test]
     #[cfg(feature = "layout")]
     fn gpos_skips_truncated_feature_variations() {
@@ -534,6 +564,33 @@ mod tests {
         buffer
     }
 
+    /// Format 1 pair whose first x_advance is 0 plus a format 3 Device table
+    /// adding `pixels` at every size from 1 to 200 ppem.
+    #[cfg(feature = "layout")]
+    fn build_gpos_pair_device_subtable(left: u16, right: u16, pixels: i8) -> Vec<u8> {
+        let coverage = coverage_table(&[left]);
+        let mut buffer = Vec::new();
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 12);
+        push_u16(&mut buffer, 0x0044);
+        push_u16(&mut buffer, 0x0000);
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, (12 + coverage.len()) as u16);
+        buffer.extend_from_slice(&coverage);
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, right);
+        push_u16(&mut buffer, 0);
+        let device_offset = buffer.len() + 2;
+        push_u16(&mut buffer, device_offset as u16);
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 200);
+        push_u16(&mut buffer, 3);
+        for _ in 0..100 {
+            push_u16(&mut buffer, u16::from_be_bytes([pixels as u8, pixels as u8]));
+        }
+        buffer
+    }
+
     #[cfg(feature = "layout")]
     fn class_def_format1(start_glyph_id: u16, class_values: &[u16]) -> Vec<u8> {
         let mut buffer = Vec::new();
@@ -787,7 +844,7 @@ mod tests {
         push_u16(&mut lookup_list, 8);
         lookup_list.extend_from_slice(&subtable);
 
-        let script_list_offset = 12u16;
+        let script_list_offset = 14u16;
         let feature_list_offset = script_list_offset + script_list.len() as u16;
         let lookup_list_offset = feature_list_offset + feature_list.len() as u16;
         let feature_variations_offset = lookup_list_offset + lookup_list.len() as u16;
@@ -798,7 +855,7 @@ mod tests {
         push_u16(&mut buffer, script_list_offset);
         push_u16(&mut buffer, feature_list_offset);
         push_u16(&mut buffer, lookup_list_offset);
-        push_u16(&mut buffer, feature_variations_offset);
+        push_u32(&mut buffer, feature_variations_offset as u32);
         buffer.extend_from_slice(&script_list);
         buffer.extend_from_slice(&feature_list);
         buffer.extend_from_slice(&lookup_list);
@@ -856,6 +913,49 @@ mod tests {
         buffer
     }
 
+    /// GSUB 1.1 whose `rvrn` feature switches from lookup 0 to lookup 1 when
+    /// the first axis is in 0.5..=1.0.
+    #[cfg(feature = "layout")]
+    fn build_gsub_v11_with_rvrn_variation(lookups: Vec<Vec<u8>>) -> Vec<u8> {
+        let table = build_gsub_table_with_feature_lookups(*b"rvrn", &[0], lookups);
+        let body = &table[10..];
+
+        let mut feature_variations = Vec::new();
+        push_u16(&mut feature_variations, 1);
+        push_u16(&mut feature_variations, 0);
+        push_u32(&mut feature_variations, 1);
+        push_u32(&mut feature_variations, 16);
+        push_u32(&mut feature_variations, 30);
+        // ConditionSet with one format 1 condition on axis 0.
+        push_u16(&mut feature_variations, 1);
+        push_u32(&mut feature_variations, 6);
+        push_u16(&mut feature_variations, 1);
+        push_u16(&mut feature_variations, 0);
+        push_u16(&mut feature_variations, 0x2000);
+        push_u16(&mut feature_variations, 0x4000);
+        // FeatureTableSubstitution for feature 0.
+        push_u16(&mut feature_variations, 1);
+        push_u16(&mut feature_variations, 0);
+        push_u16(&mut feature_variations, 1);
+        push_u16(&mut feature_variations, 0);
+        push_u32(&mut feature_variations, 12);
+        push_u16(&mut feature_variations, 0);
+        push_u16(&mut feature_variations, 1);
+        push_u16(&mut feature_variations, 1);
+
+        let mut buffer = Vec::new();
+        push_u16(&mut buffer, 1);
+        push_u16(&mut buffer, 1);
+        for offset in [4, 6, 8] {
+            let value = u16::from_be_bytes([table[offset], table[offset + 1]]);
+            push_u16(&mut buffer, value + 4);
+        }
+        push_u32(&mut buffer, (14 + body.len()) as u32);
+        buffer.extend_from_slice(body);
+        buffer.extend_from_slice(&feature_variations);
+        buffer
+    }
+
     #[cfg(feature = "layout")]
     fn build_gsub_table_with_scripted_feature_lookups(
         scripts: &[([u8; 4], &[u16])],
@@ -1400,6 +1500,71 @@ mod tests {
         assert!(gpos.lookup_pair_adjustment(31, 40, false, None).is_none());
     }
 
+    #[test]
+    #[cfg(feature = "layout")]
+    fn measure_and_glyph_runs_apply_gpos_device_deltas_at_the_same_size() {
+        let Some(path) = existing_paths(vec![latin_font_path()]).into_iter().next() else {
+            return;
+        };
+        let mut font = crate::Font::get_font_from_file(&path).expect("load latin font");
+        let h = font
+            .glyph_id_for_char('H')
+            .expect("cmap lookup")
+            .expect("H glyph");
+        let gpos = build_gpos_table(*b"kern", 2, build_gpos_pair_device_subtable(h, h, 3));
+        let length = gpos.len() as u32;
+        font.gpos = Some(crate::fontbytes::LazyTable::new(
+            crate::FontBytes::from(gpos),
+            0,
+            length,
+        ));
+        let options = crate::commands::FontOptions::from_parsed(&font).with_font_size(24.0);
+
+        let pair = font.text2glyph_run("HH", &options).expect("HH run");
+        let single = font.text2glyph_run("H", &options).expect("H run");
+        let run_pair = pair.glyphs[1].x - pair.glyphs[0].x;
+        let run_single = single.glyphs[0].glyph.metrics.advance_x;
+        assert!(
+            (run_pair - run_single - 3.0).abs() < 0.1,
+            "device delta should add 3px: {run_pair} vs {run_single}"
+        );
+
+        // `measure` is in font units, so compare the kerned advance as a ratio.
+        let measured_pair = font.measure_with_options("HH", &options).expect("measure HH");
+        let measured_single = font.measure_with_options("H", &options).expect("measure H");
+        let measured_ratio = (measured_pair - measured_single) / measured_single;
+        let run_ratio = (run_pair / run_single) as f64;
+        assert!(
+            (measured_ratio - run_ratio).abs() < 1e-3,
+            "measured {measured_ratio} != run {run_ratio}"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "layout")]
+    fn check_tables_reports_a_malformed_lazy_table() {
+        let Some(path) = existing_paths(vec![latin_font_path()]).into_iter().next() else {
+            return;
+        };
+        let mut font = crate::Font::get_font_from_file(&path).expect("load latin font");
+        font.check_tables().expect("fixture tables parse");
+
+        // A GPOS header cut short after its version.
+        font.gpos = Some(crate::fontbytes::LazyTable::new(
+            crate::FontBytes::from(vec![0, 1]),
+            0,
+            2,
+        ));
+        let options = crate::commands::FontOptions::from_parsed(&font);
+        assert!(font.text2glyph_run("AV", &options).is_ok());
+        let error = font.check_tables().expect_err("malformed GPOS");
+        assert!(error.to_string().contains("GPOS"), "{error}");
+        assert_eq!(
+            font.check_tables().map_err(|error| error.kind()),
+            Err(error.kind())
+        );
+    }
+
     #[test]
     #[cfg(feature = "layout")]
     fn gpos_locale_specific_script_and_required_feature_take_priority_over_dflt() {
@@ -1599,6 +1764,57 @@ mod tests {
         assert_eq!(glyphs, vec![(19, 0), (20, 1), (123, 2), (22, 3)]);
     }
 
+    #[test]
+    #[cfg(feature = "layout")]
+    fn gsub_context_rules_stop_at_the_last_glyph() {
+        let context = parse_gsub(build_gsub_table_with_feature_lookups(
+            *b"calt",
+            &[0, 1],
+            vec![
+                lookup_context_format1_record(10, &[11], &[2]),
+                lookup_context_format2_record(20, &[1, 2], &[2], 1, 3),
+                lookup_single_record(10, 70),
+                lookup_single_record(21, 99),
+            ],
+        ));
+        let mut glyphs = vec![(12usize, 0usize), (10usize, 1usize), (11usize, 2usize)];
+        context.apply_feature_sequence(&mut glyphs, None, &[*b"calt"]);
+        assert_eq!(glyphs, vec![(12, 0), (70, 1), (11, 2)]);
+        let mut glyphs = vec![(12usize, 0usize), (10usize, 1usize)];
+        context.apply_feature_sequence(&mut glyphs, None, &[*b"calt"]);
+        assert_eq!(glyphs, vec![(12, 0), (10, 1)]);
+        let mut glyphs = vec![(12usize, 0usize), (20usize, 1usize)];
+        context.apply_feature_sequence(&mut glyphs, None, &[*b"calt"]);
+        assert_eq!(glyphs, vec![(12, 0), (20, 1)]);
+
+        let chaining = parse_gsub(build_gsub_table_with_feature_lookups(
+            *b"calt",
+            &[0],
+            vec![
+                lookup_chaining_context_format1_record(11, &[10], &[], &[12], &[1]),
+                lookup_single_record(11, 66),
+            ],
+        ));
+        let mut glyphs = vec![(10usize, 0usize), (11usize, 1usize), (12usize, 2usize)];
+        chaining.apply_feature_sequence(&mut glyphs, None, &[*b"calt"]);
+        assert_eq!(glyphs, vec![(10, 0), (66, 1), (12, 2)]);
+        let mut glyphs = vec![(10usize, 0usize), (11usize, 1usize)];
+        chaining.apply_feature_sequence(&mut glyphs, None, &[*b"calt"]);
+        assert_eq!(glyphs, vec![(10, 0), (11, 1)]);
+
+        let chaining_classes = parse_gsub(build_gsub_table_with_feature_lookups(
+            *b"calt",
+            &[0],
+            vec![
+                lookup_chaining_context_format2_record(20, &[1], &[2], &[1], 1, 1),
+                lookup_single_record(21, 123),
+            ],
+        ));
+        let mut glyphs = vec![(19usize, 0usize), (20usize, 1usize), (21usize, 2usize)];
+        chaining_classes.apply_feature_sequence(&mut glyphs, None, &[*b"calt"]);
+        assert_eq!(glyphs, vec![(19, 0), (20, 1), (21, 2)]);
+    }
+
     #[test]
     #[cfg(feature = "layout")]
     fn gsub_apply_rtl_contextual_sequence_supports_rclt() {
@@ -1653,6 +1869,54 @@ mod tests {
         assert_eq!(arabic_forms.isolated, Some(200));
     }
 
+    #[test]
+    #[cfg(feature = "layout")]
+    fn gsub_fraction_sequence_only_touches_digits_around_slash() {
+        let gsub = parse_gsub(build_gsub_table_with_scripted_features(
+            &[(*b"DFLT", 0xFFFF, &[0, 1, 2])],
+            &[(*b"numr", &[0]), (*b"dnom", &[1]), (*b"frac", &[2])],
+            vec![
+                lookup_single_record(1, 101),
+                lookup_single_record(1, 201),
+                lookup_single_record(9, 99),
+            ],
+        ));
+
+        // "1 1/1": the leading digit is not part of the fraction.
+        let mut glyphs = vec![(1, 0), (2, 1), (1, 2), (9, 3), (1, 4)];
+        gsub.apply_fraction_sequence(
+            &mut glyphs,
+            None,
+            |source| matches!(source, 0 | 2 | 4),
+            |source| source == 3,
+        );
+        let glyph_ids: Vec<usize> = glyphs.iter().map(|(glyph_id, _)| *glyph_id).collect();
+        assert_eq!(glyph_ids, vec![1, 2, 101, 99, 201]);
+
+        // A slash without digits on both sides stays untouched.
+        let mut glyphs = vec![(9, 0), (1, 1)];
+        gsub.apply_fraction_sequence(&mut glyphs, None, |source| source == 1, |source| source == 0);
+        assert_eq!(glyphs, vec![(9, 0), (1, 1)]);
+    }
+
+    #[test]
+    #[cfg(feature = "layout")]
+    fn gsub_lookup_width_and_number_apply_single_substitutions() {
+        let gsub = parse_gsub(build_gsub_table_with_scripted_features(
+            &[(*b"DFLT", 0xFFFF, &[0, 1])],
+            &[(*b"hwid", &[0]), (*b"tnum", &[1])],
+            vec![lookup_single_record(5, 55), lookup_single_record(1, 301)],
+        ));
+
+        assert_eq!(gsub.lookup_width(5, u32::from_be_bytes(*b"hwid")), 55);
+        assert_eq!(gsub.lookup_width(5, u32::from_be_bytes(*b"fwid")), 5);
+        assert_eq!(gsub.lookup_number_feature(1, u32::from_be_bytes(*b"tnum")), 301);
+        // Non-number tags are ignored even when the font defines them.
+        assert_eq!(gsub.lookup_number_feature(5, u32::from_be_bytes(*b"hwid")), 5);
+        // The sequence form only builds `frac` ligatures.
+        assert_eq!(gsub.lookup_number(vec![1, 5]), 1);
+    }
+
     #[test]
     #[cfg(feature = "layout")]
     fn gsub_required_feature_is_applied_for_locale_specific_script() {
@@ -2732,7 +2996,7 @@ mod tests {
 
     #[cfg(feature = "layout")]
     fn first_real_arabic_joining_pair(font: &crate::LoadedFont) -> Option<(String, Vec<usize>)> {
-        let gsub = font.font().gsub.as_ref()?;
+        let gsub = font.font().current_gsub()?;
         let cmap = font.font().cmap.as_ref()?;
         let candidates: Vec<char> = (0x0621u32..=0x064Au32)
             .filter_map(char::from_u32)
@@ -2768,7 +3032,7 @@ mod tests {
 
     #[cfg(feature = "layout")]
     fn first_real_arabic_rlig_sequence(font: &crate::LoadedFont) -> Option<(String, usize)> {
-        let gsub = font.font().gsub.as_ref()?;
+        let gsub = font.font().current_gsub()?;
         let cmap = font.font().cmap.as_ref()?;
         let candidates: Vec<char> = (0x0621u32..=0x064Au32)
             .filter_map(char::from_u32)
@@ -2808,7 +3072,7 @@ mod tests {
     fn first_real_arabic_contextual_sequence_in_font(
         font: &crate::LoadedFont,
     ) -> Option<(String, Vec<usize>)> {
-        let gsub = font.font().gsub.as_ref()?;
+        let gsub = font.font().current_gsub()?;
         let cmap = font.font().cmap.as_ref()?;
         let candidates: Vec<char> = (0x0621u32..=0x064Au32)
             .filter_map(char::from_u32)
@@ -2883,7 +3147,7 @@ mod tests {
         base_range: std::ops::RangeInclusive<u32>,
         mark_range: std::ops::RangeInclusive<u32>,
     ) -> Option<String> {
-        let gdef = font.font().gdef.as_ref()?;
+        let gdef = font.font().current_gdef()?;
         let cmap = font.font().cmap.as_ref()?;
 
         for base in base_range {
@@ -3010,7 +3274,7 @@ mod tests {
         String,
         crate::opentype::extentions::gpos::MarkAttachmentAdjustment,
     )> {
-        let gpos = font.font().gpos.as_ref()?;
+        let gpos = font.font().current_gpos()?;
         let cmap = font.font().cmap.as_ref()?;
 
         for base in base_range {
@@ -3055,7 +3319,7 @@ mod tests {
         crate::opentype::extentions::gpos::MarkAttachmentAdjustment,
         crate::opentype::extentions::gpos::MarkAttachmentAdjustment,
     )> {
-        let gpos = font.font().gpos.as_ref()?;
+        let gpos = font.font().current_gpos()?;
         let cmap = font.font().cmap.as_ref()?;
 
         for base in base_range {
@@ -3163,7 +3427,7 @@ mod tests {
 
     #[cfg(feature = "layout")]
     fn first_real_kern_pair(font: &crate::LoadedFont) -> Option<(char, char, i16)> {
-        let gpos = font.font().gpos.as_ref()?;
+        let gpos = font.font().current_gpos()?;
         let cmap = font.font().cmap.as_ref()?;
         let candidates = "AVWToYLT.,abcdefghijklmnopqrstuvwxyz";
 
@@ -3200,10 +3464,18 @@ mod tests {
         test_fonts_dir().join("NotoSansJP-Black.otf")
     }
 
+    fn math_font_path() -> std::path::PathBuf {
+        test_fonts_dir().join("STIXTwoMath-Regular.otf")
+    }
+
     fn japanese_font_path() -> std::path::PathBuf {
         test_fonts_dir().join("NotoSansJP-Regular.otf")
     }
 
+    fn latin_font_path() -> std::path::PathBuf {
+        test_fonts_dir().join("windows").join("arial.ttf")
+    }
+
     #[cfg(feature = "layout")]
     fn japanese_layout_font_paths() -> Vec<std::path::PathBuf> {
         vec![
@@ -3229,7 +3501,7 @@ mod tests {
             let Some(cmap) = font.font().cmap.as_ref() else {
                 continue;
             };
-            let Some(gsub) = font.font().gsub.as_ref() else {
+            let Some(gsub) = font.font().current_gsub() else {
                 continue;
             };
 
@@ -3261,7 +3533,7 @@ mod tests {
 
     #[cfg(feature = "layout")]
     fn first_real_vertical_substitution(font: &crate::LoadedFont) -> Option<(char, u16, u16)> {
-        let gsub = font.font().gsub.as_ref()?;
+        let gsub = font.font().current_gsub()?;
         let cmap = font.font().cmap.as_ref()?;
 
         let candidates = [
@@ -3447,7 +3719,7 @@ mod tests {
     fn first_svg_gradient_payload(font_name: &str) -> Option<(String, String)> {
         let path = direct_svg_emoji_font_path(font_name);
         let font = crate::load_font_from_file(&path).ok()?;
-        let svg = font.font().svg.as_ref()?;
+        let svg = font.font().current_svg_table()?;
         for sequence in emoji_ligature_sequence_candidates() {
             let Ok(glyph_ids) = font.font().debug_shape_glyph_ids(sequence, None) else {
                 continue;
@@ -3472,7 +3744,7 @@ mod tests {
     fn first_svg_inherited_gradient_payload(font_name: &str) -> Option<(String, String)> {
         let path = direct_svg_emoji_font_path(font_name);
         let font = crate::load_font_from_file(&path).ok()?;
-        let svg = font.font().svg.as_ref()?;
+        let svg = font.font().current_svg_table()?;
         for sequence in emoji_ligature_sequence_candidates() {
             let Ok(glyph_ids) = font.font().debug_shape_glyph_ids(sequence, None) else {
                 continue;
@@ -3520,7 +3792,7 @@ mod tests {
     fn first_svg_arc_payload(font_name: &str) -> Option<(String, String)> {
         let path = direct_svg_emoji_font_path(font_name);
         let font = crate::load_font_from_file(&path).ok()?;
-        let svg = font.font().svg.as_ref()?;
+        let svg = font.font().current_svg_table()?;
         for sequence in emoji_ligature_sequence_candidates() {
             let Ok(glyph_ids) = font.font().debug_shape_glyph_ids(sequence, None) else {
                 continue;
@@ -3554,7 +3826,7 @@ mod tests {
     fn first_svg_transform_payload(font_name: &str) -> Option<(String, String)> {
         let path = direct_svg_emoji_font_path(font_name);
         let font = crate::load_font_from_file(&path).ok()?;
-        let svg = font.font().svg.as_ref()?;
+        let svg = font.font().current_svg_table()?;
         for sequence in emoji_ligature_sequence_candidates() {
             let Ok(glyph_ids) = font.font().debug_shape_glyph_ids(sequence, None) else {
                 continue;
@@ -3582,7 +3854,7 @@ mod tests {
     fn first_svg_payload_requiring_fallback(font_name: &str) -> Option<(String, String)> {
         let path = direct_svg_emoji_font_path(font_name);
         let font = crate::load_font_from_file(&path).ok()?;
-        let svg = font.font().svg.as_ref()?;
+        let svg = font.font().current_svg_table()?;
         for sequence in emoji_ligature_sequence_candidates() {
             let Ok(glyph_ids) = font.font().debug_shape_glyph_ids(sequence, None) else {
                 continue;
@@ -3605,7 +3877,7 @@ mod tests {
     fn first_svg_gid_requiring_fallback(font_name: &str) -> Option<(usize, String)> {
         let path = direct_svg_emoji_font_path(font_name);
         let font = crate::load_font_from_file(&path).ok()?;
-        let svg = font.font().svg.as_ref()?;
+        let svg = font.font().current_svg_table()?;
         let max_glyphs = font.font().maxp.as_ref()?.num_glyphs as usize;
 
         for glyph_id in 1..max_glyphs {
@@ -3685,7 +3957,7 @@ mod tests {
             else {
                 continue;
             };
-            if font.font().glyf.is_none() {
+            if font.font().current_glyf().is_none() {
                 continue;
             }
             let Some(cmap) = font.font().cmap.as_ref() else {
@@ -3889,7 +4161,7 @@ mod tests {
         let Some(font) = yu_gothic_font() else {
             return;
         };
-        let Some(gsub) = font.font().gsub.as_ref() else {
+        let Some(gsub) = font.font().current_gsub() else {
             return;
         };
         let cmap = font.font().cmap.as_ref().expect("Yu Gothic cmap");
@@ -4548,6 +4820,132 @@ mod tests {
         }
     }
 
+    #[test]
+    fn named_instances_are_exposed_and_listed_as_virtual_faces() {
+        let Some(path) = source_serif_variable_paths().into_iter().next() else {
+            return;
+        };
+        let file = crate::FontFile::from_file(&path).unwrap();
+        let face = file.current_face().unwrap();
+        let instances = face.named_instances();
+        assert!(instances.len() > 1, "expected named instances");
+        assert!(instances
+            .iter()
+            .all(|instance| instance.name.is_some() && !instance.coordinates.is_empty()));
+
+        let bold = face.find_named_instance("bold").expect("Bold instance");
+        let wght = bold
+            .coordinates
+            .iter()
+            .find(|setting| &setting.tag == b"wght")
+            .unwrap();
+        assert_eq!(wght.value, 700.0);
+        let engine = face.engine().with_named_instance("Bold");
+        assert_eq!(engine.options().variations, bold.coordinates);
+        let unchanged = face.engine().with_named_instance("No Such Instance");
+        assert!(unchanged.options().variations.is_empty());
+
+        let file = file.with_named_instance_faces();
+        assert_eq!(file.face_count(), instances.len());
+        let faces = file.faces().unwrap();
+        let bold_face = faces
+            .iter()
+            .find(|face| face.named_instance() == Some(&bold))
+            .expect("Bold virtual face");
+        assert_eq!(bold_face.weight(), crate::FontWeight(700));
+        assert!(bold_face.full_name().ends_with("Bold"));
+        assert_eq!(
+            crate::FontOptions::new(bold_face).variations,
+            bold.coordinates
+        );
+        assert!(file.face(instances.len()).is_err());
+    }
+
+    #[test]
+    fn stat_names_styles_and_feeds_family_matching() {
+        let Some(path) = source_serif_variable_paths().into_iter().next() else {
+            return;
+        };
+        let file = crate::FontFile::from_file(&path)
+            .unwrap()
+            .with_named_instance_faces();
+        let faces = file.faces().unwrap();
+        let face = &faces[0];
+        assert!(face.style_axes().iter().any(|axis| axis.tag == "wght"));
+        assert!(face.axis_values().iter().any(|value| {
+            value.name.as_deref() == Some("Bold")
+                && value
+                    .coordinates
+                    .iter()
+                    .any(|c| &c.tag == b"wght" && c.value == 700.0)
+        }));
+        assert!(face.elided_fallback_name().is_some());
+
+        let bold = faces
+            .iter()
+            .find(|face| face.named_instance().and_then(|i| i.name.as_deref()) == Some("Bold"))
+            .expect("Bold virtual face");
+        let style_name = bold.style_name().expect("STAT style name");
+        assert!(style_name.contains("Bold"), "{style_name}");
+
+        let mut family = crate::FontFamily::new(face.family());
+        for face in faces.iter().cloned() {
+            family.add_font_face(face);
+        }
+        let requested = format!("{} {}", face.family(), style_name);
+        let resolved = family
+            .resolve_descriptor(
+                None,
+                Some(&requested),
+                crate::FontWeight::default(),
+                crate::FontStyle::Normal,
+                crate::FontStretch::default(),
+            )
+            .expect("face matched by STAT style name");
+        assert_eq!(resolved.style_name.as_deref(), Some(style_name.as_str()));
+        let resolved = family
+            .resolve_face(
+                None,
+                None,
+                crate::FontWeight(700),
+                crate::FontStyle::Normal,
+                crate::FontStretch::default(),
+            )
+            .unwrap();
+        assert_eq!(resolved.weight(), crate::FontWeight(700));
+    }
+
+    #[test]
+    fn static_instance_bakes_variations_into_a_plain_font() {
+        let Some(path) = source_serif_variable_paths().into_iter().next() else {
+            return;
+        };
+        let file = crate::FontFile::from_file(&path).unwrap();
+        let face = file.current_face().unwrap();
+        let text = "Hamburgefonstiv";
+        let bold = [crate::FontVariationSetting::new("wght", 700.0).unwrap()];
+        let bytes = face.instantiate(&bold).unwrap();
+
+        let instance = crate::FontFile::from_bytes(bytes).unwrap();
+        let instance = instance.current_face().unwrap();
+        assert!(!instance.is_variable());
+        assert_eq!(instance.weight(), crate::FontWeight(700));
+        assert!(
+            instance.full_name().ends_with("Bold"),
+            "{}",
+            instance.full_name()
+        );
+        let varied = face
+            .measure_with_options(text, crate::FontOptions::new(&face).with_variations(&bold))
+            .unwrap();
+        let baked = instance.measure(text).unwrap();
+        assert!(
+            (baked - varied).abs() <= varied.abs() * 0.01,
+            "{baked} vs {varied}"
+        );
+        assert!(face.instantiate(&[]).is_ok());
+    }
+
     #[test]
     fn public_api_metadata_smoke_across_variable_font_fixtures() {
         let paths = variable_font_fixture_paths();
@@ -4959,7 +5357,7 @@ mod tests {
         let cmap = raw_font.cmap.as_ref().expect("Source Serif cmap");
         let glyph_id = cmap.get_glyph_position('Á' as u32) as usize;
         assert!(glyph_id > 0, "expected Source Serif to resolve Á");
-        let glyf = raw_font.glyf.as_ref().expect("Source Serif glyf");
+        let glyf = raw_font.current_glyf().expect("Source Serif glyf");
         let source_glyph = glyf.get_glyph(glyph_id).expect("Source Serif glyph");
         assert!(
             source_glyph.parse().number_of_contours < 0,
@@ -5058,6 +5456,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn css_weight_and_size_drive_variable_axes_unless_set_explicitly() {
+        let path = test_fonts_dir()
+            .join("source")
+            .join("SourceSerif4-VariableFont_opsz,wght.ttf");
+        if !path.exists() {
+            return;
+        }
+
+        let face = crate::FontFile::from_file(&path)
+            .expect("load Source Serif as FontFile")
+            .current_face()
+            .expect("load Source Serif face");
+        let axes = face.variation_axes();
+        let coordinate = |options: &crate::FontOptions<'_>, tag: &str| {
+            let index = axes
+                .iter()
+                .position(|axis| axis.tag == tag)
+                .expect("Source Serif axis");
+            face.font.normalized_variation_coords(options)[index]
+        };
+        let wght = axes
+            .iter()
+            .find(|axis| axis.tag == "wght")
+            .expect("Source Serif wght axis");
+
+        let bold = crate::FontOptions::new(&face).with_font_weight(crate::FontWeight::BOLD);
+        assert_eq!(
+            coordinate(&bold, "wght"),
+            coordinate(
+                &crate::FontOptions::new(&face).with_variation("wght", 700.0),
+                "wght"
+            )
+        );
+        assert_ne!(coordinate(&bold, "wght"), 0.0);
+        assert_eq!(
+            coordinate(&bold.clone().with_variation("wght", wght.min_value), "wght"),
+            -1.0,
+            "explicit settings should win over font_weight"
+        );
+
+        if axes.iter().any(|axis| axis.tag == "opsz") {
+            let small = crate::FontOptions::new(&face).with_font_size(8.0);
+            let large = crate::FontOptions::new(&face).with_font_size(60.0);
+            assert!(coordinate(&small, "opsz") < coordinate(&large, "opsz"));
+            assert_eq!(
+                coordinate(
+                    &small.with_font_optical_sizing(crate::FontOpticalSizing::None),
+                    "opsz"
+                ),
+                0.0
+            );
+        }
+
+        let descriptor = crate::FontFaceDescriptor::from_face(&face);
+        assert_eq!(
+            descriptor.font_weight_range,
+            Some((
+                crate::FontWeight(wght.min_value as u16),
+                crate::FontWeight(wght.max_value as u16)
+            ))
+        );
+    }
+
     #[test]
     fn source_serif_otf_metadata_loads_without_layout_panic() {
         for path in source_serif_otf_paths() {
@@ -5112,6 +5574,37 @@ mod tests {
         assert!(svg.contains("data:image/"));
     }
 
+    #[test]
+    fn text2command_places_sbix_bitmaps_like_glyph_runs() {
+        let Some(path) = first_real_sbix_font_path() else {
+            return;
+        };
+        let bytes = std::fs::read(&path).expect("read sbix font");
+        let font = crate::load_font_from_buffer(&bytes).expect("load sbix font");
+        let commands = font.font().text2command("🥺").expect("text2command sbix");
+        let bitmap = commands[0].bitmap.as_ref().expect("bitmap payload");
+        let layout = font.font().get_horizontal_layout(commands[0].glyph_id);
+        let baseline = (layout.accender + layout.line_gap) as f64;
+        let run = font
+            .text2glyph_run(
+                "🥺",
+                crate::FontOptions::from_font_ref(crate::FontRef::Loaded(&font))
+                    .with_font_size(32.0),
+            )
+            .expect("glyph run sbix");
+        let Some(crate::GlyphLayer::Raster(raster)) = run.glyphs[0].glyph.layers.last() else {
+            panic!("expected raster glyph layer");
+        };
+
+        // Both place the image top relative to the baseline, y down.
+        let legacy = (bitmap.offset_y - baseline) / bitmap.height;
+        let glyph_run = raster.offset_y as f64 / raster.height.unwrap_or(1) as f64;
+        assert!(
+            (legacy - glyph_run).abs() < 0.05,
+            "{legacy} vs {glyph_run}"
+        );
+    }
+
     #[test]
     fn glyph_run_sbix_bitmap_keeps_display_size() {
         let path = first_real_sbix_font_path().expect("load real sbix font");
@@ -5364,7 +5857,7 @@ mod tests {
             #[cfg(feature = "layout")]
             let mut ccmp_applied = ccmp_applied;
             #[cfg(feature = "layout")]
-            let liga = font.font().gsub.as_ref().and_then(|gsub| {
+            let liga = font.font().current_gsub().and_then(|gsub| {
                 gsub.apply_ccmp_sequence(&mut ccmp_applied);
                 gsub.lookup_liga_sequence(
                     &ccmp_applied
@@ -5374,7 +5867,7 @@ mod tests {
                 )
             });
             #[cfg(feature = "layout")]
-            let rlig = font.font().gsub.as_ref().and_then(|gsub| {
+            let rlig = font.font().current_gsub().and_then(|gsub| {
                 gsub.lookup_rlig_sequence(
                     &ccmp_applied
                         .iter()
@@ -5480,6 +5973,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sniff_encoded_image_dimensions_supports_tiff_headers() {
+        // Little-endian, SHORT width; big-endian, LONG height.
+        let little = vec![
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 2, 0, 0, 1, 3, 0, 1, 0, 0, 0, 24, 0, 0, 0, 1, 1, 4, 0,
+            1, 0, 0, 0, 12, 0, 0, 0,
+        ];
+        let big = vec![
+            b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 2, 1, 0, 0, 3, 0, 0, 0, 1, 0, 5, 0, 0, 1, 1, 0, 4, 0,
+            0, 0, 1, 0, 0, 0, 7,
+        ];
+
+        assert_eq!(
+            crate::util::sniff_encoded_image_dimensions(&little),
+            Some(("image/tiff", 24, 12))
+        );
+        assert_eq!(
+            crate::util::sniff_encoded_image_dimensions(&big),
+            Some(("image/tiff", 5, 7))
+        );
+    }
+
     #[test]
     fn glyph_run_from_truetype_outline_works() {
         let path = sample_font_path();
@@ -5969,13 +6484,13 @@ mod tests {
 
     #[test]
     #[cfg(feature = "svg-fonts")]
-    fn svg_document_to_glyph_layers_keeps_svg_fallback_for_pattern_payload() {
+    fn svg_document_to_glyph_layers_keeps_svg_fallback_for_unsupported_filter_payload() {
         let document = crate::opentype::color::svg::SvgGlyphDocument {
             payload: concat!(
                 "<svg>",
-                "<defs><pattern id=\"p\"><rect x=\"0\" y=\"0\" width=\"2\" height=\"2\"/></pattern></defs>",
+                "<defs><filter id=\"f\"><feTurbulence baseFrequency=\"0.5\"/></filter></defs>",
                 "<rect x=\"0\" y=\"0\" width=\"10\" height=\"10\" fill=\"#123456\"/>",
-                "<rect x=\"1\" y=\"1\" width=\"8\" height=\"8\" fill=\"url(#p)\"/>",
+                "<rect x=\"1\" y=\"1\" width=\"8\" height=\"8\" filter=\"url(#f)\"/>",
                 "</svg>"
             )
             .to_string(),
@@ -5986,16 +6501,16 @@ mod tests {
         };
         let layers = crate::fontreader::svg_document_to_glyph_layers(&document, 1.0, 1.0);
         assert!(
-            layers
-                .iter()
-                .any(|layer| matches!(layer, crate::GlyphLayer::Path(path) if !path.commands.is_empty())),
+            layers.iter().any(
+                |layer| matches!(layer, crate::GlyphLayer::Path(path) if !path.commands.is_empty())
+            ),
             "expected supported shapes to keep path layers"
         );
         assert!(
-            layers
-                .iter()
-                .any(|layer| matches!(layer, crate::GlyphLayer::Svg(svg) if !svg.document.is_empty())),
-            "expected unsupported pattern payload to keep raw Svg fallback"
+            layers.iter().any(
+                |layer| matches!(layer, crate::GlyphLayer::Svg(svg) if !svg.document.is_empty())
+            ),
+            "expected unsupported filter payload to keep raw Svg fallback"
         );
     }
 
@@ -6072,8 +6587,7 @@ mod tests {
             .expect("cmap")
             .get_glyph_position('🥺' as u32) as usize;
         let expected_layers = inner
-            .colr
-            .as_ref()
+            .current_colr()
             .expect("colr")
             .get_layer_record(glyph_id as u16);
         let cpal = inner.cpal.as_ref().expect("cpal");
@@ -6101,7 +6615,9 @@ mod tests {
                     crate::GlyphPaint::CurrentColor => {
                         panic!("expected COLR glyph layer to keep CPAL color")
                     }
-                    crate::GlyphPaint::LinearGradient(_) | crate::GlyphPaint::RadialGradient(_) => {
+                    crate::GlyphPaint::LinearGradient(_)
+                    | crate::GlyphPaint::RadialGradient(_)
+                    | crate::GlyphPaint::SweepGradient(_) => {
                         panic!("expected COLR glyph layer to keep solid CPAL color")
                     }
                 },
@@ -6116,6 +6632,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn glyph_run_colr_layers_follow_palette_overrides() {
+        let path = segoe_emoji_font_path();
+        if !path.exists() {
+            return;
+        }
+
+        let font = crate::load_font_from_file(path).expect("load segoe emoji");
+        let inner = font.font();
+        let glyph_id = inner
+            .cmap
+            .as_ref()
+            .expect("cmap")
+            .get_glyph_position('🥺' as u32) as usize;
+        let first_layer = inner
+            .current_colr()
+            .expect("colr")
+            .get_layer_record(glyph_id as u16)[0]
+            .clone();
+        let run = crate::text2commands(
+            "🥺",
+            crate::FontOptions::new(&font)
+                .with_font_size(32.0)
+                .with_palette_override(first_layer.palette_index, 0xff12_3456),
+        )
+        .expect("glyph run");
+
+        match &run.glyphs[0].glyph.layers[0] {
+            crate::GlyphLayer::Path(path) => {
+                assert_eq!(path.paint, crate::GlyphPaint::Solid(0xff12_3456))
+            }
+            _ => panic!("expected COLR glyph to use path layers"),
+        }
+        assert!(!font.palettes().is_empty());
+    }
+
     #[test]
     fn glyph_run_colr_layers_keep_non_empty_commands() {
         let font = crate::load_font_from_file(segoe_emoji_font_path()).expect("load segoe emoji");
@@ -6291,7 +6843,7 @@ mod tests {
     fn lookup_locale_uses_real_japanese_locl_data() {
         let path = japanese_font_path();
         let font = crate::fontload_file(&path).expect("load japanese font");
-        let gsub = font.font().gsub.as_ref().expect("gsub");
+        let gsub = font.font().current_gsub().expect("gsub");
         let max_glyphs = font.font().maxp.as_ref().expect("maxp").num_glyphs as usize;
         let locale = "ja-JP";
 
@@ -6313,7 +6865,7 @@ mod tests {
     fn text_api_uses_real_japanese_locl_substitution_when_requested() {
         let font = crate::load_font_from_file(japanese_font_path()).expect("load japanese font");
         let cmap = font.font().cmap.as_ref().expect("cmap");
-        let gsub = font.font().gsub.as_ref().expect("gsub");
+        let gsub = font.font().current_gsub().expect("gsub");
         let locale = "ja-JP";
 
         let mut found = None;
@@ -6513,7 +7065,7 @@ mod tests {
     fn lookup_liga_sequence_uses_real_font_data() {
         let path = japanese_font_path();
         let font = crate::fontload_file(&path).expect("load japanese font");
-        let gsub = font.font().gsub.as_ref().expect("gsub");
+        let gsub = font.font().current_gsub().expect("gsub");
         let cmap = font.font().cmap.as_ref().expect("cmap");
         let candidates = [
             vec!['f', 'i'],
@@ -6545,7 +7097,7 @@ mod tests {
     fn text2command_uses_real_ligature_glyph_when_layout_enabled() {
         let font = crate::load_font_from_file(fira_sans_regular_path()).expect("load fira sans");
         let cmap = font.font().cmap.as_ref().expect("cmap");
-        let gsub = font.font().gsub.as_ref().expect("gsub");
+        let gsub = font.font().current_gsub().expect("gsub");
         let glyph_ids = [
             cmap.get_glyph_position('f' as u32) as usize,
             cmap.get_glyph_position('i' as u32) as usize,
@@ -6631,6 +7183,583 @@ mod tests {
         assert!(svg.contains("<svg"));
     }
 
+    #[test]
+    fn font_engine_places_group_ruby_above_base_text() {
+        let path = japanese_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(32.0);
+        let spans = [
+            crate::RubyText::plain("この"),
+            crate::RubyText::group("紫陽花", "あじさい"),
+        ];
+
+        let run = engine
+            .text2glyph_run_with_ruby(&spans)
+            .expect("ruby glyph run");
+
+        assert_eq!(run.glyphs.len(), 9);
+        let ruby = &run.glyphs[5..];
+        assert!(ruby.iter().all(|glyph| glyph.y < run.glyphs[0].y));
+        assert!(ruby[0].x >= run.glyphs[2].x);
+        assert!(ruby.windows(2).all(|pair| pair[1].x > pair[0].x));
+    }
+
+    #[test]
+    fn font_engine_places_mono_ruby_right_of_vertical_base() {
+        let path = japanese_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(32.0).with_vertical_flow();
+        let spans = [crate::RubyText::mono("漢字", &["かん", "じ"])];
+
+        let run = engine
+            .text2glyph_run_with_ruby(&spans)
+            .expect("vertical ruby glyph run");
+
+        assert_eq!(run.glyphs.len(), 5);
+        assert!(run.glyphs[1].x > run.glyphs[0].x);
+        assert!(run.glyphs[4].y > run.glyphs[3].y);
+        let svg = engine
+            .render_svg_with_ruby(&spans)
+            .expect("render vertical ruby svg");
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn font_engine_rich_text_continues_pen_and_applies_colors() {
+        let path = japanese_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(20.0);
+
+        let run = engine
+            .rich_text2glyph_run("A<color=#ff0000>B</color>\n<size=40>C</size>")
+            .expect("rich text glyph run");
+
+        assert_eq!(run.glyphs.len(), 3);
+        assert!(run.glyphs[1].x > run.glyphs[0].x);
+        assert_eq!(run.glyphs[2].x, 0.0);
+        assert_eq!(run.glyphs[2].y, 40.0);
+        assert!(run.glyphs[1].glyph.layers.iter().all(|layer| match layer {
+            crate::GlyphLayer::Path(path) => path.paint == crate::GlyphPaint::Solid(0xffff_0000),
+            _ => true,
+        }));
+        let svg = engine
+            .rich_text2svg("<b>A</b><i>B</i>")
+            .expect("render rich text svg");
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn font_engine_rich_text_shapes_across_span_boundaries() {
+        let path = fira_sans_regular_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(32.0);
+
+        let ligature = engine
+            .rich_text2glyph_run("f<color=#ff0000>i</color>")
+            .expect("rich text ligature");
+        let plain_ligature = engine.text2glyph_run("fi").expect("plain ligature");
+        assert_eq!(ligature.glyphs.len(), plain_ligature.glyphs.len());
+        assert_eq!(ligature.glyphs.len(), 1);
+
+        let kerned = engine
+            .rich_text2glyph_run("A<color=#ff0000>V</color>")
+            .expect("rich text kerning pair");
+        let plain_kerned = engine.text2glyph_run("AV").expect("plain kerning pair");
+        assert_eq!(kerned.glyphs.len(), 2);
+        assert_eq!(kerned.glyphs[1].x, plain_kerned.glyphs[1].x);
+        assert!(kerned.glyphs[1].glyph.layers.iter().all(|layer| match layer {
+            crate::GlyphLayer::Path(path) => path.paint == crate::GlyphPaint::Solid(0xffff_0000),
+            _ => true,
+        }));
+        assert!(kerned.glyphs[0].glyph.layers.iter().all(|layer| match layer {
+            crate::GlyphLayer::Path(path) => path.paint == crate::GlyphPaint::CurrentColor,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn font_engine_appends_decoration_layers_after_glyphs() {
+        let path = japanese_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let plain = face
+            .engine()
+            .with_font_size(32.0)
+            .shape("gyp")
+            .expect("plain run");
+        let engine = face
+            .engine()
+            .with_font_size(32.0)
+            .with_underline()
+            .with_decoration(
+                crate::TextDecoration::line_through().with_style(crate::TextDecorationStyle::Wavy),
+            );
+
+        let run = engine.shape("gyp\ngyp").expect("decorated run");
+
+        assert_eq!(run.glyphs.len(), plain.glyphs.len() * 2 + 4);
+        let underline = &run.glyphs[plain.glyphs.len() * 2];
+        let crate::GlyphLayer::Path(layer) = &underline.glyph.layers[0] else {
+            panic!("expected underline path layer");
+        };
+        let segments = layer
+            .commands
+            .iter()
+            .filter(|command| matches!(command, crate::Command::Close))
+            .count();
+        assert!(segments > 1, "underline should skip descender ink");
+        assert!(engine.render_svg("gyp").expect("svg").contains("<svg"));
+    }
+
+    #[test]
+    fn font_family_draws_one_decoration_across_fallback_segments() {
+        let path = japanese_font_path();
+        if !path.exists() || !fira_sans_regular_path().exists() {
+            return;
+        }
+        let latin = crate::load_font_from_file(fira_sans_regular_path()).expect("load fira sans");
+        let japanese = crate::load_font_from_file(&path).expect("load japanese font");
+        let mut family = crate::FontFamily::new("Fira Sans");
+        family.add_loaded_font(latin);
+        family.add_loaded_font(japanese);
+
+        let run = family
+            .text2glyph_run(
+                "A漢A",
+                family.options().with_font_size(32.0).with_decoration(
+                    crate::TextDecoration::underline().with_skip_ink(false),
+                ),
+            )
+            .expect("decorated fallback run");
+
+        assert_eq!(run.glyphs.len(), 4);
+        let crate::GlyphLayer::Path(layer) = &run.glyphs[3].glyph.layers[0] else {
+            panic!("expected underline path layer");
+        };
+        let segments = layer
+            .commands
+            .iter()
+            .filter(|command| matches!(command, crate::Command::Close))
+            .count();
+        assert_eq!(segments, 1, "underline should not step between faces");
+    }
+
+    #[test]
+    fn font_family_aligns_fallback_faces_to_dominant_baseline() {
+        let path = japanese_font_path();
+        if !path.exists() || !fira_sans_regular_path().exists() {
+            return;
+        }
+        let latin = crate::load_font_from_file(fira_sans_regular_path()).expect("load fira sans");
+        let japanese = crate::load_font_from_file(&path).expect("load japanese font");
+        let expected = japanese
+            .baseline_offset(
+                crate::Baseline::Ideographic,
+                "hani",
+                crate::FontOptions::new(&japanese).with_font_size(32.0),
+            )
+            .expect("japanese ideographic baseline")
+            - latin
+                .baseline_offset(
+                    crate::Baseline::Ideographic,
+                    "hani",
+                    crate::FontOptions::new(&latin).with_font_size(32.0),
+                )
+                .expect("latin ideographic baseline");
+
+        let mut family = crate::FontFamily::new("Fira Sans");
+        family.add_loaded_font(latin);
+        family.add_loaded_font(japanese);
+
+        let plain = family
+            .text2glyph_run("A漢", family.options().with_font_size(32.0))
+            .expect("plain run");
+        let aligned = family
+            .text2glyph_run(
+                "A漢",
+                family
+                    .options()
+                    .with_font_size(32.0)
+                    .with_dominant_baseline(crate::Baseline::Ideographic),
+            )
+            .expect("aligned run");
+
+        assert_eq!(plain.glyphs.len(), aligned.glyphs.len());
+        assert_eq!(plain.glyphs[0].y, aligned.glyphs[0].y);
+        let shift = aligned.glyphs[1].y - plain.glyphs[1].y;
+        assert!((shift - expected).abs() < 0.01, "{shift} != {expected}");
+    }
+
+    #[test]
+    #[cfg(feature = "layout")]
+    fn math_stretch_glyph_covers_target_height() {
+        let path = math_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load math font")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(20.0);
+        let constants = engine
+            .math_constants()
+            .expect("math constants")
+            .expect("font has MATH constants");
+        assert!(constants.axis_height > 0.0);
+        assert!(constants.fraction_rule_thickness > 0.0);
+
+        let paren = face.glyph_id('(').expect("parenthesis glyph");
+        let run = engine
+            .math_stretch_glyph(paren, 200.0, true)
+            .expect("stretched parenthesis");
+        let (min_y, max_y) = run
+            .glyphs
+            .iter()
+            .filter_map(|glyph| {
+                glyph
+                    .glyph
+                    .metrics
+                    .bounds
+                    .map(|bounds| (bounds.min_y + glyph.y, bounds.max_y + glyph.y))
+            })
+            .fold((f32::MAX, f32::MIN), |(min, max), (low, high)| {
+                (min.min(low), max.max(high))
+            });
+        assert!(max_y - min_y >= 190.0, "height {}", max_y - min_y);
+        assert!(engine.math_stretch_glyph(paren, f32::NAN, true).is_err());
+    }
+
+    #[test]
+    fn font_engine_glyph_width_selects_full_width_latin() {
+        let path = japanese_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let proportional = face
+            .engine()
+            .with_font_size(32.0)
+            .measure("A")
+            .expect("proportional width");
+        let engine = face
+            .engine()
+            .with_font_size(32.0)
+            .with_glyph_width(crate::GlyphWidth::Half)
+            .with_glyph_width(crate::GlyphWidth::Full);
+        assert_eq!(engine.feature_settings().len(), 1);
+        let full = engine.measure("A").expect("full width");
+
+        let ideograph = engine.measure("漢").expect("ideograph width");
+        assert!(full > proportional);
+        assert!((full - ideograph).abs() < 0.5, "{full} != {ideograph}");
+    }
+
+    #[test]
+    #[cfg(feature = "layout")]
+    fn font_engine_disabled_features_turn_off_default_ligatures_and_kerning() {
+        let path = fira_sans_regular_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(32.0);
+        let ligated = engine.text2glyph_run("fi").expect("ligated fi");
+        assert_eq!(ligated.glyphs.len(), 1);
+        let unligated = engine
+            .clone()
+            .with_feature("liga", 0)
+            .text2glyph_run("fi")
+            .expect("unligated fi");
+        assert_eq!(unligated.glyphs.len(), 2);
+
+        let kerned = engine.measure("AV").expect("kerned width");
+        let unkerned = engine
+            .clone()
+            .with_feature("kern", 0)
+            .measure("AV")
+            .expect("unkerned width");
+        assert!(kerned < unkerned, "{kerned} vs {unkerned}");
+    }
+
+    #[test]
+    fn font_engine_synthesizes_small_caps_without_smcp() {
+        let path = latin_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(32.0);
+        let capital = engine.measure("H").expect("capital width");
+        let small_caps = engine
+            .clone()
+            .with_font_variant_caps(crate::FontVariantCaps::SmallCaps);
+        let small = small_caps.measure("h").expect("small capital width");
+        assert!(
+            small < capital && small > capital * 0.5,
+            "{small} vs {capital}"
+        );
+        assert_eq!(
+            small_caps.measure("H").expect("capital stays"),
+            capital,
+            "small-caps leaves capitals alone"
+        );
+        let all_small = engine
+            .clone()
+            .with_font_variant_caps(crate::FontVariantCaps::AllSmallCaps)
+            .measure("H")
+            .expect("all small caps width");
+        assert!((all_small - small).abs() < 0.01, "{all_small} != {small}");
+
+        let run = small_caps.text2glyph_run("h").expect("small caps run");
+        // Glyph runs are in px and `measure` in font units, so compare ratios.
+        let capital_run = engine.text2glyph_run("H").expect("capital run");
+        let ratio = run.glyphs[0].glyph.metrics.advance_x as f64
+            / capital_run.glyphs[0].glyph.metrics.advance_x as f64;
+        assert!(
+            (ratio - small / capital).abs() < 1e-4,
+            "run ratio {ratio} != measured ratio {}",
+            small / capital
+        );
+        let layers = &run.glyphs[0].glyph.layers;
+        assert!(layers.iter().any(|layer| matches!(
+            layer,
+            crate::GlyphLayer::Path(path)
+                if path.paint_mode == crate::PathPaintMode::Stroke && path.stroke_width > 0.0
+        )));
+    }
+
+    #[test]
+    fn font_engine_synthesizes_superscript_and_subscript_from_os2() {
+        let path = latin_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(32.0);
+        let normal = engine.text2glyph_run("2").expect("normal run");
+        let sup = engine
+            .clone()
+            .with_font_variant_position(crate::FontVariantPosition::Super)
+            .text2glyph_run("2")
+            .expect("superscript run");
+        let sub = engine
+            .clone()
+            .with_font_variant_position(crate::FontVariantPosition::Sub)
+            .text2glyph_run("2")
+            .expect("subscript run");
+
+        let normal = &normal.glyphs[0];
+        let (sup, sub) = (&sup.glyphs[0], &sub.glyphs[0]);
+        assert!(sup.glyph.metrics.advance_x < normal.glyph.metrics.advance_x);
+        assert!(sub.glyph.metrics.advance_x < normal.glyph.metrics.advance_x);
+        // y points down, so superscripts move to a smaller y.
+        assert!(sup.y < normal.y, "{} !< {}", sup.y, normal.y);
+        assert!(sub.y > normal.y, "{} !> {}", sub.y, normal.y);
+    }
+
+    #[test]
+    fn font_face_reuses_shape_plans_per_configuration() {
+        let path = latin_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(24.0);
+        let first = face.shape_plan(&engine.options());
+        let again = face.shape_plan(&engine.options().with_font_size(48.0));
+        assert!(
+            std::sync::Arc::ptr_eq(&first, &again),
+            "font size does not affect the plan"
+        );
+        assert_eq!(face.shape_plan_count(), 1);
+
+        let rtl = face.shape_plan(
+            &engine
+                .options()
+                .with_text_direction(crate::TextDirection::RightToLeft),
+        );
+        assert!(!std::sync::Arc::ptr_eq(&first, &rtl));
+        let tnum = face.shape_plan(&engine.clone().with_tabular_numbers().options());
+        assert_eq!(tnum.feature_tags(), &[*b"tnum"]);
+        assert_eq!(face.shape_plan_count(), 3);
+
+        let cached = engine.text2glyph_run("office AV").expect("cached run");
+        face.clear_shape_plans();
+        assert_eq!(face.shape_plan_count(), 0);
+        let uncached = face
+            .font()
+            .text2glyph_run("office AV", &engine.options())
+            .expect("uncached run");
+        assert_eq!(cached.glyphs.len(), uncached.glyphs.len());
+        for (a, b) in cached.glyphs.iter().zip(&uncached.glyphs) {
+            assert_eq!(
+                (a.x, a.y, a.glyph.metrics.advance_x),
+                (b.x, b.y, b.glyph.metrics.advance_x)
+            );
+        }
+    }
+
+    #[test]
+    fn shape_parallel_matches_serial_shaping_on_a_shared_face() {
+        let path = latin_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        let engine = face.engine().with_font_size(20.0);
+        let labels: Vec<String> = (0..64)
+            .map(|index| format!("Label {index} office"))
+            .collect();
+
+        let parallel = engine.shape_parallel(&labels);
+        assert_eq!(parallel.len(), labels.len());
+        for (label, run) in labels.iter().zip(parallel) {
+            let serial = engine.shape(label).expect("serial run");
+            let run = run.expect("parallel run");
+            assert_eq!(format!("{:?}", run.glyphs), format!("{:?}", serial.glyphs));
+        }
+        assert_eq!(face.shape_plan_count(), 1);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let run = face.engine().with_font_size(12.0).shape("AV").expect("run");
+                    assert_eq!(run.glyphs.len(), 2);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn font_face_caches_outlines_per_glyph_and_orientation() {
+        let path = latin_font_path();
+        if !path.exists() {
+            return;
+        }
+        let face = crate::FontFile::from_file(&path)
+            .expect("load font file")
+            .current_face()
+            .expect("current face");
+        assert_eq!(face.outline_cache_capacity(), 512);
+        let run = face
+            .engine()
+            .with_font_size(24.0)
+            .text2glyph_run("abba")
+            .expect("first run");
+        assert_eq!(face.cached_outline_count(), 2);
+        face.engine()
+            .with_font_size(48.0)
+            .text2glyph_run("ab")
+            .expect("scaled run");
+        assert_eq!(
+            face.cached_outline_count(),
+            2,
+            "font size does not affect outlines"
+        );
+        face.engine()
+            .with_font_size(24.0)
+            .with_vertical_flow()
+            .text2glyph_run("ab")
+            .expect("vertical run");
+        assert_eq!(face.cached_outline_count(), 4);
+        face.clear_outline_cache();
+        assert_eq!(face.cached_outline_count(), 0);
+
+        let uncached = face.clone().with_outline_cache_capacity(0);
+        let again = uncached
+            .engine()
+            .with_font_size(24.0)
+            .text2glyph_run("abba")
+            .expect("uncached run");
+        assert_eq!(uncached.cached_outline_count(), 0);
+        assert_eq!(format!("{:?}", run.glyphs), format!("{:?}", again.glyphs));
+    }
+
+    #[test]
+    #[cfg(feature = "layout")]
+    fn font_file_from_bytes_parses_collection_faces_and_tables_lazily() {
+        let path = collection_font_path();
+        if !path.exists() {
+            return;
+        }
+        let bytes = std::fs::read(&path).expect("read collection");
+        let file = crate::FontFile::from_bytes(bytes).expect("load collection");
+        assert!(file.face_count() > 1);
+        let faces = &file.font.more_fonts;
+        assert!((0..faces.len()).all(|index| !faces.is_parsed(index)));
+
+        let second = file.face(1).expect("second face");
+        assert!(faces.is_parsed(0));
+        assert!(!second.family().is_empty());
+
+        let first = file.face(0).expect("first face");
+        let shared = first.clone();
+        assert!(std::sync::Arc::ptr_eq(&first.font, &shared.font));
+        let gsub_parsed = |face: &crate::FontFace| {
+            face.font()
+                .gsub
+                .as_ref()
+                .map(crate::fontbytes::LazyTable::is_parsed)
+        };
+        if gsub_parsed(&first).is_some() {
+            assert_eq!(gsub_parsed(&first), Some(false));
+            first
+                .engine()
+                .with_font_size(24.0)
+                .text2glyph_run("漢字")
+                .expect("glyph run");
+            assert_eq!(gsub_parsed(&shared), Some(true));
+        }
+    }
+
     #[test]
     #[cfg(feature = "layout")]
     fn measure_with_vertical_flow_reports_positive_inline_extent() {
@@ -60,6 +60,44 @@ pub(crate) fn sniff_encoded_image_dimensions(data: &[u8]) -> Option<(&'static st
         }
     }
 
+    if data.len() >= 8 && (data.starts_with(b"II*\0") || data.starts_with(b"MM\0*")) {
+        let little = data[0] == b'I';
+        let u16_at = |at: usize| -> Option<u32> {
+            let bytes = [*data.get(at)?, *data.get(at + 1)?];
+            Some(if little {
+                u16::from_le_bytes(bytes)
+            } else {
+                u16::from_be_bytes(bytes)
+            } as u32)
+        };
+        let u32_at = |at: usize| -> Option<u32> {
+            let bytes: [u8; 4] = data.get(at..at + 4)?.try_into().ok()?;
+            Some(if little {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            })
+        };
+        // First IFD: ImageWidth (256) and ImageLength (257), SHORT or LONG.
+        let ifd = u32_at(4)? as usize;
+        let mut width = None;
+        let mut height = None;
+        for index in 0..u16_at(ifd)? as usize {
+            let entry = ifd + 2 + index * 12;
+            let value = match u16_at(entry + 2)? {
+                3 => u16_at(entry + 8)?,
+                4 => u32_at(entry + 8)?,
+                _ => continue,
+            };
+            match u16_at(entry)? {
+                256 => width = Some(value),
+                257 => height = Some(value),
+                _ => {}
+            }
+        }
+        return Some(("image/tiff", width?, height?));
+    }
+
     None
 }
 
@@ -97,3 +135,52 @@ pub(crate) fn check_sum(table: Vec<u8>) -> u32 {
     }
     sum
 }
+
+/// Maps `items` on scoped threads, one contiguous chunk per available core,
+/// and returns the results in input order. A panic in `f` is re-raised here.
+pub(crate) fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+        .min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_map_keeps_input_order() {
+        let items: Vec<usize> = (0..1000).collect();
+        let doubled = parallel_map(&items, |value| value * 2);
+        assert_eq!(
+            doubled,
+            items.iter().map(|value| value * 2).collect::<Vec<_>>()
+        );
+        assert!(parallel_map(&[] as &[usize], |value| *value).is_empty());
+    }
+}